@@ -32,7 +32,7 @@ pub fn null_mock() -> Arc<dyn Module> {
         .build()
 }
 
-pub fn large_dense_heap_mock(heap_kb: usize) -> Arc<dyn Module> {
+pub fn large_dense_heap_mock(heap_kb: usize, heap_memfd: bool) -> Arc<dyn Module> {
     extern "C" fn f(_vmctx: *mut lucet_vmctx) {}
 
     let heap_len = heap_kb * 1024;
@@ -49,14 +49,22 @@ pub fn large_dense_heap_mock(heap_kb: usize) -> Arc<dyn Module> {
         heap[i] = (i % 256) as u8;
     });
 
-    MockModuleBuilder::new()
+    let builder = MockModuleBuilder::new()
         .with_export_func(MockExportBuilder::new("f", FunctionPointer::from_usize(f as usize)))
         .with_initial_heap(heap.as_slice())
-        .with_heap_spec(heap_spec)
-        .build()
+        .with_heap_spec(heap_spec);
+    if heap_memfd {
+        builder.with_heap_memfd().build()
+    } else {
+        builder.build()
+    }
 }
 
-pub fn large_sparse_heap_mock(heap_kb: usize, stride: usize) -> Arc<dyn Module> {
+pub fn large_sparse_heap_mock(
+    heap_kb: usize,
+    stride: usize,
+    heap_memfd: bool,
+) -> Arc<dyn Module> {
     extern "C" fn f(_vmctx: *mut lucet_vmctx) {}
 
     let heap_len = heap_kb * 1024;
@@ -80,11 +88,15 @@ pub fn large_sparse_heap_mock(heap_kb: usize, stride: usize) -> Arc<dyn Module>
             }
         });
 
-    MockModuleBuilder::new()
+    let builder = MockModuleBuilder::new()
         .with_export_func(MockExportBuilder::new("f", FunctionPointer::from_usize(f as usize)))
         .with_initial_heap(heap.as_slice())
-        .with_heap_spec(heap_spec)
-        .build()
+        .with_heap_spec(heap_spec);
+    if heap_memfd {
+        builder.with_heap_memfd().build()
+    } else {
+        builder.build()
+    }
 }
 
 pub fn fib_mock() -> Arc<dyn Module> {
@@ -69,8 +69,19 @@ fn hello_instantiate<R: RegionCreate + 'static>(c: &mut Criterion) {
     workdir.close().unwrap();
 }
 
+/// Name a heap benchmark, distinguishing the memfd-backed initial heap from the copied one.
+fn heap_bench_name<R: RegionCreate>(name: &str, heap_memfd: bool) -> String {
+    if heap_memfd {
+        format!("{}_memfd ({})", name, R::TYPE_NAME)
+    } else {
+        format!("{} ({})", name, R::TYPE_NAME)
+    }
+}
+
 /// Instance instantiation with a large, dense heap.
-fn instantiate_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
+///
+/// With `heap_memfd`, the module's initial heap is mapped from a memfd rather than copied.
+fn instantiate_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
     fn body<R: Region>(module: Arc<dyn Module>, region: Arc<R>) -> InstanceHandle {
         region.new_instance(module).unwrap()
     }
@@ -83,9 +94,9 @@ fn instantiate_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
     let region = R::create(1, &limits).unwrap();
 
     c.bench_function_over_inputs(
-        &format!("instantiate_with_dense_heap ({})", R::TYPE_NAME),
+        &heap_bench_name::<R>("instantiate_with_dense_heap", heap_memfd),
         move |b, &&heap_kb| {
-            let module = large_dense_heap_mock(heap_kb);
+            let module = large_dense_heap_mock(heap_kb, heap_memfd);
             b.iter(|| body(module.clone(), region.clone()))
         },
         DENSE_HEAP_SIZES_KB,
@@ -93,7 +104,9 @@ fn instantiate_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
 }
 
 /// Instance instantiation with a large, sparse heap.
-fn instantiate_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
+///
+/// With `heap_memfd`, the module's initial heap is mapped from a memfd rather than copied.
+fn instantiate_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
     fn body<R: Region>(module: Arc<dyn Module>, region: Arc<R>) -> InstanceHandle {
         region.new_instance(module).unwrap()
     }
@@ -106,10 +119,10 @@ fn instantiate_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
     let region = R::create(1, &limits).unwrap();
 
     c.bench_function_over_inputs(
-        &format!("instantiate_with_sparse_heap ({})", R::TYPE_NAME),
+        &heap_bench_name::<R>("instantiate_with_sparse_heap", heap_memfd),
         move |b, &&heap_kb| {
             // 8 means that only every eighth page has non-zero data
-            let module = large_sparse_heap_mock(heap_kb, 8);
+            let module = large_sparse_heap_mock(heap_kb, 8, heap_memfd);
             b.iter(|| body(module.clone(), region.clone()))
         },
         SPARSE_HEAP_SIZES_KB,
@@ -145,7 +158,9 @@ fn hello_drop_instance<R: RegionCreate + 'static>(c: &mut Criterion) {
 }
 
 /// Instance destruction with a large, dense heap.
-fn drop_instance_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
+///
+/// With `heap_memfd`, the module's initial heap is mapped from a memfd rather than copied.
+fn drop_instance_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
     fn body(_inst: InstanceHandle) {}
 
     let limits = Limits {
@@ -156,9 +171,9 @@ fn drop_instance_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
     let region = R::create(1, &limits).unwrap();
 
     c.bench_function_over_inputs(
-        &format!("drop_instance_with_dense_heap ({})", R::TYPE_NAME),
+        &heap_bench_name::<R>("drop_instance_with_dense_heap", heap_memfd),
         move |b, &&heap_kb| {
-            let module = large_dense_heap_mock(heap_kb);
+            let module = large_dense_heap_mock(heap_kb, heap_memfd);
             b.iter_batched(
                 || region.clone().new_instance(module.clone()).unwrap(),
                 |inst| body(inst),
@@ -170,7 +185,9 @@ fn drop_instance_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
 }
 
 /// Instance destruction with a large, sparse heap.
-fn drop_instance_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion) {
+///
+/// With `heap_memfd`, the module's initial heap is mapped from a memfd rather than copied.
+fn drop_instance_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
     fn body(_inst: InstanceHandle) {}
 
     let limits = Limits {
@@ -181,10 +198,10 @@ fn drop_instance_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion)
     let region = R::create(1, &limits).unwrap();
 
     c.bench_function_over_inputs(
-        &format!("drop_instance_with_sparse_heap ({})", R::TYPE_NAME),
+        &heap_bench_name::<R>("drop_instance_with_sparse_heap", heap_memfd),
         move |b, &&heap_kb| {
             // 8 means that only every eighth page has non-zero data
-            let module = large_sparse_heap_mock(heap_kb, 8);
+            let module = large_sparse_heap_mock(heap_kb, 8, heap_memfd);
             b.iter_batched(
                 || region.clone().new_instance(module.clone()).unwrap(),
                 |inst| body(inst),
@@ -195,6 +212,60 @@ fn drop_instance_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion)
     );
 }
 
+/// Instance reset with a large, dense heap.
+///
+/// This is what a server reusing a single instance between requests pays. With `heap_memfd`, the
+/// module's initial heap is remapped from a memfd rather than copied.
+fn reset_with_dense_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
+    fn body(inst: &mut InstanceHandle) {
+        inst.reset().unwrap();
+    }
+
+    let limits = Limits {
+        heap_memory_size: 1024 * 1024 * 1024,
+        ..Limits::default()
+    };
+
+    let region = R::create(1, &limits).unwrap();
+
+    c.bench_function_over_inputs(
+        &heap_bench_name::<R>("reset_with_dense_heap", heap_memfd),
+        move |b, &&heap_kb| {
+            let module = large_dense_heap_mock(heap_kb, heap_memfd);
+            let mut inst = region.new_instance(module).unwrap();
+            b.iter(|| body(&mut inst))
+        },
+        DENSE_HEAP_SIZES_KB,
+    );
+}
+
+/// Instance reset with a large, sparse heap.
+///
+/// With `heap_memfd`, the module's initial heap is remapped from a memfd rather than copied.
+fn reset_with_sparse_heap<R: RegionCreate + 'static>(c: &mut Criterion, heap_memfd: bool) {
+    fn body(inst: &mut InstanceHandle) {
+        inst.reset().unwrap();
+    }
+
+    let limits = Limits {
+        heap_memory_size: 1024 * 1024 * 1024,
+        ..Limits::default()
+    };
+
+    let region = R::create(1, &limits).unwrap();
+
+    c.bench_function_over_inputs(
+        &heap_bench_name::<R>("reset_with_sparse_heap", heap_memfd),
+        move |b, &&heap_kb| {
+            // 8 means that only every eighth page has non-zero data
+            let module = large_sparse_heap_mock(heap_kb, 8, heap_memfd);
+            let mut inst = region.new_instance(module).unwrap();
+            b.iter(|| body(&mut inst))
+        },
+        SPARSE_HEAP_SIZES_KB,
+    );
+}
+
 /// Run a trivial guest function.
 ///
 /// This is primarily a measurement of the signal handler installation and removal, and the context
@@ -406,11 +477,17 @@ fn run_hostcall_raw<R: RegionCreate + 'static>(c: &mut Criterion) {
 pub fn seq_benches<R: RegionCreate + 'static>(c: &mut Criterion) {
     hello_load_mkregion_and_instantiate::<R>(c);
     hello_instantiate::<R>(c);
-    instantiate_with_dense_heap::<R>(c);
-    instantiate_with_sparse_heap::<R>(c);
+    for &heap_memfd in &[false, true] {
+        instantiate_with_dense_heap::<R>(c, heap_memfd);
+        instantiate_with_sparse_heap::<R>(c, heap_memfd);
+    }
     hello_drop_instance::<R>(c);
-    drop_instance_with_dense_heap::<R>(c);
-    drop_instance_with_sparse_heap::<R>(c);
+    for &heap_memfd in &[false, true] {
+        drop_instance_with_dense_heap::<R>(c, heap_memfd);
+        drop_instance_with_sparse_heap::<R>(c, heap_memfd);
+        reset_with_dense_heap::<R>(c, heap_memfd);
+        reset_with_sparse_heap::<R>(c, heap_memfd);
+    }
     run_null::<R>(c);
     run_fib::<R>(c);
     run_hello::<R>(c);
@@ -0,0 +1,257 @@
+//! Pretty-printing of the core dumps written by `Instance::write_coredump()` in `lucet-runtime`.
+//!
+//! The format is described in `lucet-runtime-internals/src/instance/coredump.rs`.
+
+use colored::Colorize;
+
+const UNKNOWN: u32 = std::u32::MAX;
+
+/// The names of `lucet_module_data::TrapCode`s, indexed by their numeric value.
+const TRAPCODES: &[&str] = &[
+    "StackOverflow",
+    "HeapOutOfBounds",
+    "OutOfBounds",
+    "IndirectCallToNull",
+    "BadSignature",
+    "IntegerOverflow",
+    "IntegerDivByZero",
+    "BadConversionToInteger",
+    "Interrupt",
+    "TableOutOfBounds",
+    "Unreachable",
+    "FuelExhausted",
+];
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Reader { buf, pos: 0 }
+    }
+
+    fn done(&self) -> bool {
+        self.pos >= self.buf.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> &'a [u8] {
+        let end = self.pos + len;
+        if end > self.buf.len() {
+            panic!("core dump truncated at offset {:#x}", self.buf.len());
+        }
+        let bytes = &self.buf[self.pos..end];
+        self.pos = end;
+        bytes
+    }
+
+    fn u8(&mut self) -> u8 {
+        self.bytes(1)[0]
+    }
+
+    fn u32_le(&mut self) -> u32 {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.bytes(4));
+        u32::from_le_bytes(bytes)
+    }
+
+    fn u64_le(&mut self) -> u64 {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8));
+        u64::from_le_bytes(bytes)
+    }
+
+    fn leb_u32(&mut self) -> u32 {
+        let mut result = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8();
+            result |= ((byte & 0x7F) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return result as u32;
+            }
+        }
+    }
+
+    fn leb_i64(&mut self) -> i64 {
+        let mut result = 0i64;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8();
+            if shift < 64 {
+                result |= ((byte & 0x7F) as i64) << shift;
+            }
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if shift < 64 && byte & 0x40 != 0 {
+                    result |= -1 << shift;
+                }
+                return result;
+            }
+        }
+    }
+
+    fn name(&mut self) -> String {
+        let len = self.leb_u32() as usize;
+        String::from_utf8_lossy(self.bytes(len)).into_owned()
+    }
+}
+
+fn unknown_or_hex(v: u32) -> String {
+    if v == UNKNOWN {
+        "unknown".to_owned()
+    } else {
+        format!("{:#x}", v)
+    }
+}
+
+pub fn print_coredump(buffer: &[u8]) {
+    let mut r = Reader::new(buffer);
+    if r.bytes(4) != b"\0asm" || r.u32_le() != 1 {
+        println!("Expected a WebAssembly core dump!");
+        return;
+    }
+
+    let mut saw_fault = false;
+    while !r.done() {
+        let id = r.u8();
+        let len = r.leb_u32() as usize;
+        let mut s = Reader::new(r.bytes(len));
+        match id {
+            0 => {
+                let name = s.name();
+                match name.as_str() {
+                    "core" => {
+                        s.u8();
+                        println!("Process:");
+                        println!("  {:10}: {}", "Executable", s.name());
+                    }
+                    "coremodules" => {
+                        println!("Modules:");
+                        for i in 0..s.leb_u32() {
+                            s.u8();
+                            println!("  [{}]: {}", i, s.name());
+                        }
+                    }
+                    "coreinstances" => {
+                        println!("Instances:");
+                        for i in 0..s.leb_u32() {
+                            s.u8();
+                            let module = s.leb_u32();
+                            let memories =
+                                (0..s.leb_u32()).map(|_| s.leb_u32()).collect::<Vec<_>>();
+                            let globals = (0..s.leb_u32()).map(|_| s.leb_u32()).collect::<Vec<_>>();
+                            println!(
+                                "  [{}]: module {}, memories {:?}, globals {:?}",
+                                i, module, memories, globals
+                            );
+                        }
+                    }
+                    "corestack" => {
+                        s.u8();
+                        println!("Stack (thread {}):", s.name());
+                        let count = s.leb_u32();
+                        if count == 0 {
+                            println!("  (no frames)");
+                        }
+                        for i in 0..count {
+                            s.u8();
+                            let instance = s.leb_u32();
+                            let func = s.leb_u32();
+                            let code_offset = s.leb_u32();
+                            // locals and operand stack values are not recorded by lucet
+                            s.leb_u32();
+                            s.leb_u32();
+                            println!(
+                                "  {:3}: instance {} function {} (wasm offset {})",
+                                i,
+                                instance,
+                                func,
+                                unknown_or_hex(code_offset)
+                            );
+                        }
+                    }
+                    "lucet-fault" => {
+                        saw_fault = true;
+                        let fatal = s.u8() != 0;
+                        let trapcode = s.u32_le();
+                        let rip_addr = s.u64_le();
+                        let wasm_offset = s.u32_le();
+                        println!("Fault:");
+                        if fatal {
+                            println!("  {:11}: {}", "Fatal", "yes".red().bold());
+                        } else {
+                            println!("  {:11}: no", "Fatal");
+                        }
+                        println!(
+                            "  {:11}: {}",
+                            "Trap code",
+                            TRAPCODES
+                                .get(trapcode as usize)
+                                .map(|name| name.to_string())
+                                .unwrap_or_else(|| "unknown".to_owned())
+                        );
+                        println!("  {:11}: {:#x}", "Address", rip_addr);
+                        println!("  {:11}: {}", "Wasm offset", unknown_or_hex(wasm_offset));
+                    }
+                    _ => println!("Custom section {} ({} bytes)", name, len),
+                }
+            }
+            5 => {
+                println!("Memories:");
+                for i in 0..s.leb_u32() {
+                    let flags = s.u8();
+                    let min = s.leb_u32();
+                    if flags & 1 != 0 {
+                        s.leb_u32();
+                    }
+                    println!("  [{}]: {} pages", i, min);
+                }
+            }
+            6 => {
+                println!("Globals:");
+                for i in 0..s.leb_u32() {
+                    let ty = s.u8();
+                    let mutable = if s.u8() != 0 { "mut " } else { "" };
+                    let value = match s.u8() {
+                        0x41 => format!("i32 {}", s.leb_i64() as i32),
+                        0x42 => format!("i64 {}", s.leb_i64()),
+                        0x43 => format!("f32 {}", f32::from_bits(s.u32_le())),
+                        0x44 => format!("f64 {}", f64::from_bits(s.u64_le())),
+                        op => format!("unknown initializer opcode {:#x} for type {:#x}", op, ty),
+                    };
+                    s.u8();
+                    println!("  [{}]: {}{}", i, mutable, value);
+                }
+            }
+            11 => {
+                println!("Heap contents:");
+                let count = s.leb_u32();
+                if count == 0 {
+                    println!("  (all zero)");
+                }
+                for _ in 0..count {
+                    s.u8();
+                    s.u8();
+                    let offset = s.leb_i64() as u32;
+                    s.u8();
+                    let len = s.leb_u32();
+                    s.bytes(len as usize);
+                    println!(
+                        "  {:7}: {:#10x}  {:7}: {:#x}",
+                        "Offset", offset, "Length", len
+                    );
+                }
+            }
+            _ => println!("Section {} ({} bytes)", id, len),
+        }
+        println!("");
+    }
+
+    if !saw_fault {
+        println!("Fault:");
+        println!("  (instance had not faulted)");
+    }
+}
@@ -1,3 +1,5 @@
+mod coredump;
+
 use byteorder::{LittleEndian, ReadBytesExt};
 use colored::Colorize;
 use goblin::{elf, Object};
@@ -50,6 +52,7 @@ struct TrapManifestRow {
 struct TrapSite {
     offset: u32,
     trapcode: u32,
+    wasm_offset: u32,
 }
 
 #[derive(Debug)]
@@ -334,6 +337,9 @@ impl<'a> ArtifactSummary<'a> {
             let func_len = rdr.read_u64::<LittleEndian>().unwrap();
             let traps = rdr.read_u64::<LittleEndian>().unwrap();
             let traps_len = rdr.read_u64::<LittleEndian>().unwrap();
+            // the function's wasm offset map is not summarized
+            let _wasm_offsets = rdr.read_u64::<LittleEndian>().unwrap();
+            let _wasm_offsets_len = rdr.read_u64::<LittleEndian>().unwrap();
             let func_name = self
                 .get_func_name_for_addr(func_start)
                 .unwrap_or("(not found)");
@@ -341,15 +347,20 @@ impl<'a> ArtifactSummary<'a> {
             let mut sites = Vec::new();
 
             // Find the table
-            let serialized_table = self.read_memory(traps, 8 * traps_len).unwrap();
+            let serialized_table = self.read_memory(traps, 12 * traps_len).unwrap();
             let mut table_rdr = Cursor::new(serialized_table);
 
             // Iterate through each site
             for _ in 0..traps_len {
                 let offset = table_rdr.read_u32::<LittleEndian>().unwrap();
                 let trapcode = table_rdr.read_u32::<LittleEndian>().unwrap();
+                let wasm_offset = table_rdr.read_u32::<LittleEndian>().unwrap();
 
-                sites.push(TrapSite { offset, trapcode });
+                sites.push(TrapSite {
+                    offset,
+                    trapcode,
+                    wasm_offset,
+                });
             }
 
             manifest.records.push(TrapManifestRow {
@@ -382,10 +393,19 @@ impl<'a> ArtifactSummary<'a> {
 }
 
 fn main() {
-    let path = env::args().nth(1).unwrap();
+    let mut path = env::args().nth(1).unwrap();
+    let is_coredump = path == "coredump";
+    if is_coredump {
+        path = env::args().nth(2).expect("coredump path");
+    }
     let mut fd = File::open(path).expect("open");
     let mut buffer = Vec::new();
     fd.read_to_end(&mut buffer).expect("read");
+    if is_coredump {
+        coredump::print_coredump(&buffer);
+        return;
+    }
+
     let object = Object::parse(&buffer).expect("parse");
 
     if let Object::Elf(eo) = object {
@@ -1,4 +1,5 @@
 use crate::traps::{TrapManifest, TrapSite};
+use crate::wasm_offsets::{WasmOffset, WasmOffsetMap};
 use cranelift_codegen::entity::entity_impl;
 use serde::{Deserialize, Serialize};
 
@@ -68,6 +69,23 @@ impl OwnedImportFunction {
     }
 }
 
+/// ImportLinking describes how calls from a module to the functions it imports are linked.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum ImportLinking {
+    /// Imported functions are symbols named by the bindings given to lucetc, and are resolved by
+    /// the dynamic linker when the module is loaded.
+    Symbols,
+    /// Imported functions are called through a table of host functions that the runtime fills in
+    /// for each instance, from the functions the embedder provides when creating the instance.
+    Table,
+}
+
+impl Default for ImportLinking {
+    fn default() -> Self {
+        ImportLinking::Symbols
+    }
+}
+
 /// UniqueSignatureIndex names a signature after collapsing duplicate signatures to a single
 /// identifier, whereas SignatureIndex is directly what the original module specifies, and may
 /// specify duplicates of types that are structurally equal.
@@ -132,7 +150,8 @@ pub struct FunctionHandle {
 
 // The layout of this struct is very tightly coupled to lucetc's `write_function_manifest`!
 //
-// Specifically, `write_function_manifest` sets up relocations on `code_addr` and `traps_addr`.
+// Specifically, `write_function_manifest` sets up relocations on `code_addr`, `traps_addr`, and
+// `wasm_offsets_addr`.
 // It does not explicitly serialize a correctly formed `FunctionSpec`, because addresses
 // for these fields do not exist until the object is loaded in the future.
 //
@@ -144,12 +163,28 @@ pub struct FunctionSpec {
     code_addr: u64,
     code_len: u32,
     traps_addr: u64,
-    traps_len: u64
+    traps_len: u64,
+    wasm_offsets_addr: u64,
+    wasm_offsets_len: u64
 }
 
 impl FunctionSpec {
-    pub fn new(code_addr: u64, code_len: u32, traps_addr: u64, traps_len: u64) -> Self {
-        FunctionSpec { code_addr, code_len, traps_addr, traps_len }
+    pub fn new(
+        code_addr: u64,
+        code_len: u32,
+        traps_addr: u64,
+        traps_len: u64,
+        wasm_offsets_addr: u64,
+        wasm_offsets_len: u64
+    ) -> Self {
+        FunctionSpec {
+            code_addr,
+            code_len,
+            traps_addr,
+            traps_len,
+            wasm_offsets_addr,
+            wasm_offsets_len,
+        }
     }
     pub fn ptr(&self) -> FunctionPointer {
         FunctionPointer::from_usize(self.code_addr as usize)
@@ -160,6 +195,9 @@ impl FunctionSpec {
     pub fn traps_len(&self) -> u64 {
         self.traps_len
     }
+    pub fn wasm_offsets_len(&self) -> u64 {
+        self.wasm_offsets_len
+    }
     pub fn contains(&self, addr: u64) -> bool {
         addr >= self.code_addr && (addr - self.code_addr) < (self.code_len as u64)
     }
@@ -187,4 +225,16 @@ impl FunctionSpec {
             None
         }
     }
+    pub fn wasm_offsets(&self) -> Option<WasmOffsetMap> {
+        let entries_ptr = self.wasm_offsets_addr as *const WasmOffset;
+        if !entries_ptr.is_null() {
+            let entries_slice =
+                unsafe {
+                    from_raw_parts(entries_ptr, self.wasm_offsets_len as usize)
+                };
+            Some(WasmOffsetMap::new(entries_slice))
+        } else {
+            None
+        }
+    }
 }
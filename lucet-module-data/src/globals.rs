@@ -1,79 +1,104 @@
+use crate::types::ValueType;
 use serde::{Deserialize, Serialize};
 
-/// A WebAssembly global along with its export specification.
+/// A WebAssembly global along with its type and export specification.
 ///
 /// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
 /// leaves of the structure. For a variant with owned types at the leaves, see
 /// [`OwnedGlobalSpec`](owned/struct.OwnedGlobalSpec.html).
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GlobalSpec<'a> {
     #[serde(borrow)]
     global: Global<'a>,
-    export: Option<&'a str>,
+    ty: ValueType,
+    mutable: bool,
+    #[serde(borrow)]
+    export_names: Vec<&'a str>,
 }
 
 impl<'a> GlobalSpec<'a> {
-    pub fn new(global: Global<'a>, export: Option<&'a str>) -> Self {
-        Self { global, export }
+    pub fn new(
+        global: Global<'a>,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<&'a str>,
+    ) -> Self {
+        Self {
+            global,
+            ty,
+            mutable,
+            export_names,
+        }
     }
 
-    /// Create a new global definition with an initial value and an optional export name.
-    pub fn new_def(init_val: i64, export: Option<&'a str>) -> Self {
-        Self::new(
-            Global::Def {
-                def: GlobalDef::new(init_val),
-            },
-            export,
-        )
+    /// Create a new global definition with an initializer and the names it is exported as.
+    pub fn new_def(
+        def: GlobalDef,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<&'a str>,
+    ) -> Self {
+        Self::new(Global::Def { def }, ty, mutable, export_names)
     }
 
-    /// Create a new global import definition with a module and field name, and an optional export
-    /// name.
-    pub fn new_import(module: &'a str, field: &'a str, export: Option<&'a str>) -> Self {
-        Self::new(Global::Import { module, field }, export)
+    /// Create a new global import definition with a module and field name, and the names it is
+    /// exported as.
+    pub fn new_import(
+        module: &'a str,
+        field: &'a str,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<&'a str>,
+    ) -> Self {
+        Self::new(Global::Import { module, field }, ty, mutable, export_names)
     }
 
     pub fn global(&self) -> &Global {
         &self.global
     }
 
-    pub fn export(&self) -> Option<&str> {
-        self.export
+    /// The WebAssembly type of the global.
+    pub fn ty(&self) -> ValueType {
+        self.ty
+    }
+
+    /// Whether the global may be modified after initialization.
+    pub fn is_mutable(&self) -> bool {
+        self.mutable
+    }
+
+    /// The names the global is exported as, if any.
+    pub fn export_names(&self) -> &[&str] {
+        &self.export_names
     }
 }
 
 /// A WebAssembly global is either defined locally, or is defined in relation to a field of another
 /// WebAssembly module.
 ///
-/// Lucet currently does not support import globals, but we support the metadata for future
-/// compatibility.
+/// The values of imported globals are provided by the runtime user when an instance is created.
 ///
 /// The lifetime parameter exists to support zero-copy deserialization for the `&str` fields at the
 /// leaves of the structure. For a variant with owned types at the leaves, see
 /// [`OwnedGlobal`](owned/struct.OwnedGlobal.html).
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Global<'a> {
     Def { def: GlobalDef },
     Import { module: &'a str, field: &'a str },
 }
 
-/// A global definition.
+/// A global definition, given by the global's initializer.
 ///
-/// Currently we cast everything to an `i64`, but in the future this may have explicit variants for
-/// the different WebAssembly scalar types.
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
-pub struct GlobalDef {
-    init_val: i64,
-}
-
-impl GlobalDef {
-    pub fn new(init_val: i64) -> Self {
-        Self { init_val }
-    }
-
-    pub fn init_val(&self) -> i64 {
-        self.init_val
-    }
+/// The runtime stores every global in 64 bits. Values of narrower types occupy the low bits, and
+/// floating-point values are stored as their bit patterns.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum GlobalDef {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+    /// Initialized to the value of the global with this index, which must precede this global.
+    GetGlobal(u32),
 }
 
 /////////////////////////////////////////////////////////////////////////////////////////////////////////
@@ -83,38 +108,62 @@ impl GlobalDef {
 /// This type is useful when directly building up a value to be serialized.
 pub struct OwnedGlobalSpec {
     global: OwnedGlobal,
-    export: Option<String>,
+    ty: ValueType,
+    mutable: bool,
+    export_names: Vec<String>,
 }
 
 impl OwnedGlobalSpec {
-    pub fn new(global: OwnedGlobal, export: Option<String>) -> Self {
-        Self { global, export }
+    pub fn new(
+        global: OwnedGlobal,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<String>,
+    ) -> Self {
+        Self {
+            global,
+            ty,
+            mutable,
+            export_names,
+        }
     }
 
-    /// Create a new global definition with an initial value and an optional export name.
-    pub fn new_def(init_val: i64, export: Option<String>) -> Self {
-        Self::new(
-            OwnedGlobal::Def {
-                def: GlobalDef::new(init_val),
-            },
-            export,
-        )
+    /// Create a new global definition with an initializer and the names it is exported as.
+    pub fn new_def(
+        def: GlobalDef,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<String>,
+    ) -> Self {
+        Self::new(OwnedGlobal::Def { def }, ty, mutable, export_names)
     }
 
-    /// Create a new global import definition with a module and field name, and an optional export
-    /// name.
-    pub fn new_import(module: String, field: String, export: Option<String>) -> Self {
-        Self::new(OwnedGlobal::Import { module, field }, export)
+    /// Create a new global import definition with a module and field name, and the names it is
+    /// exported as.
+    pub fn new_import(
+        module: String,
+        field: String,
+        ty: ValueType,
+        mutable: bool,
+        export_names: Vec<String>,
+    ) -> Self {
+        Self::new(
+            OwnedGlobal::Import { module, field },
+            ty,
+            mutable,
+            export_names,
+        )
     }
 
     /// Create a [`GlobalSpec`](../struct.GlobalSpec.html) backed by the values in this
     /// `OwnedGlobalSpec`.
     pub fn to_ref<'a>(&'a self) -> GlobalSpec<'a> {
-        let export = match &self.export {
-            Some(e) => Some(e.as_str()),
-            None => None,
-        };
-        GlobalSpec::new(self.global.to_ref(), export)
+        GlobalSpec::new(
+            self.global.to_ref(),
+            self.ty,
+            self.mutable,
+            self.export_names.iter().map(|x| x.as_str()).collect(),
+        )
     }
 }
 
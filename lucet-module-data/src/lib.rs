@@ -10,14 +10,16 @@ mod linear_memory;
 mod module_data;
 mod traps;
 mod types;
+mod wasm_offsets;
 
 pub use crate::error::Error;
 pub use crate::globals::{Global, GlobalDef, GlobalSpec};
 pub use crate::linear_memory::{HeapSpec, SparseData, LinearMemorySpec};
 pub use crate::module_data::ModuleData;
-pub use crate::functions::{ExportFunction, FunctionHandle, FunctionIndex, FunctionMetadata, FunctionPointer, FunctionSpec, ImportFunction, UniqueSignatureIndex};
+pub use crate::functions::{ExportFunction, FunctionHandle, FunctionIndex, FunctionMetadata, FunctionPointer, FunctionSpec, ImportFunction, ImportLinking, UniqueSignatureIndex};
 pub use crate::traps::{TrapManifest, TrapSite, TrapCode};
 pub use crate::types::{Signature, ValueType};
+pub use crate::wasm_offsets::{WasmOffset, WasmOffsetMap};
 
 /// Owned variants of the module data types, useful for serialization and testing.
 pub mod owned {
@@ -1,5 +1,8 @@
 use crate::{
-    functions::{ExportFunction, FunctionIndex, FunctionMetadata, ImportFunction, OwnedFunctionMetadata},
+    functions::{
+        ExportFunction, FunctionIndex, FunctionMetadata, ImportFunction, ImportLinking,
+        OwnedFunctionMetadata,
+    },
     globals::GlobalSpec,
     linear_memory::{HeapSpec, LinearMemorySpec, SparseData},
     types::Signature,
@@ -25,6 +28,7 @@ pub struct ModuleData<'a> {
     function_info: Vec<FunctionMetadata<'a>>,
     #[serde(borrow)]
     import_functions: Vec<ImportFunction<'a>>,
+    import_linking: ImportLinking,
     #[serde(borrow)]
     export_functions: Vec<ExportFunction<'a>>,
     signatures: Vec<Signature>,
@@ -36,6 +40,7 @@ impl<'a> ModuleData<'a> {
         globals_spec: Vec<GlobalSpec<'a>>,
         function_info: Vec<FunctionMetadata<'a>>,
         import_functions: Vec<ImportFunction<'a>>,
+        import_linking: ImportLinking,
         export_functions: Vec<ExportFunction<'a>>,
         signatures: Vec<Signature>,
     ) -> Self {
@@ -44,6 +49,7 @@ impl<'a> ModuleData<'a> {
             globals_spec,
             function_info,
             import_functions,
+            import_linking,
             export_functions,
             signatures,
         }
@@ -77,6 +83,11 @@ impl<'a> ModuleData<'a> {
         &self.import_functions
     }
 
+    /// How calls to the functions in [`import_functions()`](#method.import_functions) are linked.
+    pub fn import_linking(&self) -> ImportLinking {
+        self.import_linking
+    }
+
     pub fn export_functions(&self) -> &[ExportFunction] {
         &self.export_functions
     }
@@ -128,6 +139,7 @@ pub struct OwnedModuleData {
     globals_spec: Vec<OwnedGlobalSpec>,
     function_info: Vec<OwnedFunctionMetadata>,
     imports: Vec<OwnedImportFunction>,
+    import_linking: ImportLinking,
     exports: Vec<OwnedExportFunction>,
     signatures: Vec<Signature>,
 }
@@ -138,6 +150,7 @@ impl OwnedModuleData {
         globals_spec: Vec<OwnedGlobalSpec>,
         function_info: Vec<OwnedFunctionMetadata>,
         imports: Vec<OwnedImportFunction>,
+        import_linking: ImportLinking,
         exports: Vec<OwnedExportFunction>,
         signatures: Vec<Signature>,
     ) -> Self {
@@ -146,6 +159,7 @@ impl OwnedModuleData {
             globals_spec,
             function_info,
             imports,
+            import_linking,
             exports,
             signatures,
         }
@@ -163,13 +177,22 @@ impl OwnedModuleData {
             self.globals_spec.iter().map(|gs| gs.to_ref()).collect(),
             self.function_info.iter().map(|info| info.to_ref()).collect(),
             self.imports.iter().map(|imp| imp.to_ref()).collect(),
+            self.import_linking,
             self.exports.iter().map(|exp| exp.to_ref()).collect(),
             self.signatures.clone(),
         )
     }
 
     pub fn empty() -> Self {
-        Self::new(None, vec![], vec![], vec![], vec![], vec![])
+        Self::new(
+            None,
+            vec![],
+            vec![],
+            vec![],
+            ImportLinking::default(),
+            vec![],
+            vec![],
+        )
     }
 
     pub fn with_heap_spec(mut self, heap_spec: HeapSpec) -> Self {
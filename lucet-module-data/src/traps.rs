@@ -17,6 +17,7 @@ pub enum TrapCode {
     Interrupt = 8,
     TableOutOfBounds = 9,
     Unreachable = 10,
+    FuelExhausted = 11,
 }
 
 impl TrapCode {
@@ -35,7 +36,10 @@ impl TrapCode {
 #[derive(Clone, Debug)]
 pub struct TrapSite {
     pub offset: u32,
-    pub code: TrapCode
+    pub code: TrapCode,
+    /// The byte offset in the WebAssembly module binary of the instruction that this trap site
+    /// was compiled from, or `WasmOffset::UNKNOWN` if it was not compiled from a wasm instruction.
+    pub wasm_offset: u32
 }
 
 /// A collection of trap sites, typically obtained from a
@@ -51,12 +55,15 @@ impl <'a> TrapManifest<'a> {
         TrapManifest { traps }
     }
     pub fn lookup_addr(&self, addr: u32) -> Option<TrapCode> {
+        self.lookup_site(addr).map(|site| site.code)
+    }
+    pub fn lookup_site(&self, addr: u32) -> Option<&TrapSite> {
         // predicate to find the trapsite for the addr via binary search
         let f =
             |ts: &TrapSite| ts.offset.cmp(&addr);
 
         if let Ok(i) = self.traps.binary_search_by(f) {
-            Some(self.traps[i].code)
+            Some(&self.traps[i])
         } else {
             None
         }
@@ -0,0 +1,49 @@
+/// The wasm bytecode offset that a range of a compiled function's native code was translated
+/// from.
+///
+/// `wasm_offset` is a byte offset into the WebAssembly module binary, so it can be looked up with
+/// tools like `wasm-objdump -d`. It is `WasmOffset::UNKNOWN` for code that does not correspond to
+/// any wasm instruction, such as function prologues.
+///
+/// To support zero-copy deserialization of wasm offset maps, this must be repr(C)
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WasmOffset {
+    pub offset: u32,
+    pub wasm_offset: u32
+}
+
+impl WasmOffset {
+    /// The `wasm_offset` of code that was not translated from a wasm instruction.
+    pub const UNKNOWN: u32 = std::u32::MAX;
+}
+
+/// A map from native code offsets in a single function to wasm bytecode offsets (see
+/// [`FunctionSpec::wasm_offsets`])
+///
+/// Each entry covers the native code from its `offset` up to the `offset` of the next entry, so
+/// entries are sorted by `offset`.
+#[repr(C)]
+#[derive(Clone, Debug)]
+pub struct WasmOffsetMap<'a> {
+    pub entries: &'a [WasmOffset]
+}
+
+impl <'a> WasmOffsetMap<'a> {
+    pub fn new(entries: &'a [WasmOffset]) -> WasmOffsetMap {
+        WasmOffsetMap { entries }
+    }
+    /// Find the wasm bytecode offset that the native code at `addr` was translated from.
+    pub fn lookup_addr(&self, addr: u32) -> Option<u32> {
+        // the entry covering `addr` is the last one that starts at or before it
+        let i = match self.entries.binary_search_by(|entry| entry.offset.cmp(&addr)) {
+            Ok(i) => i,
+            Err(0) => return None,
+            Err(i) => i - 1,
+        };
+        match self.entries[i].wasm_offset {
+            WasmOffset::UNKNOWN => None,
+            wasm_offset => Some(wasm_offset),
+        }
+    }
+}
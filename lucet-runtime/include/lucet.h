@@ -15,10 +15,35 @@
 
 enum lucet_error lucet_dl_module_load(const char *path, struct lucet_dl_module **mod_out);
 
+/**
+ * Load a module from the `len` bytes of a shared object at `bytes`, which are copied and need not
+ * outlive the module.
+ */
+enum lucet_error lucet_dl_module_load_from_bytes(const uint8_t *          bytes,
+                                                 uintptr_t                len,
+                                                 struct lucet_dl_module **mod_out);
+
 void lucet_dl_module_release(const struct lucet_dl_module *module);
 
 const char *lucet_error_name(enum lucet_error e);
 
+/**
+ * Add the function `func` for the import `module.name`. `func` takes a `struct lucet_vmctx *`
+ * followed by `params_len` arguments of the types in `params`, and returns a value of type `*ret`,
+ * or nothing if `ret` is `NULL`.
+ */
+enum lucet_error lucet_host_functions_add(struct lucet_host_functions *funcs,
+                                          const char *                 module,
+                                          const char *                 name,
+                                          const void *                 func,
+                                          uintptr_t                    params_len,
+                                          const enum lucet_val_type *  params,
+                                          const enum lucet_val_type *  ret);
+
+enum lucet_error lucet_host_functions_create(struct lucet_host_functions **funcs_out);
+
+void lucet_host_functions_release(struct lucet_host_functions *funcs);
+
 bool lucet_instance_check_heap(const struct lucet_instance *inst, const void *ptr, uintptr_t len);
 
 void *lucet_instance_embed_ctx(struct lucet_instance *inst);
@@ -27,14 +52,54 @@ enum lucet_error lucet_instance_grow_heap(struct lucet_instance *inst,
                                           uint32_t               additional_pages,
                                           uint32_t *             previous_pages_out);
 
+uint64_t lucet_instance_fuel_consumed(const struct lucet_instance *inst);
+
+/**
+ * Get the value of the global exported as `name`. The value has the WebAssembly type of the global:
+ * `lucet_val_type_i32`, `lucet_val_type_i64`, `lucet_val_type_f32`, or `lucet_val_type_f64`.
+ */
+enum lucet_error lucet_instance_get_global(const struct lucet_instance *inst,
+                                           const char *                  name,
+                                           struct lucet_val *            val_out);
+
+/**
+ * Set the value of the global exported as `name`. The global must be mutable, and the value must
+ * have the WebAssembly type of the global.
+ */
+enum lucet_error lucet_instance_set_global(struct lucet_instance * inst,
+                                           const char *            name,
+                                           const struct lucet_val *val);
+
 uint8_t *lucet_instance_heap(struct lucet_instance *inst);
 
 uint32_t lucet_instance_heap_len(const struct lucet_instance *inst);
 
+/**
+ * Get a kill switch that can terminate the instance from another thread. The kill switch must be
+ * released with `lucet_kill_switch_release`, and may outlive the instance.
+ */
+enum lucet_error lucet_instance_kill_switch(struct lucet_instance *    inst,
+                                            struct lucet_kill_switch **kill_switch_out);
+
 void lucet_instance_release(struct lucet_instance *inst);
 
 enum lucet_error lucet_instance_reset(struct lucet_instance *inst);
 
+/**
+ * Get the resources the instance has used since it was created.
+ */
+enum lucet_error lucet_instance_resource_usage(const struct lucet_instance *inst,
+                                               struct lucet_resource_usage *usage_out);
+
+/**
+ * Resume an instance that yielded with `lucet_vmctx_yield`, which returns `val` to the yielding
+ * hostcall.
+ *
+ * The instance must be resumed on the thread it yielded on; otherwise
+ * `lucet_error_invalid_argument` is returned.
+ */
+enum lucet_error lucet_instance_resume(struct lucet_instance *inst, void *val);
+
 enum lucet_error lucet_instance_run(struct lucet_instance * inst,
                                     const char *            entrypoint,
                                     uintptr_t               argc,
@@ -49,6 +114,12 @@ enum lucet_error lucet_instance_run_func_idx(struct lucet_instance * inst,
 enum lucet_error lucet_instance_set_fatal_handler(struct lucet_instance *inst,
                                                   lucet_fatal_handler    fatal_handler);
 
+/**
+ * Set the amount of fuel available to code compiled with fuel metering. Running out of fuel
+ * faults the instance with `lucet_trapcode_type_fuel_exhausted`.
+ */
+enum lucet_error lucet_instance_set_fuel(struct lucet_instance *inst, uint64_t fuel);
+
 /**
  * Release or run* must not be called in the body of this function!
  */
@@ -58,6 +129,14 @@ enum lucet_error lucet_instance_set_signal_handler(struct lucet_instance *inst,
 enum lucet_error lucet_instance_state(const struct lucet_instance *inst,
                                       struct lucet_state *         state_out);
 
+/**
+ * Returns false if the instance no longer exists. If the instance is in a hostcall, the thread
+ * running it is sent `SIGALRM` so that a system call the hostcall is blocked in fails with `EINTR`.
+ */
+bool lucet_kill_switch_terminate(const struct lucet_kill_switch *kill_switch);
+
+void lucet_kill_switch_release(struct lucet_kill_switch *kill_switch);
+
 enum lucet_error lucet_mmap_region_create(uint64_t                         instance_capacity,
                                           const struct lucet_alloc_limits *limits,
                                           struct lucet_region **           region_out);
@@ -71,8 +150,25 @@ enum lucet_error lucet_region_new_instance_with_ctx(const struct lucet_region *
                                                     void *                        embed_ctx,
                                                     struct lucet_instance **      inst_out);
 
+/**
+ * Create an instance of a module compiled to link its imports through an import table, calling the
+ * functions in `funcs` for its imports.
+ */
+enum lucet_error
+lucet_region_new_instance_with_host_functions(const struct lucet_region *        region,
+                                              const struct lucet_dl_module *     module,
+                                              void *                             embed_ctx,
+                                              const struct lucet_host_functions *funcs,
+                                              struct lucet_instance **           inst_out);
+
 void lucet_region_release(const struct lucet_region *region);
 
+/**
+ * Get how many instances the region holds, and how much memory they use.
+ */
+enum lucet_error lucet_region_stats(const struct lucet_region *region,
+                                    struct lucet_region_stats *stats_out);
+
 float lucet_retval_f32(const struct lucet_untyped_retval *retval);
 
 double lucet_retval_f64(const struct lucet_untyped_retval *retval);
@@ -22,6 +22,7 @@ enum lucet_error {
     lucet_error_region_full,
     lucet_error_module,
     lucet_error_limits_exceeded,
+    lucet_error_no_linear_memory,
     lucet_error_symbol_not_found,
     lucet_error_func_not_found,
     lucet_error_runtime_fault,
@@ -29,6 +30,8 @@ enum lucet_error {
     lucet_error_dl,
     lucet_error_internal,
     lucet_error_unsupported,
+    lucet_error_instance_not_returned,
+    lucet_error_instance_not_yielded,
 };
 
 enum lucet_signal_behavior {
@@ -42,12 +45,20 @@ enum lucet_state_tag {
     lucet_state_tag_running,
     lucet_state_tag_fault,
     lucet_state_tag_terminated,
+    lucet_state_tag_yielded,
 };
 
 enum lucet_terminated_reason {
     lucet_terminated_reason_signal,
     lucet_terminated_reason_get_embed_ctx,
+    lucet_terminated_reason_borrow_error,
     lucet_terminated_reason_provided,
+    lucet_terminated_reason_remote,
+    lucet_terminated_reason_guest_memory_error,
+    lucet_terminated_reason_host_panic,
+    lucet_terminated_reason_yield_in_guest_call,
+    lucet_terminated_reason_yield_abandoned,
+    lucet_terminated_reason_host_stack_overflow,
 };
 
 enum lucet_trapcode_type {
@@ -62,6 +73,7 @@ enum lucet_trapcode_type {
     lucet_trapcode_type_interrupt,
     lucet_trapcode_type_table_out_of_bounds,
     lucet_trapcode_type_user,
+    lucet_trapcode_type_fuel_exhausted,
     lucet_trapcode_type_unknown,
 };
 
@@ -98,8 +110,12 @@ struct lucet_val {
 
 struct lucet_dl_module;
 
+struct lucet_host_functions;
+
 struct lucet_instance;
 
+struct lucet_kill_switch;
+
 struct lucet_region;
 
 /**
@@ -125,6 +141,54 @@ struct lucet_alloc_limits {
     uint64_t globals_size;
 };
 
+/**
+ * The occupancy and memory use of a region.
+ */
+struct lucet_region_stats {
+    /**
+     * The number of instances the region can hold.
+     */
+    uint64_t capacity;
+    /**
+     * The number of slots with an instance in them.
+     */
+    uint64_t in_use;
+    /**
+     * The number of slots available for new instances.
+     */
+    uint64_t free;
+    /**
+     * The memory of the region that is resident in physical memory, in bytes.
+     */
+    uint64_t resident_bytes;
+};
+
+/**
+ * The resources used by an instance since it was created.
+ */
+struct lucet_resource_usage {
+    /**
+     * The current size of the heap, in bytes.
+     */
+    uint64_t heap_size;
+    /**
+     * The largest size of the heap, in bytes.
+     */
+    uint64_t heap_high_water;
+    /**
+     * The most guest stack used at once, in bytes.
+     */
+    uint64_t stack_high_water;
+    /**
+     * The number of guest functions run from the host.
+     */
+    uint64_t runs;
+    /**
+     * The CPU time spent running guest functions, including their hostcalls, in nanoseconds.
+     */
+    uint64_t guest_cpu_time_ns;
+};
+
 struct lucet_trapcode {
     enum lucet_trapcode_type code;
     uint16_t                 tag;
@@ -163,11 +227,16 @@ struct lucet_terminated {
     void *                       provided;
 };
 
+struct lucet_yielded {
+    void *val;
+};
+
 union lucet_state_val {
     struct lucet_untyped_retval returned;
     bool                        running;
     struct lucet_runtime_fault  fault;
     struct lucet_terminated     terminated;
+    struct lucet_yielded        yielded;
 };
 
 struct lucet_state {
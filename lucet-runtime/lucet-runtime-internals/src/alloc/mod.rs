@@ -1,5 +1,7 @@
 use crate::error::Error;
+use crate::instance::InstanceSnapshot;
 use crate::module::Module;
+use crate::region::mmap::CleanHeap;
 use crate::region::RegionInternal;
 use libc::{c_void, SIGSTKSZ};
 use nix::unistd::{sysconf, SysconfVar};
@@ -100,6 +102,12 @@ impl Slot {
 pub struct Alloc {
     pub heap_accessible_size: usize,
     pub heap_inaccessible_size: usize,
+    /// Whether the start of the heap is currently a private mapping of a module's `HeapMemFd`,
+    /// rather than anonymous memory.
+    pub heap_memfd_mapped: bool,
+    /// What the heap held when the soft-dirty bits of its pages were last cleared, if the region
+    /// tracks the pages that are written to.
+    pub(crate) heap_clean: Option<CleanHeap>,
     pub slot: Option<Slot>,
     pub region: Arc<dyn RegionInternal>,
 }
@@ -123,6 +131,13 @@ impl Alloc {
         (addr as usize >= guard_start) && ((addr as usize) < guard_end)
     }
 
+    /// Check whether `addr` is in the page just below the stack, which is where an overflowing
+    /// stack faults. That page is also part of the heap guard.
+    pub fn addr_in_stack_guard(&self, addr: *const c_void) -> bool {
+        let stack = self.slot().stack as usize;
+        (addr as usize >= stack - host_page_size()) && ((addr as usize) < stack)
+    }
+
     pub fn expand_heap(&mut self, expand_bytes: u32, module: &dyn Module) -> Result<u32, Error> {
         let slot = self.slot();
 
@@ -200,6 +215,27 @@ impl Alloc {
         self.region.clone().reset_heap(self, module)
     }
 
+    pub fn restore_heap(
+        &mut self,
+        snapshot: &InstanceSnapshot,
+        module: &dyn Module,
+    ) -> Result<(), Error> {
+        let heap_len = snapshot.heap_len();
+        let limits = &self.slot().limits;
+        if heap_len > limits.heap_memory_size {
+            bail_limits_exceeded!(
+                "snapshot heap would exceed runtime-specified heap limit: {:?}",
+                limits
+            );
+        }
+        if let Some(heap_spec) = module.heap_spec() {
+            if heap_len + heap_spec.guard_size as usize > limits.heap_address_space_size {
+                bail_limits_exceeded!("snapshot heap would leave guard memory too small");
+            }
+        }
+        self.region.clone().restore_heap(self, snapshot)
+    }
+
     pub fn heap_len(&self) -> usize {
         self.heap_accessible_size
     }
@@ -289,6 +325,23 @@ impl Alloc {
         )
     }
 
+    /// Return the most stack that has been used since the slot was allocated, in bytes.
+    ///
+    /// Stacks start out zeroed, so this is found by looking for the deepest nonzero word, and
+    /// misses any part of a frame below it that was only ever written with zeroes.
+    pub fn stack_high_water(&self) -> usize {
+        let stack = unsafe {
+            std::slice::from_raw_parts(
+                self.slot().stack as *const u64,
+                self.slot().limits.stack_size / 8,
+            )
+        };
+        stack
+            .iter()
+            .position(|word| *word != 0)
+            .map_or(0, |deepest| (stack.len() - deepest) * 8)
+    }
+
     /// Return the globals as a slice.
     pub unsafe fn globals(&self) -> &[i64] {
         std::slice::from_raw_parts(
@@ -329,7 +382,7 @@ impl Alloc {
 /// Runtime limits for the various memories that back a Lucet instance.
 ///
 /// Each value is specified in bytes, and must be evenly divisible by the host page size (4K).
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 #[repr(C)]
 pub struct Limits {
     /// Max size of the heap, which can be backed by real memory. (default 1M)
@@ -378,6 +431,14 @@ impl Limits {
         .expect("total_memory_size doesn't overflow")
     }
 
+    /// Whether each of these limits is no larger than the corresponding limit in `other`.
+    pub fn fits_within(&self, other: &Limits) -> bool {
+        self.heap_memory_size <= other.heap_memory_size
+            && self.heap_address_space_size <= other.heap_address_space_size
+            && self.stack_size <= other.stack_size
+            && self.globals_size <= other.globals_size
+    }
+
     /// Validate that the limits are aligned to page sizes, and that the stack is not empty.
     pub fn validate(&self) -> Result<(), Error> {
         if self.heap_memory_size % host_page_size() != 0 {
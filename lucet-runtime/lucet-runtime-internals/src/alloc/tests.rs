@@ -481,6 +481,99 @@ macro_rules! alloc_tests {
             assert_eq!(heap[reset_heap_len - 1], 0xFF);
         }
 
+        /// This test shows that a heap mapped from a memfd starts with the module's initial heap,
+        /// and that reset discards writes made by the guest, even after the heap grows.
+        #[test]
+        fn memfd_heap_grow_reset() {
+            let mut initial_heap = vec![0u8; 3 * 4096];
+            initial_heap[0] = 0x11;
+            initial_heap[2 * 4096 + 7] = 0x22;
+
+            let region = TestRegion::create(1, &LIMITS).expect("region created");
+            let module = MockModuleBuilder::new()
+                .with_heap_spec(THREE_PAGE_MAX_HEAP)
+                .with_initial_heap(&initial_heap)
+                .with_heap_memfd()
+                .build();
+            let mut inst = region
+                .new_instance(module.clone())
+                .expect("new_instance succeeds");
+
+            let heap_len = inst.alloc().heap_len();
+            assert_eq!(heap_len, THREEPAGE_INITIAL_SIZE as usize);
+
+            let heap = unsafe { inst.alloc_mut().heap_mut() };
+            assert_eq!(heap[0], 0x11);
+            assert_eq!(heap[4096], 0);
+            assert_eq!(heap[2 * 4096 + 7], 0x22);
+            assert_eq!(heap[heap_len - 1], 0);
+            heap[0] = 0xFF;
+            heap[4096] = 0xFF;
+            heap[heap_len - 1] = 0xFF;
+
+            inst.alloc_mut()
+                .expand_heap(
+                    (THREEPAGE_MAX_SIZE - THREEPAGE_INITIAL_SIZE) as u32,
+                    module.as_ref(),
+                )
+                .expect("expand_heap succeeds");
+            let heap = unsafe { inst.alloc_mut().heap_mut() };
+            assert_eq!(heap[heap_len], 0);
+            heap[heap_len] = 0xFF;
+
+            inst.alloc_mut()
+                .reset_heap(module.as_ref())
+                .expect("reset succeeds");
+
+            let reset_heap_len = inst.alloc().heap_len();
+            assert_eq!(reset_heap_len, THREEPAGE_INITIAL_SIZE as usize);
+
+            let heap = unsafe { inst.alloc_mut().heap_mut() };
+            assert_eq!(heap[0], 0x11);
+            assert_eq!(heap[4096], 0);
+            assert_eq!(heap[2 * 4096 + 7], 0x22);
+            assert_eq!(heap[reset_heap_len - 1], 0);
+
+            // growing again exposes zeroed memory, not what was written before the reset
+            inst.alloc_mut()
+                .expand_heap(
+                    (THREEPAGE_MAX_SIZE - THREEPAGE_INITIAL_SIZE) as u32,
+                    module.as_ref(),
+                )
+                .expect("expand_heap succeeds");
+            let heap = unsafe { inst.alloc_mut().heap_mut() };
+            assert_eq!(heap[reset_heap_len], 0);
+        }
+
+        /// This test shows that a slot whose heap was mapped from a memfd is zeroed before it is
+        /// reused by an instance of a module without one.
+        #[test]
+        fn memfd_heap_reuse_slot() {
+            let initial_heap = vec![0xAAu8; 4096];
+
+            let region = TestRegion::create(1, &LIMITS).expect("region created");
+            let memfd_module = MockModuleBuilder::new()
+                .with_heap_spec(ONE_PAGE_HEAP)
+                .with_initial_heap(&initial_heap)
+                .with_heap_memfd()
+                .build();
+            let inst = region
+                .new_instance(memfd_module)
+                .expect("new_instance succeeds");
+            assert_eq!(unsafe { inst.alloc().heap() }[0], 0xAA);
+            drop(inst);
+
+            let mut inst = region
+                .new_instance(
+                    MockModuleBuilder::new()
+                        .with_heap_spec(ONE_PAGE_HEAP)
+                        .build(),
+                )
+                .expect("new_instance succeeds");
+            let heap = unsafe { inst.alloc_mut().heap_mut() };
+            assert!(heap.iter().all(|b| *b == 0));
+        }
+
         const GUARDLESS_HEAP: HeapSpec = HeapSpec {
             reserved_size: SPEC_HEAP_RESERVED_SIZE,
             guard_size: 0,
@@ -674,3 +767,8 @@ macro_rules! alloc_tests {
 
 #[cfg(test)]
 alloc_tests!(crate::region::mmap::MmapRegion);
+
+#[cfg(test)]
+mod on_demand {
+    alloc_tests!(crate::region::mmap::OnDemandMmapRegion);
+}
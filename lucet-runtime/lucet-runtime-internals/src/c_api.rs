@@ -6,6 +6,10 @@ pub use self::lucet_val::*;
 use crate::alloc::Limits;
 use crate::error::Error;
 use crate::instance::signals::SignalBehavior;
+use crate::instance::ResourceUsage;
+use crate::region::RegionStats;
+use crate::vmctx::VmctxInternal;
+use crate::WASM_PAGE_SIZE;
 use libc::{c_int, c_void};
 use num_derive::FromPrimitive;
 
@@ -78,13 +82,15 @@ pub enum lucet_error {
     Dl,
     Internal,
     Unsupported,
+    InstanceNotReturned,
+    InstanceNotYielded,
 }
 
 impl From<Error> for lucet_error {
     fn from(e: Error) -> lucet_error {
         match e {
             Error::InvalidArgument(_) => lucet_error::InvalidArgument,
-            Error::RegionFull(_) => lucet_error::RegionFull,
+            Error::RegionFull(_, _) => lucet_error::RegionFull,
             Error::ModuleError(_) => lucet_error::Module,
             Error::LimitsExceeded(_) => lucet_error::LimitsExceeded,
             Error::NoLinearMemory(_) => lucet_error::NoLinearMemory,
@@ -95,6 +101,8 @@ impl From<Error> for lucet_error {
             Error::DlError(_) => lucet_error::Dl,
             Error::InternalError(_) => lucet_error::Internal,
             Error::Unsupported(_) => lucet_error::Unsupported,
+            Error::InstanceNotReturned => lucet_error::InstanceNotReturned,
+            Error::InstanceNotYielded => lucet_error::InstanceNotYielded,
         }
     }
 }
@@ -111,6 +119,14 @@ pub struct lucet_dl_module {
     _unused: [u8; 0],
 }
 
+pub struct lucet_kill_switch {
+    _unused: [u8; 0],
+}
+
+pub struct lucet_host_functions {
+    _unused: [u8; 0],
+}
+
 /// Runtime limits for the various memories that back a Lucet instance.
 ///
 /// Each value is specified in bytes, and must be evenly divisible by the host page size (4K).
@@ -161,6 +177,59 @@ impl From<&lucet_alloc_limits> for Limits {
     }
 }
 
+/// The occupancy and memory use of a region.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct lucet_region_stats {
+    /// The number of instances the region can hold.
+    pub capacity: u64,
+    /// The number of slots with an instance in them.
+    pub in_use: u64,
+    /// The number of slots available for new instances.
+    pub free: u64,
+    /// The memory of the region that is resident in physical memory, in bytes.
+    pub resident_bytes: u64,
+}
+
+impl From<&RegionStats> for lucet_region_stats {
+    fn from(stats: &RegionStats) -> lucet_region_stats {
+        lucet_region_stats {
+            capacity: stats.capacity as u64,
+            in_use: stats.in_use as u64,
+            free: stats.free as u64,
+            resident_bytes: stats.resident_bytes as u64,
+        }
+    }
+}
+
+/// The resources used by an instance since it was created.
+#[derive(Clone, Debug)]
+#[repr(C)]
+pub struct lucet_resource_usage {
+    /// The current size of the heap, in bytes.
+    pub heap_size: u64,
+    /// The largest size of the heap, in bytes.
+    pub heap_high_water: u64,
+    /// The most guest stack used at once, in bytes.
+    pub stack_high_water: u64,
+    /// The number of guest functions run from the host.
+    pub runs: u64,
+    /// The CPU time spent running guest functions, including their hostcalls, in nanoseconds.
+    pub guest_cpu_time_ns: u64,
+}
+
+impl From<&ResourceUsage> for lucet_resource_usage {
+    fn from(usage: &ResourceUsage) -> lucet_resource_usage {
+        lucet_resource_usage {
+            heap_size: usage.heap_size as u64,
+            heap_high_water: usage.heap_high_water as u64,
+            stack_high_water: usage.stack_high_water as u64,
+            runs: usage.runs,
+            guest_cpu_time_ns: usage.guest_cpu_time.as_nanos() as u64,
+        }
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug)]
 pub enum lucet_signal_behavior {
@@ -202,8 +271,44 @@ pub struct CTerminationDetails {
 unsafe impl Send for CTerminationDetails {}
 unsafe impl Sync for CTerminationDetails {}
 
+/// A value passed between C hostcalls and embedders by `lucet_vmctx_yield` and
+/// `lucet_instance_resume`.
+pub struct CYieldedVal {
+    pub val: *mut c_void,
+}
+
+unsafe impl Send for CYieldedVal {}
+unsafe impl Sync for CYieldedVal {}
+
+// The runtime functions that code generated by `lucetc` calls. They are defined here rather than
+// with the rest of the C API so that `ElfImports` can resolve them.
+lucet_hostcalls! {
+    #[no_mangle]
+    /// Get the number of WebAssembly pages currently in the heap.
+    pub unsafe extern "C" fn lucet_vmctx_current_memory(
+        &mut vmctx,
+    ) -> libc::uint32_t {
+        vmctx.instance().alloc().heap_len() as u32 / WASM_PAGE_SIZE
+    }
+
+    #[no_mangle]
+    /// Grows the guest heap by the given number of WebAssembly pages.
+    ///
+    /// On success, returns the number of pages that existed before the call. On failure, returns `-1`.
+    pub unsafe extern "C" fn lucet_vmctx_grow_memory(
+        &mut vmctx,
+        additional_pages: libc::uint32_t,
+    ) -> libc::int32_t {
+        if let Ok(old_pages) = vmctx.instance_mut().grow_memory(additional_pages) {
+            old_pages as libc::int32_t
+        } else {
+            -1
+        }
+    }
+}
+
 pub mod lucet_state {
-    use crate::c_api::{lucet_val, CTerminationDetails};
+    use crate::c_api::{lucet_val, CTerminationDetails, CYieldedVal};
     use crate::instance::{State, TerminationDetails};
     use crate::module::AddrDetails;
     use crate::sysdeps::UContext;
@@ -266,6 +371,41 @@ pub mod lucet_state {
                                     .map(|CTerminationDetails { details }| *details)
                                     .unwrap_or(std::ptr::null_mut()),
                             },
+                            TerminationDetails::Remote => lucet_terminated {
+                                reason: lucet_terminated_reason::Remote,
+                                provided: std::ptr::null_mut(),
+                            },
+                            TerminationDetails::GuestMemoryError(_) => lucet_terminated {
+                                reason: lucet_terminated_reason::GuestMemoryError,
+                                provided: std::ptr::null_mut(),
+                            },
+                            TerminationDetails::HostPanic { .. } => lucet_terminated {
+                                reason: lucet_terminated_reason::HostPanic,
+                                provided: std::ptr::null_mut(),
+                            },
+                            TerminationDetails::YieldInGuestCall => lucet_terminated {
+                                reason: lucet_terminated_reason::YieldInGuestCall,
+                                provided: std::ptr::null_mut(),
+                            },
+                            TerminationDetails::YieldAbandoned => lucet_terminated {
+                                reason: lucet_terminated_reason::YieldAbandoned,
+                                provided: std::ptr::null_mut(),
+                            },
+                            TerminationDetails::HostStackOverflow => lucet_terminated {
+                                reason: lucet_terminated_reason::HostStackOverflow,
+                                provided: std::ptr::null_mut(),
+                            },
+                        },
+                    },
+                },
+                State::Yielded { val, .. } => lucet_state {
+                    tag: lucet_state_tag::Yielded,
+                    val: lucet_state_val {
+                        yielded: lucet_yielded {
+                            val: val
+                                .downcast_ref()
+                                .map(|CYieldedVal { val }| *val)
+                                .unwrap_or(std::ptr::null_mut()),
                         },
                     },
                 },
@@ -287,6 +427,7 @@ pub mod lucet_state {
         Running,
         Fault,
         Terminated,
+        Yielded,
     }
 
     #[repr(C)]
@@ -297,6 +438,7 @@ pub mod lucet_state {
         pub running: bool,
         pub fault: lucet_runtime_fault,
         pub terminated: lucet_terminated,
+        pub yielded: lucet_yielded,
     }
 
     #[repr(C)]
@@ -306,6 +448,12 @@ pub mod lucet_state {
         pub provided: *mut c_void,
     }
 
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    pub struct lucet_yielded {
+        pub val: *mut c_void,
+    }
+
     #[repr(C)]
     #[derive(Clone, Copy)]
     pub enum lucet_terminated_reason {
@@ -313,6 +461,12 @@ pub mod lucet_state {
         CtxNotFound,
         BorrowError,
         Provided,
+        Remote,
+        GuestMemoryError,
+        HostPanic,
+        YieldInGuestCall,
+        YieldAbandoned,
+        HostStackOverflow,
     }
 
     #[repr(C)]
@@ -340,6 +494,7 @@ pub mod lucet_state {
         Interrupt,
         TableOutOfBounds,
         Unreachable,
+        FuelExhausted,
         Unknown,
     }
 
@@ -364,6 +519,7 @@ pub mod lucet_state {
                     TrapCode::Interrupt => lucet_trapcode::Interrupt,
                     TrapCode::TableOutOfBounds => lucet_trapcode::TableOutOfBounds,
                     TrapCode::Unreachable => lucet_trapcode::Unreachable,
+                    TrapCode::FuelExhausted => lucet_trapcode::FuelExhausted,
                 }
             } else {
                 lucet_trapcode::Unknown
@@ -1,3 +1,4 @@
+use crate::alloc::Limits;
 use crate::instance::{FaultDetails, TerminationDetails};
 use failure::Fail;
 
@@ -8,8 +9,11 @@ pub enum Error {
     InvalidArgument(&'static str),
 
     /// A [`Region`](trait.Region.html) cannot currently accommodate additional instances.
-    #[fail(display = "Region capacity reached: {} instances", _0)]
-    RegionFull(usize),
+    ///
+    /// The capacity and limits are those of the class of slots that ran out; regions with a single
+    /// class of slots report their capacity and limits.
+    #[fail(display = "Region capacity reached: {} instances with {:?}", _0, _1)]
+    RegionFull(usize, Limits),
 
     /// A module error occurred.
     #[fail(display = "Module error: {}", _0)]
@@ -45,6 +49,16 @@ pub enum Error {
     #[fail(display = "Runtime terminated")]
     RuntimeTerminated(TerminationDetails),
 
+    /// A method that expects a guest function to have returned was called on an instance that
+    /// yielded instead.
+    #[fail(display = "Instance not returned")]
+    InstanceNotReturned,
+
+    /// [`Instance::resume()`](struct.Instance.html#method.resume) or a method that expects a
+    /// yielded value was called when the instance had not yielded.
+    #[fail(display = "Instance not yielded")]
+    InstanceNotYielded,
+
     /// IO errors arising during dynamic loading with [`DlModule`](struct.DlModule.html).
     #[fail(display = "Dynamic loading error: {}", _0)]
     DlError(#[cause] std::io::Error),
@@ -0,0 +1,332 @@
+//! Checked pointers into the guest heap.
+//!
+//! WebAssembly guests pass pointers to hostcalls as 32-bit offsets into their heap. The types in
+//! this module hold offsets that have been checked to be in bounds of the heap and aligned for
+//! their pointee type, so that hostcalls can use them without decoding and checking the offsets by
+//! hand. Hostcalls defined with `#[lucet_hostcall]` can take them as arguments directly.
+//!
+//! Values are read and written by copy, so any plain-old-data type can be used as a pointee by
+//! implementing [`GuestType`](trait.GuestType.html) for it. Arrays are read with
+//! [`GuestSlice`](struct.GuestSlice.html), either by borrowing them from the heap or by iterating
+//! over pointers to their elements, and UTF-8 strings with [`GuestStr`](struct.GuestStr.html).
+//!
+//! Checks fail with a [`GuestMemoryError`](enum.GuestMemoryError.html). A hostcall can map it to an
+//! error code for the guest, or terminate the instance by panicking with
+//! `TerminationDetails::from(e)`, which is what `#[lucet_hostcall]` does for its arguments.
+//!
+//! The heap of an instance never shrinks while it runs, so an offset that is in bounds when it is
+//! checked stays in bounds for the rest of the hostcall. The types are not tied to the hostcall,
+//! though, and the heap can shrink when the instance is reset or restored, so the offsets are
+//! checked again whenever the heap is accessed through them. An access through an offset that is
+//! no longer in bounds terminates the instance with `TerminationDetails::GuestMemoryError`.
+
+use crate::instance::TerminationDetails;
+use crate::vmctx::Vmctx;
+use failure::Fail;
+use std::cell::{Ref, RefMut};
+use std::marker::PhantomData;
+use std::mem::{align_of, size_of};
+
+/// Errors from checking guest pointers.
+#[derive(Clone, Copy, Debug, Fail, PartialEq)]
+pub enum GuestMemoryError {
+    /// The `len` bytes at `offset` are not all within the guest heap.
+    #[fail(display = "Guest memory out of bounds: {} bytes at {:#x}", len, offset)]
+    OutOfBounds { offset: u32, len: usize },
+
+    /// The offset is not a multiple of the alignment of the pointee type.
+    #[fail(
+        display = "Guest pointer {:#x} is not aligned to {} bytes",
+        offset, align
+    )]
+    Misaligned { offset: u32, align: usize },
+
+    /// The bytes of a string are not valid UTF-8.
+    #[fail(display = "Guest string at {:#x} is not valid UTF-8", offset)]
+    InvalidUtf8 { offset: u32 },
+}
+
+/// Types that can be read from and written to the guest heap.
+///
+/// Besides the primitive numeric types, this can be implemented for `#[repr(C)]` structs and
+/// unions of `GuestType` fields, whose layout then matches the one the guest's compiler gives them
+/// on wasm32. Values are copied as they are, so types whose fields are not little-endian in the
+/// guest must be converted by the hostcall.
+///
+/// Implicit padding bytes of a value written to the heap are copied along with its fields, so
+/// padding should be declared as explicit fields, as `bindgen` does, to avoid exposing host memory
+/// to the guest.
+///
+/// # Safety
+///
+/// Every bit pattern of the size of the type must be a valid value of the type, so that it can be
+/// read from whatever the guest has left in its heap. This rules out references, `bool`, `char`,
+/// and most enums.
+pub unsafe trait GuestType: Copy {}
+
+unsafe impl GuestType for u8 {}
+unsafe impl GuestType for u16 {}
+unsafe impl GuestType for u32 {}
+unsafe impl GuestType for u64 {}
+unsafe impl GuestType for i8 {}
+unsafe impl GuestType for i16 {}
+unsafe impl GuestType for i32 {}
+unsafe impl GuestType for i64 {}
+unsafe impl GuestType for f32 {}
+unsafe impl GuestType for f64 {}
+
+/// Check that `count` values of type `T` at `offset` are aligned and within a heap of `heap_len`
+/// bytes.
+fn check_range<T>(heap_len: usize, offset: u32, count: u32) -> Result<(), GuestMemoryError> {
+    if offset as usize % align_of::<T>() != 0 {
+        return Err(GuestMemoryError::Misaligned {
+            offset,
+            align: align_of::<T>(),
+        });
+    }
+    let len = size_of::<T>()
+        .checked_mul(count as usize)
+        .unwrap_or(std::usize::MAX);
+    match (offset as usize).checked_add(len) {
+        Some(end) if end <= heap_len => Ok(()),
+        _ => Err(GuestMemoryError::OutOfBounds { offset, len }),
+    }
+}
+
+/// Check `count` values of type `T` at `offset` again before accessing them, terminating the
+/// instance if they are no longer within a heap of `heap_len` bytes.
+fn recheck_range<T>(heap_len: usize, offset: u32, count: u32) {
+    check_range::<T>(heap_len, offset, count)
+        .unwrap_or_else(|e| panic!(TerminationDetails::from(e)))
+}
+
+/// A pointer to a `T` in the guest heap.
+pub struct GuestPtr<T> {
+    offset: u32,
+    _ty: PhantomData<*mut T>,
+}
+
+impl<T> Clone for GuestPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GuestPtr<T> {}
+
+impl<T> std::fmt::Debug for GuestPtr<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GuestPtr({:#x})", self.offset)
+    }
+}
+
+impl<T: GuestType> GuestPtr<T> {
+    /// Check that a `T` at `offset` is aligned and within the heap.
+    pub fn new(vmctx: &Vmctx, offset: u32) -> Result<Self, GuestMemoryError> {
+        check_range::<T>(vmctx.heap().len(), offset, 1)?;
+        Ok(GuestPtr {
+            offset,
+            _ty: PhantomData,
+        })
+    }
+
+    /// The offset of the pointee in the heap.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// Read the pointee.
+    pub fn read(&self, vmctx: &Vmctx) -> T {
+        let heap = vmctx.heap();
+        recheck_range::<T>(heap.len(), self.offset, 1);
+        unsafe { (heap.as_ptr().add(self.offset as usize) as *const T).read() }
+    }
+
+    /// Write the pointee.
+    pub fn write(&self, vmctx: &Vmctx, val: T) {
+        let mut heap = vmctx.heap_mut();
+        recheck_range::<T>(heap.len(), self.offset, 1);
+        unsafe { (heap.as_mut_ptr().add(self.offset as usize) as *mut T).write(val) }
+    }
+}
+
+/// A slice of `T`s in the guest heap, passed by guests as an offset and a number of elements.
+pub struct GuestSlice<T> {
+    offset: u32,
+    len: u32,
+    _ty: PhantomData<*mut T>,
+}
+
+impl<T> Clone for GuestSlice<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for GuestSlice<T> {}
+
+impl<T> std::fmt::Debug for GuestSlice<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GuestSlice({:#x}, {})", self.offset, self.len)
+    }
+}
+
+impl<T: GuestType> GuestSlice<T> {
+    /// Check that `len` `T`s at `offset` are aligned and within the heap.
+    pub fn new(vmctx: &Vmctx, offset: u32, len: u32) -> Result<Self, GuestMemoryError> {
+        check_range::<T>(vmctx.heap().len(), offset, len)?;
+        Ok(GuestSlice {
+            offset,
+            len,
+            _ty: PhantomData,
+        })
+    }
+
+    /// The offset of the first element in the heap.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// The number of elements.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// A pointer to the element at `index`, or `None` if it is out of bounds of the slice.
+    pub fn get(&self, index: u32) -> Option<GuestPtr<T>> {
+        if index < self.len {
+            Some(GuestPtr {
+                offset: self.offset + index * size_of::<T>() as u32,
+                _ty: PhantomData,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Iterate over pointers to the elements.
+    ///
+    /// Unlike [`as_slice()`](#method.as_slice), this does not borrow the heap, so the hostcall can
+    /// read and write the elements, and anything else in the heap, as it goes.
+    pub fn iter(&self) -> impl Iterator<Item = GuestPtr<T>> {
+        let slice = *self;
+        (0..self.len).map(move |i| slice.get(i).expect("index is in bounds"))
+    }
+
+    /// Copy the elements out of the heap.
+    pub fn to_vec(&self, vmctx: &Vmctx) -> Vec<T> {
+        self.as_slice(vmctx).to_vec()
+    }
+
+    /// Copy the elements of `src` into the heap.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `src` is not the same length as the slice.
+    pub fn copy_from_slice(&self, vmctx: &Vmctx, src: &[T]) {
+        self.as_slice_mut(vmctx).copy_from_slice(src)
+    }
+
+    /// A raw pointer to the first element, for passing the slice to a system call such as `readv`.
+    ///
+    /// The pointer is valid for as long as the instance runs, but the heap is not borrowed, so it
+    /// is up to the hostcall not to use it while a borrow of the heap is alive elsewhere.
+    pub fn as_ptr(&self, vmctx: &Vmctx) -> *const T {
+        let heap = vmctx.heap();
+        recheck_range::<T>(heap.len(), self.offset, self.len);
+        unsafe { heap.as_ptr().add(self.offset as usize) as *const T }
+    }
+
+    /// A raw mutable pointer to the first element, for passing the slice to a system call such as
+    /// `readv`.
+    ///
+    /// As with [`as_ptr()`](#method.as_ptr), the heap is not borrowed.
+    pub fn as_mut_ptr(&self, vmctx: &Vmctx) -> *mut T {
+        let mut heap = vmctx.heap_mut();
+        recheck_range::<T>(heap.len(), self.offset, self.len);
+        unsafe { heap.as_mut_ptr().add(self.offset as usize) as *mut T }
+    }
+
+    /// Borrow the elements from the heap.
+    ///
+    /// As with `Vmctx::heap()`, the instance terminates with `TerminationDetails::BorrowError` if
+    /// the heap is already mutably borrowed.
+    pub fn as_slice<'a>(&self, vmctx: &'a Vmctx) -> Ref<'a, [T]> {
+        let heap = vmctx.heap();
+        recheck_range::<T>(heap.len(), self.offset, self.len);
+        let (offset, len) = (self.offset as usize, self.len as usize);
+        Ref::map(heap, |heap| unsafe {
+            std::slice::from_raw_parts(heap.as_ptr().add(offset) as *const T, len)
+        })
+    }
+
+    /// Mutably borrow the elements from the heap.
+    ///
+    /// As with `Vmctx::heap_mut()`, the instance terminates with `TerminationDetails::BorrowError`
+    /// if the heap is already borrowed.
+    pub fn as_slice_mut<'a>(&self, vmctx: &'a Vmctx) -> RefMut<'a, [T]> {
+        let heap = vmctx.heap_mut();
+        recheck_range::<T>(heap.len(), self.offset, self.len);
+        let (offset, len) = (self.offset as usize, self.len as usize);
+        RefMut::map(heap, |heap| unsafe {
+            std::slice::from_raw_parts_mut(heap.as_mut_ptr().add(offset) as *mut T, len)
+        })
+    }
+}
+
+/// A UTF-8 string in the guest heap, passed by guests as an offset and a length in bytes.
+#[derive(Clone, Copy)]
+pub struct GuestStr {
+    offset: u32,
+    len: u32,
+}
+
+impl std::fmt::Debug for GuestStr {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "GuestStr({:#x}, {})", self.offset, self.len)
+    }
+}
+
+impl GuestStr {
+    /// Check that the `len` bytes at `offset` are within the heap, and are valid UTF-8.
+    pub fn new(vmctx: &Vmctx, offset: u32, len: u32) -> Result<Self, GuestMemoryError> {
+        let s = GuestStr { offset, len };
+        s.as_str(vmctx)?;
+        Ok(s)
+    }
+
+    /// The offset of the string in the heap.
+    pub fn offset(&self) -> u32 {
+        self.offset
+    }
+
+    /// The length of the string in bytes.
+    pub fn len(&self) -> u32 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Borrow the string from the heap.
+    ///
+    /// The string is checked again, as the hostcall may have changed the heap since the `GuestStr`
+    /// was created. As with `Vmctx::heap()`, the instance terminates with
+    /// `TerminationDetails::BorrowError` if the heap is already mutably borrowed.
+    pub fn as_str<'a>(&self, vmctx: &'a Vmctx) -> Result<Ref<'a, str>, GuestMemoryError> {
+        let heap = vmctx.heap();
+        check_range::<u8>(heap.len(), self.offset, self.len)?;
+        let range = self.offset as usize..self.offset as usize + self.len as usize;
+        std::str::from_utf8(&heap[range.clone()]).map_err(|_| GuestMemoryError::InvalidUtf8 {
+            offset: self.offset,
+        })?;
+        Ok(Ref::map(heap, |heap| unsafe {
+            std::str::from_utf8_unchecked(&heap[range])
+        }))
+    }
+}
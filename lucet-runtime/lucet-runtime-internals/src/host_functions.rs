@@ -0,0 +1,282 @@
+//! Host functions for modules that link their imports through an import table.
+//!
+//! By default, the functions a module imports are symbols that the dynamic linker resolves against
+//! the host executable when the module is loaded. Modules compiled by `lucetc` with
+//! `ImportLinking::Table` instead call their imports through a table in each instance. The table
+//! is filled in when the instance is built, from the [`HostFunction`](struct.HostFunction.html)s
+//! given to
+//! [`InstanceBuilder::with_host_functions()`](../region/struct.InstanceBuilder.html#method.with_host_functions),
+//! keyed by the module and name of the import.
+//!
+//! Host functions can be Rust closures, so different instances of the same module can call
+//! different functions, or the same function with different state.
+
+use crate::error::Error;
+use crate::hostcall_macros::run_hostcall_body;
+use crate::instance::{TypedRet, TypedVal};
+use crate::module::Module;
+use crate::vmctx::{instance_from_vmctx, lucet_vmctx, Vmctx};
+use libc::c_void;
+use lucet_module_data::{FunctionPointer, ImportLinking, Signature};
+use std::any::Any;
+use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
+use std::ptr;
+use std::sync::Arc;
+
+/// A function that guest code can call through the import table of an instance.
+#[derive(Clone)]
+pub struct HostFunction {
+    func: FunctionPointer,
+    ctx: *const c_void,
+    signature: Signature,
+    /// The closure that `ctx` points to, if the function is a closure
+    closure: Option<Arc<dyn Any + Send + Sync>>,
+}
+
+// `ctx` is either null or points into `closure`, which is `Send + Sync`
+unsafe impl Send for HostFunction {}
+unsafe impl Sync for HostFunction {}
+
+impl std::fmt::Debug for HostFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HostFunction")
+            .field("func", &self.func)
+            .field("signature", &self.signature)
+            .field("closure", &self.closure.is_some())
+            .finish()
+    }
+}
+
+impl HostFunction {
+    /// Create a host function from a Rust closure.
+    ///
+    /// The closure takes `&mut Vmctx` followed by up to eight arguments of type `i32`, `u32`,
+    /// `i64`, `u64`, `f32`, or `f64`, and returns a value of one of those types or `()`. The
+    /// signature of the host function is derived from those types. As with `lucet_hostcalls!`, a
+    /// panic in the closure terminates the instance rather than unwinding into the guest.
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::host_functions::HostFunction;
+    /// # use lucet_runtime_internals::vmctx::Vmctx;
+    /// let offset = 10;
+    /// let add_offset = HostFunction::new(move |_vmctx: &mut Vmctx, x: u32| x + offset);
+    /// ```
+    pub fn new<F, Args, Ret>(f: F) -> Self
+    where
+        F: HostClosure<Args, Ret>,
+    {
+        let closure = Arc::new(f);
+        HostFunction {
+            func: F::shim(),
+            ctx: &*closure as *const F as *const c_void,
+            signature: F::signature(),
+            closure: Some(closure),
+        }
+    }
+
+    /// Create a host function from a function pointer.
+    ///
+    /// The function is called with the `vmctx` pointer of the instance, followed by the arguments
+    /// that the guest passes to the import. Hostcalls defined with `#[lucet_hostcall]` or
+    /// `lucet_hostcalls!` can be used this way, as can C functions written against `lucet.h`. They
+    /// do not need to be exported from the host executable.
+    ///
+    /// # Safety
+    ///
+    /// `func` must be an `extern "C"` function that takes a `vmctx` pointer and arguments matching
+    /// `signature`, and returns a value matching it. Instances check the signature of the import
+    /// against `signature`, but nothing checks `func` itself.
+    pub unsafe fn from_raw(func: FunctionPointer, signature: Signature) -> Self {
+        HostFunction {
+            func,
+            ctx: ptr::null(),
+            signature,
+            closure: None,
+        }
+    }
+
+    /// The signature of the function, which must match that of the imports it is used for.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+}
+
+/// Closures that can be made into a [`HostFunction`](struct.HostFunction.html).
+///
+/// This is implemented for closures that take `&mut Vmctx` followed by up to eight `TypedVal`
+/// arguments, and that return a `TypedRet`. `Args` is the tuple of argument types.
+pub trait HostClosure<Args, Ret>: Send + Sync + 'static {
+    /// The WebAssembly signature of the closure.
+    fn signature() -> Signature;
+
+    /// The `extern "C"` function that the import table points to, which calls the closure that
+    /// the context pointer of the entry points to.
+    fn shim() -> FunctionPointer;
+}
+
+/// Get the context pointer of the import table entry being called, which the trampoline that
+/// `lucetc` generates for each import stores in the instance before calling the entry's function.
+unsafe fn import_ctx(vmctx: *mut lucet_vmctx) -> *const c_void {
+    instance_from_vmctx(vmctx).get_import_ctx()
+}
+
+macro_rules! impl_host_closure {
+    ( $( $arg:ident ),* ) => {
+        impl<F, Ret, $( $arg ),*> HostClosure<( $( $arg, )* ), Ret> for F
+        where
+            F: Fn(&mut Vmctx, $( $arg ),*) -> Ret + Send + Sync + 'static,
+            $( $arg: TypedVal, )*
+            Ret: TypedRet,
+        {
+            fn signature() -> Signature {
+                Signature {
+                    params: vec![$( <$arg as TypedVal>::value_type() ),*],
+                    ret_ty: <Ret as TypedRet>::value_type(),
+                }
+            }
+
+            fn shim() -> FunctionPointer {
+                #[allow(non_snake_case)]
+                unsafe extern "C" fn shim<F, Ret, $( $arg ),*>(
+                    vmctx_raw: *mut lucet_vmctx,
+                    $( $arg: $arg ),*
+                ) -> Ret
+                where
+                    F: Fn(&mut Vmctx, $( $arg ),*) -> Ret,
+                {
+                    // read the context before anything else can call an import
+                    let f = &*(import_ctx(vmctx_raw) as *const F);
+                    run_hostcall_body(
+                        vmctx_raw,
+                        AssertUnwindSafe(move || {
+                            let mut vmctx = Vmctx::from_raw(vmctx_raw);
+                            f(&mut vmctx, $( $arg ),*)
+                        }),
+                    )
+                }
+                FunctionPointer::from_usize(shim::<F, Ret, $( $arg ),*> as usize)
+            }
+        }
+    };
+}
+
+impl_host_closure!();
+impl_host_closure!(A1);
+impl_host_closure!(A1, A2);
+impl_host_closure!(A1, A2, A3);
+impl_host_closure!(A1, A2, A3, A4);
+impl_host_closure!(A1, A2, A3, A4, A5);
+impl_host_closure!(A1, A2, A3, A4, A5, A6);
+impl_host_closure!(A1, A2, A3, A4, A5, A6, A7);
+impl_host_closure!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+/// A set of host functions, keyed by the module and name of the imports they are used for.
+///
+/// A registry can hold functions for imports of many different modules; instances only use the
+/// functions their module imports.
+#[derive(Clone, Debug, Default)]
+pub struct HostFunctionRegistry {
+    funcs: HashMap<(String, String), HostFunction>,
+}
+
+impl HostFunctionRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a function for the import `module.name`, replacing any function already added for it.
+    pub fn insert(&mut self, module: &str, name: &str, func: HostFunction) {
+        self.funcs
+            .insert((module.to_owned(), name.to_owned()), func);
+    }
+
+    /// Add a function for the import `module.name`, replacing any function already added for it.
+    pub fn with_function(mut self, module: &str, name: &str, func: HostFunction) -> Self {
+        self.insert(module, name, func);
+        self
+    }
+
+    /// Get the function for the import `module.name`, if there is one.
+    pub fn get(&self, module: &str, name: &str) -> Option<&HostFunction> {
+        self.funcs.get(&(module.to_owned(), name.to_owned()))
+    }
+
+    /// Add all of the functions in `other`, replacing any functions already added for the same
+    /// imports.
+    pub fn extend(&mut self, other: &HostFunctionRegistry) {
+        for (key, func) in other.funcs.iter() {
+            self.funcs.insert(key.clone(), func.clone());
+        }
+    }
+}
+
+/// An entry of an import table, laid out as the code `lucetc` generates expects.
+///
+/// The fields are only read by guest code.
+#[repr(C)]
+pub(crate) struct ImportTableEntry {
+    _func: *const c_void,
+    _ctx: *const c_void,
+}
+
+/// The import table of an instance, indexed like the functions the module imports.
+///
+/// This is built from the host functions given to an `InstanceBuilder`, and is only used by the
+/// runtime.
+pub struct ImportTable {
+    entries: Box<[ImportTableEntry]>,
+    /// The closures that the context pointers of the entries point to
+    _closures: Vec<Arc<dyn Any + Send + Sync>>,
+}
+
+impl ImportTable {
+    /// An empty table, for modules that do not link their imports through a table.
+    pub(crate) fn empty() -> Self {
+        ImportTable {
+            entries: Box::new([]),
+            _closures: vec![],
+        }
+    }
+
+    pub(crate) fn as_ptr(&self) -> *const ImportTableEntry {
+        self.entries.as_ptr()
+    }
+}
+
+/// Build the import table for an instance of `module` from the provided host functions.
+///
+/// Every function the module imports must be provided, with a matching signature. Modules that
+/// link their imports as symbols get an empty table, regardless of the functions provided.
+pub(crate) fn resolve_host_functions(
+    module: &dyn Module,
+    provided: &HostFunctionRegistry,
+) -> Result<ImportTable, Error> {
+    if module.import_linking() != ImportLinking::Table {
+        return Ok(ImportTable::empty());
+    }
+    let mut entries = Vec::with_capacity(module.import_functions().len());
+    let mut closures = vec![];
+    for import in module.import_functions() {
+        let func = provided.get(import.module, import.name).ok_or_else(|| {
+            Error::SymbolNotFound(format!(
+                "function import {}::{}",
+                import.module, import.name
+            ))
+        })?;
+        if func.signature() != module.get_signature(import.fn_idx) {
+            return Err(Error::InvalidArgument(
+                "host function provided for an import does not have the import's signature",
+            ));
+        }
+        entries.push(ImportTableEntry {
+            _func: func.func.as_usize() as *const c_void,
+            _ctx: func.ctx,
+        });
+        closures.extend(func.closure.clone());
+    }
+    Ok(ImportTable {
+        entries: entries.into_boxed_slice(),
+        _closures: closures,
+    })
+}
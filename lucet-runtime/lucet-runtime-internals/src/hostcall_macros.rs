@@ -1,10 +1,56 @@
+use crate::instance::{hostcall_panic_termination, run_hostcall, TerminationDetails};
+use crate::vmctx::{instance_from_vmctx, lucet_vmctx, Vmctx};
+use std::panic::{self, UnwindSafe};
+
+/// Run the body of a hostcall, terminating the instance if it panics or if its `KillSwitch` has
+/// been triggered.
+///
+/// This is used by `lucet_hostcalls!` and `#[lucet_hostcall]`, and is not meant to be called
+/// directly.
+#[doc(hidden)]
+pub unsafe fn run_hostcall_body<F, R>(vmctx_raw: *mut lucet_vmctx, f: F) -> R
+where
+    F: FnOnce() -> R + UnwindSafe,
+{
+    // a `KillSwitch` interrupts the hostcall if it is blocked in a system call
+    let kill_state = instance_from_vmctx(vmctx_raw).kill_state.clone();
+    let previous_hostcall_thread = kill_state.begin_hostcall();
+    let res = run_hostcall(vmctx_raw, move || panic::catch_unwind(f));
+    kill_state.end_hostcall(previous_hostcall_thread);
+    match res {
+        Ok(res) => {
+            let mut vmctx = Vmctx::from_raw(vmctx_raw);
+            if vmctx.kill_requested() {
+                vmctx.terminate_no_unwind(TerminationDetails::Remote);
+            }
+            res
+        }
+        Err(e) => {
+            // unwinding into the guest is undefined behavior, so every panic terminates the
+            // instance
+            let details = match e.downcast::<TerminationDetails>() {
+                Ok(details) => *details,
+                Err(e) => hostcall_panic_termination(e),
+            };
+            let mut vmctx = Vmctx::from_raw(vmctx_raw);
+            vmctx.terminate_no_unwind(details);
+        }
+    }
+}
+
 /// The macro that surrounds definitions of Lucet hostcalls in Rust.
 ///
 /// It is important to use this macro for hostcalls, rather than exporting them directly, as it
-/// installs unwind protection that prevents panics from unwinding into the guest stack.
+/// installs unwind protection that prevents panics from unwinding into the guest stack. A panic
+/// terminates the instance with `TerminationDetails::HostPanic`, or aborts the process if the
+/// policy set with `set_hostcall_panic_policy()` is `HostcallPanicPolicy::Abort`. It also
+/// terminates the instance when the hostcall returns if its `KillSwitch` has been triggered, and
+/// runs the hostcall on a host stack if the instance has one set with
+/// `Instance::set_hostcall_stack_size()`.
 ///
-/// Since this is not yet a proc macro, the syntax is unfortunately fairly brittle. The functions it
-/// encloses must be of the form:
+/// The syntax of this macro is fairly brittle; the `#[lucet_hostcall]` attribute from
+/// `lucet-runtime` handles hostcalls the same way, with ordinary function syntax and checked guest
+/// pointer arguments. The functions this macro encloses must be of the form:
 ///
 /// ```ignore
 /// #[$attr1]
@@ -45,20 +91,9 @@ macro_rules! lucet_hostcalls {
                 ) -> $ret_ty {
                     $($body)*
                 }
-                let res = std::panic::catch_unwind(move || {
+                $crate::hostcall_macros::run_hostcall_body(vmctx_raw, move || {
                     hostcall_impl(&mut $crate::vmctx::Vmctx::from_raw(vmctx_raw), $( $arg ),*)
-                });
-                match res {
-                    Ok(res) => res,
-                    Err(e) => {
-                        if let Some(details) = e.downcast_ref::<$crate::instance::TerminationDetails>() {
-                            let mut vmctx = $crate::vmctx::Vmctx::from_raw(vmctx_raw);
-                            vmctx.terminate_no_unwind(details.clone());
-                        } else {
-                            std::panic::resume_unwind(e);
-                        }
-                    }
-                }
+                })
             }
         )*
     }
@@ -1,31 +1,114 @@
+mod backtrace;
+mod coredump;
+mod host_stack;
+mod kill_switch;
 mod siginfo_ext;
 pub mod signals;
-
+mod snapshot;
+mod typed_func;
+
+pub use crate::instance::backtrace::Frame;
+#[doc(hidden)]
+pub use crate::instance::host_stack::run_hostcall;
+pub use crate::instance::kill_switch::KillSwitch;
+pub(crate) use crate::instance::kill_switch::{KillState, KILL_SIGNAL};
 pub use crate::instance::signals::{signal_handler_none, SignalBehavior, SignalHandler};
+pub use crate::instance::snapshot::InstanceSnapshot;
+pub use crate::instance::typed_func::{TypedArgs, TypedFunc, TypedRet, TypedVal};
 
-use crate::alloc::{Alloc, HOST_PAGE_SIZE_EXPECTED};
+use crate::alloc::{host_page_size, Alloc, HOST_PAGE_SIZE_EXPECTED};
 use crate::context::Context;
 use crate::embed_ctx::CtxMap;
 use crate::error::Error;
+use crate::guest_memory::GuestMemoryError;
+use crate::host_functions::{ImportTable, ImportTableEntry};
+use crate::instance::backtrace::walk_guest_stack;
+use crate::instance::host_stack::HostStack;
 use crate::instance::siginfo_ext::SiginfoExt;
-use crate::module::{self, Global, Module};
+use crate::module::{self, Global, GlobalDef, Module, ValueType};
 use crate::region::RegionInternal;
 use crate::sysdeps::UContext;
-use crate::val::{UntypedRetVal, Val};
+use crate::val::{val_to_stack, UntypedRetVal, Val};
 use crate::WASM_PAGE_SIZE;
 use libc::{c_void, siginfo_t, uintptr_t, SIGBUS, SIGSEGV};
+use log::warn;
 use lucet_module_data::{FunctionHandle, FunctionPointer, TrapCode};
 use memoffset::offset_of;
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::cell::{BorrowError, BorrowMutError, Ref, RefCell, RefMut, UnsafeCell};
+use std::collections::HashMap;
 use std::ffi::{CStr, CString};
 use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::ptr::{self, NonNull};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::thread::{self, ThreadId};
+use std::time::Duration;
 
 pub const LUCET_INSTANCE_MAGIC: u64 = 746932922;
 
+/// The deepest that guest functions called from hostcalls with
+/// [`Vmctx::call_guest()`](../vmctx/struct.Vmctx.html#method.call_guest) can nest.
+pub const MAX_GUEST_CALL_DEPTH: u32 = 64;
+
+/// The stack left between a hostcall and a guest function it calls, for the frames of the runtime
+/// code that starts the guest function.
+const HOSTCALL_FRAME_RESERVE: usize = 4096;
+
+/// What happens when a hostcall defined with `lucet_hostcalls!` panics.
+///
+/// The policy applies to every instance in the process, and is set with
+/// [`set_hostcall_panic_policy()`](fn.set_hostcall_panic_policy.html).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HostcallPanicPolicy {
+    /// Terminate the instance with `TerminationDetails::HostPanic`, leaving the host running. This
+    /// is the default.
+    Terminate,
+    /// Abort the host process.
+    Abort,
+}
+
+static HOSTCALL_PANIC_ABORT: AtomicBool = AtomicBool::new(false);
+
+/// Set what happens when a hostcall panics, for every instance in the process.
+///
+/// Panics that `lucet_hostcall_terminate!` uses to terminate instances are not affected.
+pub fn set_hostcall_panic_policy(policy: HostcallPanicPolicy) {
+    HOSTCALL_PANIC_ABORT.store(policy == HostcallPanicPolicy::Abort, Ordering::SeqCst);
+}
+
+/// Get what happens when a hostcall panics.
+pub fn hostcall_panic_policy() -> HostcallPanicPolicy {
+    if HOSTCALL_PANIC_ABORT.load(Ordering::SeqCst) {
+        HostcallPanicPolicy::Abort
+    } else {
+        HostcallPanicPolicy::Terminate
+    }
+}
+
+/// Turn a panic caught in a hostcall into the termination details of its instance, or abort the
+/// process if the hostcall panic policy is `Abort`.
+///
+/// This is used by `lucet_hostcalls!`, and is not meant to be called directly.
+#[doc(hidden)]
+pub fn hostcall_panic_termination(payload: Box<dyn Any + Send>) -> TerminationDetails {
+    if hostcall_panic_policy() == HostcallPanicPolicy::Abort {
+        // the panic hook has already reported the panic
+        std::process::abort();
+    }
+    let message = if let Some(msg) = payload.downcast_ref::<&'static str>() {
+        msg.to_string()
+    } else if let Some(msg) = payload.downcast_ref::<String>() {
+        msg.clone()
+    } else {
+        "Box<Any>".to_owned()
+    };
+    let location = HOSTCALL_PANIC_LOCATION.with(|location| location.borrow_mut().take());
+    TerminationDetails::HostPanic { message, location }
+}
+
 thread_local! {
     /// The host context.
     ///
@@ -43,6 +126,10 @@ thread_local! {
 
     /// The currently-running `Instance`, if one exists.
     pub(crate) static CURRENT_INSTANCE: RefCell<Option<NonNull<Instance>>> = RefCell::new(None);
+
+    /// The source location of the last panic while an instance was running, recorded by the panic
+    /// hook for `TerminationDetails::HostPanic`.
+    pub(crate) static HOSTCALL_PANIC_LOCATION: RefCell<Option<String>> = RefCell::new(None);
 }
 
 /// A smart pointer to an [`Instance`](struct.Instance.html) that properly manages cleanup when dropped.
@@ -75,6 +162,8 @@ pub fn new_instance_handle(
     module: Arc<dyn Module>,
     alloc: Alloc,
     embed_ctx: CtxMap,
+    global_imports: Vec<Option<i64>>,
+    import_table: ImportTable,
 ) -> Result<InstanceHandle, Error> {
     let inst = NonNull::new(instance)
         .ok_or(lucet_format_err!("instance pointer is null; this is a bug"))?;
@@ -89,7 +178,7 @@ pub fn new_instance_handle(
         needs_inst_drop: false,
     };
 
-    let inst = Instance::new(alloc, module, embed_ctx);
+    let inst = Instance::new(alloc, module, embed_ctx, global_imports, import_table);
 
     unsafe {
         // this is wildly unsafe! you must be very careful to not let the drop impls run on the
@@ -107,11 +196,65 @@ pub fn new_instance_handle(
     Ok(handle)
 }
 
+/// Match the values provided for imported globals against the globals a module imports.
+///
+/// The result is indexed like the module's globals, with `None` for the globals the module defines
+/// itself.
+pub(crate) fn resolve_global_imports(
+    module: &dyn Module,
+    provided: &HashMap<(String, String), Val>,
+) -> Result<Vec<Option<i64>>, Error> {
+    let mut resolved = Vec::with_capacity(module.globals().len());
+    for spec in module.globals() {
+        resolved.push(match spec.global() {
+            Global::Import {
+                module: import_module,
+                field,
+            } => {
+                let val = provided
+                    .get(&(import_module.to_string(), field.to_string()))
+                    .ok_or_else(|| {
+                        Error::SymbolNotFound(format!("global import {}::{}", import_module, field))
+                    })?;
+                if val.value_type() != spec.ty() {
+                    return Err(Error::InvalidArgument(
+                        "value provided for a global import does not have the global's type",
+                    ));
+                }
+                Some(val_to_stack(val) as i64)
+            }
+            Global::Def { .. } => None,
+        });
+    }
+    for (provided_module, provided_field) in provided.keys() {
+        let imported = module.globals().iter().any(|spec| match spec.global() {
+            Global::Import { module, field } => {
+                *module == provided_module.as_str() && *field == provided_field.as_str()
+            }
+            Global::Def { .. } => false,
+        });
+        if !imported {
+            return Err(Error::InvalidArgument(
+                "value provided for a global the module does not import",
+            ));
+        }
+    }
+    Ok(resolved)
+}
+
 pub fn instance_handle_to_raw(mut inst: InstanceHandle) -> *mut Instance {
     inst.needs_inst_drop = false;
     inst.inst.as_ptr()
 }
 
+impl InstanceHandle {
+    /// Get a [`KillSwitch`](struct.KillSwitch.html) that can terminate this instance from another
+    /// thread.
+    pub fn kill_switch(&self) -> KillSwitch {
+        KillSwitch::new(&self.kill_state)
+    }
+}
+
 pub unsafe fn instance_handle_from_raw(
     ptr: *mut Instance,
     needs_inst_drop: bool,
@@ -145,6 +288,11 @@ impl Drop for InstanceHandle {
             unsafe {
                 let inst = self.inst.as_mut();
 
+                // Unwind a suspended hostcall so its frames are dropped. This fails if the
+                // instance is dropped on a thread other than the one it yielded on, in which case
+                // the frames are leaked.
+                let _ = inst.unwind_yielded();
+
                 // Grab a handle to the region to ensure it outlives `inst`.
                 //
                 // This ensures that the region won't be dropped by `inst` being
@@ -188,7 +336,7 @@ pub struct Instance {
     module: Arc<dyn Module>,
 
     /// The `Context` in which the guest program runs
-    ctx: Context,
+    pub(crate) ctx: Context,
 
     /// Instance state and error information
     pub(crate) state: State,
@@ -217,6 +365,57 @@ pub struct Instance {
     /// Pointer to the function used as the entrypoint (for use in backtraces)
     entrypoint: Option<FunctionPointer>,
 
+    /// The return type of the entrypoint, used to type the value it returns
+    entrypoint_ret_ty: Option<ValueType>,
+
+    /// The amount of fuel most recently given to the instance by `Instance::set_fuel()`
+    fuel_allotted: i64,
+
+    /// Shared with the instance's `KillSwitch`es, which set its flag to request that the instance
+    /// terminate
+    pub(crate) kill_state: Arc<KillState>,
+
+    /// The value passed to `Instance::resume()`, to be returned by `Vmctx::yield_val()`
+    pub(crate) resumed_val: Option<Box<dyn Any + 'static>>,
+
+    /// The values of imported globals, indexed like the module's globals, with `None` for globals
+    /// defined by the module
+    global_imports: Vec<Option<i64>>,
+
+    /// The host functions called by modules that link their imports through an import table
+    _import_table: ImportTable,
+
+    /// The directory that core dumps are written to when the instance faults, if any
+    coredump_dir: Option<PathBuf>,
+
+    /// The number of guest functions called from hostcalls that are currently running
+    pub(crate) guest_call_depth: u32,
+
+    /// The size of the host stack that hostcalls run on, if they do not run on the guest stack
+    hostcall_stack_size: Option<usize>,
+
+    /// The bounds of the host stack that the running hostcall is on, if it is on one
+    host_stack_in_use: Option<(usize, usize)>,
+
+    /// The bounds of the host stack left below the running hostcall, set while a guest function it
+    /// called is running
+    host_stack_free: Option<(usize, usize)>,
+
+    /// The guest stack pointer when the running hostcall switched to a host stack
+    guest_stack_top: Option<usize>,
+
+    /// The host stack of a hostcall that yielded while running on it
+    owned_host_stack: Option<HostStack>,
+
+    /// The largest the heap has been before it was last reset or restored
+    heap_high_water: usize,
+
+    /// The number of guest functions run from the host
+    runs: u64,
+
+    /// The CPU time spent in the guest context, including hostcalls
+    guest_cpu_time: Duration,
+
     /// `_padding` must be the last member of the structure.
     /// This marks where the padding starts to make the structure exactly 4096 bytes long.
     /// It is also used to compute the size of the structure up to that point, i.e. without padding.
@@ -284,10 +483,12 @@ impl Instance {
     ///
     /// ```no_run
     /// # use lucet_runtime_internals::instance::InstanceHandle;
+    /// # use lucet_runtime_internals::val::Val;
     /// # let instance: InstanceHandle = unimplemented!();
-    /// // regular execution yields `Ok(UntypedRetVal)`
-    /// let retval = instance.run("factorial", &[5u64.into()]).unwrap();
-    /// assert_eq!(u64::from(retval), 120u64);
+    /// // regular execution yields `Ok(RunResult::Returned { .. })`, with the returned value typed
+    /// // by the entrypoint's signature
+    /// let val = instance.run("factorial", &[5u64.into()]).unwrap().unwrap_returned_val();
+    /// assert_eq!(val, Some(Val::I64(120)));
     ///
     /// // runtime faults yield `Err(Error)`
     /// let result = instance.run("faulting_function", &[]);
@@ -310,7 +511,7 @@ impl Instance {
     ///
     /// For the moment, we do not mark this as `unsafe` in the Rust type system, but that may change
     /// in the future.
-    pub fn run(&mut self, entrypoint: &str, args: &[Val]) -> Result<UntypedRetVal, Error> {
+    pub fn run(&mut self, entrypoint: &str, args: &[Val]) -> Result<RunResult, Error> {
         let func = self.module.get_export_func(entrypoint)?;
         self.run_func(func, &args)
     }
@@ -324,13 +525,78 @@ impl Instance {
         table_idx: u32,
         func_idx: u32,
         args: &[Val],
-    ) -> Result<UntypedRetVal, Error> {
+    ) -> Result<RunResult, Error> {
         let func = self.module.get_func_from_idx(table_idx, func_idx)?;
         self.run_func(func, &args)
     }
 
+    /// Get a handle to an exported function that can be called without looking it up or checking
+    /// its arguments each time.
+    ///
+    /// The function's signature is checked against `Args` and `Ret` here, and an
+    /// `Error::InvalidArgument` is returned if they do not match:
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::instance::InstanceHandle;
+    /// # let mut instance: InstanceHandle = unimplemented!();
+    /// let f = instance.typed_func::<(i32, i64), f64>("f").unwrap();
+    /// let ret: f64 = f.call(&mut instance, (1, 2)).unwrap();
+    /// ```
+    pub fn typed_func<Args: TypedArgs, Ret: TypedRet>(
+        &self,
+        entrypoint: &str,
+    ) -> Result<TypedFunc<Args, Ret>, Error> {
+        let func = self.module.get_export_func(entrypoint)?;
+        TypedFunc::new(self.module.clone(), func)
+    }
+
+    /// Resume execution of an instance that yielded, passing `val` back to the hostcall.
+    ///
+    /// `val` becomes the return value of the
+    /// [`Vmctx::yield_val()`](../vmctx/struct.Vmctx.html#method.yield_val) call that suspended the
+    /// guest, and so its type must match the type that call expects.
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::instance::InstanceHandle;
+    /// # let instance: InstanceHandle = unimplemented!();
+    /// let yielded = instance.run("ask_host", &[]).unwrap().unwrap_yielded();
+    /// let question = yielded.downcast_ref::<String>().unwrap();
+    /// let retval = instance.resume(question.len() as u64).unwrap().unwrap_returned();
+    /// ```
+    ///
+    /// The instance must be resumed on the thread it yielded on, as the suspended hostcall returns
+    /// to that thread's host context when the guest function finishes. Resuming on any other
+    /// thread returns `Error::InvalidArgument`.
+    ///
+    /// The same safety caveats of [`Instance::run()`](struct.Instance.html#method.run) apply.
+    pub fn resume<A: Any + 'static>(&mut self, val: A) -> Result<RunResult, Error> {
+        match &self.state {
+            State::Yielded {
+                expecting, thread, ..
+            } => {
+                if *thread != thread::current().id() {
+                    return Err(Error::InvalidArgument(
+                        "instance must be resumed on the thread it yielded on",
+                    ));
+                }
+                if *expecting != TypeId::of::<A>() {
+                    return Err(Error::InvalidArgument(
+                        "resume value does not have the type expected by the yielding hostcall",
+                    ));
+                }
+            }
+            _ => return Err(Error::InstanceNotYielded),
+        }
+        self.resumed_val = Some(Box::new(val) as Box<dyn Any + 'static>);
+        self.swap_and_return()
+    }
+
     /// Reset the instance's heap and global variables to their initial state.
     ///
+    /// Any termination requested by a [`KillSwitch`](struct.KillSwitch.html) is cleared. Imported
+    /// globals are set back to the values provided by
+    /// [`InstanceBuilder::with_global_import()`](struct.InstanceBuilder.html#method.with_global_import).
+    ///
     /// The WebAssembly `start` section will also be run, if one exists.
     ///
     /// The embedder contexts present at instance creation or added with
@@ -342,31 +608,118 @@ impl Instance {
     ///
     /// This function runs the guest code for the WebAssembly `start` section, and running any guest
     /// code is potentially unsafe; see [`Instance::run()`](struct.Instance.html#method.run).
+    ///
+    /// If the instance has yielded, the suspended hostcall is first unwound, as described for
+    /// [`Vmctx::yield_val()`](../vmctx/struct.Vmctx.html#method.yield_val), which must happen on
+    /// the thread the instance yielded on.
     pub fn reset(&mut self) -> Result<(), Error> {
+        self.unwind_yielded()?;
+        self.kill_state.flag.store(false, Ordering::SeqCst);
+        self.note_heap_high_water();
         self.alloc.reset_heap(self.module.as_ref())?;
         let globals = unsafe { self.alloc.globals_mut() };
         let mod_globals = self.module.globals();
         for (i, v) in mod_globals.iter().enumerate() {
             globals[i] = match v.global() {
-                Global::Import { .. } => {
-                    return Err(Error::Unsupported(format!(
-                        "global imports are unsupported; found: {:?}",
-                        i
-                    )));
-                }
-                Global::Def { def } => def.init_val(),
+                Global::Import { module, field } => self
+                    .global_imports
+                    .get(i)
+                    .and_then(|val| *val)
+                    .ok_or_else(|| {
+                        lucet_format_err!("no value for global import {}::{}", module, field)
+                    })?,
+                Global::Def { def } => match *def {
+                    // narrower values go in the low bits, and floats are stored as their bit
+                    // patterns, as that is how the guest reads them
+                    GlobalDef::I32(v) => v as i64,
+                    GlobalDef::I64(v) => v,
+                    GlobalDef::F32(v) => v.to_bits() as i64,
+                    GlobalDef::F64(v) => v.to_bits() as i64,
+                    GlobalDef::GetGlobal(idx) => {
+                        if idx as usize >= i {
+                            return Err(lucet_incorrect_module!(
+                                "global {} is initialized from global {}, which is not before it",
+                                i,
+                                idx
+                            ));
+                        }
+                        globals[idx as usize]
+                    }
+                },
             };
         }
 
         self.state = State::Ready {
             retval: UntypedRetVal::default(),
         };
+        self.clear_hostcall_stacks();
 
         self.run_start()?;
 
         Ok(())
     }
 
+    /// Take a snapshot of the instance's heap and global variables.
+    ///
+    /// The instance must be in the ready state, having returned from its last guest function or
+    /// having just been reset; the guest stack is not part of the snapshot.
+    pub fn snapshot(&self) -> Result<InstanceSnapshot, Error> {
+        if !self.state.is_ready() {
+            return Err(Error::InvalidArgument(
+                "instance must be ready to take a snapshot",
+            ));
+        }
+        let globals = &self.globals()[..self.module.globals().len()];
+        Ok(InstanceSnapshot::new(
+            self.module.clone(),
+            self.heap(),
+            globals,
+        ))
+    }
+
+    /// Restore the instance's heap and global variables from a snapshot.
+    ///
+    /// This is like [`Instance::reset()`](struct.Instance.html#method.reset), except the instance
+    /// is left in the state captured by the snapshot rather than its initial state, and the
+    /// WebAssembly `start` section is not run. The snapshot must have been taken from an instance
+    /// of the same module.
+    ///
+    /// As with `reset()`, embedder contexts are not modified by this call, and a yielded instance
+    /// has its suspended hostcall unwound first.
+    pub fn restore(&mut self, snapshot: &InstanceSnapshot) -> Result<(), Error> {
+        if !snapshot.is_of_module(&self.module) {
+            return Err(Error::InvalidArgument(
+                "snapshot was taken from an instance of a different module",
+            ));
+        }
+        self.unwind_yielded()?;
+        self.kill_state.flag.store(false, Ordering::SeqCst);
+        self.note_heap_high_water();
+        self.alloc.restore_heap(snapshot, self.module.as_ref())?;
+        let globals = unsafe { self.alloc.globals_mut() };
+        globals[..snapshot.globals().len()].copy_from_slice(snapshot.globals());
+
+        self.state = State::Ready {
+            retval: UntypedRetVal::default(),
+        };
+        self.clear_hostcall_stacks();
+
+        Ok(())
+    }
+
+    /// Write a core dump of the instance to the file at `path`.
+    ///
+    /// The dump holds the heap, the globals, and, if the instance has faulted, the trap code,
+    /// faulting address, and guest backtrace. It can be taken in any state, and is written in the
+    /// WebAssembly core dump format documented in the
+    /// [`coredump`](https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md)
+    /// conventions, with an extra `lucet-fault` custom section for the fault details. Use
+    /// `lucet-analyze coredump <path>` to print one.
+    pub fn write_coredump<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let file = std::fs::File::create(path).map_err(|e| Error::InternalError(e.into()))?;
+        coredump::write_coredump(self, std::io::BufWriter::new(file))
+    }
+
     /// Grow the guest memory by the given number of WebAssembly pages.
     ///
     /// On success, returns the number of pages that existed before the call.
@@ -413,6 +766,37 @@ impl Instance {
         unsafe { self.alloc.globals_mut() }
     }
 
+    /// Get the value of the WebAssembly global exported as `name`.
+    ///
+    /// The value is returned as a `Val` of the global's type: `I32`, `I64`, `F32`, or `F64`.
+    pub fn get_global(&self, name: &str) -> Result<Val, Error> {
+        let (idx, spec) = self.module.get_export_global(name)?;
+        let bits = self.globals()[idx];
+        Ok(match spec.ty() {
+            ValueType::I32 => Val::I32(bits as i32),
+            ValueType::I64 => Val::I64(bits),
+            ValueType::F32 => Val::F32(f32::from_bits(bits as u32)),
+            ValueType::F64 => Val::F64(f64::from_bits(bits as u64)),
+        })
+    }
+
+    /// Set the value of the WebAssembly global exported as `name`.
+    ///
+    /// The global must be mutable, and the value must have the global's type.
+    pub fn set_global(&mut self, name: &str, val: Val) -> Result<(), Error> {
+        let (idx, spec) = self.module.get_export_global(name)?;
+        if !spec.is_mutable() {
+            return Err(Error::InvalidArgument("global is immutable"));
+        }
+        if spec.ty() != val.value_type() {
+            return Err(Error::InvalidArgument(
+                "value does not have the type of the global",
+            ));
+        }
+        self.globals_mut()[idx] = val_to_stack(&val) as i64;
+        Ok(())
+    }
+
     /// Check whether a given range in the host address space overlaps with the memory that backs
     /// the instance heap.
     pub fn check_heap<T>(&self, ptr: *const T, len: usize) -> bool {
@@ -499,12 +883,98 @@ impl Instance {
     pub fn set_c_fatal_handler(&mut self, handler: unsafe extern "C" fn(*mut Instance)) {
         self.c_fatal_handler = Some(handler);
     }
+
+    /// Write a core dump into `dir` whenever the instance faults, or stop writing them if `dir` is
+    /// `None`.
+    ///
+    /// Dumps are written as with
+    /// [`Instance::write_coredump()`](struct.Instance.html#method.write_coredump), to files named
+    /// `lucet-<pid>-<n>.core`, before any fatal handler runs. Failing to write a dump does not
+    /// change the result of the run; the failure is logged as a warning through the `log` crate.
+    /// This setting is kept when the instance is reset.
+    pub fn set_coredump_dir(&mut self, dir: Option<PathBuf>) {
+        self.coredump_dir = dir;
+    }
+
+    /// Set the size of the host stack that hostcalls defined with `lucet_hostcalls!` run on, or
+    /// `None` to run them on the guest stack.
+    ///
+    /// Each thread running instances with this setting has a host stack with a guard page below
+    /// it, which is allocated as the instance starts running and is large enough for every such
+    /// instance on the thread. A hostcall switches to it on entry and back to the guest stack on
+    /// return.
+    ///
+    /// The size must be a nonzero multiple of the host page size. This setting is kept when the
+    /// instance is reset, and takes effect at the next call to
+    /// [`Instance::run()`](struct.Instance.html#method.run).
+    ///
+    /// Hostcalls that overflow their stack terminate the instance with
+    /// `TerminationDetails::HostStackOverflow` whether or not this is set. The frames of the
+    /// hostcall are abandoned rather than unwound.
+    pub fn set_hostcall_stack_size(&mut self, size: Option<usize>) -> Result<(), Error> {
+        if let Some(size) = size {
+            if size == 0 || size % host_page_size() != 0 {
+                return Err(Error::InvalidArgument(
+                    "hostcall stack size must be a nonzero multiple of the host page size",
+                ));
+            }
+        }
+        self.hostcall_stack_size = size;
+        Ok(())
+    }
+
+    /// Set the amount of fuel available to guest code compiled with fuel metering.
+    ///
+    /// A unit of fuel is consumed at each function entry and loop iteration. When the fuel runs
+    /// out, the guest faults with `TrapCode::FuelExhausted`; the instance can then be given more
+    /// fuel and run again.
+    ///
+    /// Instances start with an effectively unlimited amount of fuel, which is not modified by
+    /// [`Instance::reset()`](struct.Instance.html#method.reset). Values larger than `i64::MAX`
+    /// are clamped. This setting has no effect on modules compiled without fuel metering.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        let fuel = fuel.min(std::i64::MAX as u64) as i64;
+        self.fuel_allotted = fuel;
+        self.set_fuel_counter(fuel);
+    }
+
+    /// Return the amount of fuel consumed since the last call to
+    /// [`Instance::set_fuel()`](struct.Instance.html#method.set_fuel).
+    pub fn fuel_consumed(&self) -> u64 {
+        // the counter goes negative on the metering check that exhausts the fuel
+        let remaining = self.get_fuel_counter().max(0);
+        (self.fuel_allotted - remaining) as u64
+    }
+
+    /// Return the resources the instance has used since it was created.
+    ///
+    /// The usage is not cleared by [`Instance::reset()`](struct.Instance.html#method.reset) or
+    /// [`Instance::restore()`](struct.Instance.html#method.restore).
+    pub fn resource_usage(&self) -> ResourceUsage {
+        let heap_size = self.alloc.heap_len();
+        ResourceUsage {
+            heap_size,
+            heap_high_water: self.heap_high_water.max(heap_size),
+            stack_high_water: self.alloc.stack_high_water(),
+            runs: self.runs,
+            guest_cpu_time: self.guest_cpu_time,
+        }
+    }
 }
 
 // Private API
 impl Instance {
-    fn new(alloc: Alloc, module: Arc<dyn Module>, embed_ctx: CtxMap) -> Self {
+    fn new(
+        alloc: Alloc,
+        module: Arc<dyn Module>,
+        embed_ctx: CtxMap,
+        global_imports: Vec<Option<i64>>,
+        import_table: ImportTable,
+    ) -> Self {
         let globals_ptr = alloc.slot().globals as *mut i64;
+        let kill_state = Arc::new(KillState::new());
+        let kill_flag_ptr = &kill_state.flag as *const AtomicBool;
+        let import_table_ptr = import_table.as_ptr();
         let mut inst = Instance {
             magic: LUCET_INSTANCE_MAGIC,
             embed_ctx: embed_ctx,
@@ -518,13 +988,40 @@ impl Instance {
             c_fatal_handler: None,
             signal_handler: Box::new(signal_handler_none) as Box<SignalHandler>,
             entrypoint: None,
+            entrypoint_ret_ty: None,
+            fuel_allotted: 0,
+            kill_state,
+            resumed_val: None,
+            global_imports,
+            _import_table: import_table,
+            coredump_dir: None,
+            guest_call_depth: 0,
+            hostcall_stack_size: None,
+            host_stack_in_use: None,
+            host_stack_free: None,
+            guest_stack_top: None,
+            owned_host_stack: None,
+            heap_high_water: 0,
+            runs: 0,
+            guest_cpu_time: Duration::default(),
             _padding: (),
         };
         inst.set_globals_ptr(globals_ptr);
+        inst.set_fuel(std::i64::MAX as u64);
+        inst.set_kill_flag_ptr(kill_flag_ptr);
+        inst.set_import_table_ptr(import_table_ptr);
 
         assert_eq!(mem::size_of::<Instance>(), HOST_PAGE_SIZE_EXPECTED);
         let unpadded_size = offset_of!(Instance, _padding);
-        assert!(unpadded_size <= HOST_PAGE_SIZE_EXPECTED - mem::size_of::<*mut i64>());
+        assert!(
+            unpadded_size
+                <= HOST_PAGE_SIZE_EXPECTED
+                    - mem::size_of::<*mut i64>()
+                    - mem::size_of::<i64>()
+                    - mem::size_of::<*const AtomicBool>()
+                    - mem::size_of::<*const ImportTableEntry>()
+                    - mem::size_of::<*const c_void>()
+        );
         inst
     }
 
@@ -550,12 +1047,95 @@ impl Instance {
         }
     }
 
+    // The fuel counter used by fuel-metered code is stored right before the globals pointer, so
+    // that it is 16 bytes before the heap.
+    #[inline]
+    fn get_fuel_counter(&self) -> i64 {
+        unsafe {
+            *((self as *const _ as *const u8).offset(
+                (HOST_PAGE_SIZE_EXPECTED - mem::size_of::<*mut i64>() - mem::size_of::<i64>())
+                    as isize,
+            ) as *const i64)
+        }
+    }
+
+    #[inline]
+    fn set_fuel_counter(&mut self, fuel: i64) {
+        unsafe {
+            *((self as *mut _ as *mut u8).offset(
+                (HOST_PAGE_SIZE_EXPECTED - mem::size_of::<*mut i64>() - mem::size_of::<i64>())
+                    as isize,
+            ) as *mut i64) = fuel;
+        }
+    }
+
+    // The pointer to the kill flag checked by code compiled with interrupt checks is stored right
+    // before the fuel counter, so that it is 24 bytes before the heap.
+    #[inline]
+    fn set_kill_flag_ptr(&mut self, kill_flag_ptr: *const AtomicBool) {
+        unsafe {
+            *((self as *mut _ as *mut u8).offset(
+                (HOST_PAGE_SIZE_EXPECTED
+                    - mem::size_of::<*mut i64>()
+                    - mem::size_of::<i64>()
+                    - mem::size_of::<*const AtomicBool>()) as isize,
+            ) as *mut *const AtomicBool) = kill_flag_ptr;
+        }
+    }
+
+    // The pointer to the import table used by code that links its imports through the table is
+    // stored right before the kill flag pointer, so that it is 32 bytes before the heap.
+    #[inline]
+    fn set_import_table_ptr(&mut self, import_table_ptr: *const ImportTableEntry) {
+        unsafe {
+            *((self as *mut _ as *mut u8).offset(
+                (HOST_PAGE_SIZE_EXPECTED
+                    - mem::size_of::<*mut i64>()
+                    - mem::size_of::<i64>()
+                    - mem::size_of::<*const AtomicBool>()
+                    - mem::size_of::<*const ImportTableEntry>()) as isize,
+            ) as *mut *const ImportTableEntry) = import_table_ptr;
+        }
+    }
+
+    // The context pointer of the import table entry being called is stored right before the
+    // import table pointer, so that it is 40 bytes before the heap. The code that calls through the
+    // import table stores it there before each call.
+    #[inline]
+    pub(crate) fn get_import_ctx(&self) -> *const c_void {
+        unsafe {
+            *((self as *const _ as *const u8).offset(
+                (HOST_PAGE_SIZE_EXPECTED
+                    - mem::size_of::<*mut i64>()
+                    - mem::size_of::<i64>()
+                    - mem::size_of::<*const AtomicBool>()
+                    - mem::size_of::<*const ImportTableEntry>()
+                    - mem::size_of::<*const c_void>()) as isize,
+            ) as *const *const c_void)
+        }
+    }
+
+    /// Record the size of the heap before it is reset or restored, which may shrink it.
+    ///
+    /// The heap only grows otherwise, so its high-water mark is the larger of this and its current
+    /// size.
+    fn note_heap_high_water(&mut self) {
+        self.heap_high_water = self.heap_high_water.max(self.alloc.heap_len());
+    }
+
+    /// Check whether a `KillSwitch` has requested that the instance terminate.
+    pub(crate) fn kill_requested(&self) -> bool {
+        self.kill_state.flag.load(Ordering::SeqCst)
+    }
+
     /// Run a function in guest context at the given entrypoint.
-    fn run_func(&mut self, func: FunctionHandle, args: &[Val]) -> Result<UntypedRetVal, Error> {
-        lucet_ensure!(
-            self.state.is_ready() || (self.state.is_fault() && !self.state.is_fatal()),
-            "instance must be ready or non-fatally faulted"
-        );
+    fn run_func(&mut self, func: FunctionHandle, args: &[Val]) -> Result<RunResult, Error> {
+        let ret_ty = self.check_func(func, args)?;
+        self.run_func_unchecked(func.ptr, args, ret_ty)
+    }
+
+    /// Check that `func` can be called with `args`, and return its return type.
+    fn check_func(&self, func: FunctionHandle, args: &[Val]) -> Result<Option<ValueType>, Error> {
         if func.ptr.as_usize() == 0 {
             return Err(Error::InvalidArgument(
                 "entrypoint function cannot be null; this is probably a malformed module",
@@ -564,8 +1144,8 @@ impl Instance {
 
         let sig = self.module.get_signature(func.id);
 
-        // in typechecking these values, we can only really check that arguments are correct.
-        // in the future we might want to make return value use more type safe as well.
+        // the arguments are checked here; the return value is typed by `ret_ty` once the function
+        // returns
 
         if sig.params.len() != args.len() {
             return Err(Error::InvalidArgument(
@@ -581,7 +1161,36 @@ impl Instance {
             }
         }
 
-        self.entrypoint = Some(func.ptr);
+        Ok(sig.ret_ty)
+    }
+
+    /// Run a function in guest context without checking its arguments against its signature.
+    ///
+    /// The caller must ensure that `func` is a non-null function of this instance's module, that
+    /// `args` match its parameters, and that `ret_ty` is its return type.
+    pub(crate) fn run_func_unchecked(
+        &mut self,
+        func: FunctionPointer,
+        args: &[Val],
+        ret_ty: Option<ValueType>,
+    ) -> Result<RunResult, Error> {
+        lucet_ensure!(
+            self.state.is_ready() || (self.state.is_fault() && !self.state.is_fatal()),
+            "instance must be ready or non-fatally faulted"
+        );
+
+        // a termination requested since the last run takes effect before any guest code runs,
+        // whether or not the module was compiled with interrupt checks
+        if self.kill_requested() {
+            self.state = State::Terminated {
+                details: TerminationDetails::Remote,
+            };
+            return Err(Error::RuntimeTerminated(TerminationDetails::Remote));
+        }
+
+        self.entrypoint = Some(func);
+        self.entrypoint_ret_ty = ret_ty;
+        self.clear_hostcall_stacks();
 
         let mut args_with_vmctx = vec![Val::from(self.alloc.slot().heap)];
         args_with_vmctx.extend_from_slice(args);
@@ -591,11 +1200,25 @@ impl Instance {
                 unsafe { self.alloc.stack_u64_mut() },
                 unsafe { &mut *host_ctx.get() },
                 &mut self.ctx,
-                func.ptr,
+                func,
                 &args_with_vmctx,
             )
         })?;
 
+        self.runs += 1;
+        self.swap_and_return()
+    }
+
+    /// Swap to the guest context, and then interpret the instance state once control returns to the
+    /// host.
+    ///
+    /// This is shared by `run_func()`, which has just initialized the guest context, and
+    /// `resume()`, which continues a guest context saved by `Vmctx::yield_val()`.
+    fn swap_and_return(&mut self) -> Result<RunResult, Error> {
+        if let Some(size) = self.hostcall_stack_size {
+            host_stack::ensure_host_stack(size)?;
+        }
+
         self.state = State::Running;
 
         // there should never be another instance running on this thread when we enter this function
@@ -609,15 +1232,17 @@ impl Instance {
             *current_instance = Some(unsafe { NonNull::new_unchecked(self) });
         });
 
+        let cpu_time_before = thread_cpu_time();
         self.with_signals_on(|i| {
             HOST_CTX.with(|host_ctx| {
                 // Save the current context into `host_ctx`, and jump to the guest context. The
                 // lucet context is linked to host_ctx, so it will return here after it finishes,
-                // successfully or otherwise.
+                // successfully or otherwise, or after a hostcall yields.
                 unsafe { Context::swap(&mut *host_ctx.get(), &mut i.ctx) };
                 Ok(())
             })
         })?;
+        self.guest_cpu_time += thread_cpu_time() - cpu_time_before;
 
         CURRENT_INSTANCE.with(|current_instance| {
             *current_instance.borrow_mut() = None;
@@ -626,50 +1251,229 @@ impl Instance {
         // Sandbox has jumped back to the host process, indicating it has either:
         //
         // * trapped, or called hostcall_error: state tag changed to something other than `Running`
+        // * yielded from a hostcall: state tag changed to `Yielded`
         // * function body returned: set state back to `Ready` with return value
 
         match &self.state {
             State::Running => {
                 let retval = self.ctx.get_untyped_retval();
                 self.state = State::Ready { retval };
-                Ok(retval)
+                let val = self.entrypoint_ret_ty.map(|ty| retval.to_val(ty));
+                Ok(RunResult::Returned { val, retval })
             }
-            State::Terminated { details, .. } => Err(Error::RuntimeTerminated(details.clone())),
-            State::Fault { .. } => {
-                // Sandbox is no longer runnable. It's unsafe to determine all error details in the signal
-                // handler, so we fill in extra details here.
-                self.populate_fault_detail()?;
-                if let State::Fault { ref details, .. } = self.state {
-                    if details.fatal {
-                        // Some errors indicate that the guest is not functioning correctly or that
-                        // the loaded code violated some assumption, so bail out via the fatal
-                        // handler.
-
-                        // Run the C-style fatal handler, if it exists.
-                        self.c_fatal_handler
-                            .map(|h| unsafe { h(self as *mut Instance) });
-
-                        // If there is no C-style fatal handler, or if it (erroneously) returns,
-                        // call the Rust handler that we know will not return
-                        (self.fatal_handler)(self)
-                    } else {
-                        // leave the full fault details in the instance state, and return the
-                        // higher-level info to the user
-                        Err(Error::RuntimeFault(details.clone()))
-                    }
-                } else {
-                    panic!("state remains Fault after populate_fault_detail()")
+            State::Yielded { val, .. } => {
+                let val = val.clone();
+                if self.host_stack_in_use.is_some() && self.owned_host_stack.is_none() {
+                    // the yielding hostcall is on this thread's host stack, which it needs when it
+                    // is resumed
+                    self.owned_host_stack = host_stack::take_host_stack();
                 }
+                Ok(RunResult::Yielded(val))
             }
+            State::Terminated { details, .. } => Err(Error::RuntimeTerminated(details.clone())),
+            // leave the full fault details in the instance state, and return the higher-level
+            // info to the user
+            State::Fault { .. } => Err(Error::RuntimeFault(self.handle_fault()?)),
             State::Ready { .. } => {
                 panic!("instance in Ready state after returning from guest context")
             }
         }
     }
 
+    /// Handle a fault that has just returned control to the host, returning its details if it is
+    /// not fatal.
+    fn handle_fault(&mut self) -> Result<FaultDetails, Error> {
+        // Sandbox is no longer runnable. It's unsafe to determine all error details in the signal
+        // handler, so we fill in extra details here.
+        self.populate_fault_detail()?;
+        self.write_fault_coredump();
+        if let State::Fault { ref details, .. } = self.state {
+            if details.fatal {
+                // Some errors indicate that the guest is not functioning correctly or that the
+                // loaded code violated some assumption, so bail out via the fatal handler.
+
+                // Run the C-style fatal handler, if it exists.
+                self.c_fatal_handler
+                    .map(|h| unsafe { h(self as *mut Instance) });
+
+                // If there is no C-style fatal handler, or if it (erroneously) returns, call the
+                // Rust handler that we know will not return
+                (self.fatal_handler)(self)
+            } else {
+                Ok(details.clone())
+            }
+        } else {
+            panic!("state remains Fault after populate_fault_detail()")
+        }
+    }
+
+    /// Run a guest function from a hostcall, for
+    /// [`Vmctx::call_guest()`](../vmctx/struct.Vmctx.html#method.call_guest).
+    ///
+    /// The callee runs on the unused part of the guest stack below the hostcall, or below where the
+    /// hostcall switched to a host stack. In that case, hostcalls made by the callee run on the
+    /// host stack below the hostcall. While the callee runs, `HOST_CTX` holds the point in this
+    /// function where it started, so that the callee returns, faults, or terminates back to here
+    /// rather than to `swap_and_return()`. The host context and guest context of the calling guest
+    /// are saved beforehand and restored afterwards, as the callee's stack points to their
+    /// locations.
+    pub(crate) fn call_guest_from_hostcall(
+        &mut self,
+        func: FunctionHandle,
+        args: &[Val],
+    ) -> Result<UntypedRetVal, Error> {
+        lucet_ensure!(
+            self.state.is_running(),
+            "guest functions can only be called from a running instance"
+        );
+        self.check_func(func, args)?;
+        if self.guest_call_depth >= MAX_GUEST_CALL_DEPTH {
+            return Err(Error::LimitsExceeded(format!(
+                "guest functions called from hostcalls nested more than {} deep",
+                MAX_GUEST_CALL_DEPTH
+            )));
+        }
+
+        // The address of a local approximates the stack pointer. The space just below it is left
+        // for the frames of the calls that set up and swap to the callee's context.
+        let stack_bottom = self.alloc.slot().stack as usize;
+        let local = &stack_bottom as *const usize as usize;
+        let host_stack_free = self.host_stack_below(local).transpose()?;
+        let here = self.guest_stack_top.unwrap_or(local);
+        if here <= stack_bottom || here > self.alloc.slot().stack_top() as usize {
+            lucet_bail!("guest function called from a hostcall that is not on the guest stack");
+        }
+        if here - stack_bottom < HOSTCALL_FRAME_RESERVE + host_page_size() {
+            return Err(Error::LimitsExceeded(
+                "not enough guest stack left to call a guest function".to_owned(),
+            ));
+        }
+        let stack_end = (here - HOSTCALL_FRAME_RESERVE) & !15;
+        let stack = unsafe {
+            std::slice::from_raw_parts_mut(
+                stack_bottom as *mut u64,
+                (stack_end - stack_bottom) / mem::size_of::<u64>(),
+            )
+        };
+
+        let mut args_with_vmctx = vec![Val::from(self.alloc.slot().heap)];
+        args_with_vmctx.extend_from_slice(args);
+
+        let retval = HOST_CTX.with(|host_ctx| {
+            let host_ctx = host_ctx.get();
+            // `Context` is plain data, so these copies can be written back over the originals
+            let saved_host_ctx = unsafe { ptr::read(host_ctx) };
+            let saved_guest_ctx = unsafe { ptr::read(&self.ctx) };
+            // the callee's hostcalls change these as they run, and may not return to restore them
+            let saved_host_stack_in_use = self.host_stack_in_use.take();
+            let saved_guest_stack_top = self.guest_stack_top.take();
+            self.host_stack_free = host_stack_free;
+
+            let res = Context::init(
+                stack,
+                unsafe { &mut *host_ctx },
+                &mut self.ctx,
+                func.ptr,
+                &args_with_vmctx,
+            );
+            if res.is_ok() {
+                self.guest_call_depth += 1;
+                unsafe { Context::swap(&mut *host_ctx, &self.ctx) };
+                self.guest_call_depth -= 1;
+            }
+            let retval = self.ctx.get_untyped_retval();
+
+            unsafe {
+                ptr::write(host_ctx, saved_host_ctx);
+                ptr::write(&mut self.ctx, saved_guest_ctx);
+            }
+            self.host_stack_in_use = saved_host_stack_in_use;
+            self.guest_stack_top = saved_guest_stack_top;
+            self.host_stack_free = None;
+            res.map(|_| retval)
+        })?;
+
+        // the calling guest keeps running whatever happened to the callee
+        match mem::replace(&mut self.state, State::Running) {
+            State::Running => Ok(retval),
+            State::Terminated { details, .. } => Err(Error::RuntimeTerminated(details)),
+            State::Yielded { .. } => Err(Error::Unsupported(
+                "yielding from a guest function called by a hostcall".to_owned(),
+            )),
+            state @ State::Fault { .. } => {
+                self.state = state;
+                let details = self.handle_fault()?;
+                self.state = State::Running;
+                Err(Error::RuntimeFault(details))
+            }
+            State::Ready { .. } => {
+                panic!("instance in Ready state after returning from guest context")
+            }
+        }
+    }
+
+    /// Forget the host stacks of hostcalls from an earlier run, which can no longer be resumed.
+    /// Unwind the hostcall suspended by `Vmctx::yield_val()`, if the instance has yielded.
+    ///
+    /// The guest is resumed without a value, so the hostcall terminates the instance with
+    /// `TerminationDetails::YieldAbandoned` and its frames are dropped on the way out.
+    fn unwind_yielded(&mut self) -> Result<(), Error> {
+        match &self.state {
+            State::Yielded { thread, .. } => {
+                if *thread != thread::current().id() {
+                    return Err(Error::InvalidArgument(
+                        "yielded instance must be reset or restored on the thread it yielded on",
+                    ));
+                }
+            }
+            _ => return Ok(()),
+        }
+        if CURRENT_INSTANCE.with(|current_instance| current_instance.borrow().is_some()) {
+            return Err(Error::Unsupported(
+                "cannot unwind a yielded instance while another instance is running on this thread"
+                    .to_owned(),
+            ));
+        }
+        self.resumed_val = None;
+        let res = self.swap_and_return();
+        if self.state.is_yielded() {
+            return Err(res.err().unwrap_or_else(|| {
+                Error::Unsupported("hostcall yielded again while being unwound".to_owned())
+            }));
+        }
+        Ok(())
+    }
+
+    fn clear_hostcall_stacks(&mut self) {
+        self.host_stack_in_use = None;
+        self.host_stack_free = None;
+        self.guest_stack_top = None;
+        self.owned_host_stack = None;
+    }
+
+    fn write_fault_coredump(&self) {
+        static COREDUMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        if let Some(ref dir) = self.coredump_dir {
+            let path = dir.join(format!(
+                "lucet-{}-{}.core",
+                std::process::id(),
+                COREDUMP_COUNT.fetch_add(1, Ordering::SeqCst)
+            ));
+            if let Err(e) = self.write_coredump(&path) {
+                warn!(
+                    "instance {:p} failed to write core dump to {}: {}",
+                    self,
+                    path.display(),
+                    e
+                );
+            }
+        }
+    }
+
     fn run_start(&mut self) -> Result<(), Error> {
         if let Some(start) = self.module.get_start_func()? {
-            self.run_func(start, &[])?;
+            self.run_func(start, &[])?.returned()?;
         }
         Ok(())
     }
@@ -680,8 +1484,11 @@ impl Instance {
                 FaultDetails {
                     rip_addr,
                     ref mut rip_addr_details,
+                    ref mut wasm_offset,
+                    ref mut backtrace,
                     ..
                 },
+            ref mut context,
             ..
         } = self.state
         {
@@ -690,6 +1497,18 @@ impl Instance {
             // FIXME after lucet-module is complete it should be possible to fill this in without
             // consulting the process symbol table
             *rip_addr_details = self.module.addr_details(rip_addr as *const c_void)?.clone();
+            *wasm_offset = self.module.lookup_wasm_offset(rip_addr as *const c_void);
+
+            // The backtrace allocates, so it is also not built in the signal handler
+            let ctx = context.as_ptr();
+            let slot = self.alloc.slot();
+            *backtrace = walk_guest_stack(
+                &*self.module,
+                (slot.stack as uintptr_t, slot.stack_top() as uintptr_t),
+                rip_addr,
+                ctx.get_fp() as uintptr_t,
+                ctx.get_sp() as uintptr_t,
+            );
         }
         Ok(())
     }
@@ -708,6 +1527,140 @@ pub enum State {
     Terminated {
         details: TerminationDetails,
     },
+    Yielded {
+        val: YieldedVal,
+        /// The type of value the yielding hostcall expects to be passed to `Instance::resume()`.
+        expecting: TypeId,
+        /// The thread the instance yielded on, which is the only thread it can be resumed on.
+        thread: ThreadId,
+    },
+}
+
+/// The result of running or resuming an [`Instance`](struct.Instance.html).
+#[derive(Debug)]
+pub enum RunResult {
+    /// The guest function returned normally.
+    ///
+    /// `val` is the returned value typed by the function's signature, or `None` if the function
+    /// does not return a value. `retval` is the same value without type information.
+    Returned {
+        val: Option<Val>,
+        retval: UntypedRetVal,
+    },
+    /// A hostcall suspended the guest with
+    /// [`Vmctx::yield_val()`](../vmctx/struct.Vmctx.html#method.yield_val).
+    ///
+    /// The guest can be continued with [`Instance::resume()`](struct.Instance.html#method.resume).
+    Yielded(YieldedVal),
+}
+
+impl RunResult {
+    /// Get the returned value, or fail with `Error::InstanceNotReturned` if the instance yielded.
+    pub fn returned(self) -> Result<UntypedRetVal, Error> {
+        match self {
+            RunResult::Returned { retval, .. } => Ok(retval),
+            RunResult::Yielded(_) => Err(Error::InstanceNotReturned),
+        }
+    }
+
+    /// Get the returned value, panicking if the instance yielded.
+    pub fn unwrap_returned(self) -> UntypedRetVal {
+        self.returned().expect("instance returned")
+    }
+
+    /// Get the returned value typed by the function's signature, or fail with
+    /// `Error::InstanceNotReturned` if the instance yielded.
+    ///
+    /// The value is `None` if the function does not return a value.
+    pub fn returned_val(self) -> Result<Option<Val>, Error> {
+        match self {
+            RunResult::Returned { val, .. } => Ok(val),
+            RunResult::Yielded(_) => Err(Error::InstanceNotReturned),
+        }
+    }
+
+    /// Get the returned value typed by the function's signature, panicking if the instance
+    /// yielded.
+    pub fn unwrap_returned_val(self) -> Option<Val> {
+        self.returned_val().expect("instance returned")
+    }
+
+    /// Get the yielded value, or fail with `Error::InstanceNotYielded` if the instance returned.
+    pub fn yielded(self) -> Result<YieldedVal, Error> {
+        match self {
+            RunResult::Returned { .. } => Err(Error::InstanceNotYielded),
+            RunResult::Yielded(val) => Ok(val),
+        }
+    }
+
+    /// Get the yielded value, panicking if the instance returned.
+    pub fn unwrap_yielded(self) -> YieldedVal {
+        self.yielded().expect("instance yielded")
+    }
+
+    pub fn is_returned(&self) -> bool {
+        if let RunResult::Returned { .. } = self {
+            true
+        } else {
+            false
+        }
+    }
+
+    pub fn is_yielded(&self) -> bool {
+        if let RunResult::Yielded(_) = self {
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// A value yielded by a hostcall with
+/// [`Vmctx::yield_val()`](../vmctx/struct.Vmctx.html#method.yield_val).
+#[derive(Clone)]
+pub struct YieldedVal {
+    val: Arc<dyn Any + 'static + Send + Sync>,
+}
+
+impl YieldedVal {
+    pub(crate) fn new<A: Any + 'static + Send + Sync>(val: A) -> Self {
+        YieldedVal { val: Arc::new(val) }
+    }
+
+    /// Check whether the yielded value has a particular type.
+    pub fn is<A: Any>(&self) -> bool {
+        self.val.is::<A>()
+    }
+
+    /// Get a reference to the yielded value, if it has a particular type.
+    pub fn downcast_ref<A: Any>(&self) -> Option<&A> {
+        self.val.downcast_ref::<A>()
+    }
+}
+
+impl std::fmt::Debug for YieldedVal {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "YieldedVal(Any)")
+    }
+}
+
+/// The resources used by an instance; returned by
+/// [`Instance::resource_usage()`](struct.Instance.html#method.resource_usage).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResourceUsage {
+    /// The current size of the heap, in bytes.
+    pub heap_size: usize,
+    /// The largest size of the heap, in bytes.
+    pub heap_high_water: usize,
+    /// The most guest stack used at once, in bytes.
+    ///
+    /// This includes the stack used by hostcalls that run on the guest stack.
+    pub stack_high_water: usize,
+    /// The number of guest functions run from the host, including the WebAssembly `start`
+    /// section. Resuming a yielded instance continues a run rather than starting a new one.
+    pub runs: u64,
+    /// The CPU time spent running guest functions, including the hostcalls they make.
+    pub guest_cpu_time: Duration,
 }
 
 /// Information about a runtime fault.
@@ -724,6 +1677,13 @@ pub struct FaultDetails {
     pub rip_addr: uintptr_t,
     /// Extra information about the instruction pointer's location, if available.
     pub rip_addr_details: Option<module::AddrDetails>,
+    /// The byte offset in the WebAssembly module binary of the instruction that faulted, if the
+    /// fault happened in code compiled from WebAssembly.
+    pub wasm_offset: Option<u32>,
+    /// The guest stack at the time of the fault, starting with the faulting function.
+    ///
+    /// This is empty if the fault did not happen in WebAssembly code.
+    pub backtrace: Vec<Frame>,
 }
 
 impl std::fmt::Display for FaultDetails {
@@ -742,6 +1702,10 @@ impl std::fmt::Display for FaultDetails {
 
         write!(f, "code at address {:p}", self.rip_addr as *const c_void)?;
 
+        if let Some(wasm_offset) = self.wasm_offset {
+            write!(f, " (wasm offset {:#x})", wasm_offset)?;
+        }
+
         if let Some(ref addr_details) = self.rip_addr_details {
             if let Some(ref fname) = addr_details.file_name {
                 let sname = addr_details
@@ -752,13 +1716,21 @@ impl std::fmt::Display for FaultDetails {
                 write!(f, " (symbol {}:{})", fname, sname)?;
             }
             if addr_details.in_module_code {
-                write!(f, " (inside module code)")
+                write!(f, " (inside module code)")?;
             } else {
-                write!(f, " (not inside module code)")
+                write!(f, " (not inside module code)")?;
             }
         } else {
-            write!(f, " (unknown whether in module)")
+            write!(f, " (unknown whether in module)")?;
+        }
+
+        if !self.backtrace.is_empty() {
+            write!(f, "\nbacktrace:")?;
+            for (i, frame) in self.backtrace.iter().enumerate() {
+                write!(f, "\n  {}: {}", i, frame)?;
+            }
         }
+        Ok(())
     }
 }
 
@@ -777,6 +1749,27 @@ pub enum TerminationDetails {
     BorrowError(&'static str),
     /// Calls to `lucet_hostcall_terminate` provide a payload for use by the embedder.
     Provided(Arc<dyn Any + 'static + Send + Sync>),
+    /// Returned when the instance is terminated by a `KillSwitch`.
+    Remote,
+    /// Returned when a hostcall defined with `#[lucet_hostcall]` is passed a guest pointer that is
+    /// out of bounds or misaligned, or a string that is not valid UTF-8, or when a hostcall
+    /// terminates the instance with a `GuestMemoryError` of its own.
+    GuestMemoryError(GuestMemoryError),
+    /// Returned when a hostcall panics, with the panic message and the source location of the
+    /// panic, if it is known.
+    HostPanic {
+        message: String,
+        location: Option<String>,
+    },
+    /// Returned when a guest function called by a hostcall with `Vmctx::call_guest()` tries to
+    /// yield, which would abandon the hostcall that called it.
+    YieldInGuestCall,
+    /// Returned to a yielded hostcall that is unwound because its instance is reset, restored from
+    /// a snapshot, or dropped instead of resumed.
+    YieldAbandoned,
+    /// Returned when a hostcall overflows its stack, whether that is the guest stack or a host
+    /// stack set with `Instance::set_hostcall_stack_size()`.
+    HostStackOverflow,
 }
 
 impl TerminationDetails {
@@ -791,6 +1784,12 @@ impl TerminationDetails {
     }
 }
 
+impl From<GuestMemoryError> for TerminationDetails {
+    fn from(e: GuestMemoryError) -> Self {
+        TerminationDetails::GuestMemoryError(e)
+    }
+}
+
 // Because of deref coercions, the code above was tricky to get right-
 // test that a string makes it through
 #[test]
@@ -811,6 +1810,21 @@ impl PartialEq for TerminationDetails {
             (Signal, Signal) => true,
             (BorrowError(msg1), BorrowError(msg2)) => msg1 == msg2,
             (CtxNotFound, CtxNotFound) => true,
+            (Remote, Remote) => true,
+            (YieldInGuestCall, YieldInGuestCall) => true,
+            (YieldAbandoned, YieldAbandoned) => true,
+            (HostStackOverflow, HostStackOverflow) => true,
+            (GuestMemoryError(e1), GuestMemoryError(e2)) => e1 == e2,
+            (
+                HostPanic {
+                    message: message1,
+                    location: location1,
+                },
+                HostPanic {
+                    message: message2,
+                    location: location2,
+                },
+            ) => message1 == message2 && location1 == location2,
             // can't compare `Any`
             _ => false,
         }
@@ -825,6 +1839,15 @@ impl std::fmt::Debug for TerminationDetails {
             TerminationDetails::BorrowError(msg) => write!(f, "BorrowError({})", msg),
             TerminationDetails::CtxNotFound => write!(f, "CtxNotFound"),
             TerminationDetails::Provided(_) => write!(f, "Provided(Any)"),
+            TerminationDetails::Remote => write!(f, "Remote"),
+            TerminationDetails::GuestMemoryError(e) => write!(f, "GuestMemoryError({})", e),
+            TerminationDetails::HostPanic { message, location } => match location {
+                Some(location) => write!(f, "HostPanic({} at {})", message, location),
+                None => write!(f, "HostPanic({})", message),
+            },
+            TerminationDetails::YieldInGuestCall => write!(f, "YieldInGuestCall"),
+            TerminationDetails::YieldAbandoned => write!(f, "YieldAbandoned"),
+            TerminationDetails::HostStackOverflow => write!(f, "HostStackOverflow"),
         }
     }
 }
@@ -861,6 +1884,7 @@ impl std::fmt::Display for State {
                 Ok(())
             }
             State::Terminated { .. } => write!(f, "terminated"),
+            State::Yielded { .. } => write!(f, "yielded"),
         }
     }
 }
@@ -909,12 +1933,31 @@ impl State {
             false
         }
     }
+
+    pub fn is_yielded(&self) -> bool {
+        if let State::Yielded { .. } = self {
+            true
+        } else {
+            false
+        }
+    }
 }
 
 fn default_fatal_handler(inst: &Instance) -> ! {
     panic!("> instance {:p} had fatal error: {}", inst, inst.state);
 }
 
+/// The CPU time used by the current thread.
+fn thread_cpu_time() -> Duration {
+    let mut ts = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // this clock is always available, so this cannot fail given a valid pointer
+    unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
 // TODO: PR into `libc`
 extern "C" {
     #[no_mangle]
@@ -0,0 +1,127 @@
+use crate::module::{FunctionIndex, Module};
+use libc::{c_void, uintptr_t};
+
+/// The most frames a backtrace will contain, in case a corrupted stack links frames in a cycle.
+const MAX_FRAMES: usize = 1024;
+
+/// A frame of a guest stack backtrace, as found in
+/// [`FaultDetails::backtrace`](struct.FaultDetails.html#structfield.backtrace).
+#[derive(Clone, Debug)]
+pub struct Frame {
+    /// The address of the faulting instruction for the innermost frame, and the return address
+    /// for the others.
+    pub addr: uintptr_t,
+    /// The index of the WebAssembly function the frame belongs to.
+    pub fn_idx: FunctionIndex,
+    /// The name recorded for the function in the module's function metadata, if any.
+    pub name: Option<String>,
+    /// The offset of `addr` from the start of the function's code.
+    pub code_offset: u32,
+    /// The byte offset in the WebAssembly module binary of the faulting instruction for the
+    /// innermost frame, and of the call instruction for the others, if known.
+    pub wasm_offset: Option<u32>,
+}
+
+impl std::fmt::Display for Frame {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (function {}) + {:#x}",
+            self.name
+                .as_ref()
+                .map(String::as_str)
+                .unwrap_or("<unknown>"),
+            self.fn_idx.as_u32(),
+            self.code_offset
+        )?;
+        if let Some(wasm_offset) = self.wasm_offset {
+            write!(f, " (wasm offset {:#x})", wasm_offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// Walk the guest stack by following frame pointers, starting at a fault.
+///
+/// Functions compiled by `lucetc` always start with `push rbp; mov rbp, rsp`, so each guest frame
+/// holds the caller's frame pointer, with the return address just above it. The stack probe that
+/// `lucetc` adds to every module has no frame of its own, so when a fault happens in the probe,
+/// the function that called it does not appear in the backtrace.
+///
+/// The walk stops at the first frame whose address is not in the module's code, which is normally
+/// the entry into the guest from the host. A fault outside of the module's code, such as in a
+/// hostcall, yields an empty backtrace.
+///
+/// Only addresses within `stack` are read, so a corrupted guest stack cannot make the walk read
+/// arbitrary memory.
+pub(crate) fn walk_guest_stack(
+    module: &dyn Module,
+    stack: (uintptr_t, uintptr_t),
+    rip: uintptr_t,
+    fp: uintptr_t,
+    sp: uintptr_t,
+) -> Vec<Frame> {
+    let (stack_bottom, stack_top) = stack;
+    let read_stack = |addr: uintptr_t| -> Option<uintptr_t> {
+        if addr % 8 != 0 || addr < stack_bottom || addr + 8 > stack_top {
+            None
+        } else {
+            Some(unsafe { *(addr as *const uintptr_t) })
+        }
+    };
+
+    let mut backtrace = vec![];
+    let frame = match lookup_frame(module, rip, rip) {
+        Some(frame) => frame,
+        None => return backtrace,
+    };
+
+    // If the fault happened in a function prologue before `mov rbp, rsp`, the frame pointer still
+    // belongs to the caller, and the return address is found relative to the stack pointer.
+    let mut ret_addr = match frame.code_offset {
+        0 => read_stack(sp),
+        1 => read_stack(sp + 8),
+        _ => read_stack(fp + 8),
+    };
+    let mut fp = if frame.code_offset <= 1 {
+        Some(fp)
+    } else {
+        read_stack(fp)
+    };
+    backtrace.push(frame);
+
+    while let (Some(addr), Some(next_fp)) = (ret_addr, fp) {
+        if backtrace.len() >= MAX_FRAMES {
+            break;
+        }
+        // a return address points just past the call, which may be the end of the function, so
+        // look up the call instruction itself
+        match lookup_frame(module, addr, addr.wrapping_sub(1)) {
+            Some(frame) => backtrace.push(frame),
+            None => break,
+        }
+        ret_addr = read_stack(next_fp + 8);
+        fp = read_stack(next_fp);
+    }
+
+    backtrace
+}
+
+/// Find the function containing `lookup_addr`, and make a frame for `addr` within it.
+fn lookup_frame(module: &dyn Module, addr: uintptr_t, lookup_addr: uintptr_t) -> Option<Frame> {
+    module
+        .function_manifest()
+        .iter()
+        .enumerate()
+        .find(|(_, fn_spec)| fn_spec.contains(lookup_addr as u64))
+        .map(|(idx, fn_spec)| {
+            let fn_idx = FunctionIndex::from_u32(idx as u32);
+            Frame {
+                addr,
+                fn_idx,
+                name: module.get_func_name(fn_idx).map(str::to_owned),
+                code_offset: (addr - fn_spec.ptr().as_usize()) as u32,
+                wasm_offset: module.lookup_wasm_offset(lookup_addr as *const c_void),
+            }
+        })
+}
@@ -0,0 +1,235 @@
+//! WebAssembly core dumps of instances.
+//!
+//! A core dump is a WebAssembly module binary laid out as described by the [WebAssembly tool
+//! conventions for core dumps][coredump], so that tools that understand that format can inspect
+//! it. `lucet-analyze coredump <file>` prints one in a readable form.
+//!
+//! The dump contains, in order:
+//!
+//! - a custom section `core`: `0x00 executable-name:name`, naming the host process's executable.
+//!
+//! - a custom section `coremodules`: `vec(0x00 module-name:name)`, with a single module named
+//!   `guest`.
+//!
+//! - a custom section `coreinstances`: `vec(0x00 moduleidx:u32 memories:vec(u32)
+//!   globals:vec(u32))`, with a single instance of module 0 that owns memory 0 and every global.
+//!
+//! - a custom section `corestack`: `0x00 thread-name:name frames:vec(frame)`, where each frame is
+//!   `0x00 instanceidx:u32 funcidx:u32 codeoffset:u32 locals:vec(value) stack:vec(value)`. The
+//!   frames are the guest backtrace of the fault, innermost first, and are empty if the instance
+//!   has not faulted. `codeoffset` is the byte offset of the instruction in the original module
+//!   binary, or `0xffffffff` if it is not known. Locals and operand stack values are not recorded.
+//!
+//! - a memory section with one memory, whose minimum size is the size of the heap in WebAssembly
+//!   pages.
+//!
+//! - a global section with the type, mutability, and current value of each global, as a constant
+//!   initializer expression.
+//!
+//! - a data section with one active segment for each run of host pages in the heap that are not
+//!   all zero, at the offset of the run.
+//!
+//! - a custom section `lucet-fault`, present only if the instance has faulted: `fatal:u8
+//!   trapcode:u32le rip-addr:u64le wasm-offset:u32le`. `fatal` is 1 for fatal faults and 0
+//!   otherwise, `trapcode` is the numeric `TrapCode`, and `trapcode` and `wasm-offset` are
+//!   `0xffffffff` when unknown.
+//!
+//! Values named `u32` are unsigned LEB128, and `name` is a `u32` length followed by UTF-8 bytes,
+//! as in the rest of the WebAssembly binary format.
+//!
+//! [coredump]: https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md
+
+use crate::alloc::host_page_size;
+use crate::error::Error;
+use crate::instance::{Instance, State};
+use crate::module::ValueType;
+use crate::WASM_PAGE_SIZE;
+use std::io::Write;
+
+/// The value written for trap codes, code offsets, and wasm offsets that are not known.
+const UNKNOWN: u32 = std::u32::MAX;
+
+const SECTION_CUSTOM: u8 = 0;
+const SECTION_MEMORY: u8 = 5;
+const SECTION_GLOBAL: u8 = 6;
+const SECTION_DATA: u8 = 11;
+
+/// Write a core dump of `inst` to `out`.
+pub(crate) fn write_coredump<W: Write>(inst: &Instance, mut out: W) -> Result<(), Error> {
+    let details = match inst.state {
+        State::Fault { ref details, .. } => Some(details),
+        _ => None,
+    };
+
+    let mut buf = vec![];
+    buf.extend_from_slice(b"\0asm");
+    buf.extend_from_slice(&1u32.to_le_bytes());
+
+    let mut core = vec![0x00];
+    let exe = std::env::current_exe()
+        .ok()
+        .and_then(|path| {
+            path.file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default();
+    write_name(&mut core, &exe);
+    write_custom_section(&mut buf, "core", &core);
+
+    let mut coremodules = vec![];
+    write_u32(&mut coremodules, 1);
+    coremodules.push(0x00);
+    write_name(&mut coremodules, "guest");
+    write_custom_section(&mut buf, "coremodules", &coremodules);
+
+    let num_globals = inst.module.globals().len();
+    let mut coreinstances = vec![];
+    write_u32(&mut coreinstances, 1);
+    coreinstances.push(0x00);
+    write_u32(&mut coreinstances, 0);
+    write_u32(&mut coreinstances, 1);
+    write_u32(&mut coreinstances, 0);
+    write_u32(&mut coreinstances, num_globals as u32);
+    for i in 0..num_globals {
+        write_u32(&mut coreinstances, i as u32);
+    }
+    write_custom_section(&mut buf, "coreinstances", &coreinstances);
+
+    let mut corestack = vec![0x00];
+    write_name(&mut corestack, "main");
+    let frames = details.map(|d| d.backtrace.as_slice()).unwrap_or(&[]);
+    write_u32(&mut corestack, frames.len() as u32);
+    for frame in frames {
+        corestack.push(0x00);
+        write_u32(&mut corestack, 0);
+        write_u32(&mut corestack, frame.fn_idx.as_u32());
+        write_u32(&mut corestack, frame.wasm_offset.unwrap_or(UNKNOWN));
+        // locals and operand stack values are not recorded
+        write_u32(&mut corestack, 0);
+        write_u32(&mut corestack, 0);
+    }
+    write_custom_section(&mut buf, "corestack", &corestack);
+
+    let heap = inst.heap();
+    let mut memory = vec![];
+    write_u32(&mut memory, 1);
+    memory.push(0x00);
+    write_u32(&mut memory, (heap.len() / WASM_PAGE_SIZE as usize) as u32);
+    write_section(&mut buf, SECTION_MEMORY, &memory);
+
+    let globals = inst.globals();
+    let mut global = vec![];
+    write_u32(&mut global, num_globals as u32);
+    for (spec, &val) in inst.module.globals().iter().zip(globals.iter()) {
+        match spec.ty() {
+            ValueType::I32 => {
+                global.extend_from_slice(&[0x7F, spec.is_mutable() as u8, 0x41]);
+                write_i64(&mut global, val as i32 as i64);
+            }
+            ValueType::I64 => {
+                global.extend_from_slice(&[0x7E, spec.is_mutable() as u8, 0x42]);
+                write_i64(&mut global, val);
+            }
+            ValueType::F32 => {
+                global.extend_from_slice(&[0x7D, spec.is_mutable() as u8, 0x43]);
+                global.extend_from_slice(&(val as u32).to_le_bytes());
+            }
+            ValueType::F64 => {
+                global.extend_from_slice(&[0x7C, spec.is_mutable() as u8, 0x44]);
+                global.extend_from_slice(&(val as u64).to_le_bytes());
+            }
+        }
+        global.push(0x0B);
+    }
+    write_section(&mut buf, SECTION_GLOBAL, &global);
+
+    let mut segments = vec![];
+    let mut start = None;
+    for (page_num, page) in heap.chunks(host_page_size()).enumerate() {
+        let offset = page_num * host_page_size();
+        let nonzero = page.iter().any(|&b| b != 0);
+        match (nonzero, start) {
+            (true, None) => start = Some(offset),
+            (false, Some(s)) => {
+                segments.push((s, offset));
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        segments.push((s, heap.len()));
+    }
+    let mut data = vec![];
+    write_u32(&mut data, segments.len() as u32);
+    for (start, end) in segments {
+        data.push(0x00);
+        data.push(0x41);
+        // offsets past 2GiB wrap to negative `i32.const`s, which the guest reads as unsigned
+        write_i64(&mut data, start as u32 as i32 as i64);
+        data.push(0x0B);
+        write_u32(&mut data, (end - start) as u32);
+        data.extend_from_slice(&heap[start..end]);
+    }
+    write_section(&mut buf, SECTION_DATA, &data);
+
+    if let Some(details) = details {
+        let mut fault = vec![details.fatal as u8];
+        fault.extend_from_slice(
+            &details
+                .trapcode
+                .map(|code| code as u32)
+                .unwrap_or(UNKNOWN)
+                .to_le_bytes(),
+        );
+        fault.extend_from_slice(&(details.rip_addr as u64).to_le_bytes());
+        fault.extend_from_slice(&details.wasm_offset.unwrap_or(UNKNOWN).to_le_bytes());
+        write_custom_section(&mut buf, "lucet-fault", &fault);
+    }
+
+    out.write_all(&buf)
+        .and_then(|_| out.flush())
+        .map_err(|e| Error::InternalError(e.into()))
+}
+
+fn write_u32(buf: &mut Vec<u8>, mut v: u32) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if v == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_i64(buf: &mut Vec<u8>, mut v: i64) {
+    loop {
+        let byte = (v & 0x7F) as u8;
+        v >>= 7;
+        if (v == 0 && byte & 0x40 == 0) || (v == -1 && byte & 0x40 != 0) {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+fn write_name(buf: &mut Vec<u8>, name: &str) {
+    write_u32(buf, name.len() as u32);
+    buf.extend_from_slice(name.as_bytes());
+}
+
+fn write_section(buf: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    buf.push(id);
+    write_u32(buf, contents.len() as u32);
+    buf.extend_from_slice(contents);
+}
+
+fn write_custom_section(buf: &mut Vec<u8>, name: &str, contents: &[u8]) {
+    let mut section = vec![];
+    write_name(&mut section, name);
+    section.extend_from_slice(contents);
+    write_section(buf, SECTION_CUSTOM, &section);
+}
@@ -0,0 +1,235 @@
+//! Running hostcalls on a host stack rather than on the guest stack.
+//!
+//! Hostcalls are called directly by guest code, so they start out on the guest stack. Guest stacks
+//! are sized for guest code, and a hostcall that needs more stack than the guest left it overflows
+//! into the guard page below the guest stack. When an instance has a hostcall stack size set with
+//! [`Instance::set_hostcall_stack_size()`](../struct.Instance.html#method.set_hostcall_stack_size),
+//! hostcalls defined with `lucet_hostcalls!` switch to a stack owned by the host thread on entry,
+//! and back to the guest stack when they return.
+//!
+//! Each thread has one host stack, which is allocated when an instance that needs it starts running
+//! on the thread, and is shared by every such instance. A hostcall that yields leaves its frames on
+//! the host stack until it is resumed, so a yielding instance takes the host stack with it, and the
+//! thread gets a fresh one the next time it is needed.
+//!
+//! Overflowing either a host stack or the guest stack from a hostcall terminates the instance with
+//! `TerminationDetails::HostStackOverflow`, rather than faulting with the
+//! `TrapCode::StackOverflow` of guest code. The frames of the hostcall are abandoned rather than
+//! unwound, so the destructors of its values do not run.
+
+use crate::alloc::host_page_size;
+use crate::context::Context;
+use crate::error::Error;
+use crate::instance::{Instance, HOSTCALL_FRAME_RESERVE};
+use crate::val::Val;
+use crate::vmctx::{instance_from_vmctx, lucet_vmctx};
+use libc::c_void;
+use lucet_module_data::FunctionPointer;
+use nix::sys::mman::{mmap, mprotect, munmap, MapFlags, ProtFlags};
+use std::cell::RefCell;
+use std::ptr;
+
+thread_local! {
+    /// The host stack for hostcalls made on this thread, once an instance has needed one.
+    static HOST_STACK: RefCell<Option<HostStack>> = RefCell::new(None);
+}
+
+/// A stack for hostcalls, with a guard page below it.
+pub(crate) struct HostStack {
+    /// The start of the mapping, which is the guard page.
+    mem: *mut c_void,
+    /// The size of the stack above the guard page.
+    size: usize,
+}
+
+impl HostStack {
+    fn new(size: usize) -> Result<Self, Error> {
+        let mem = unsafe {
+            mmap(
+                ptr::null_mut(),
+                size + host_page_size(),
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE,
+                0,
+                0,
+            )?
+        };
+        // the mapping is released by `drop` if making the stack accessible fails
+        let stack = HostStack { mem, size };
+        unsafe {
+            mprotect(
+                stack.bottom() as *mut c_void,
+                size,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            )?
+        };
+        Ok(stack)
+    }
+
+    fn bottom(&self) -> usize {
+        self.mem as usize + host_page_size()
+    }
+
+    fn top(&self) -> usize {
+        self.bottom() + self.size
+    }
+
+    fn addr_in_guard(&self, addr: usize) -> bool {
+        addr >= self.mem as usize && addr < self.bottom()
+    }
+}
+
+impl Drop for HostStack {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.mem, self.size + host_page_size()).expect("munmap succeeds during drop");
+        }
+    }
+}
+
+/// Make sure this thread has a host stack of at least `size` bytes.
+///
+/// This must not be called while a hostcall is running on the thread's host stack.
+pub(crate) fn ensure_host_stack(size: usize) -> Result<(), Error> {
+    HOST_STACK.with(|host_stack| {
+        let mut host_stack = host_stack.borrow_mut();
+        if host_stack.as_ref().map(|s| s.size < size).unwrap_or(true) {
+            // free any smaller stack before mapping its replacement
+            *host_stack = None;
+            *host_stack = Some(HostStack::new(size)?);
+        }
+        Ok(())
+    })
+}
+
+/// Take this thread's host stack, for an instance whose hostcall yielded while running on it.
+pub(crate) fn take_host_stack() -> Option<HostStack> {
+    HOST_STACK.with(|host_stack| host_stack.borrow_mut().take())
+}
+
+impl Instance {
+    /// Check whether `addr` is in the guard page of a stack that a hostcall of this instance could
+    /// be running on.
+    ///
+    /// This is called from the signal handler, so it must not allocate.
+    pub(crate) fn addr_in_hostcall_stack_guard(&self, addr: *const c_void) -> bool {
+        if self.alloc.addr_in_stack_guard(addr) {
+            return true;
+        }
+        if let Some(ref stack) = self.owned_host_stack {
+            if stack.addr_in_guard(addr as usize) {
+                return true;
+            }
+        }
+        HOST_STACK.with(|host_stack| match host_stack.try_borrow() {
+            Ok(host_stack) => host_stack
+                .as_ref()
+                .map(|s| s.addr_in_guard(addr as usize))
+                .unwrap_or(false),
+            Err(_) => false,
+        })
+    }
+
+    /// The part of the host stack that the hostcalls of a guest function called from the running
+    /// hostcall go on, or `None` if the running hostcall is not on a host stack.
+    ///
+    /// `here` is the address of a local in the calling hostcall.
+    pub(crate) fn host_stack_below(&self, here: usize) -> Option<Result<(usize, usize), Error>> {
+        self.host_stack_in_use.map(|(bottom, _)| {
+            if here < bottom + HOSTCALL_FRAME_RESERVE + host_page_size() {
+                Err(Error::LimitsExceeded(
+                    "not enough host stack left to call a guest function".to_owned(),
+                ))
+            } else {
+                Ok((bottom, (here - HOSTCALL_FRAME_RESERVE) & !15))
+            }
+        })
+    }
+}
+
+/// Run the body of a hostcall, on a host stack if the instance has hostcall stacks enabled.
+///
+/// This is used by `lucet_hostcalls!`, and is not meant to be called directly. `f` must not panic;
+/// the macro catches any panics of the hostcall within `f`.
+#[doc(hidden)]
+pub unsafe fn run_hostcall<F, R>(vmctx: *mut lucet_vmctx, f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    // The instance is accessed through a raw pointer rather than a reference, as the hostcall
+    // changes it while this function is suspended in `Context::swap()`.
+    let inst = instance_from_vmctx(vmctx) as *mut Instance;
+
+    if (*inst).hostcall_stack_size.is_none() {
+        return f();
+    }
+
+    // The address of a local approximates the stack pointer. A hostcall that is not on the guest
+    // stack, such as one called directly by another hostcall, is already on a host stack.
+    let here = &inst as *const *mut Instance as usize;
+    let slot = (*inst).alloc.slot();
+    if here <= slot.stack as usize || here > slot.stack_top() as usize {
+        return f();
+    }
+
+    let (bottom, top) = match (*inst).host_stack_free {
+        // a guest function called from a hostcall on the host stack made this hostcall, so it runs
+        // on the host stack below that hostcall
+        Some(free) => free,
+        None => match (*inst).owned_host_stack {
+            Some(ref stack) => (stack.bottom(), stack.top()),
+            None => HOST_STACK.with(|host_stack| {
+                let host_stack = host_stack.borrow();
+                let stack = host_stack
+                    .as_ref()
+                    .expect("host stack is allocated before the instance runs");
+                (stack.bottom(), stack.top())
+            }),
+        },
+    };
+    let stack_end = top & !15;
+    let stack = std::slice::from_raw_parts_mut(
+        bottom as *mut u64,
+        (stack_end - bottom) / std::mem::size_of::<u64>(),
+    );
+
+    let outermost = (*inst).host_stack_free.is_none();
+    let saved_in_use = (*inst).host_stack_in_use.replace((bottom, stack_end));
+    let saved_free = (*inst).host_stack_free.take();
+    let saved_guest_stack_top = (*inst).guest_stack_top.replace(here);
+
+    let mut data: (Option<F>, Option<R>) = (Some(f), None);
+    let mut parent = Context::new();
+    let mut child = Context::new();
+    Context::init(
+        stack,
+        &mut parent,
+        &mut child,
+        FunctionPointer::from_usize(hostcall_trampoline::<F, R> as usize),
+        &[Val::from(
+            &mut data as *mut (Option<F>, Option<R>) as *mut c_void,
+        )],
+    )
+    .expect("host stack is aligned");
+    Context::swap(&mut parent, &child);
+
+    (*inst).host_stack_in_use = saved_in_use;
+    (*inst).host_stack_free = saved_free;
+    (*inst).guest_stack_top = saved_guest_stack_top;
+    if outermost {
+        // no hostcall frames are left on a stack that the instance took when it yielded
+        (*inst).owned_host_stack = None;
+    }
+
+    data.1.expect("hostcall body ran to completion")
+}
+
+/// The entrypoint of a hostcall's context on the host stack.
+extern "C" fn hostcall_trampoline<F, R>(data: *mut c_void)
+where
+    F: FnOnce() -> R,
+{
+    let data = unsafe { &mut *(data as *mut (Option<F>, Option<R>)) };
+    let f = data.0.take().expect("hostcall body runs once");
+    data.1 = Some(f());
+}
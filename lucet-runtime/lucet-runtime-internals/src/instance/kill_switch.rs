@@ -0,0 +1,124 @@
+use libc::pthread_t;
+use nix::sys::signal::{pthread_sigmask, SigSet, SigmaskHow, Signal};
+use std::mem;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+
+/// The signal that a `KillSwitch` sends to a thread running a hostcall of its instance.
+pub(crate) const KILL_SIGNAL: Signal = Signal::SIGALRM;
+
+/// A handle that can terminate an instance from another thread.
+///
+/// Kill switches are obtained from
+/// [`InstanceHandle::kill_switch()`](struct.InstanceHandle.html#method.kill_switch). Triggering one
+/// makes the instance's current call to `Instance::run()`, or its next one if no call is in
+/// progress, return `Err(Error::RuntimeTerminated(TerminationDetails::Remote))`. A call that starts
+/// after the kill switch is triggered returns that error without running any guest code.
+///
+/// Guest code notices the kill switch at the interrupt checks that `lucetc` inserts at function
+/// entries and loop headers when interrupt checks are enabled. Hostcalls defined with
+/// `lucet_hostcalls!` notice it when they return to the guest. If the instance is in such a
+/// hostcall, the kill switch also sends `SIGALRM` to the thread running it, so that a system call
+/// the hostcall is blocked in fails with `EINTR`, and the hostcall can return. Hostcalls that wait
+/// in other ways, or that retry interrupted system calls, such as `std::thread::sleep()`, should
+/// poll [`Vmctx::kill_requested()`](../vmctx/struct.Vmctx.html#method.kill_requested) instead.
+///
+/// While instances are running, `SIGALRM` signals that are not sent by a kill switch are passed on
+/// to the handler that was installed before.
+#[derive(Clone)]
+pub struct KillSwitch {
+    state: Weak<KillState>,
+}
+
+impl KillSwitch {
+    pub(crate) fn new(state: &Arc<KillState>) -> KillSwitch {
+        KillSwitch {
+            state: Arc::downgrade(state),
+        }
+    }
+
+    /// Request that the instance terminate.
+    ///
+    /// Returns `false` if the instance no longer exists, in which case this has no effect.
+    pub fn terminate(&self) -> bool {
+        if let Some(state) = self.state.upgrade() {
+            state.terminate();
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// The state an instance shares with its kill switches.
+pub(crate) struct KillState {
+    /// Set by a `KillSwitch` to request that the instance terminate
+    pub(crate) flag: AtomicBool,
+    /// The thread running a hostcall of the instance, if it is in one
+    hostcall_thread: Mutex<Option<pthread_t>>,
+}
+
+impl KillState {
+    pub(crate) fn new() -> KillState {
+        KillState {
+            flag: AtomicBool::new(false),
+            hostcall_thread: Mutex::new(None),
+        }
+    }
+
+    fn terminate(&self) {
+        // the lock keeps the hostcall from returning between setting the flag and sending the
+        // signal, after which the thread may no longer be running the instance
+        let hostcall_thread = self.hostcall_thread.lock().unwrap();
+        self.flag.store(true, Ordering::SeqCst);
+        if let Some(thread) = *hostcall_thread {
+            unsafe { libc::pthread_kill(thread, KILL_SIGNAL as libc::c_int) };
+        }
+    }
+
+    /// Note that the current thread has started running a hostcall, returning what was noted
+    /// before so that it can be passed back to `end_hostcall()`.
+    pub(crate) fn begin_hostcall(&self) -> Option<pthread_t> {
+        self.hostcall_thread
+            .lock()
+            .unwrap()
+            .replace(unsafe { libc::pthread_self() })
+    }
+
+    /// Note that the current thread has finished running a hostcall, or is about to leave it by
+    /// yielding.
+    ///
+    /// A kill signal that was sent during the hostcall, but has not been delivered yet, is
+    /// discarded, so that it is not delivered once the thread has left the instance.
+    pub(crate) fn end_hostcall(&self, previous: Option<pthread_t>) {
+        *self.hostcall_thread.lock().unwrap() = previous;
+        if self.flag.load(Ordering::SeqCst) {
+            discard_pending_kill_signal();
+        }
+    }
+}
+
+fn discard_pending_kill_signal() {
+    let mut kill_signal = SigSet::empty();
+    kill_signal.add(KILL_SIGNAL);
+    let mut previous_mask = SigSet::empty();
+    // the signal must be blocked to take it with `sigwait()` rather than have it delivered
+    pthread_sigmask(
+        SigmaskHow::SIG_BLOCK,
+        Some(&kill_signal),
+        Some(&mut previous_mask),
+    )
+    .expect("pthread_sigmask succeeds");
+    // the signal is only pending if it has not been delivered already
+    let pending = unsafe {
+        let mut pending: libc::sigset_t = mem::zeroed();
+        libc::sigemptyset(&mut pending);
+        libc::sigpending(&mut pending) == 0
+            && libc::sigismember(&pending, KILL_SIGNAL as libc::c_int) == 1
+    };
+    if pending {
+        kill_signal.wait().expect("sigwait succeeds");
+    }
+    pthread_sigmask(SigmaskHow::SIG_SETMASK, Some(&previous_mask), None)
+        .expect("pthread_sigmask succeeds");
+}
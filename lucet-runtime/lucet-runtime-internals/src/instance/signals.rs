@@ -1,7 +1,7 @@
 use crate::context::Context;
 use crate::instance::{
     siginfo_ext::SiginfoExt, FaultDetails, Instance, State, TerminationDetails, CURRENT_INSTANCE,
-    HOST_CTX,
+    HOSTCALL_PANIC_LOCATION, HOST_CTX, KILL_SIGNAL,
 };
 use crate::sysdeps::UContextPtr;
 use failure::Error;
@@ -165,6 +165,36 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
 
         let trapcode = inst.module.lookup_trapcode(rip);
 
+        // Hostcalls are not guest code, so they have no trap codes. A fault of a hostcall on the
+        // guard page below its stack, whether that is the guest stack or a host stack, means that
+        // the hostcall overflowed its stack. That is not a trap of the guest, so it terminates the
+        // instance rather than faulting it, and there is nothing for the signal handler to do.
+        if trapcode.is_none() && (signal == Signal::SIGSEGV || signal == Signal::SIGBUS) {
+            // safety: pointer is checked for null at the top of the function, and the manpage
+            // guarantees that a siginfo_t will be passed as the second argument
+            let fault_addr = unsafe { (*siginfo_ptr).si_addr_ext() };
+            let in_guest_code = inst
+                .module
+                .function_manifest()
+                .iter()
+                .any(|f| f.contains(rip as u64));
+            if !in_guest_code && inst.addr_in_hostcall_stack_guard(fault_addr) {
+                inst.state = State::Terminated {
+                    details: TerminationDetails::HostStackOverflow,
+                };
+                return true;
+            }
+        }
+
+        if trapcode == Some(TrapCode::Interrupt) && inst.kill_requested() {
+            // interrupt checks only trap when a `KillSwitch` has been triggered, so this is a
+            // termination rather than a fault, and there is nothing for the signal handler to do
+            inst.state = State::Terminated {
+                details: TerminationDetails::Remote,
+            };
+            return true;
+        }
+
         let behavior = (inst.signal_handler)(inst, &trapcode, signum, siginfo_ptr, ucontext_ptr);
         match behavior {
             SignalBehavior::Continue => {
@@ -200,6 +230,8 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
                         // Details set to `None` here: have to wait until `verify_trap_safety` to
                         // fill in these details, because access may not be signal safe.
                         rip_addr_details: None,
+                        wasm_offset: None,
+                        backtrace: vec![],
                     },
                     siginfo,
                     context: ctx.into(),
@@ -218,12 +250,37 @@ extern "C" fn handle_signal(signum: c_int, siginfo_ptr: *mut siginfo_t, ucontext
     }
 }
 
+/// Signal handler for the signal a `KillSwitch` sends to a thread running a hostcall.
+///
+/// The handler only needs to return: the hostcall is interrupted, and the instance terminates once
+/// it returns to the guest. Signals that are not from a kill switch of the current instance go to
+/// the handler that was installed before.
+extern "C" fn handle_kill_signal(
+    signum: c_int,
+    siginfo_ptr: *mut siginfo_t,
+    ucontext_ptr: *mut c_void,
+) {
+    let kill_requested = CURRENT_INSTANCE.with(|current_instance| {
+        current_instance
+            .try_borrow()
+            .ok()
+            .and_then(|current_instance| {
+                (*current_instance).map(|inst| unsafe { inst.as_ref() }.kill_requested())
+            })
+            .unwrap_or(false)
+    });
+    if !kill_requested {
+        unsafe { reraise_host_signal_in_handler(KILL_SIGNAL, signum, siginfo_ptr, ucontext_ptr) };
+    }
+}
+
 struct SignalState {
     counter: usize,
     saved_sigbus: SigAction,
     saved_sigfpe: SigAction,
     saved_sigill: SigAction,
     saved_sigsegv: SigAction,
+    saved_sigalrm: SigAction,
     saved_panic_hook: Option<Arc<Box<dyn Fn(&panic::PanicInfo) + Sync + Send + 'static>>>,
 }
 
@@ -248,6 +305,14 @@ unsafe fn setup_guest_signal_state(ostate: &mut Option<SignalState>) {
     let saved_sigill = sigaction(Signal::SIGILL, &sa).expect("sigaction succeeds");
     let saved_sigsegv = sigaction(Signal::SIGSEGV, &sa).expect("sigaction succeeds");
 
+    // no `SA_RESTART`, so that system calls a hostcall is blocked in fail with `EINTR`
+    let kill_sa = SigAction::new(
+        SigHandler::SigAction(handle_kill_signal),
+        SaFlags::SA_SIGINFO | SaFlags::SA_ONSTACK,
+        SigSet::empty(),
+    );
+    let saved_sigalrm = sigaction(KILL_SIGNAL, &kill_sa).expect("sigaction succeeds");
+
     let saved_panic_hook = Some(setup_guest_panic_hook());
 
     *ostate = Some(SignalState {
@@ -256,6 +321,7 @@ unsafe fn setup_guest_signal_state(ostate: &mut Option<SignalState>) {
         saved_sigfpe,
         saved_sigill,
         saved_sigsegv,
+        saved_sigalrm,
         saved_panic_hook,
     });
 }
@@ -269,6 +335,19 @@ fn setup_guest_panic_hook() -> Arc<Box<dyn Fn(&panic::PanicInfo) + Sync + Send +
             .downcast_ref::<TerminationDetails>()
             .is_none()
         {
+            // a panic in a hostcall terminates the instance, so keep its location for the
+            // termination details
+            let in_instance = CURRENT_INSTANCE.with(|current_instance| {
+                current_instance
+                    .try_borrow()
+                    .map(|current_instance| current_instance.is_some())
+                    .unwrap_or(false)
+            });
+            if in_instance {
+                HOSTCALL_PANIC_LOCATION.with(|location| {
+                    *location.borrow_mut() = panic_info.location().map(|l| l.to_string());
+                });
+            }
             closure_saved_panic_hook(panic_info);
         } else {
             // this is a panic used to implement instance termination (such as
@@ -285,6 +364,7 @@ unsafe fn restore_host_signal_state(state: &mut SignalState) {
     sigaction(Signal::SIGFPE, &state.saved_sigfpe).expect("sigaction succeeds");
     sigaction(Signal::SIGILL, &state.saved_sigill).expect("sigaction succeeds");
     sigaction(Signal::SIGSEGV, &state.saved_sigsegv).expect("sigaction succeeds");
+    sigaction(KILL_SIGNAL, &state.saved_sigalrm).expect("sigaction succeeds");
 
     // restore panic hook
     drop(panic::take_hook());
@@ -309,6 +389,7 @@ unsafe fn reraise_host_signal_in_handler(
                 Signal::SIGFPE => state.saved_sigfpe.clone(),
                 Signal::SIGILL => state.saved_sigill.clone(),
                 Signal::SIGSEGV => state.saved_sigsegv.clone(),
+                Signal::SIGALRM => state.saved_sigalrm.clone(),
                 sig => panic!(
                     "unexpected signal in reraise_host_signal_in_handler: {:?}",
                     sig
@@ -0,0 +1,74 @@
+use crate::alloc::host_page_size;
+use crate::module::Module;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+static NEXT_SNAPSHOT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A copy of the heap and globals of an instance, taken with
+/// [`Instance::snapshot()`](struct.Instance.html#method.snapshot).
+///
+/// A snapshot can be restored into any instance of the same module with
+/// [`Instance::restore()`](struct.Instance.html#method.restore), any number of times. This allows
+/// expensive guest initialization to be done once, rather than after every `reset()`.
+///
+/// The heap is stored sparsely: pages that are entirely zero are not copied, and the region
+/// restores them by zeroing the heap rather than by copying.
+pub struct InstanceSnapshot {
+    /// Distinguishes the snapshot from every other snapshot taken in the process.
+    id: usize,
+    module: Arc<dyn Module>,
+    heap_len: usize,
+    heap_pages: Vec<Option<Box<[u8]>>>,
+    globals: Vec<i64>,
+}
+
+impl InstanceSnapshot {
+    pub(crate) fn new(module: Arc<dyn Module>, heap: &[u8], globals: &[i64]) -> Self {
+        let heap_pages = heap
+            .chunks(host_page_size())
+            .map(|page| {
+                if page.iter().all(|b| *b == 0) {
+                    None
+                } else {
+                    Some(page.to_vec().into_boxed_slice())
+                }
+            })
+            .collect();
+        InstanceSnapshot {
+            id: NEXT_SNAPSHOT_ID.fetch_add(1, Ordering::SeqCst),
+            module,
+            heap_len: heap.len(),
+            heap_pages,
+            globals: globals.to_vec(),
+        }
+    }
+
+    /// The length of the snapshotted heap in bytes.
+    pub fn heap_len(&self) -> usize {
+        self.heap_len
+    }
+
+    /// Get the contents of a page of the snapshotted heap, or `None` if the page is all zeroes.
+    pub fn heap_page(&self, page: usize) -> Option<&[u8]> {
+        self.heap_pages
+            .get(page)
+            .and_then(|p| p.as_ref().map(|p| p.as_ref()))
+    }
+
+    pub(crate) fn id(&self) -> usize {
+        self.id
+    }
+
+    /// The snapshotted values of the module's globals.
+    pub fn globals(&self) -> &[i64] {
+        &self.globals
+    }
+
+    /// Check whether this snapshot was taken from an instance of `module`.
+    pub(crate) fn is_of_module(&self, module: &Arc<dyn Module>) -> bool {
+        // compare only the data pointers, as vtable pointers for the same type can differ
+        &*self.module as *const dyn Module as *const u8
+            == &**module as *const dyn Module as *const u8
+    }
+}
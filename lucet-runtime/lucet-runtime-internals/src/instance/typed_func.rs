@@ -0,0 +1,177 @@
+use crate::error::Error;
+use crate::instance::Instance;
+use crate::module::{Module, ValueType};
+use crate::val::{UntypedRetVal, Val};
+use lucet_module_data::{FunctionHandle, FunctionPointer};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+/// A WebAssembly value type that can be passed to or returned from a
+/// [`TypedFunc`](struct.TypedFunc.html).
+///
+/// This is implemented for `i32`, `u32`, `i64`, `u64`, `f32`, and `f64`.
+pub trait TypedVal: Into<Val> {
+    /// The WebAssembly type of the value.
+    fn value_type() -> ValueType;
+}
+
+/// The argument types of a [`TypedFunc`](struct.TypedFunc.html).
+///
+/// This is implemented for tuples of up to eight `TypedVal`s, including the empty tuple for
+/// functions that take no arguments.
+pub trait TypedArgs {
+    /// The WebAssembly types of the arguments.
+    fn value_types() -> Vec<ValueType>;
+
+    /// Convert the arguments into values that can be passed to the guest.
+    fn into_vals(self) -> Vec<Val>;
+}
+
+/// The return type of a [`TypedFunc`](struct.TypedFunc.html).
+///
+/// This is implemented for every `TypedVal`, and for `()` for functions that do not return a
+/// value.
+pub trait TypedRet {
+    /// The WebAssembly type of the return value, or `None` if there is no return value.
+    fn value_type() -> Option<ValueType>;
+
+    /// Interpret a value returned by the guest.
+    fn from_retval(retval: UntypedRetVal) -> Self;
+}
+
+macro_rules! impl_typed_val {
+    ( { $( $ty:ty : $vty:ident ),* } ) => {
+        $(
+            impl TypedVal for $ty {
+                fn value_type() -> ValueType {
+                    ValueType::$vty
+                }
+            }
+
+            impl TypedRet for $ty {
+                fn value_type() -> Option<ValueType> {
+                    Some(ValueType::$vty)
+                }
+
+                fn from_retval(retval: UntypedRetVal) -> Self {
+                    <$ty>::from(retval)
+                }
+            }
+        )*
+    };
+}
+
+impl_typed_val!({
+    i32: I32,
+    u32: I32,
+    i64: I64,
+    u64: I64,
+    f32: F32,
+    f64: F64
+});
+
+impl TypedRet for () {
+    fn value_type() -> Option<ValueType> {
+        None
+    }
+
+    fn from_retval(_retval: UntypedRetVal) -> Self {}
+}
+
+macro_rules! impl_typed_args {
+    ( $( $arg:ident ),* ) => {
+        impl<$( $arg ),*> TypedArgs for ( $( $arg, )* )
+        where
+            $( $arg: TypedVal ),*
+        {
+            fn value_types() -> Vec<ValueType> {
+                vec![$( <$arg as TypedVal>::value_type() ),*]
+            }
+
+            #[allow(non_snake_case)]
+            fn into_vals(self) -> Vec<Val> {
+                let ( $( $arg, )* ) = self;
+                vec![$( $arg.into() ),*]
+            }
+        }
+    };
+}
+
+impl_typed_args!();
+impl_typed_args!(A1);
+impl_typed_args!(A1, A2);
+impl_typed_args!(A1, A2, A3);
+impl_typed_args!(A1, A2, A3, A4);
+impl_typed_args!(A1, A2, A3, A4, A5);
+impl_typed_args!(A1, A2, A3, A4, A5, A6);
+impl_typed_args!(A1, A2, A3, A4, A5, A6, A7);
+impl_typed_args!(A1, A2, A3, A4, A5, A6, A7, A8);
+
+/// A handle to an exported function whose signature has already been checked, obtained with
+/// [`Instance::typed_func()`](struct.Instance.html#method.typed_func).
+///
+/// The export is looked up and its signature is checked against `Args` and `Ret` once, when the
+/// handle is created. Calls through the handle then skip the lookup and the argument checks that
+/// `Instance::run()` performs every time.
+///
+/// ```no_run
+/// # use lucet_runtime_internals::instance::{InstanceHandle, TypedFunc};
+/// # let mut instance: InstanceHandle = unimplemented!();
+/// let add: TypedFunc<(i32, i64), f64> = instance.typed_func("add").unwrap();
+/// for i in 0..10 {
+///     let sum = add.call(&mut instance, (i, 1)).unwrap();
+/// }
+/// ```
+pub struct TypedFunc<Args, Ret> {
+    module: Arc<dyn Module>,
+    func: FunctionPointer,
+    _signature: PhantomData<fn(Args) -> Ret>,
+}
+
+impl<Args: TypedArgs, Ret: TypedRet> TypedFunc<Args, Ret> {
+    pub(crate) fn new(module: Arc<dyn Module>, func: FunctionHandle) -> Result<Self, Error> {
+        if func.ptr.as_usize() == 0 {
+            return Err(Error::InvalidArgument(
+                "entrypoint function cannot be null; this is probably a malformed module",
+            ));
+        }
+
+        let sig = module.get_signature(func.id);
+        if sig.params != Args::value_types() || sig.ret_ty != Ret::value_type() {
+            return Err(Error::InvalidArgument(
+                "entrypoint function signature mismatch",
+            ));
+        }
+
+        Ok(TypedFunc {
+            module,
+            func: func.ptr,
+            _signature: PhantomData,
+        })
+    }
+
+    /// Run the function in the guest context of `inst`.
+    ///
+    /// `inst` must be an instance of the module this function was obtained from; this is the only
+    /// check made on each call, since running one module's code with another module's instance is
+    /// unsound.
+    ///
+    /// If a hostcall yields, this returns `Err(Error::InstanceNotReturned)` and the instance can be
+    /// continued with [`Instance::resume()`](struct.Instance.html#method.resume) as usual.
+    ///
+    /// The safety caveats of [`Instance::run()`](struct.Instance.html#method.run) apply.
+    pub fn call(&self, inst: &mut Instance, args: Args) -> Result<Ret, Error> {
+        // compare only the data pointers, as vtable pointers for the same type can differ
+        if &*self.module as *const dyn Module as *const u8
+            != &*inst.module as *const dyn Module as *const u8
+        {
+            return Err(Error::InvalidArgument(
+                "typed function called with an instance of a different module",
+            ));
+        }
+
+        inst.run_func_unchecked(self.func, &args.into_vals(), Ret::value_type())?
+            .returned()
+            .map(Ret::from_retval)
+    }
+}
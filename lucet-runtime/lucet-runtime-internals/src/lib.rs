@@ -17,6 +17,8 @@ pub mod alloc;
 pub mod c_api;
 pub mod context;
 pub mod embed_ctx;
+pub mod guest_memory;
+pub mod host_functions;
 pub mod instance;
 pub mod module;
 pub mod region;
@@ -1,12 +1,17 @@
 mod dl;
+mod elf;
+mod heap_memfd;
 mod mock;
 mod sparse_page_data;
 
 pub use crate::module::dl::DlModule;
+pub use crate::module::elf::{ElfImports, ElfModule};
+pub use crate::module::heap_memfd::HeapMemFd;
 pub use crate::module::mock::{MockExportBuilder, MockModuleBuilder};
 pub use lucet_module_data::{
-    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, Global, GlobalSpec, HeapSpec,
-    Signature, TrapCode, TrapManifest, ValueType,
+    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, Global, GlobalDef, GlobalSpec,
+    HeapSpec, ImportFunction, ImportLinking, Signature, TrapCode, TrapManifest, ValueType,
+    WasmOffset,
 };
 
 use crate::alloc::Limits;
@@ -53,6 +58,13 @@ pub trait ModuleInternal: Send + Sync {
     /// Get the number of pages in the sparse page data.
     fn sparse_page_data_len(&self) -> usize;
 
+    /// Get the memfd holding the initial heap, if the module was loaded with one.
+    ///
+    /// Regions may map this over an instance heap instead of copying the sparse page data.
+    fn heap_memfd(&self) -> Option<&HeapMemFd> {
+        None
+    }
+
     /// Get the table elements from the module.
     fn table_elements(&self) -> Result<&[TableElement], Error>;
 
@@ -60,6 +72,15 @@ pub trait ModuleInternal: Send + Sync {
 
     fn get_func_from_idx(&self, table_id: u32, func_id: u32) -> Result<FunctionHandle, Error>;
 
+    /// Look up a global by the name it is exported as, returning its index and specification.
+    fn get_export_global(&self, name: &str) -> Result<(usize, &GlobalSpec), Error> {
+        self.globals()
+            .iter()
+            .enumerate()
+            .find(|(_, spec)| spec.export_names().contains(&name))
+            .ok_or_else(|| Error::SymbolNotFound(name.to_string()))
+    }
+
     fn get_start_func(&self) -> Result<Option<FunctionHandle>, Error>;
 
     fn function_manifest(&self) -> &[FunctionSpec];
@@ -68,6 +89,15 @@ pub trait ModuleInternal: Send + Sync {
 
     fn get_signature(&self, fn_id: FunctionIndex) -> &Signature;
 
+    /// Get the functions the module imports.
+    fn import_functions(&self) -> &[ImportFunction];
+
+    /// Get how calls to the functions the module imports are linked.
+    fn import_linking(&self) -> ImportLinking;
+
+    /// Get the name recorded in the function metadata for a function, if it has one.
+    fn get_func_name(&self, fn_id: FunctionIndex) -> Option<&str>;
+
     fn function_handle_from_ptr(&self, ptr: FunctionPointer) -> FunctionHandle {
         let id = self
             .function_manifest()
@@ -96,6 +126,28 @@ pub trait ModuleInternal: Send + Sync {
         None
     }
 
+    /// Look up the byte offset in the WebAssembly module binary of the instruction that the code at
+    /// an instruction pointer was compiled from.
+    ///
+    /// Trap sites record the offset of the instruction that trapped. Other addresses are looked up
+    /// in the function's wasm offset map.
+    fn lookup_wasm_offset(&self, rip: *const c_void) -> Option<u32> {
+        for fn_spec in self.function_manifest() {
+            if let Some(offset) = fn_spec.relative_addr(rip as u64) {
+                let trap_wasm_offset = fn_spec
+                    .traps()
+                    .and_then(|traps| traps.lookup_site(offset).map(|site| site.wasm_offset))
+                    .filter(|wasm_offset| *wasm_offset != WasmOffset::UNKNOWN);
+                return trap_wasm_offset.or_else(|| {
+                    fn_spec
+                        .wasm_offsets()
+                        .and_then(|wasm_offsets| wasm_offsets.lookup_addr(offset))
+                });
+            }
+        }
+        None
+    }
+
     /// Check that the specifications of the WebAssembly module are valid given certain `Limit`s.
     ///
     /// Returns a `Result<(), Error>` rather than a boolean in order to provide a richer accounting
@@ -1,12 +1,17 @@
 use crate::error::Error;
-use crate::module::{AddrDetails, GlobalSpec, HeapSpec, Module, ModuleInternal, TableElement};
+use crate::module::{
+    AddrDetails, GlobalSpec, HeapMemFd, HeapSpec, Module, ModuleInternal, TableElement,
+};
 use libc::c_void;
 use libloading::{Library, Symbol};
 use lucet_module_data::{
-    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, ModuleData, Signature,
+    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, ImportFunction, ImportLinking,
+    ModuleData, Signature,
 };
 use std::ffi::CStr;
+use std::fs::File;
 use std::mem;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 use std::slice;
 use std::slice::from_raw_parts;
@@ -16,6 +21,14 @@ use std::sync::Arc;
 pub struct DlModule {
     lib: Library,
 
+    /// The in-memory file the shared object was loaded from, if it was loaded with
+    /// [`load_from_bytes()`](struct.DlModule.html#method.load_from_bytes)
+    ///
+    /// This stays open while the library is loaded, so that the `/proc/self/fd` path the library
+    /// was opened by cannot name a different file, which the dynamic linker would take to be the
+    /// library that is already loaded.
+    _so_file: Option<File>,
+
     /// Base address of the dynamically-loaded module
     fbase: *const c_void,
 
@@ -23,6 +36,10 @@ pub struct DlModule {
     module_data: ModuleData<'static>,
 
     function_manifest: &'static [FunctionSpec],
+
+    /// The initial heap, if the module was loaded with
+    /// [`load_with_heap_memfd()`](struct.DlModule.html#method.load_with_heap_memfd)
+    heap_memfd: Option<HeapMemFd>,
 }
 
 // for the one raw pointer only
@@ -32,13 +49,47 @@ unsafe impl Sync for DlModule {}
 impl DlModule {
     /// Create a module, loading code from a shared object on the filesystem.
     pub fn load<P: AsRef<Path>>(so_path: P) -> Result<Arc<Self>, Error> {
+        Self::load_impl(so_path, false)
+    }
+
+    /// Create a module, loading code from a shared object on the filesystem, and keep its initial
+    /// heap in a memfd.
+    ///
+    /// The memfd is created and filled once, here. Regions that support it then map the memfd
+    /// copy-on-write over instance heaps, making instance creation and `reset()` a remap rather
+    /// than a copy of the module's data segments. This is most worthwhile for modules with large
+    /// initial heaps. Only available on Linux.
+    pub fn load_with_heap_memfd<P: AsRef<Path>>(so_path: P) -> Result<Arc<Self>, Error> {
+        Self::load_impl(so_path, true)
+    }
+
+    /// Create a module, loading code from the bytes of a shared object in memory.
+    ///
+    /// The bytes are copied into an anonymous in-memory file, which is then loaded and validated
+    /// like a shared object on the filesystem is by [`load()`](#method.load). Only available on
+    /// Linux.
+    pub fn load_from_bytes(so_bytes: &[u8]) -> Result<Arc<Self>, Error> {
+        let so_file = so_memfd(so_bytes)?;
+        let lib = Library::new(format!("/proc/self/fd/{}", so_file.as_raw_fd()))
+            .map_err(Error::DlError)?;
+        Self::from_library(lib, Some(so_file), false)
+    }
+
+    fn load_impl<P: AsRef<Path>>(so_path: P, heap_memfd: bool) -> Result<Arc<Self>, Error> {
         // Load the dynamic library. The undefined symbols corresponding to the lucet_syscall_
         // functions will be provided by the current executable.  We trust our wasm->dylib compiler
         // to make sure these function calls are the way the dylib can touch memory outside of its
         // stack and heap.
         let abs_so_path = so_path.as_ref().canonicalize().map_err(Error::DlError)?;
         let lib = Library::new(abs_so_path.as_os_str()).map_err(Error::DlError)?;
+        Self::from_library(lib, None, heap_memfd)
+    }
 
+    fn from_library(
+        lib: Library,
+        so_file: Option<File>,
+        heap_memfd: bool,
+    ) -> Result<Arc<Self>, Error> {
         let module_data_ptr = unsafe {
             lib.get::<*const u8>(b"lucet_module_data").map_err(|e| {
                 lucet_incorrect_module!("error loading required symbol `lucet_module_data`: {}", e)
@@ -117,12 +168,19 @@ impl DlModule {
             }
         };
 
-        Ok(Arc::new(DlModule {
+        let mut module = DlModule {
             lib,
+            _so_file: so_file,
             fbase,
             module_data,
             function_manifest,
-        }))
+            heap_memfd: None,
+        };
+        if heap_memfd {
+            module.heap_memfd = Some(HeapMemFd::new(&module)?);
+        }
+
+        Ok(Arc::new(module))
     }
 }
 
@@ -149,6 +207,10 @@ impl ModuleInternal for DlModule {
         self.module_data.sparse_data().map(|d| d.len()).unwrap_or(0)
     }
 
+    fn heap_memfd(&self) -> Option<&HeapMemFd> {
+        self.heap_memfd.as_ref()
+    }
+
     fn table_elements(&self) -> Result<&[TableElement], Error> {
         let p_table_segment: Symbol<*const TableElement> = unsafe {
             self.lib.get(b"guest_table_0").map_err(|e| {
@@ -235,6 +297,44 @@ impl ModuleInternal for DlModule {
     fn get_signature(&self, fn_id: FunctionIndex) -> &Signature {
         self.module_data.get_signature(fn_id)
     }
+
+    fn import_functions(&self) -> &[ImportFunction] {
+        self.module_data.import_functions()
+    }
+
+    fn import_linking(&self) -> ImportLinking {
+        self.module_data.import_linking()
+    }
+
+    fn get_func_name(&self, fn_id: FunctionIndex) -> Option<&str> {
+        self.module_data
+            .function_info()
+            .get(fn_id.as_u32() as usize)
+            .and_then(|info| info.name)
+    }
+}
+
+/// Copy the bytes of a shared object into an anonymous in-memory file.
+#[cfg(target_os = "linux")]
+fn so_memfd(so_bytes: &[u8]) -> Result<File, Error> {
+    use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+    use std::ffi::CString;
+    use std::io::Write;
+    use std::os::unix::io::FromRawFd;
+
+    let name = CString::new("lucet_module").expect("name has no nul bytes");
+    let fd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC)?;
+    // from here on, dropping `so_file` closes the file if writing to it fails
+    let mut so_file = unsafe { File::from_raw_fd(fd) };
+    so_file.write_all(so_bytes).map_err(Error::DlError)?;
+    Ok(so_file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn so_memfd(_so_bytes: &[u8]) -> Result<File, Error> {
+    Err(Error::Unsupported(
+        "loading modules from memory is only available on Linux".to_owned(),
+    ))
 }
 
 fn is_undefined_symbol(e: &std::io::Error) -> bool {
@@ -246,7 +346,7 @@ fn is_undefined_symbol(e: &std::io::Error) -> bool {
 
 // TODO: PR to nix or libloading?
 // TODO: possibly not safe to use without grabbing the mutex within libloading::Library?
-fn dladdr(addr: *const c_void) -> Option<libc::Dl_info> {
+pub(crate) fn dladdr(addr: *const c_void) -> Option<libc::Dl_info> {
     let mut info = unsafe { mem::uninitialized::<libc::Dl_info>() };
     let res = unsafe { libc::dladdr(addr, &mut info as *mut libc::Dl_info) };
     if res != 0 {
@@ -0,0 +1,916 @@
+use crate::alloc::host_page_size;
+use crate::c_api::{lucet_vmctx_current_memory, lucet_vmctx_grow_memory};
+use crate::error::Error;
+use crate::module::dl::dladdr;
+use crate::module::{AddrDetails, GlobalSpec, HeapSpec, Module, ModuleInternal, TableElement};
+use libc::c_void;
+use lucet_module_data::{
+    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, ImportFunction, ImportLinking,
+    ModuleData, Signature,
+};
+use nix::sys::mman::{mmap, mprotect, munmap, MapFlags, ProtFlags};
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::ops::Range;
+use std::path::Path;
+use std::ptr;
+use std::slice::from_raw_parts;
+use std::sync::Arc;
+
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_DYN: u16 = 3;
+const EM_X86_64: u16 = 62;
+
+const PT_LOAD: u32 = 1;
+const PT_DYNAMIC: u32 = 2;
+const PT_TLS: u32 = 7;
+const PT_GNU_RELRO: u32 = 0x6474_e552;
+
+const PROGRAM_HEADER_SIZE: usize = 56;
+const SECTION_HEADER_SIZE: usize = 64;
+
+const PF_X: u32 = 1;
+const PF_W: u32 = 2;
+const PF_R: u32 = 4;
+
+const SHT_RELA: u32 = 4;
+const SHT_REL: u32 = 9;
+const SHT_DYNSYM: u32 = 11;
+const SHF_ALLOC: u64 = 2;
+
+const SHN_UNDEF: u16 = 0;
+const SHN_ABS: u16 = 0xfff1;
+const STB_LOCAL: u8 = 0;
+const STB_WEAK: u8 = 2;
+const STT_FUNC: u8 = 2;
+
+const DT_NULL: u64 = 0;
+const DT_INIT: u64 = 12;
+const DT_INIT_ARRAY: u64 = 25;
+const DT_PREINIT_ARRAY: u64 = 32;
+
+const R_X86_64_NONE: u32 = 0;
+const R_X86_64_64: u32 = 1;
+const R_X86_64_GLOB_DAT: u32 = 6;
+const R_X86_64_JUMP_SLOT: u32 = 7;
+const R_X86_64_RELATIVE: u32 = 8;
+
+extern "C" {
+    // the functions that `lucetc` calls for floating-point rounding, from libm
+    fn ceilf(x: f32) -> f32;
+    fn ceil(x: f64) -> f64;
+    fn floorf(x: f32) -> f32;
+    fn floor(x: f64) -> f64;
+    fn truncf(x: f32) -> f32;
+    fn trunc(x: f64) -> f64;
+    fn nearbyintf(x: f32) -> f32;
+    fn nearbyint(x: f64) -> f64;
+}
+
+/// The symbols that an [`ElfModule`](struct.ElfModule.html) resolves the undefined symbols of its
+/// shared object to.
+///
+/// The functions that code generated by `lucetc` calls for some WebAssembly operations, such as
+/// `floorf`, `memcpy`, and `lucet_vmctx_grow_memory`, are always in the table. Hostcalls must be
+/// added to it, unless the table falls back to the symbols of the host process.
+#[derive(Clone, Debug)]
+pub struct ElfImports {
+    symbols: HashMap<String, usize>,
+    host_process: bool,
+}
+
+impl Default for ElfImports {
+    fn default() -> Self {
+        let libcalls: &[(&str, usize)] = &[
+            ("ceilf", ceilf as usize),
+            ("ceil", ceil as usize),
+            ("floorf", floorf as usize),
+            ("floor", floor as usize),
+            ("truncf", truncf as usize),
+            ("trunc", trunc as usize),
+            ("nearbyintf", nearbyintf as usize),
+            ("nearbyint", nearbyint as usize),
+            ("memcpy", libc::memcpy as usize),
+            ("memset", libc::memset as usize),
+            ("memmove", libc::memmove as usize),
+            (
+                "lucet_vmctx_current_memory",
+                lucet_vmctx_current_memory as usize,
+            ),
+            ("lucet_vmctx_grow_memory", lucet_vmctx_grow_memory as usize),
+        ];
+        ElfImports {
+            symbols: libcalls
+                .iter()
+                .map(|(name, addr)| (name.to_string(), *addr))
+                .collect(),
+            host_process: false,
+        }
+    }
+}
+
+impl ElfImports {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A table that resolves symbols it does not have to the symbols exported by the host
+    /// process, as the dynamic linker does for a `DlModule`.
+    ///
+    /// The symbols are looked up with `dlsym(RTLD_DEFAULT, ...)` while the module is loaded. As
+    /// with a `DlModule`, hostcalls must then be `#[no_mangle]`, and the host executable must
+    /// export them, for example by linking with `-rdynamic`.
+    pub fn from_host_process() -> Self {
+        ElfImports {
+            host_process: true,
+            ..Self::default()
+        }
+    }
+
+    /// Resolve the symbol `name` to `addr`, replacing any address it already resolves to.
+    pub fn insert(&mut self, name: &str, addr: *const c_void) {
+        self.symbols.insert(name.to_owned(), addr as usize);
+    }
+
+    /// Resolve the symbol `name` to `addr`, replacing any address it already resolves to.
+    pub fn with_symbol(mut self, name: &str, addr: *const c_void) -> Self {
+        self.insert(name, addr);
+        self
+    }
+
+    fn resolve(&self, name: &str) -> Option<usize> {
+        if let Some(addr) = self.symbols.get(name) {
+            return Some(*addr);
+        }
+        if self.host_process {
+            let name = CString::new(name).ok()?;
+            let addr = unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) };
+            if !addr.is_null() {
+                return Some(addr as usize);
+            }
+        }
+        None
+    }
+}
+
+/// A Lucet module loaded from a shared object without the dynamic linker.
+///
+/// Unlike a [`DlModule`](struct.DlModule.html), loading and dropping an `ElfModule` does not take
+/// the global lock of the dynamic linker or use one of its slots for loaded objects, and its
+/// undefined symbols are resolved from an explicit [`ElfImports`](struct.ElfImports.html) table
+/// rather than against every symbol the host process exports. Only the subset of ELF that `lucetc`
+/// produces is supported: x86-64 shared objects with `RELA` relocations, and without thread-local
+/// storage or initializers.
+///
+/// The code and data of the module are copied into memory owned by the module, rather than mapped
+/// from the file, so loading a module from bytes in memory costs the same as loading it from the
+/// filesystem. All of the undefined symbols of the shared object are resolved when it is loaded,
+/// and loading fails with `Error::SymbolNotFound` if one is not in the imports table.
+pub struct ElfModule {
+    /// The path the module was loaded from, if any
+    path: Option<String>,
+
+    /// The defined functions of the shared object, as `(start, end, name)`, sorted by address
+    func_symbols: Vec<(usize, usize, String)>,
+
+    /// Metadata decoded from inside the module
+    module_data: ModuleData<'static>,
+
+    function_manifest: &'static [FunctionSpec],
+
+    table_elements: Option<&'static [TableElement]>,
+
+    start_func: Option<FunctionPointer>,
+
+    /// The memory the segments of the shared object are loaded into, which the fields above refer
+    /// to, and so is unmapped after them
+    mapping: Mapping,
+}
+
+// for the raw pointers in the mapping and the references into it
+unsafe impl Send for ElfModule {}
+unsafe impl Sync for ElfModule {}
+
+impl ElfModule {
+    /// Create a module, loading code from a shared object on the filesystem.
+    pub fn load<P: AsRef<Path>>(so_path: P, imports: &ElfImports) -> Result<Arc<Self>, Error> {
+        let so_bytes = std::fs::read(so_path.as_ref()).map_err(Error::DlError)?;
+        let path = so_path.as_ref().to_string_lossy().into_owned();
+        Self::load_impl(&so_bytes, Some(path), imports)
+    }
+
+    /// Create a module, loading code from the bytes of a shared object in memory.
+    pub fn load_from_bytes(so_bytes: &[u8], imports: &ElfImports) -> Result<Arc<Self>, Error> {
+        Self::load_impl(so_bytes, None, imports)
+    }
+
+    fn load_impl(
+        so_bytes: &[u8],
+        path: Option<String>,
+        imports: &ElfImports,
+    ) -> Result<Arc<Self>, Error> {
+        let elf = ElfFile::parse(so_bytes)?;
+        let mapping = Mapping::new(&elf)?;
+        let symbols = elf.dynamic_symbols()?;
+        elf.relocate(&mapping, &symbols, imports)?;
+        mapping.protect(&elf)?;
+
+        let mut exports = HashMap::new();
+        let mut func_symbols = vec![];
+        for sym in symbols.iter() {
+            if sym.shndx == SHN_UNDEF || sym.info >> 4 == STB_LOCAL {
+                continue;
+            }
+            let addr = mapping.symbol_addr(sym);
+            if sym.info & 0xf == STT_FUNC {
+                let end = addr
+                    .checked_add(sym.size as usize)
+                    .ok_or(lucet_incorrect_module!("symbol {} overflows", sym.name))?;
+                func_symbols.push((addr, end, sym.name.clone()));
+            }
+            exports.insert(sym.name.as_str(), addr);
+        }
+        func_symbols.sort();
+
+        let module_data_ptr = *exports
+            .get("lucet_module_data")
+            .ok_or(lucet_incorrect_module!(
+                "required symbol `lucet_module_data` is not defined"
+            ))?;
+        let module_data_len =
+            *exports
+                .get("lucet_module_data_len")
+                .ok_or(lucet_incorrect_module!(
+                    "required symbol `lucet_module_data_len` is not defined"
+                ))?;
+        let module_data_len = mapping.read::<u32>(module_data_len)? as usize;
+
+        // Deserialize the slice into ModuleData, which will hold refs into the mapping. Both of
+        // these get a 'static lifetime for the same reason as in `DlModule`: the mapping lives as
+        // long as the module, which is the lifetime that ModuleData is exposed with.
+        let module_data_slice: &'static [u8] = mapping.get(module_data_ptr, module_data_len)?;
+        let module_data = ModuleData::deserialize(module_data_slice)?;
+
+        let function_manifest = match (
+            exports.get("lucet_function_manifest"),
+            exports.get("lucet_function_manifest_len"),
+        ) {
+            (Some(ptr), Some(len_ptr)) => {
+                let len = mapping.read::<u32>(*len_ptr)? as usize;
+                mapping.get(*ptr, len)?
+            }
+            (None, None) => &[],
+            (Some(_), None) => {
+                return Err(lucet_incorrect_module!(
+                    "symbol `lucet_function_manifest_len` is not defined"
+                ));
+            }
+            (None, Some(_)) => {
+                return Err(lucet_incorrect_module!(
+                    "symbol `lucet_function_manifest` is not defined"
+                ));
+            }
+        };
+
+        let table_elements = match (
+            exports.get("guest_table_0"),
+            exports.get("guest_table_0_len"),
+        ) {
+            (Some(ptr), Some(len_ptr)) => {
+                let len = mapping.read::<usize>(*len_ptr)?;
+                if len > std::u32::MAX as usize {
+                    return Err(lucet_incorrect_module!("table segment too long: {}", len));
+                }
+                Some(mapping.get(*ptr, len)?)
+            }
+            _ => None,
+        };
+
+        // `guest_start` is a pointer to the start function, as with `DlModule`
+        let start_func = match exports.get("guest_start") {
+            Some(ptr) => {
+                let func = mapping.read::<usize>(*ptr)?;
+                if func == 0 {
+                    return Err(lucet_incorrect_module!("`guest_start` is defined but null"));
+                }
+                Some(FunctionPointer::from_usize(func))
+            }
+            None => None,
+        };
+
+        Ok(Arc::new(ElfModule {
+            path,
+            func_symbols,
+            module_data,
+            function_manifest,
+            table_elements,
+            start_func,
+            mapping,
+        }))
+    }
+}
+
+impl Module for ElfModule {}
+
+impl ModuleInternal for ElfModule {
+    fn heap_spec(&self) -> Option<&HeapSpec> {
+        self.module_data.heap_spec()
+    }
+
+    fn globals(&self) -> &[GlobalSpec] {
+        self.module_data.globals_spec()
+    }
+
+    fn get_sparse_page_data(&self, page: usize) -> Option<&[u8]> {
+        if let Some(ref sparse_data) = self.module_data.sparse_data() {
+            *sparse_data.get_page(page)
+        } else {
+            None
+        }
+    }
+
+    fn sparse_page_data_len(&self) -> usize {
+        self.module_data.sparse_data().map(|d| d.len()).unwrap_or(0)
+    }
+
+    fn table_elements(&self) -> Result<&[TableElement], Error> {
+        self.table_elements.ok_or(lucet_incorrect_module!(
+            "required symbols `guest_table_0` and `guest_table_0_len` are not defined"
+        ))
+    }
+
+    fn get_export_func(&self, sym: &str) -> Result<FunctionHandle, Error> {
+        self.module_data
+            .get_export_func_id(sym)
+            .ok_or_else(|| Error::SymbolNotFound(sym.to_string()))
+            .map(|id| {
+                let ptr = self.function_manifest()[id.as_u32() as usize].ptr();
+                FunctionHandle { ptr, id }
+            })
+    }
+
+    fn get_func_from_idx(&self, table_id: u32, func_id: u32) -> Result<FunctionHandle, Error> {
+        if table_id != 0 {
+            return Err(Error::FuncNotFound(table_id, func_id));
+        }
+        let table = self.table_elements()?;
+        let func: FunctionPointer = table
+            .get(func_id as usize)
+            .map(|element| FunctionPointer::from_usize(element.rf as usize))
+            .ok_or(Error::FuncNotFound(table_id, func_id))?;
+
+        Ok(self.function_handle_from_ptr(func))
+    }
+
+    fn get_start_func(&self) -> Result<Option<FunctionHandle>, Error> {
+        Ok(self
+            .start_func
+            .map(|func| self.function_handle_from_ptr(func)))
+    }
+
+    fn function_manifest(&self) -> &[FunctionSpec] {
+        self.function_manifest
+    }
+
+    fn addr_details(&self, addr: *const c_void) -> Result<Option<AddrDetails>, Error> {
+        let addr = addr as usize;
+        if !self.mapping.contains(addr) {
+            // not in the module, but possibly in the host, which the dynamic linker knows about
+            return Ok(dladdr(addr as *const c_void).map(|dli| AddrDetails {
+                in_module_code: false,
+                file_name: unsafe { c_str_to_string(dli.dli_fname) },
+                sym_name: unsafe { c_str_to_string(dli.dli_sname) },
+            }));
+        }
+        let sym_name = match self
+            .func_symbols
+            .binary_search_by(|(start, _, _)| start.cmp(&addr))
+        {
+            Ok(i) => Some(i),
+            Err(0) => None,
+            Err(i) => Some(i - 1),
+        }
+        .map(|i| &self.func_symbols[i])
+        .filter(|(_, end, _)| addr < *end)
+        .map(|(_, _, name)| name.clone());
+        Ok(Some(AddrDetails {
+            in_module_code: true,
+            file_name: self.path.clone(),
+            sym_name,
+        }))
+    }
+
+    fn get_signature(&self, fn_id: FunctionIndex) -> &Signature {
+        self.module_data.get_signature(fn_id)
+    }
+
+    fn import_functions(&self) -> &[ImportFunction] {
+        self.module_data.import_functions()
+    }
+
+    fn import_linking(&self) -> ImportLinking {
+        self.module_data.import_linking()
+    }
+
+    fn get_func_name(&self, fn_id: FunctionIndex) -> Option<&str> {
+        self.module_data
+            .function_info()
+            .get(fn_id.as_u32() as usize)
+            .and_then(|info| info.name)
+    }
+}
+
+unsafe fn c_str_to_string(s: *const libc::c_char) -> Option<String> {
+    if s.is_null() {
+        None
+    } else {
+        CStr::from_ptr(s).to_str().ok().map(|s| s.to_owned())
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, Error> {
+    let mut buf = [0; 2];
+    buf.copy_from_slice(read_bytes(bytes, offset, 2)?);
+    Ok(u16::from_le_bytes(buf))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    let mut buf = [0; 4];
+    buf.copy_from_slice(read_bytes(bytes, offset, 4)?);
+    Ok(u32::from_le_bytes(buf))
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> Result<u64, Error> {
+    let mut buf = [0; 8];
+    buf.copy_from_slice(read_bytes(bytes, offset, 8)?);
+    Ok(u64::from_le_bytes(buf))
+}
+
+fn read_bytes(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or(lucet_incorrect_module!(
+            "ELF file is truncated: {} bytes at {:#x}",
+            len,
+            offset
+        ))
+}
+
+struct ProgramHeader {
+    p_type: u32,
+    p_flags: u32,
+    p_offset: u64,
+    p_vaddr: u64,
+    p_filesz: u64,
+    p_memsz: u64,
+}
+
+struct SectionHeader {
+    sh_type: u32,
+    sh_flags: u64,
+    sh_offset: u64,
+    sh_size: u64,
+    sh_link: u32,
+    sh_entsize: u64,
+}
+
+struct ElfSymbol {
+    name: String,
+    info: u8,
+    shndx: u16,
+    value: u64,
+    size: u64,
+}
+
+/// The parts of a shared object that are needed to load it.
+struct ElfFile<'a> {
+    bytes: &'a [u8],
+    program_headers: Vec<ProgramHeader>,
+    section_headers: Vec<SectionHeader>,
+}
+
+impl<'a> ElfFile<'a> {
+    fn parse(bytes: &'a [u8]) -> Result<Self, Error> {
+        if read_bytes(bytes, 0, 4).ok() != Some(b"\x7fELF") {
+            return Err(lucet_incorrect_module!("not an ELF file"));
+        }
+        if read_bytes(bytes, 4, 2)? != [ELFCLASS64, ELFDATA2LSB] {
+            return Err(lucet_incorrect_module!(
+                "not a 64-bit little-endian ELF file"
+            ));
+        }
+        if read_u16(bytes, 16)? != ET_DYN {
+            return Err(lucet_incorrect_module!("not an ELF shared object"));
+        }
+        if read_u16(bytes, 18)? != EM_X86_64 {
+            return Err(lucet_incorrect_module!("not an x86-64 ELF file"));
+        }
+
+        let phoff = read_u64(bytes, 32)? as usize;
+        let phentsize = read_u16(bytes, 54)? as usize;
+        let phnum = read_u16(bytes, 56)? as usize;
+        let mut program_headers = Vec::with_capacity(phnum);
+        for i in 0..phnum {
+            let ph = i
+                .checked_mul(phentsize)
+                .and_then(|off| off.checked_add(phoff))
+                .ok_or(lucet_incorrect_module!("program header {} overflows", i))?;
+            let ph = read_bytes(bytes, ph, PROGRAM_HEADER_SIZE)?;
+            program_headers.push(ProgramHeader {
+                p_type: read_u32(ph, 0)?,
+                p_flags: read_u32(ph, 4)?,
+                p_offset: read_u64(ph, 8)?,
+                p_vaddr: read_u64(ph, 16)?,
+                p_filesz: read_u64(ph, 32)?,
+                p_memsz: read_u64(ph, 40)?,
+            });
+        }
+
+        let shoff = read_u64(bytes, 40)? as usize;
+        let shentsize = read_u16(bytes, 58)? as usize;
+        let shnum = read_u16(bytes, 60)? as usize;
+        let mut section_headers = Vec::with_capacity(shnum);
+        for i in 0..shnum {
+            let sh = i
+                .checked_mul(shentsize)
+                .and_then(|off| off.checked_add(shoff))
+                .ok_or(lucet_incorrect_module!("section header {} overflows", i))?;
+            let sh = read_bytes(bytes, sh, SECTION_HEADER_SIZE)?;
+            section_headers.push(SectionHeader {
+                sh_type: read_u32(sh, 4)?,
+                sh_flags: read_u64(sh, 8)?,
+                sh_offset: read_u64(sh, 24)?,
+                sh_size: read_u64(sh, 32)?,
+                sh_link: read_u32(sh, 40)?,
+                sh_entsize: read_u64(sh, 56)?,
+            });
+        }
+
+        let elf = ElfFile {
+            bytes,
+            program_headers,
+            section_headers,
+        };
+        elf.check_supported()?;
+        Ok(elf)
+    }
+
+    /// Reject shared objects that need more than copying, relocating, and protecting their
+    /// segments to be loaded.
+    fn check_supported(&self) -> Result<(), Error> {
+        for ph in self.program_headers.iter() {
+            match ph.p_type {
+                PT_TLS => {
+                    return Err(lucet_incorrect_module!(
+                        "shared object uses thread-local storage"
+                    ));
+                }
+                PT_DYNAMIC => {
+                    let dynamic =
+                        read_bytes(self.bytes, ph.p_offset as usize, ph.p_filesz as usize)?;
+                    for entry in dynamic.chunks(16) {
+                        match read_u64(entry, 0)? {
+                            DT_NULL => break,
+                            DT_INIT | DT_INIT_ARRAY | DT_PREINIT_ARRAY => {
+                                return Err(lucet_incorrect_module!(
+                                    "shared object has initializers"
+                                ));
+                            }
+                            _ => (),
+                        }
+                    }
+                }
+                PT_LOAD if ph.p_filesz > ph.p_memsz => {
+                    return Err(lucet_incorrect_module!(
+                        "loadable segment is larger in the file than in memory"
+                    ));
+                }
+                _ => (),
+            }
+        }
+        if self
+            .section_headers
+            .iter()
+            .any(|sh| sh.sh_type == SHT_REL && sh.sh_flags & SHF_ALLOC != 0)
+        {
+            return Err(lucet_incorrect_module!(
+                "shared object has REL relocations rather than RELA"
+            ));
+        }
+        Ok(())
+    }
+
+    fn loadable_segments(&self) -> impl Iterator<Item = &ProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(|ph| ph.p_type == PT_LOAD)
+    }
+
+    fn dynsym_index(&self) -> Result<usize, Error> {
+        self.section_headers
+            .iter()
+            .position(|sh| sh.sh_type == SHT_DYNSYM)
+            .ok_or(lucet_incorrect_module!(
+                "shared object has no dynamic symbol table"
+            ))
+    }
+
+    fn section_bytes(&self, sh: &SectionHeader) -> Result<&'a [u8], Error> {
+        read_bytes(self.bytes, sh.sh_offset as usize, sh.sh_size as usize)
+    }
+
+    /// The dynamic symbol table, indexed as relocations refer to it.
+    fn dynamic_symbols(&self) -> Result<Vec<ElfSymbol>, Error> {
+        let dynsym = &self.section_headers[self.dynsym_index()?];
+        let dynstr =
+            self.section_headers
+                .get(dynsym.sh_link as usize)
+                .ok_or(lucet_incorrect_module!(
+                    "dynamic symbol table has no string table"
+                ))?;
+        let strings = self.section_bytes(dynstr)?;
+        let entries = self.section_bytes(dynsym)?;
+        if dynsym.sh_entsize != 24 {
+            return Err(lucet_incorrect_module!(
+                "unexpected dynamic symbol size: {}",
+                dynsym.sh_entsize
+            ));
+        }
+
+        let mut symbols = Vec::with_capacity(entries.len() / 24);
+        for entry in entries.chunks(24) {
+            let name_offset = read_u32(entry, 0)? as usize;
+            let name = strings
+                .get(name_offset..)
+                .and_then(|s| s.iter().position(|b| *b == 0).map(|len| &s[..len]))
+                .and_then(|s| std::str::from_utf8(s).ok())
+                .ok_or(lucet_incorrect_module!("invalid dynamic symbol name"))?;
+            symbols.push(ElfSymbol {
+                name: name.to_owned(),
+                info: read_bytes(entry, 4, 1)?[0],
+                shndx: read_u16(entry, 6)?,
+                value: read_u64(entry, 8)?,
+                size: read_u64(entry, 16)?,
+            });
+        }
+        Ok(symbols)
+    }
+
+    /// Apply the dynamic relocations of the shared object to its loaded segments.
+    fn relocate(
+        &self,
+        mapping: &Mapping,
+        symbols: &[ElfSymbol],
+        imports: &ElfImports,
+    ) -> Result<(), Error> {
+        let dynsym_index = self.dynsym_index()?;
+        let rela_sections = self.section_headers.iter().filter(|sh| {
+            sh.sh_type == SHT_RELA
+                && sh.sh_flags & SHF_ALLOC != 0
+                && sh.sh_link as usize == dynsym_index
+        });
+        for sh in rela_sections {
+            for entry in self.section_bytes(sh)?.chunks(24) {
+                let offset = read_u64(entry, 0)? as usize;
+                let info = read_u64(entry, 8)?;
+                let addend = read_u64(entry, 16)?;
+                let (sym_index, ty) = ((info >> 32) as usize, info as u32);
+
+                let sym_value = || -> Result<u64, Error> {
+                    let sym = symbols.get(sym_index).ok_or(lucet_incorrect_module!(
+                        "relocation refers to nonexistent symbol {}",
+                        sym_index
+                    ))?;
+                    if sym.shndx != SHN_UNDEF {
+                        // symbols defined by the module are bound to its own definitions
+                        Ok(mapping.symbol_addr(sym) as u64)
+                    } else if let Some(addr) = imports.resolve(&sym.name) {
+                        Ok(addr as u64)
+                    } else if sym.info >> 4 == STB_WEAK {
+                        Ok(0)
+                    } else {
+                        Err(Error::SymbolNotFound(sym.name.clone()))
+                    }
+                };
+
+                let value = match ty {
+                    R_X86_64_NONE => continue,
+                    R_X86_64_RELATIVE => (mapping.bias() as u64).wrapping_add(addend),
+                    R_X86_64_64 | R_X86_64_GLOB_DAT | R_X86_64_JUMP_SLOT => {
+                        sym_value()?.wrapping_add(addend)
+                    }
+                    _ => {
+                        return Err(lucet_incorrect_module!(
+                            "unsupported relocation type: {}",
+                            ty
+                        ));
+                    }
+                };
+                let target = mapping.bias().wrapping_add(offset);
+                unsafe {
+                    (mapping.get_mut::<u64>(target)? as *mut u64).write_unaligned(value);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The memory that the loadable segments of a shared object are copied into.
+struct Mapping {
+    base: *mut c_void,
+    len: usize,
+    /// The difference between the addresses in the mapping and the virtual addresses in the file
+    bias: usize,
+}
+
+impl Mapping {
+    /// Map memory for the loadable segments of `elf`, and copy them into it.
+    fn new(elf: &ElfFile) -> Result<Self, Error> {
+        let page_size = host_page_size() as u64;
+        let mut lo = std::u64::MAX;
+        let mut hi = 0;
+        for ph in elf.loadable_segments() {
+            let end = ph
+                .p_vaddr
+                .checked_add(ph.p_memsz)
+                .and_then(|end| end.checked_add(page_size - 1))
+                .ok_or(lucet_incorrect_module!("loadable segment overflows"))?;
+            lo = lo.min(ph.p_vaddr / page_size * page_size);
+            hi = hi.max(end / page_size * page_size);
+        }
+        if lo >= hi {
+            return Err(lucet_incorrect_module!(
+                "shared object has no loadable segments"
+            ));
+        }
+        let len = (hi - lo) as usize;
+
+        let base = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE,
+                0,
+                0,
+            )?
+        };
+        // the memory is unmapped by `drop` if copying the segments fails
+        let mapping = Mapping {
+            base,
+            len,
+            bias: (base as usize).wrapping_sub(lo as usize),
+        };
+        for ph in elf.loadable_segments() {
+            let contents = read_bytes(elf.bytes, ph.p_offset as usize, ph.p_filesz as usize)?;
+            let dest = mapping.bias.wrapping_add(ph.p_vaddr as usize) as *mut u8;
+            // the rest of the segment, if any, is already zero
+            unsafe { ptr::copy_nonoverlapping(contents.as_ptr(), dest, contents.len()) };
+        }
+        Ok(mapping)
+    }
+
+    /// Set the protection of each page to that of the segments it holds, with the pages that only
+    /// need to be written by relocations made read-only.
+    fn protect(&self, elf: &ElfFile) -> Result<(), Error> {
+        let page_size = host_page_size();
+        let mut page_flags = vec![0; self.len / page_size];
+        let page_range =
+            |vaddr: u64, len: u64, round_up_end: bool| -> Result<Range<usize>, Error> {
+                let start = self
+                    .bias
+                    .wrapping_add(vaddr as usize)
+                    .wrapping_sub(self.base as usize);
+                let end = start
+                    .checked_add(len as usize)
+                    .filter(|end| *end <= self.len)
+                    .ok_or(lucet_incorrect_module!(
+                        "segment at {:#x} is outside of the loadable segments",
+                        vaddr
+                    ))?;
+                // `end` is at most the length of the mapping, which is a whole number of pages
+                let end = if round_up_end {
+                    (end + page_size - 1) / page_size
+                } else {
+                    end / page_size
+                };
+                Ok(start / page_size..end)
+            };
+        for ph in elf.loadable_segments() {
+            for page in page_range(ph.p_vaddr, ph.p_memsz, true)? {
+                page_flags[page] |= ph.p_flags;
+            }
+        }
+        for ph in elf
+            .program_headers
+            .iter()
+            .filter(|ph| ph.p_type == PT_GNU_RELRO)
+        {
+            for page in page_range(ph.p_vaddr, ph.p_memsz, false)? {
+                if let Some(flags) = page_flags.get_mut(page) {
+                    *flags &= !PF_W;
+                }
+            }
+        }
+
+        let mut start = 0;
+        while start < page_flags.len() {
+            let flags = page_flags[start];
+            let end = page_flags[start..]
+                .iter()
+                .position(|f| *f != flags)
+                .map(|len| start + len)
+                .unwrap_or(page_flags.len());
+            let mut prot = ProtFlags::PROT_NONE;
+            if flags & PF_R != 0 {
+                prot |= ProtFlags::PROT_READ;
+            }
+            if flags & PF_W != 0 {
+                prot |= ProtFlags::PROT_WRITE;
+            }
+            if flags & PF_X != 0 {
+                prot |= ProtFlags::PROT_EXEC;
+            }
+            unsafe {
+                mprotect(
+                    (self.base as usize + start * page_size) as *mut c_void,
+                    (end - start) * page_size,
+                    prot,
+                )?
+            };
+            start = end;
+        }
+        Ok(())
+    }
+
+    fn bias(&self) -> usize {
+        self.bias
+    }
+
+    fn contains(&self, addr: usize) -> bool {
+        addr >= self.base as usize && addr < self.base as usize + self.len
+    }
+
+    fn symbol_addr(&self, sym: &ElfSymbol) -> usize {
+        if sym.shndx == SHN_ABS {
+            sym.value as usize
+        } else {
+            self.bias.wrapping_add(sym.value as usize)
+        }
+    }
+
+    /// Check that `len` values of type `T` at `addr` are within the mapping.
+    fn check_range<T>(&self, addr: usize, len: usize) -> Result<(), Error> {
+        let size = len
+            .checked_mul(std::mem::size_of::<T>())
+            .ok_or(lucet_incorrect_module!(
+                "symbol at {:#x} is too large",
+                addr
+            ))?;
+        if !self.contains(addr) || size > self.base as usize + self.len - addr {
+            return Err(lucet_incorrect_module!(
+                "symbol at {:#x} is outside of the loaded segments",
+                addr
+            ));
+        }
+        Ok(())
+    }
+
+    /// Get `len` values of type `T` at `addr`, which must be within the mapping and aligned for
+    /// `T`.
+    fn get<T: 'static>(&self, addr: usize, len: usize) -> Result<&'static [T], Error> {
+        self.check_range::<T>(addr, len)?;
+        if addr % std::mem::align_of::<T>() != 0 {
+            return Err(lucet_incorrect_module!(
+                "symbol at {:#x} is not aligned to {} bytes",
+                addr,
+                std::mem::align_of::<T>()
+            ));
+        }
+        Ok(unsafe { from_raw_parts(addr as *const T, len) })
+    }
+
+    /// Get a pointer to a value of type `T` at `addr`, which must be within the mapping, but may
+    /// be unaligned; the pointer must only be used with unaligned reads and writes.
+    fn get_mut<T: 'static>(&self, addr: usize) -> Result<*mut T, Error> {
+        self.check_range::<T>(addr, 1)?;
+        Ok(addr as *mut T)
+    }
+
+    /// Read a value of type `T` at `addr`, which must be within the mapping, but may be unaligned.
+    fn read<T: Copy + 'static>(&self, addr: usize) -> Result<T, Error> {
+        self.check_range::<T>(addr, 1)?;
+        Ok(unsafe { ptr::read_unaligned(addr as *const T) })
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.base, self.len).expect("munmap succeeds during drop");
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::alloc::host_page_size;
+use crate::error::Error;
+use crate::module::ModuleInternal;
+use std::os::unix::io::RawFd;
+
+/// A module's initial heap, written once into an anonymous in-memory file.
+///
+/// Regions that support it map this file `MAP_PRIVATE` over an instance's heap, so that creating
+/// or resetting an instance is a remap rather than a copy of every initialized page. Pages are
+/// only copied when the guest first writes to them.
+pub struct HeapMemFd {
+    fd: RawFd,
+    len: usize,
+}
+
+impl HeapMemFd {
+    /// Create a memfd containing the initial heap of `module`.
+    ///
+    /// The file is the size of the initial heap; pages without sparse page data are left as holes,
+    /// which read as zero and take up no memory.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn new(module: &dyn ModuleInternal) -> Result<Self, Error> {
+        use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+        use nix::sys::uio::pwrite;
+        use nix::unistd::ftruncate;
+        use std::ffi::CString;
+
+        let len = module
+            .heap_spec()
+            .map(|h| h.initial_size as usize)
+            .unwrap_or(0);
+        if len % host_page_size() != 0 {
+            return Err(lucet_incorrect_module!(
+                "initial heap size {} is not divisible by host page size ({})",
+                len,
+                host_page_size()
+            ));
+        }
+        if module.sparse_page_data_len() * host_page_size() > len {
+            return Err(lucet_incorrect_module!(
+                "sparse page data length exceeded initial heap size"
+            ));
+        }
+
+        let name = CString::new("lucet_initial_heap").expect("name has no nul bytes");
+        let fd = memfd_create(&name, MemFdCreateFlag::MFD_CLOEXEC)?;
+        // from here on, dropping `memfd` closes the file if initialization fails
+        let memfd = HeapMemFd { fd, len };
+
+        ftruncate(fd, len as libc::off_t)?;
+        for page_num in 0..module.sparse_page_data_len() {
+            if let Some(contents) = module.get_sparse_page_data(page_num) {
+                let offset = page_num * host_page_size();
+                let written = pwrite(fd, contents, offset as libc::off_t)?;
+                if written != contents.len() {
+                    lucet_bail!("short write to initial heap memfd");
+                }
+            }
+        }
+
+        Ok(memfd)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) fn new(_module: &dyn ModuleInternal) -> Result<Self, Error> {
+        Err(Error::Unsupported(
+            "memfd-backed heaps are only available on Linux".to_owned(),
+        ))
+    }
+
+    /// The file descriptor of the memfd.
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// The length of the initial heap stored in the memfd, in bytes.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+}
+
+impl Drop for HeapMemFd {
+    fn drop(&mut self) {
+        // there is nothing useful to do if closing fails
+        let _ = nix::unistd::close(self.fd);
+    }
+}
@@ -1,13 +1,15 @@
 use crate::error::Error;
-use crate::module::{AddrDetails, GlobalSpec, HeapSpec, Module, ModuleInternal, TableElement};
+use crate::module::{
+    AddrDetails, GlobalSpec, HeapMemFd, HeapSpec, Module, ModuleInternal, TableElement,
+};
 use libc::c_void;
 use lucet_module_data::owned::{
     OwnedExportFunction, OwnedFunctionMetadata, OwnedGlobalSpec, OwnedImportFunction,
     OwnedLinearMemorySpec, OwnedModuleData, OwnedSparseData,
 };
 use lucet_module_data::{
-    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, ModuleData, Signature, TrapSite,
-    UniqueSignatureIndex,
+    FunctionHandle, FunctionIndex, FunctionPointer, FunctionSpec, GlobalDef, ImportFunction,
+    ImportLinking, ModuleData, Signature, TrapSite, UniqueSignatureIndex, ValueType,
 };
 use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
@@ -24,8 +26,10 @@ pub struct MockModuleBuilder {
     function_manifest: Vec<FunctionSpec>,
     function_info: Vec<OwnedFunctionMetadata>,
     imports: Vec<OwnedImportFunction>,
+    import_linking: ImportLinking,
     exports: Vec<OwnedExportFunction>,
     signatures: Vec<Signature>,
+    heap_memfd: bool,
 }
 
 impl MockModuleBuilder {
@@ -62,24 +66,46 @@ impl MockModuleBuilder {
         self
     }
 
+    /// Keep the initial heap in a memfd, as with
+    /// [`DlModule::load_with_heap_memfd()`](struct.DlModule.html#method.load_with_heap_memfd).
+    pub fn with_heap_memfd(mut self) -> Self {
+        self.heap_memfd = true;
+        self
+    }
+
+    /// Add a mutable `i64` global with an initial value.
     pub fn with_global(mut self, idx: u32, init_val: i64) -> Self {
-        self.globals
-            .insert(idx as usize, OwnedGlobalSpec::new_def(init_val, None));
+        self.globals.insert(
+            idx as usize,
+            OwnedGlobalSpec::new_def(GlobalDef::I64(init_val), ValueType::I64, true, vec![]),
+        );
         self
     }
 
     pub fn with_exported_global(mut self, idx: u32, init_val: i64, export_name: &str) -> Self {
         self.globals.insert(
             idx as usize,
-            OwnedGlobalSpec::new_def(init_val, Some(export_name.to_string())),
+            OwnedGlobalSpec::new_def(
+                GlobalDef::I64(init_val),
+                ValueType::I64,
+                true,
+                vec![export_name.to_string()],
+            ),
         );
         self
     }
 
+    /// Add an immutable `i64` global import.
     pub fn with_import(mut self, idx: u32, import_module: &str, import_field: &str) -> Self {
         self.globals.insert(
             idx as usize,
-            OwnedGlobalSpec::new_import(import_module.to_string(), import_field.to_string(), None),
+            OwnedGlobalSpec::new_import(
+                import_module.to_string(),
+                import_field.to_string(),
+                ValueType::I64,
+                false,
+                vec![],
+            ),
         );
         self
     }
@@ -96,7 +122,9 @@ impl MockModuleBuilder {
             OwnedGlobalSpec::new_import(
                 import_module.to_string(),
                 import_field.to_string(),
-                Some(export_name.to_string()),
+                ValueType::I64,
+                false,
+                vec![export_name.to_string()],
             ),
         );
         self
@@ -137,10 +165,37 @@ impl MockModuleBuilder {
             export.func_len() as u32,
             export.traps().as_ptr() as u64,
             export.traps().len() as u64,
+            0,
+            0,
         ));
         self
     }
 
+    /// Add a function import, which takes the next function index.
+    ///
+    /// Mock modules have no code to call imports with, so this is only useful for testing how
+    /// imports are resolved.
+    pub fn with_import_func(mut self, module: &str, name: &str, sig: Signature) -> Self {
+        let sig_idx = self.record_sig(sig);
+        self.function_info.push(OwnedFunctionMetadata {
+            signature: sig_idx,
+            name: None,
+        });
+        self.imports.push(OwnedImportFunction {
+            fn_idx: FunctionIndex::from_u32(self.function_manifest.len() as u32),
+            module: module.to_string(),
+            name: name.to_string(),
+        });
+        self.function_manifest
+            .push(FunctionSpec::new(0, 0, 0, 0, 0, 0));
+        self
+    }
+
+    pub fn with_import_linking(mut self, import_linking: ImportLinking) -> Self {
+        self.import_linking = import_linking;
+        self
+    }
+
     pub fn with_table_func(mut self, table_idx: u32, func_idx: u32, func: FunctionPointer) -> Self {
         self.func_table.insert((table_idx, func_idx), func);
         self
@@ -190,6 +245,7 @@ impl MockModuleBuilder {
             globals_spec,
             self.function_info.clone(),
             self.imports,
+            self.import_linking,
             self.exports,
             self.signatures,
         );
@@ -200,7 +256,7 @@ impl MockModuleBuilder {
         let module_data = ModuleData::deserialize(&serialized_module_data)
             .map(|md| unsafe { std::mem::transmute(md) })
             .expect("module data can be deserialized");
-        let mock = MockModule {
+        let mut mock = MockModule {
             serialized_module_data,
             module_data,
             table_elements,
@@ -208,7 +264,11 @@ impl MockModuleBuilder {
             func_table: self.func_table,
             start_func: self.start_func,
             function_manifest: self.function_manifest,
+            heap_memfd: None,
         };
+        if self.heap_memfd {
+            mock.heap_memfd = Some(HeapMemFd::new(&mock).expect("heap memfd can be created"));
+        }
         Arc::new(mock)
     }
 }
@@ -222,6 +282,7 @@ pub struct MockModule {
     pub func_table: HashMap<(u32, u32), FunctionPointer>,
     pub start_func: Option<FunctionPointer>,
     pub function_manifest: Vec<FunctionSpec>,
+    heap_memfd: Option<HeapMemFd>,
 }
 
 unsafe impl Send for MockModule {}
@@ -250,6 +311,10 @@ impl ModuleInternal for MockModule {
         self.module_data.sparse_data().map(|d| d.len()).unwrap_or(0)
     }
 
+    fn heap_memfd(&self) -> Option<&HeapMemFd> {
+        self.heap_memfd.as_ref()
+    }
+
     fn table_elements(&self) -> Result<&[TableElement], Error> {
         Ok(&self.table_elements)
     }
@@ -292,6 +357,21 @@ impl ModuleInternal for MockModule {
     fn get_signature(&self, fn_id: FunctionIndex) -> &Signature {
         self.module_data.get_signature(fn_id)
     }
+
+    fn import_functions(&self) -> &[ImportFunction] {
+        self.module_data.import_functions()
+    }
+
+    fn import_linking(&self) -> ImportLinking {
+        self.module_data.import_linking()
+    }
+
+    fn get_func_name(&self, fn_id: FunctionIndex) -> Option<&str> {
+        self.module_data
+            .function_info()
+            .get(fn_id.as_u32() as usize)
+            .and_then(|info| info.name)
+    }
 }
 
 pub struct MockExportBuilder {
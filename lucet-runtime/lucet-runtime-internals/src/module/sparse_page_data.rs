@@ -67,4 +67,8 @@ macro_rules! sparse_page_data_tests {
 #[cfg(test)]
 mod tests {
     sparse_page_data_tests!(crate::region::mmap::MmapRegion);
+
+    mod on_demand {
+        sparse_page_data_tests!(crate::region::mmap::OnDemandMmapRegion);
+    }
 }
@@ -1,44 +1,135 @@
 use crate::alloc::{host_page_size, instance_heap_offset, Alloc, Limits, Slot};
 use crate::embed_ctx::CtxMap;
 use crate::error::Error;
-use crate::instance::{new_instance_handle, Instance, InstanceHandle};
+use crate::host_functions::ImportTable;
+use crate::instance::{new_instance_handle, Instance, InstanceHandle, InstanceSnapshot};
 use crate::module::Module;
-use crate::region::{Region, RegionCreate, RegionInternal};
+use crate::region::{Region, RegionCreate, RegionInternal, RegionStats};
+use lazy_static::lazy_static;
 #[cfg(not(target_os = "linux"))]
 use libc::memset;
 use libc::{c_void, SIGSTKSZ};
 use nix::sys::mman::{madvise, mmap, munmap, MapFlags, MmapAdvise, ProtFlags};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::ops::{Deref, Range};
+use std::os::unix::fs::FileExt;
 use std::ptr;
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex, RwLock, Weak};
+use std::thread::{self, JoinHandle};
+
+lazy_static! {
+    /// The number of times a region has cleared the soft-dirty bits of the process.
+    ///
+    /// Scans of the bits hold the lock for reading, so that the bits are not cleared partway
+    /// through a scan.
+    static ref SOFT_DIRTY_CLEARS: RwLock<u64> = RwLock::new(0);
+}
 
 /// A [`Region`](trait.Region.html) backed by `mmap`.
+///
+/// The slots of a region are divided into classes, each with its own limits. Regions with more
+/// than one class, for example a few slots with large heaps alongside many with small heaps, are
+/// created with [`MmapRegion::builder()`](struct.MmapRegion.html#method.builder).
+///
+/// By default, the memory for every slot is mapped when the region is created. Regions built with
+/// [`on_demand()`](struct.MmapRegionBuilder.html#method.on_demand) instead map the memory for a
+/// slot when an instance is created in it, and unmap it when the instance is dropped, so that a
+/// region only takes up address space for the instances it holds.
+///
+/// The slots of dropped instances are cleared for reuse on the thread that drops them, unless the
+/// region is built with
+/// [`scrub_in_background()`](struct.MmapRegionBuilder.html#method.scrub_in_background).
+///
+/// Regions built with
+/// [`track_dirty_pages()`](struct.MmapRegionBuilder.html#method.track_dirty_pages) reset or
+/// restore the heap of an instance by resetting only the pages written since the heap was last
+/// reset, so that the cost of a reset mostly follows the memory the instance wrote to rather than
+/// the size of its heap.
 pub struct MmapRegion {
+    /// The classes of slots, from the smallest to the largest; shared with the scrubber thread
+    classes: Arc<Vec<SlotClass>>,
+    on_demand: bool,
+    /// The region itself, which slots refer back to
+    this: Mutex<Weak<MmapRegion>>,
+    /// Sends the slots of dropped instances to the scrubber thread, if there is one
+    scrubber: Option<Mutex<Sender<Scrub>>>,
+    scrubber_thread: Option<JoinHandle<()>>,
+    /// Finds the pages written to heaps, if the region tracks them; heaps are reset in full
+    /// without it
+    dirty_tracking: Option<DirtyTracking>,
+}
+
+/// The slots of a region that share the same limits.
+struct SlotClass {
     capacity: usize,
-    freelist: Mutex<Vec<Slot>>,
+    slots: Mutex<ClassSlots>,
+    /// Notified when the scrubber thread returns a slot to the class
+    scrubbed: Condvar,
     limits: Limits,
 }
 
-impl Region for MmapRegion {}
+struct ClassSlots {
+    free: Vec<Slot>,
+    /// The start addresses of the slots whose memory is mapped, whether free or in use
+    mapped: Vec<usize>,
+    /// The number of slots waiting to be scrubbed before they are free
+    scrubbing: usize,
+}
+
+/// The slot of a dropped instance, and what is needed to clear it for reuse.
+struct Scrub {
+    slot: Slot,
+    heap_accessible_size: usize,
+    heap_memfd_mapped: bool,
+}
+
+impl Region for MmapRegion {
+    /// The resident memory of an instance whose heap is mapped from a module's initial heap image
+    /// includes the pages of the image that are in the page cache, which are shared with other
+    /// such instances.
+    ///
+    /// Slots that are waiting to be scrubbed count as free, as they are returned to the region
+    /// without any further use of the region.
+    fn stats(&self) -> Result<RegionStats, Error> {
+        let mut stats = RegionStats {
+            capacity: 0,
+            in_use: 0,
+            free: 0,
+            resident_bytes: 0,
+        };
+        for class in self.classes.iter() {
+            let slots = class.slots.lock().unwrap();
+            let in_use = slots.mapped.len() - slots.free.len() - slots.scrubbing;
+            stats.capacity += class.capacity;
+            stats.in_use += in_use;
+            stats.free += class.capacity - in_use;
+            for start in slots.mapped.iter() {
+                stats.resident_bytes += Self::slot_resident_size(*start, &class.limits)?;
+            }
+        }
+        Ok(stats)
+    }
+}
 
 impl RegionInternal for MmapRegion {
     fn new_instance_with(
         &self,
         module: Arc<dyn Module>,
+        limits: Option<&Limits>,
         embed_ctx: CtxMap,
+        global_imports: Vec<Option<i64>>,
+        import_table: ImportTable,
     ) -> Result<InstanceHandle, Error> {
-        let slot = self
-            .freelist
-            .lock()
-            .unwrap()
-            .pop()
-            .ok_or(Error::RegionFull(self.capacity))?;
+        let slot = self.take_slot(module.as_ref(), limits)?;
 
         if slot.heap as usize % host_page_size() != 0 {
             lucet_bail!("heap is not page-aligned; this is a bug");
         }
 
         let limits = &slot.limits;
-        module.validate_runtime_spec(limits)?;
 
         for (ptr, len) in [
             // make the stack read/writable
@@ -70,29 +161,170 @@ impl RegionInternal for MmapRegion {
         let alloc = Alloc {
             heap_accessible_size: 0, // the `reset` call in `new_instance_handle` will set this
             heap_inaccessible_size: slot.limits.heap_address_space_size,
+            heap_memfd_mapped: false,
+            heap_clean: None,
             slot: Some(slot),
             region,
         };
 
-        let inst = new_instance_handle(inst_ptr, module, alloc, embed_ctx)?;
+        let inst = new_instance_handle(
+            inst_ptr,
+            module,
+            alloc,
+            embed_ctx,
+            global_imports,
+            import_table,
+        )?;
 
         Ok(inst)
     }
 
     fn drop_alloc(&self, alloc: &mut Alloc) {
-        let slot = alloc
-            .slot
-            .take()
-            .expect("alloc didn't have a slot during drop; dropped twice?");
+        if self.on_demand {
+            // unmapping the slot discards everything in it, so there is nothing to clear
+            let slot = alloc
+                .slot
+                .take()
+                .expect("alloc didn't have a slot during drop; dropped twice?");
+            let start = slot.start as usize;
+            self.class_of(&slot)
+                .slots
+                .lock()
+                .unwrap()
+                .mapped
+                .retain(|mapped| *mapped != start);
+            Self::free_slot(slot);
+            return;
+        }
+
+        let scrub = Scrub {
+            slot: alloc
+                .slot
+                .take()
+                .expect("alloc didn't have a slot during drop; dropped twice?"),
+            heap_accessible_size: alloc.heap_accessible_size,
+            heap_memfd_mapped: alloc.heap_memfd_mapped,
+        };
+
+        if let Some(scrubber) = &self.scrubber {
+            self.class_of(&scrub.slot).slots.lock().unwrap().scrubbing += 1;
+            scrubber
+                .lock()
+                .unwrap()
+                .send(scrub)
+                .expect("scrubber thread is running");
+            return;
+        }
+
+        scrub.run();
+        self.class_of(&scrub.slot)
+            .slots
+            .lock()
+            .unwrap()
+            .free
+            .push(scrub.slot);
+    }
+
+    fn expand_heap(&self, slot: &Slot, start: u32, len: u32) -> Result<(), Error> {
+        unsafe {
+            mprotect(
+                (slot.heap as usize + start as usize) as *mut c_void,
+                len as usize,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error> {
+        let initial_size = module
+            .heap_spec()
+            .map(|h| h.initial_size as usize)
+            .unwrap_or(0);
+
+        if let Some(tracking) = &self.dirty_tracking {
+            // the pages of a heap mapped from the module's heap memfd revert to the initial heap
+            // when they are discarded, so none of them need to be copied
+            let memfd_mapped = alloc.heap_memfd_mapped;
+            let page_contents = |page_num| {
+                if memfd_mapped {
+                    None
+                } else {
+                    module.get_sparse_page_data(page_num)
+                }
+            };
+            if tracking.reset_dirty_pages(
+                alloc,
+                HeapContents::Initial,
+                initial_size,
+                page_contents,
+            )? {
+                return Ok(());
+            }
+        }
+
+        Self::reset_heap_in_full(alloc, module, initial_size)?;
+        if let Some(tracking) = &self.dirty_tracking {
+            tracking.mark_clean(alloc, HeapContents::Initial);
+        }
+        Ok(())
+    }
+
+    fn restore_heap(&self, alloc: &mut Alloc, snapshot: &InstanceSnapshot) -> Result<(), Error> {
+        let contents = HeapContents::Snapshot(snapshot.id());
+
+        if let Some(tracking) = &self.dirty_tracking {
+            let page_contents = |page_num| snapshot.heap_page(page_num);
+            if tracking.reset_dirty_pages(alloc, contents, snapshot.heap_len(), page_contents)? {
+                return Ok(());
+            }
+        }
+
+        Self::restore_heap_in_full(alloc, snapshot)?;
+        if let Some(tracking) = &self.dirty_tracking {
+            tracking.mark_clean(alloc, contents);
+        }
+        Ok(())
+    }
+
+    fn as_dyn_internal(&self) -> &dyn RegionInternal {
+        self
+    }
+}
+
+impl Drop for MmapRegion {
+    fn drop(&mut self) {
+        // closing the channel stops the scrubber thread once it has returned every slot sent to it
+        self.scrubber.take();
+        if let Some(thread) = self.scrubber_thread.take() {
+            thread.join().expect("scrubber thread does not panic");
+        }
+        for class in self.classes.iter() {
+            for slot in class.slots.lock().unwrap().free.drain(0..) {
+                Self::free_slot(slot);
+            }
+        }
+    }
+}
+
+impl Scrub {
+    /// Clear and disable access to the heap, stack, globals, and sigstack of the slot.
+    fn run(&self) {
+        let slot = &self.slot;
+
+        // `MADV_DONTNEED` on a private file mapping reverts pages to the file contents rather than
+        // zeroing them, so the heap has to go back to anonymous memory before the slot is reused
+        if self.heap_memfd_mapped {
+            MmapRegion::remap_heap(slot).expect("heap can be cleared during drop");
+        }
 
         if slot.heap as usize % host_page_size() != 0 {
             panic!("heap is not page-aligned");
         }
 
-        // clear and disable access to the heap, stack, globals, and sigstack
         for (ptr, len) in [
             // We don't ever shrink the heap, so we only need to zero up until the accessible size
-            (slot.heap, alloc.heap_accessible_size),
+            (slot.heap, self.heap_accessible_size),
             (slot.stack, slot.limits.stack_size),
             (slot.globals, slot.limits.globals_size),
             (slot.sigstack, SIGSTKSZ),
@@ -113,26 +345,255 @@ impl RegionInternal for MmapRegion {
                     .expect("madvise succeeds during drop");
             }
         }
+    }
+}
 
-        self.freelist.lock().unwrap().push(slot);
+/// The body of the scrubber thread: scrub the slots of dropped instances, and return them to their
+/// classes, until the region is dropped.
+fn scrub_slots(classes: Arc<Vec<SlotClass>>, scrubs: Receiver<Scrub>) {
+    for scrub in scrubs.iter() {
+        scrub.run();
+        let class = find_class(&classes, &scrub.slot);
+        let mut slots = class.slots.lock().unwrap();
+        slots.free.push(scrub.slot);
+        slots.scrubbing -= 1;
+        class.scrubbed.notify_all();
+    }
+}
+
+/// The class that a slot belongs to.
+fn find_class<'a>(classes: &'a [SlotClass], slot: &Slot) -> &'a SlotClass {
+    classes
+        .iter()
+        .find(|class| class.limits == slot.limits)
+        .expect("slot belongs to a class of the region")
+}
+
+impl RegionCreate for MmapRegion {
+    const TYPE_NAME: &'static str = "MmapRegion";
+
+    fn create(instance_capacity: usize, limits: &Limits) -> Result<Arc<Self>, Error> {
+        MmapRegion::create(instance_capacity, limits)
+    }
+}
+
+/// An [`MmapRegion`](struct.MmapRegion.html) that maps the memory for its slots on demand.
+///
+/// This is the region built by `MmapRegion::builder().slots(capacity, limits).on_demand()`, as a
+/// type that implements [`RegionCreate`](trait.RegionCreate.html).
+pub struct OnDemandMmapRegion {
+    region: Arc<MmapRegion>,
+}
+
+impl Deref for OnDemandMmapRegion {
+    type Target = MmapRegion;
+
+    fn deref(&self) -> &MmapRegion {
+        &self.region
+    }
+}
+
+impl Region for OnDemandMmapRegion {
+    fn stats(&self) -> Result<RegionStats, Error> {
+        self.region.stats()
+    }
+}
+
+impl RegionInternal for OnDemandMmapRegion {
+    fn new_instance_with(
+        &self,
+        module: Arc<dyn Module>,
+        limits: Option<&Limits>,
+        embed_ctx: CtxMap,
+        global_imports: Vec<Option<i64>>,
+        import_table: ImportTable,
+    ) -> Result<InstanceHandle, Error> {
+        self.region
+            .new_instance_with(module, limits, embed_ctx, global_imports, import_table)
+    }
+
+    fn drop_alloc(&self, alloc: &mut Alloc) {
+        self.region.drop_alloc(alloc)
     }
 
     fn expand_heap(&self, slot: &Slot, start: u32, len: u32) -> Result<(), Error> {
+        self.region.expand_heap(slot, start, len)
+    }
+
+    fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error> {
+        self.region.reset_heap(alloc, module)
+    }
+
+    fn restore_heap(&self, alloc: &mut Alloc, snapshot: &InstanceSnapshot) -> Result<(), Error> {
+        self.region.restore_heap(alloc, snapshot)
+    }
+
+    fn as_dyn_internal(&self) -> &dyn RegionInternal {
+        // instances refer to the underlying region, so this can be dropped before them
+        self.region.as_dyn_internal()
+    }
+}
+
+impl RegionCreate for OnDemandMmapRegion {
+    const TYPE_NAME: &'static str = "OnDemandMmapRegion";
+
+    fn create(instance_capacity: usize, limits: &Limits) -> Result<Arc<Self>, Error> {
+        let region = MmapRegion::builder()
+            .slots(instance_capacity, limits)
+            .on_demand()
+            .build()?;
+        Ok(Arc::new(OnDemandMmapRegion { region }))
+    }
+}
+
+impl MmapRegion {
+    /// Create a new `MmapRegion` that can support a given number instances, each subject to the
+    /// same runtime limits.
+    ///
+    /// The region is returned in an `Arc`, because any instances created from it carry a reference
+    /// back to the region.
+    pub fn create(instance_capacity: usize, limits: &Limits) -> Result<Arc<Self>, Error> {
+        MmapRegion::builder()
+            .slots(instance_capacity, limits)
+            .build()
+    }
+
+    /// Return a builder for a region with more than one class of slots.
+    ///
+    /// ```no_run
+    /// # use lucet_runtime_internals::alloc::Limits;
+    /// # use lucet_runtime_internals::region::mmap::MmapRegion;
+    /// let small = Limits::default();
+    /// let large = Limits {
+    ///     heap_memory_size: 256 * 1024 * 1024,
+    ///     ..Limits::default()
+    /// };
+    /// let region = MmapRegion::builder()
+    ///     .slots(1000, &small)
+    ///     .slots(10, &large)
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> MmapRegionBuilder {
+        MmapRegionBuilder::default()
+    }
+
+    /// Wait until the slots of every instance dropped so far have been scrubbed and returned to
+    /// the region.
+    ///
+    /// This returns immediately unless the region was built with
+    /// [`scrub_in_background()`](struct.MmapRegionBuilder.html#method.scrub_in_background).
+    pub fn wait_for_scrubs(&self) {
+        for class in self.classes.iter() {
+            let mut slots = class.slots.lock().unwrap();
+            while slots.scrubbing > 0 {
+                slots = class.scrubbed.wait(slots).unwrap();
+            }
+        }
+    }
+
+    /// Whether the region resets heaps by resetting only the pages written to; see
+    /// [`track_dirty_pages()`](struct.MmapRegionBuilder.html#method.track_dirty_pages).
+    pub fn tracks_dirty_pages(&self) -> bool {
+        self.dirty_tracking.is_some()
+    }
+
+    fn create_slot(region: &Weak<MmapRegion>, limits: &Limits) -> Result<Slot, Error> {
+        // get the chunk of virtual memory that the `Slot` will manage
+        let mem = unsafe {
+            mmap(
+                ptr::null_mut(),
+                limits.total_memory_size(),
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE,
+                0,
+                0,
+            )?
+        };
+
+        // set the first part of the memory to read/write so that the `Instance` can be stored there
+        // TODO: post slot refactor, is this necessary/desirable?
         unsafe {
             mprotect(
-                (slot.heap as usize + start as usize) as *mut c_void,
-                len as usize,
+                mem,
+                instance_heap_offset(),
                 ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
-            )?;
+            )?
+        };
+
+        // lay out the other sections in memory
+        let heap = mem as usize + instance_heap_offset();
+        let stack = heap + limits.heap_address_space_size;
+        let globals = stack + limits.stack_size + host_page_size();
+        let sigstack = globals + host_page_size();
+
+        Ok(Slot {
+            start: mem,
+            heap: heap as *mut c_void,
+            stack: stack as *mut c_void,
+            globals: globals as *mut c_void,
+            sigstack: sigstack as *mut c_void,
+            limits: limits.clone(),
+            region: region.clone() as Weak<dyn RegionInternal>,
+        })
+    }
+
+    /// Take a free slot for an instance of `module` from the smallest class whose limits fit the
+    /// module, and `limits` if given.
+    fn take_slot(&self, module: &dyn Module, limits: Option<&Limits>) -> Result<Slot, Error> {
+        let mut full_class = None;
+        let mut spec_error = None;
+        for class in self.classes.iter() {
+            if limits.map_or(false, |limits| !limits.fits_within(&class.limits)) {
+                continue;
+            }
+            if let Err(e) = module.validate_runtime_spec(&class.limits) {
+                spec_error.get_or_insert(e);
+                continue;
+            }
+            let mut slots = class.slots.lock().unwrap();
+            // a slot that is being scrubbed is about to be free, so the class is not full yet
+            while slots.free.is_empty() && slots.scrubbing > 0 {
+                slots = class.scrubbed.wait(slots).unwrap();
+            }
+            if let Some(slot) = slots.free.pop() {
+                return Ok(slot);
+            }
+            if self.on_demand && slots.mapped.len() < class.capacity {
+                let slot = Self::create_slot(&self.this.lock().unwrap(), &class.limits)?;
+                slots.mapped.push(slot.start as usize);
+                return Ok(slot);
+            }
+            full_class.get_or_insert(class);
+        }
+        // a class that would have fit but ran out is the more useful error
+        if let Some(class) = full_class {
+            Err(Error::RegionFull(class.capacity, class.limits.clone()))
+        } else if let Some(e) = spec_error {
+            Err(e)
+        } else {
+            Err(Error::LimitsExceeded(format!(
+                "no slots in the region fit the requested limits: {:?}",
+                limits
+            )))
         }
-        Ok(())
     }
 
-    fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error> {
-        let heap = alloc.slot().heap;
+    /// The class of the region that a slot belongs to.
+    fn class_of(&self, slot: &Slot) -> &SlotClass {
+        find_class(&self.classes, slot)
+    }
 
-        if alloc.heap_accessible_size > 0 {
-            // zero the whole heap, if any of it is currently accessible
+    /// Zero the whole heap of an `Alloc` and make it inaccessible, if any of it is currently
+    /// accessible.
+    ///
+    /// The accessible and inaccessible sizes of the `Alloc` are left for the caller to update.
+    fn clear_heap(alloc: &mut Alloc) -> Result<(), Error> {
+        if alloc.heap_memfd_mapped {
+            Self::remap_heap(alloc.slot())?;
+            alloc.heap_memfd_mapped = false;
+        } else if alloc.heap_accessible_size > 0 {
+            let heap = alloc.slot().heap;
             let heap_size = alloc.slot().limits.heap_address_space_size;
 
             unsafe {
@@ -151,11 +612,55 @@ impl RegionInternal for MmapRegion {
                 madvise(heap, heap_size, MmapAdvise::MADV_DONTNEED)?;
             }
         }
+        Ok(())
+    }
 
-        let initial_size = module
-            .heap_spec()
-            .map(|h| h.initial_size as usize)
-            .unwrap_or(0);
+    /// Reset the heap of an `Alloc` to the initial heap of `module`, of `initial_size` bytes,
+    /// regardless of what it currently holds.
+    fn reset_heap_in_full(
+        alloc: &mut Alloc,
+        module: &dyn Module,
+        initial_size: usize,
+    ) -> Result<(), Error> {
+        let heap = alloc.slot().heap;
+
+        if let Some(memfd) = module.heap_memfd() {
+            if memfd.len() != initial_size {
+                lucet_bail!("heap memfd does not match the initial heap size; this is a bug");
+            }
+            Self::clear_heap(alloc)?;
+
+            // the memfd already holds the initial heap, so rather than copying the sparse page
+            // data, map it copy-on-write over the start of the heap
+            if initial_size > 0 {
+                unsafe {
+                    mmap(
+                        heap,
+                        initial_size,
+                        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+                        MapFlags::MAP_PRIVATE | MapFlags::MAP_FIXED,
+                        memfd.fd(),
+                        0,
+                    )?
+                };
+                alloc.heap_memfd_mapped = true;
+            }
+            alloc.heap_accessible_size = initial_size;
+            alloc.heap_inaccessible_size =
+                alloc.slot().limits.heap_address_space_size - initial_size;
+            return Ok(());
+        }
+
+        let initial_pages =
+            initial_size
+                .checked_div(host_page_size())
+                .ok_or(lucet_incorrect_module!(
+                    "initial heap size {} is not divisible by host page size ({})",
+                    initial_size,
+                    host_page_size()
+                ))?;
+
+        Self::clear_heap(alloc)?;
 
         // reset the heap to the initial size, and mprotect those pages appropriately
         if initial_size > 0 {
@@ -179,14 +684,6 @@ impl RegionInternal for MmapRegion {
         // Any pages which don't have an entry in the sparse page data, either because their entry
         // is None, or because the sparse data has fewer pages than the initial heap, are zeroed.
         let heap = unsafe { alloc.heap_mut() };
-        let initial_pages =
-            initial_size
-                .checked_div(host_page_size())
-                .ok_or(lucet_incorrect_module!(
-                    "initial heap size {} is not divisible by host page size ({})",
-                    initial_size,
-                    host_page_size()
-                ))?;
         for page_num in 0..initial_pages {
             let page_base = page_num * host_page_size();
             if heap.len() < page_base {
@@ -203,103 +700,461 @@ impl RegionInternal for MmapRegion {
         Ok(())
     }
 
-    fn as_dyn_internal(&self) -> &dyn RegionInternal {
-        self
+    /// Restore the heap of an `Alloc` to a snapshot, regardless of what it currently holds.
+    fn restore_heap_in_full(alloc: &mut Alloc, snapshot: &InstanceSnapshot) -> Result<(), Error> {
+        let heap = alloc.slot().heap;
+
+        Self::clear_heap(alloc)?;
+
+        // make the snapshotted part of the heap accessible again; its pages are now all zero
+        let heap_len = snapshot.heap_len();
+        if heap_len > 0 {
+            unsafe { mprotect(heap, heap_len, ProtFlags::PROT_READ | ProtFlags::PROT_WRITE)? };
+        }
+        alloc.heap_accessible_size = heap_len;
+        alloc.heap_inaccessible_size = alloc.slot().limits.heap_address_space_size - heap_len;
+
+        // only pages that were not all zero when snapshotted need to be copied
+        let heap = unsafe { alloc.heap_mut() };
+        for (page_num, page_base) in (0..heap_len).step_by(host_page_size()).enumerate() {
+            if let Some(contents) = snapshot.heap_page(page_num) {
+                heap[page_base..page_base + contents.len()].copy_from_slice(contents);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replace the heap of a slot with fresh, inaccessible anonymous memory.
+    ///
+    /// This both discards the copy-on-write pages of a memfd mapped over the heap and detaches the
+    /// heap from the memfd.
+    fn remap_heap(slot: &Slot) -> Result<(), Error> {
+        unsafe {
+            mmap(
+                slot.heap,
+                slot.limits.heap_address_space_size,
+                ProtFlags::PROT_NONE,
+                MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE | MapFlags::MAP_FIXED,
+                0,
+                0,
+            )?
+        };
+        Ok(())
+    }
+
+    /// The resident size of the memory of the slot at `start` that can be made accessible.
+    ///
+    /// Only the heap up to the largest size it can grow to is counted, as the rest of its address
+    /// space is never accessible.
+    fn slot_resident_size(start: usize, limits: &Limits) -> Result<usize, Error> {
+        let heap_end = start + instance_heap_offset() + limits.heap_memory_size;
+        let stack = start + instance_heap_offset() + limits.heap_address_space_size;
+        let slot_end = start + limits.total_memory_size();
+        Ok(resident_size(start, heap_end - start)? + resident_size(stack, slot_end - stack)?)
+    }
+
+    fn free_slot(slot: Slot) {
+        // eprintln!(
+        //     "unmapping {:p}[{:x}]",
+        //     slot.start,
+        //     slot.limits.total_memory_size()
+        // );
+        let res = unsafe { munmap(slot.start, slot.limits.total_memory_size()) };
+        res.expect("munmap succeeded");
     }
 }
 
-impl Drop for MmapRegion {
-    fn drop(&mut self) {
-        for slot in self.freelist.get_mut().unwrap().drain(0..) {
-            Self::free_slot(slot);
+/// A builder for regions with more than one class of slots; created by
+/// [`MmapRegion::builder()`](struct.MmapRegion.html#method.builder).
+#[derive(Clone, Debug, Default)]
+pub struct MmapRegionBuilder {
+    classes: Vec<(usize, Limits)>,
+    on_demand: bool,
+    scrub_in_background: bool,
+    track_dirty_pages: bool,
+}
+
+impl MmapRegionBuilder {
+    /// Add `count` slots, each subject to `limits`.
+    ///
+    /// Slots added with the same limits as earlier slots are part of the same class.
+    pub fn slots(mut self, count: usize, limits: &Limits) -> Self {
+        if let Some((capacity, _)) = self.classes.iter_mut().find(|(_, l)| l == limits) {
+            *capacity += count;
+        } else {
+            self.classes.push((count, limits.clone()));
         }
+        self
     }
-}
 
-impl RegionCreate for MmapRegion {
-    const TYPE_NAME: &'static str = "MmapRegion";
+    /// Map the memory for each slot only while an instance is using it.
+    ///
+    /// The region starts out with no memory mapped, maps a slot when an instance is created and
+    /// there is no free slot, up to the number of slots added to the region, and unmaps the slot
+    /// when the instance is dropped. This trades a `mmap()` per instance for not reserving address
+    /// space for slots that are not in use.
+    pub fn on_demand(mut self) -> Self {
+        self.on_demand = true;
+        self
+    }
 
-    fn create(instance_capacity: usize, limits: &Limits) -> Result<Arc<Self>, Error> {
-        MmapRegion::create(instance_capacity, limits)
+    /// Clear the slots of dropped instances for reuse on a background thread, rather than on the
+    /// thread that drops them.
+    ///
+    /// Clearing a slot takes longer the more of its memory the instance used, so this takes that
+    /// time off the thread that drops the instance. A slot is returned to the region once it is
+    /// cleared; creating an instance when the only slots that fit are still being cleared waits for
+    /// one of them. [`MmapRegion::wait_for_scrubs()`](struct.MmapRegion.html#method.wait_for_scrubs)
+    /// waits for every slot that is being cleared.
+    ///
+    /// Regions that map slots [`on_demand()`](#method.on_demand) unmap them rather than clearing
+    /// them, so this cannot be combined with that.
+    pub fn scrub_in_background(mut self) -> Self {
+        self.scrub_in_background = true;
+        self
     }
-}
 
-impl MmapRegion {
-    /// Create a new `MmapRegion` that can support a given number instances, each subject to the
-    /// same runtime limits.
+    /// Reset and restore the heaps of instances by resetting only the pages written to since the
+    /// heap was last reset or restored.
+    ///
+    /// The written pages are found with the soft-dirty bits of the process's page table entries,
+    /// which are only available on Linux kernels built with `CONFIG_MEM_SOFT_DIRTY`. Where they
+    /// are not available, heaps are reset in full, as they are without this option.
+    ///
+    /// The first reset of each instance, and any reset to different contents than the heap was
+    /// last reset to, is in full. After that, a reset finds the pages written since the soft-dirty
+    /// bits of the process were last cleared, which includes pages written before the heap's last
+    /// reset. So that these do not pile up, the region clears the bits once for every 16 tracked
+    /// resets per slot, by writing to `/proc/self/clear_refs`, which takes longer the more memory
+    /// the process has mapped. A clear stops the tracking of every other heap in the process, so
+    /// their next reset is in full. Anything else in the process that writes to
+    /// `/proc/self/clear_refs` cannot be detected, and leaves heaps that were written to before the
+    /// write only partly reset.
+    ///
+    /// [`MmapRegion::tracks_dirty_pages()`](struct.MmapRegion.html#method.tracks_dirty_pages)
+    /// reports whether the region ended up tracking written pages.
+    pub fn track_dirty_pages(mut self) -> Self {
+        self.track_dirty_pages = true;
+        self
+    }
+
+    /// Build the region, creating all of its slots unless they are created on demand.
     ///
     /// The region is returned in an `Arc`, because any instances created from it carry a reference
     /// back to the region.
-    pub fn create(instance_capacity: usize, limits: &Limits) -> Result<Arc<Self>, Error> {
+    pub fn build(mut self) -> Result<Arc<MmapRegion>, Error> {
         assert!(
             SIGSTKSZ % host_page_size() == 0,
             "signal stack size is a multiple of host page size"
         );
-        limits.validate()?;
+        if self.classes.is_empty() {
+            return Err(Error::InvalidArgument(
+                "a region must have at least one class of slots",
+            ));
+        }
+        for (_, limits) in self.classes.iter() {
+            limits.validate()?;
+        }
+        if self.on_demand && self.scrub_in_background {
+            return Err(Error::InvalidArgument(
+                "on-demand regions unmap slots rather than scrubbing them",
+            ));
+        }
+        self.classes
+            .sort_by_key(|(_, limits)| limits.total_memory_size());
+
+        let classes = Arc::new(
+            self.classes
+                .iter()
+                .map(|(capacity, limits)| SlotClass {
+                    capacity: *capacity,
+                    slots: Mutex::new(ClassSlots {
+                        free: vec![],
+                        mapped: vec![],
+                        scrubbing: 0,
+                    }),
+                    scrubbed: Condvar::new(),
+                    limits: limits.clone(),
+                })
+                .collect::<Vec<_>>(),
+        );
+        let (scrubber, scrubber_thread) = if self.scrub_in_background {
+            let (sender, receiver) = mpsc::channel();
+            let thread_classes = classes.clone();
+            let thread = thread::Builder::new()
+                .name("lucet-scrubber".to_owned())
+                .spawn(move || scrub_slots(thread_classes, receiver))
+                .map_err(|e| Error::InternalError(e.into()))?;
+            (Some(Mutex::new(sender)), Some(thread))
+        } else {
+            (None, None)
+        };
 
         let region = Arc::new(MmapRegion {
-            capacity: instance_capacity,
-            freelist: Mutex::new(Vec::with_capacity(instance_capacity)),
-            limits: limits.clone(),
+            classes,
+            on_demand: self.on_demand,
+            this: Mutex::new(Weak::new()),
+            scrubber,
+            scrubber_thread,
+            dirty_tracking: if self.track_dirty_pages {
+                DirtyTracking::open(self.classes.iter().map(|(capacity, _)| capacity).sum())
+            } else {
+                None
+            },
         });
-        {
-            let mut freelist = region.freelist.lock().unwrap();
-            for _ in 0..instance_capacity {
-                freelist.push(MmapRegion::create_slot(&region)?);
+        let this = Arc::downgrade(&region);
+        *region.this.lock().unwrap() = this.clone();
+        if !region.on_demand {
+            for class in region.classes.iter() {
+                let mut slots = class.slots.lock().unwrap();
+                for _ in 0..class.capacity {
+                    let slot = MmapRegion::create_slot(&this, &class.limits)?;
+                    slots.mapped.push(slot.start as usize);
+                    slots.free.push(slot);
+                }
             }
         }
 
         Ok(region)
     }
+}
 
-    fn create_slot(region: &Arc<MmapRegion>) -> Result<Slot, Error> {
-        // get the chunk of virtual memory that the `Slot` will manage
-        let mem = unsafe {
+/// The size of the pages in `len` bytes at `addr` that are resident in physical memory.
+fn resident_size(addr: usize, len: usize) -> Result<usize, Error> {
+    let page_size = host_page_size();
+    let mut pages = vec![0u8; (len + page_size - 1) / page_size];
+    nix::errno::Errno::result(unsafe {
+        libc::mincore(addr as *mut c_void, len, pages.as_mut_ptr() as *mut _)
+    })?;
+    Ok(pages.iter().filter(|page| *page & 1 != 0).count() * page_size)
+}
+
+/// What the heap of an instance held when it was last reset, as of the given clear of the
+/// soft-dirty bits of the process. Until the bits are cleared again, only the pages that are
+/// soft-dirty can differ from it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) struct CleanHeap {
+    /// The value of `SOFT_DIRTY_CLEARS` when the heap was reset
+    clears: u64,
+    contents: HeapContents,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum HeapContents {
+    /// The initial heap of the instance's module
+    Initial,
+    /// The snapshot with the given id
+    Snapshot(usize),
+}
+
+/// Finds the heap pages written since a heap was reset, using the soft-dirty bits of the page
+/// table entries of the process.
+///
+/// Writing `4` to `/proc/self/clear_refs` clears the bit for every page of the process, and the
+/// kernel sets it again when a page is written to, which `/proc/self/pagemap` reports as bit 55 of
+/// the entry for the page. Memory mapped since the last clear is reported as soft-dirty whether or
+/// not it was written to, so the bits only ever overstate the pages that were written.
+struct DirtyTracking {
+    pagemap: File,
+    clear_refs: File,
+    /// The number of tracked resets after which the region clears the bits
+    resets_per_clear: usize,
+    /// The number of tracked resets in the region
+    resets: AtomicUsize,
+}
+
+/// The number of tracked resets per slot of a region between clears of the soft-dirty bits.
+pub(crate) const RESETS_PER_CLEAR_PER_SLOT: usize = 16;
+
+impl DirtyTracking {
+    /// Open the files used to track written pages, if this platform sets soft-dirty bits, for a
+    /// region with `slots` slots.
+    fn open(slots: usize) -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+        let tracking = DirtyTracking {
+            pagemap: File::open("/proc/self/pagemap").ok()?,
+            clear_refs: OpenOptions::new()
+                .write(true)
+                .open("/proc/self/clear_refs")
+                .ok()?,
+            resets_per_clear: slots.max(1) * RESETS_PER_CLEAR_PER_SLOT,
+            resets: AtomicUsize::new(0),
+        };
+        // kernels built without soft-dirty bits accept the write to `clear_refs`, but never set
+        // the bit
+        if tracking.probe().unwrap_or(false) {
+            Some(tracking)
+        } else {
+            None
+        }
+    }
+
+    /// Whether a page written to after a clear is reported as soft-dirty.
+    fn probe(&self) -> Result<bool, Error> {
+        let page_size = host_page_size();
+        let page = unsafe {
             mmap(
                 ptr::null_mut(),
-                region.limits.total_memory_size(),
-                ProtFlags::PROT_NONE,
+                page_size,
+                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
                 MapFlags::MAP_ANON | MapFlags::MAP_PRIVATE,
                 0,
                 0,
             )?
         };
+        let dirty = self.clear().and_then(|_| {
+            unsafe { ptr::write_volatile(page as *mut u8, 1) };
+            self.dirty_pages(page as usize, page_size)
+        });
+        unsafe { munmap(page, page_size)? };
+        Ok(dirty? == [0])
+    }
 
-        // set the first part of the memory to read/write so that the `Instance` can be stored there
-        // TODO: post slot refactor, is this necessary/desirable?
-        unsafe {
-            mprotect(
-                mem,
-                instance_heap_offset(),
-                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
-            )?
+    /// Clear the soft-dirty bits of every page in the process, returning the new value of
+    /// `SOFT_DIRTY_CLEARS`.
+    fn clear(&self) -> Result<u64, Error> {
+        let mut clears = SOFT_DIRTY_CLEARS.write().unwrap();
+        // counted before the write, so that a write that fails partway still stops the tracking
+        // of other heaps
+        *clears += 1;
+        (&self.clear_refs)
+            .write_all(b"4")
+            .map_err(|e| Error::InternalError(e.into()))?;
+        Ok(*clears)
+    }
+
+    /// Start tracking the pages written to the heap of `alloc`, which has just been reset in full
+    /// to `contents`.
+    ///
+    /// The bits are not cleared, as that would stop the tracking of every other heap; the pages
+    /// that are already soft-dirty are reset again by the next reset.
+    fn mark_clean(&self, alloc: &mut Alloc, contents: HeapContents) {
+        let clears = *SOFT_DIRTY_CLEARS.read().unwrap();
+        alloc.heap_clean = Some(CleanHeap { clears, contents });
+    }
+
+    /// Reset the heap of `alloc` to `contents`, of `len` accessible bytes, by resetting only the
+    /// pages written since the heap last held `contents`. The contents of each page are given by
+    /// `page_contents`, or are zero if it returns `None`.
+    ///
+    /// Returns `false`, leaving the heap as it is, if the written pages are not known because the
+    /// heap has not held `contents` since the soft-dirty bits were last cleared.
+    fn reset_dirty_pages<'a>(
+        &self,
+        alloc: &mut Alloc,
+        contents: HeapContents,
+        len: usize,
+        page_contents: impl Fn(usize) -> Option<&'a [u8]>,
+    ) -> Result<bool, Error> {
+        let heap = alloc.slot().heap as usize;
+        let accessible_size = alloc.heap_accessible_size;
+        let page_size = host_page_size();
+
+        if heap % page_size != 0 || len % page_size != 0 {
+            lucet_bail!("heap is not page-aligned; this is a bug");
+        }
+
+        // until the reset succeeds, the heap may be partly reset
+        let clean = alloc.heap_clean.take();
+        let dirty = {
+            let clears = SOFT_DIRTY_CLEARS.read().unwrap();
+            let expected = CleanHeap {
+                clears: *clears,
+                contents,
+            };
+            // the heap never shrinks, so it cannot have held `contents` if they are larger
+            if clean != Some(expected) || len > accessible_size {
+                return Ok(false);
+            }
+            self.dirty_pages(heap, accessible_size)?
         };
 
-        // lay out the other sections in memory
-        let heap = mem as usize + instance_heap_offset();
-        let stack = heap + region.limits.heap_address_space_size;
-        let globals = stack + region.limits.stack_size + host_page_size();
-        let sigstack = globals + host_page_size();
+        // discarding a page zeroes it, or reverts it to the memfd if the heap is mapped from one
+        let mut discard: Vec<Range<usize>> = vec![];
+        let mut rewrite = vec![];
+        for page_num in dirty {
+            let page = if page_num * page_size < len {
+                page_contents(page_num)
+            } else {
+                None
+            };
+            match page {
+                Some(page) => rewrite.push((page_num * page_size, page)),
+                None => match discard.last_mut() {
+                    Some(pages) if pages.end == page_num => pages.end += 1,
+                    _ => discard.push(page_num..page_num + 1),
+                },
+            }
+        }
+        for pages in discard {
+            unsafe {
+                madvise(
+                    (heap + pages.start * page_size) as *mut c_void,
+                    (pages.end - pages.start) * page_size,
+                    MmapAdvise::MADV_DONTNEED,
+                )?
+            };
+        }
 
-        Ok(Slot {
-            start: mem,
-            heap: heap as *mut c_void,
-            stack: stack as *mut c_void,
-            globals: globals as *mut c_void,
-            sigstack: sigstack as *mut c_void,
-            limits: region.limits.clone(),
-            region: Arc::downgrade(region) as Weak<dyn RegionInternal>,
-        })
+        if len < accessible_size {
+            unsafe {
+                mprotect(
+                    (heap + len) as *mut c_void,
+                    accessible_size - len,
+                    ProtFlags::PROT_NONE,
+                )?
+            };
+        }
+        alloc.heap_accessible_size = len;
+        alloc.heap_inaccessible_size = alloc.slot().limits.heap_address_space_size - len;
+
+        let heap = unsafe { alloc.heap_mut() };
+        for (page_base, page) in rewrite {
+            heap[page_base..page_base + page.len()].copy_from_slice(page);
+        }
+
+        // the reset pages are still soft-dirty, so the next reset resets them again, along with
+        // any pages written before this reset. The bits are cleared once per batch of resets so
+        // that these do not pile up, which is also when this heap starts over with no dirty pages
+        let resets = self.resets.fetch_add(1, Ordering::SeqCst) + 1;
+        if resets % self.resets_per_clear == 0 {
+            let clears = self.clear()?;
+            alloc.heap_clean = Some(CleanHeap { clears, contents });
+        } else {
+            alloc.heap_clean = clean;
+        }
+        Ok(true)
     }
 
-    fn free_slot(slot: Slot) {
-        // eprintln!(
-        //     "unmapping {:p}[{:x}]",
-        //     slot.start,
-        //     slot.limits.total_memory_size()
-        // );
-        let res = unsafe { munmap(slot.start, slot.limits.total_memory_size()) };
-        res.expect("munmap succeeded");
+    /// The numbers of the pages in `[addr, addr + len)` that are soft-dirty.
+    fn dirty_pages(&self, addr: usize, len: usize) -> Result<Vec<usize>, Error> {
+        const SOFT_DIRTY: u64 = 1 << 55;
+        // read the pagemap a chunk at a time, rather than allocating an entry for every page
+        const CHUNK_PAGES: usize = 512;
+
+        let page_size = host_page_size();
+        let first_page = addr / page_size;
+        let num_pages = len / page_size;
+        let mut dirty = vec![];
+        let mut entries = [0u8; CHUNK_PAGES * 8];
+        for chunk_start in (0..num_pages).step_by(CHUNK_PAGES) {
+            let chunk_len = CHUNK_PAGES.min(num_pages - chunk_start);
+            let buf = &mut entries[..chunk_len * 8];
+            self.pagemap
+                .read_exact_at(buf, ((first_page + chunk_start) * 8) as u64)
+                .map_err(|e| Error::InternalError(e.into()))?;
+            for (i, entry) in buf.chunks(8).enumerate() {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(entry);
+                if u64::from_ne_bytes(bytes) & SOFT_DIRTY != 0 {
+                    dirty.push(chunk_start + i);
+                }
+            }
+        }
+        Ok(dirty)
     }
 }
 
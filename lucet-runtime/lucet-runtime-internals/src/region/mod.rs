@@ -1,11 +1,19 @@
 pub mod mmap;
 
+#[cfg(test)]
+mod tests;
+
 use crate::alloc::{Alloc, Limits, Slot};
 use crate::embed_ctx::CtxMap;
 use crate::error::Error;
-use crate::instance::InstanceHandle;
+use crate::host_functions::{
+    resolve_host_functions, HostFunction, HostFunctionRegistry, ImportTable,
+};
+use crate::instance::{resolve_global_imports, InstanceHandle, InstanceSnapshot};
 use crate::module::Module;
+use crate::val::Val;
 use std::any::Any;
+use std::collections::HashMap;
 use std::sync::Arc;
 
 /// A memory region in which Lucet instances are created and run.
@@ -32,14 +40,35 @@ pub trait Region: RegionInternal {
     fn new_instance_builder<'a>(&'a self, module: Arc<dyn Module>) -> InstanceBuilder<'a> {
         InstanceBuilder::new(self.as_dyn_internal(), module)
     }
+
+    /// Return how many instances the region holds, and how much memory they use.
+    fn stats(&self) -> Result<RegionStats, Error>;
+}
+
+/// The occupancy and memory use of a region; returned by
+/// [`Region::stats()`](trait.Region.html#method.stats).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionStats {
+    /// The number of instances the region can hold.
+    pub capacity: usize,
+    /// The number of slots with an instance in them.
+    pub in_use: usize,
+    /// The number of slots available for new instances.
+    pub free: usize,
+    /// The memory of the region that is resident in physical memory, in bytes.
+    pub resident_bytes: usize,
 }
 
 /// A `RegionInternal` is a collection of `Slot`s which are managed as a whole.
 pub trait RegionInternal: Send + Sync {
+    /// Create an instance in a free slot whose limits fit the module, and `limits` if given.
     fn new_instance_with(
         &self,
         module: Arc<dyn Module>,
+        limits: Option<&Limits>,
         embed_ctx: CtxMap,
+        global_imports: Vec<Option<i64>>,
+        import_table: ImportTable,
     ) -> Result<InstanceHandle, Error>;
 
     /// Unmaps the heap, stack, and globals of an `Alloc`, while retaining the virtual address
@@ -51,6 +80,11 @@ pub trait RegionInternal: Send + Sync {
 
     fn reset_heap(&self, alloc: &mut Alloc, module: &dyn Module) -> Result<(), Error>;
 
+    /// Replace the heap of an `Alloc` with the contents of a snapshot.
+    ///
+    /// The caller is responsible for checking that the snapshot fits within the `Alloc`'s limits.
+    fn restore_heap(&self, alloc: &mut Alloc, snapshot: &InstanceSnapshot) -> Result<(), Error>;
+
     fn as_dyn_internal(&self) -> &dyn RegionInternal;
 }
 
@@ -72,7 +106,10 @@ pub trait RegionCreate: Region {
 pub struct InstanceBuilder<'a> {
     region: &'a dyn RegionInternal,
     module: Arc<dyn Module>,
+    limits: Option<Limits>,
     embed_ctx: CtxMap,
+    global_imports: HashMap<(String, String), Val>,
+    host_functions: HostFunctionRegistry,
 }
 
 impl<'a> InstanceBuilder<'a> {
@@ -80,10 +117,24 @@ impl<'a> InstanceBuilder<'a> {
         InstanceBuilder {
             region,
             module,
+            limits: None,
             embed_ctx: CtxMap::new(),
+            global_imports: HashMap::new(),
+            host_functions: HostFunctionRegistry::new(),
         }
     }
 
+    /// Require the built instance to have at least the given limits.
+    ///
+    /// Regions with slots of more than one size create the instance in a free slot whose limits
+    /// are all at least as large as `limits`, preferring the smallest such slots. The instance is
+    /// subject to the limits of the slot it is created in. Without this, the instance is created in
+    /// the smallest free slot whose limits fit the module.
+    pub fn with_limits(mut self, limits: &Limits) -> Self {
+        self.limits = Some(limits.clone());
+        self
+    }
+
     /// Add an embedder context to the built instance.
     ///
     /// Up to one context value of any particular type may exist in the instance. If a context value
@@ -93,6 +144,41 @@ impl<'a> InstanceBuilder<'a> {
         self
     }
 
+    /// Provide the value of a WebAssembly global that the module imports as `module.field`.
+    ///
+    /// Every global the module imports must be given a value, and every value must correspond to
+    /// a global the module imports; this is checked when the instance is built. The value is
+    /// reinstated whenever the instance is reset. If a value was already provided for the same
+    /// import, it is replaced by the new value.
+    pub fn with_global_import(mut self, module: &str, field: &str, val: Val) -> Self {
+        self.global_imports
+            .insert((module.to_owned(), field.to_owned()), val);
+        self
+    }
+
+    /// Provide the functions that a module compiled to link its imports through an import table
+    /// calls for its imports.
+    ///
+    /// Every function the module imports must be provided, with the signature the module imports
+    /// it with; this is checked when the instance is built. Functions for imports the module does
+    /// not have are ignored, so one registry can be used for many different modules. Functions
+    /// provided by earlier calls are replaced by those for the same imports in `funcs`.
+    ///
+    /// Modules that link their imports as symbols ignore the provided functions.
+    pub fn with_host_functions(mut self, funcs: &HostFunctionRegistry) -> Self {
+        self.host_functions.extend(funcs);
+        self
+    }
+
+    /// Provide the function that a module compiled to link its imports through an import table
+    /// calls for the import `module.name`.
+    ///
+    /// This is like [`with_host_functions()`](#method.with_host_functions), for a single function.
+    pub fn with_host_function(mut self, module: &str, name: &str, func: HostFunction) -> Self {
+        self.host_functions.insert(module, name, func);
+        self
+    }
+
     /// Build the instance.
     ///
     /// # Safety
@@ -100,6 +186,14 @@ impl<'a> InstanceBuilder<'a> {
     /// This function runs the guest code for the WebAssembly `start` section, and running any guest
     /// code is potentially unsafe; see [`Instance::run()`](struct.Instance.html#method.run).
     pub fn build(self) -> Result<InstanceHandle, Error> {
-        self.region.new_instance_with(self.module, self.embed_ctx)
+        let global_imports = resolve_global_imports(self.module.as_ref(), &self.global_imports)?;
+        let import_table = resolve_host_functions(self.module.as_ref(), &self.host_functions)?;
+        self.region.new_instance_with(
+            self.module,
+            self.limits.as_ref(),
+            self.embed_ctx,
+            global_imports,
+            import_table,
+        )
     }
 }
@@ -0,0 +1,676 @@
+use crate::alloc::Limits;
+use crate::error::Error;
+use crate::instance::InstanceInternal;
+use crate::module::{HeapSpec, MockModuleBuilder};
+use crate::region::mmap::{MmapRegion, OnDemandMmapRegion, RESETS_PER_CLEAR_PER_SLOT};
+use crate::region::{Region, RegionCreate, RegionStats};
+use lazy_static::lazy_static;
+use libc::c_void;
+use std::sync::Mutex;
+
+const SMALL: Limits = Limits {
+    heap_memory_size: 64 * 1024,
+    heap_address_space_size: 8 * 1024 * 1024,
+    stack_size: 64 * 1024,
+    globals_size: 4 * 1024,
+};
+
+const LARGE: Limits = Limits {
+    heap_memory_size: 1024 * 1024,
+    heap_address_space_size: 64 * 1024 * 1024,
+    stack_size: 128 * 1024,
+    globals_size: 4 * 1024,
+};
+
+/// A heap whose reserved size only fits in the address space of `LARGE` slots.
+const LARGE_HEAP: HeapSpec = HeapSpec {
+    reserved_size: 32 * 1024 * 1024,
+    guard_size: 0,
+    initial_size: 64 * 1024,
+    max_size: None,
+};
+
+fn heterogeneous_region() -> std::sync::Arc<MmapRegion> {
+    // the large class is added first, to show that classes are tried from the smallest
+    MmapRegion::builder()
+        .slots(1, &LARGE)
+        .slots(2, &SMALL)
+        .build()
+        .expect("region can be created")
+}
+
+#[test]
+fn instances_use_smallest_fitting_class() {
+    let region = heterogeneous_region();
+    let inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    assert_eq!(inst.alloc().slot().limits, SMALL);
+}
+
+#[test]
+fn with_limits_picks_fitting_class() {
+    let region = heterogeneous_region();
+    let requested = Limits {
+        heap_memory_size: 512 * 1024,
+        ..SMALL
+    };
+    let inst = region
+        .new_instance_builder(MockModuleBuilder::new().build())
+        .with_limits(&requested)
+        .build()
+        .expect("instance can be created");
+    assert_eq!(inst.alloc().slot().limits, LARGE);
+}
+
+#[test]
+fn module_heap_spec_picks_fitting_class() {
+    let region = heterogeneous_region();
+    let inst = region
+        .new_instance(MockModuleBuilder::new().with_heap_spec(LARGE_HEAP).build())
+        .expect("instance can be created");
+    assert_eq!(inst.alloc().slot().limits, LARGE);
+}
+
+#[test]
+fn region_full_reports_class() {
+    let region = heterogeneous_region();
+    let _large = region
+        .new_instance_builder(MockModuleBuilder::new().build())
+        .with_limits(&LARGE)
+        .build()
+        .expect("instance can be created");
+    match region
+        .new_instance_builder(MockModuleBuilder::new().build())
+        .with_limits(&LARGE)
+        .build()
+    {
+        Err(Error::RegionFull(capacity, limits)) => {
+            assert_eq!(capacity, 1);
+            assert_eq!(limits, LARGE);
+        }
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+
+    // the small class still has room
+    let _small = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+}
+
+#[test]
+fn small_class_full_falls_back_to_larger() {
+    let region = heterogeneous_region();
+    let _small1 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let _small2 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    assert_eq!(inst.alloc().slot().limits, LARGE);
+}
+
+#[test]
+fn released_slot_returns_to_its_class() {
+    let region = heterogeneous_region();
+    for _ in 0..3 {
+        let inst = region
+            .new_instance_builder(MockModuleBuilder::new().build())
+            .with_limits(&LARGE)
+            .build()
+            .expect("instance can be created");
+        assert_eq!(inst.alloc().slot().limits, LARGE);
+    }
+}
+
+#[test]
+fn with_limits_larger_than_all_classes() {
+    let region = heterogeneous_region();
+    let requested = Limits {
+        stack_size: 1024 * 1024,
+        ..LARGE
+    };
+    match region
+        .new_instance_builder(MockModuleBuilder::new().build())
+        .with_limits(&requested)
+        .build()
+    {
+        Err(Error::LimitsExceeded(_)) => (),
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn slots_with_same_limits_share_a_class() {
+    let region = MmapRegion::builder()
+        .slots(1, &SMALL)
+        .slots(1, &SMALL)
+        .build()
+        .expect("region can be created");
+    let _inst1 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let _inst2 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    match region.new_instance(MockModuleBuilder::new().build()) {
+        Err(Error::RegionFull(capacity, limits)) => {
+            assert_eq!(capacity, 2);
+            assert_eq!(limits, SMALL);
+        }
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn empty_builder_is_rejected() {
+    match MmapRegion::builder().build() {
+        Err(Error::InvalidArgument(_)) => (),
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+/// Whether the page at `addr` is mapped; `msync()` fails with `ENOMEM` for unmapped memory.
+fn is_mapped(addr: *mut c_void) -> bool {
+    unsafe { libc::msync(addr, 4096, libc::MS_ASYNC) == 0 }
+}
+
+#[test]
+fn on_demand_slots_are_unmapped_when_released() {
+    let region = OnDemandMmapRegion::create(2, &SMALL).expect("region can be created");
+    let inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let start = inst.alloc().slot().start;
+    assert!(is_mapped(start));
+    drop(inst);
+    assert!(!is_mapped(start));
+}
+
+#[test]
+fn on_demand_region_full_at_capacity() {
+    let region = OnDemandMmapRegion::create(2, &SMALL).expect("region can be created");
+    let _inst1 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let inst2 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    match region.new_instance(MockModuleBuilder::new().build()) {
+        Err(Error::RegionFull(capacity, limits)) => {
+            assert_eq!(capacity, 2);
+            assert_eq!(limits, SMALL);
+        }
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+
+    // releasing an instance makes room for another
+    drop(inst2);
+    let _inst3 = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+}
+
+#[test]
+fn on_demand_slots_start_zeroed() {
+    let region = OnDemandMmapRegion::create(1, &SMALL).expect("region can be created");
+    let module = MockModuleBuilder::new()
+        .with_heap_spec(HeapSpec {
+            reserved_size: 4 * 1024 * 1024,
+            guard_size: 4 * 1024 * 1024,
+            initial_size: 64 * 1024,
+            max_size: None,
+        })
+        .build();
+    for _ in 0..2 {
+        let mut inst = region
+            .new_instance(module.clone())
+            .expect("instance can be created");
+        let heap = unsafe { inst.alloc_mut().heap_mut() };
+        assert!(heap.iter().all(|b| *b == 0));
+        for b in heap.iter_mut() {
+            *b = 0xFF;
+        }
+    }
+}
+
+#[test]
+fn on_demand_heterogeneous_region() {
+    let region = MmapRegion::builder()
+        .slots(1, &SMALL)
+        .slots(1, &LARGE)
+        .on_demand()
+        .build()
+        .expect("region can be created");
+    let small = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let large = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    assert_eq!(small.alloc().slot().limits, SMALL);
+    assert_eq!(large.alloc().slot().limits, LARGE);
+}
+
+#[test]
+fn stats_count_instances() {
+    let region = heterogeneous_region();
+    let stats = region.stats().expect("stats can be taken");
+    assert_eq!((stats.capacity, stats.in_use, stats.free), (3, 0, 3));
+
+    let _inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    let stats = region.stats().expect("stats can be taken");
+    assert_eq!((stats.capacity, stats.in_use, stats.free), (3, 1, 2));
+    // at least the page holding the instance is resident
+    assert!(stats.resident_bytes >= 4096);
+}
+
+#[test]
+fn on_demand_stats_resident_bytes() {
+    let region = OnDemandMmapRegion::create(2, &SMALL).expect("region can be created");
+    let empty = RegionStats {
+        capacity: 2,
+        in_use: 0,
+        free: 2,
+        resident_bytes: 0,
+    };
+    assert_eq!(region.stats().expect("stats can be taken"), empty);
+
+    let mut inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    for b in inst.heap_mut().iter_mut() {
+        *b = 0xFF;
+    }
+    let stats = region.stats().expect("stats can be taken");
+    assert_eq!((stats.in_use, stats.free), (1, 1));
+    assert!(stats.resident_bytes >= 4096 + 64 * 1024);
+
+    // the memory of the instance is released with it
+    drop(inst);
+    assert_eq!(region.stats().expect("stats can be taken"), empty);
+}
+
+fn scrubbing_region(capacity: usize) -> std::sync::Arc<MmapRegion> {
+    MmapRegion::builder()
+        .slots(capacity, &SMALL)
+        .scrub_in_background()
+        .build()
+        .expect("region can be created")
+}
+
+#[test]
+fn scrubbed_slots_are_zeroed_when_reused() {
+    let region = scrubbing_region(1);
+    // every other instance maps its heap from a memfd, which has to be detached when scrubbed
+    let modules = [
+        MockModuleBuilder::new().build(),
+        MockModuleBuilder::new()
+            .with_initial_heap(&[0xAA; 4096])
+            .with_heap_memfd()
+            .build(),
+    ];
+    for i in 0..64 {
+        // a capacity of one means each instance waits for the previous one's slot to be scrubbed
+        let mut inst = region
+            .new_instance(modules[0].clone())
+            .expect("instance can be created");
+        unsafe {
+            let alloc = inst.alloc_mut();
+            assert!(alloc.heap_mut().iter().all(|b| *b == 0));
+            assert!(alloc.stack_mut().iter().all(|b| *b == 0));
+            assert!(alloc.globals_mut().iter().all(|g| *g == 0));
+            assert!(alloc.sigstack_mut().iter().all(|b| *b == 0));
+        }
+        drop(inst);
+
+        let mut inst = region
+            .new_instance(modules[i % 2].clone())
+            .expect("instance can be created");
+        unsafe {
+            let alloc = inst.alloc_mut();
+            for b in alloc.heap_mut().iter_mut() {
+                *b = 0xFF;
+            }
+            for b in alloc.stack_mut().iter_mut() {
+                *b = 0xFF;
+            }
+            for g in alloc.globals_mut().iter_mut() {
+                *g = -1;
+            }
+            for b in alloc.sigstack_mut().iter_mut() {
+                *b = 0xFF;
+            }
+        }
+    }
+}
+
+#[test]
+fn wait_for_scrubs_frees_slots() {
+    let region = scrubbing_region(2);
+    let insts = (0..2)
+        .map(|_| {
+            region
+                .new_instance(MockModuleBuilder::new().build())
+                .expect("instance can be created")
+        })
+        .collect::<Vec<_>>();
+    drop(insts);
+    region.wait_for_scrubs();
+    let stats = region.stats().expect("stats can be taken");
+    assert_eq!((stats.capacity, stats.in_use, stats.free), (2, 0, 2));
+}
+
+#[test]
+fn scrubbing_slots_count_as_free() {
+    let region = scrubbing_region(1);
+    let inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    drop(inst);
+    // whether or not the slot has been scrubbed yet, it is not in use
+    let stats = region.stats().expect("stats can be taken");
+    assert_eq!((stats.in_use, stats.free), (0, 1));
+}
+
+#[test]
+fn region_full_with_scrubbing_in_background() {
+    let region = scrubbing_region(1);
+    let _inst = region
+        .new_instance(MockModuleBuilder::new().build())
+        .expect("instance can be created");
+    match region.new_instance(MockModuleBuilder::new().build()) {
+        Err(Error::RegionFull(capacity, limits)) => {
+            assert_eq!(capacity, 1);
+            assert_eq!(limits, SMALL);
+        }
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+#[test]
+fn region_dropped_with_pending_scrubs() {
+    let region = scrubbing_region(4);
+    for _ in 0..4 {
+        let mut inst = region
+            .new_instance(MockModuleBuilder::new().build())
+            .expect("instance can be created");
+        for b in inst.heap_mut().iter_mut() {
+            *b = 0xFF;
+        }
+    }
+    drop(region);
+}
+
+#[test]
+fn on_demand_scrubbing_is_rejected() {
+    match MmapRegion::builder()
+        .slots(1, &SMALL)
+        .on_demand()
+        .scrub_in_background()
+        .build()
+    {
+        Err(Error::InvalidArgument(_)) => (),
+        res => panic!("unexpected result: {:?}", res.map(|_| ())),
+    }
+}
+
+/// A heap that can grow past an initial size of two pages, the first of which is all `0xAA`.
+fn growable_module(heap_memfd: bool) -> std::sync::Arc<dyn crate::module::Module> {
+    let builder = MockModuleBuilder::new()
+        .with_heap_spec(HeapSpec {
+            reserved_size: 4 * 1024 * 1024,
+            guard_size: 4 * 1024 * 1024,
+            initial_size: 64 * 1024,
+            max_size: None,
+        })
+        .with_initial_heap(&[0xAA; 4096]);
+    if heap_memfd {
+        builder.with_heap_memfd().build()
+    } else {
+        builder.build()
+    }
+}
+
+lazy_static! {
+    /// Held by tests of regions that track dirty pages, as clearing the soft-dirty bits stops the
+    /// tracking of every other heap in the process.
+    static ref DIRTY_TRACKING_LOCK: Mutex<()> = Mutex::new(());
+}
+
+fn tracking_region(slots: usize) -> std::sync::Arc<MmapRegion> {
+    MmapRegion::builder()
+        .slots(slots, &LARGE)
+        .track_dirty_pages()
+        .build()
+        .expect("region can be created")
+}
+
+fn initial_heap() -> Vec<u8> {
+    let mut heap = vec![0; 64 * 1024];
+    for b in heap[..4096].iter_mut() {
+        *b = 0xAA;
+    }
+    heap
+}
+
+fn reset_restores_written_pages(heap_memfd: bool) {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(1);
+    let mut inst = region
+        .new_instance(growable_module(heap_memfd))
+        .expect("instance can be created");
+    for _ in 0..3 {
+        assert_eq!(inst.heap(), &initial_heap()[..]);
+
+        // write to the initialized page, a zero page, and a page beyond the initial size
+        inst.grow_memory(1).expect("memory can grow");
+        let heap = inst.heap_mut();
+        heap[0] = 0x55;
+        heap[8192] = 0x55;
+        heap[64 * 1024 + 4096] = 0x55;
+
+        inst.reset().expect("instance resets");
+    }
+
+    // the page beyond the initial size is zero when the heap grows again
+    inst.grow_memory(1).expect("memory can grow");
+    assert!(inst.heap()[64 * 1024..].iter().all(|b| *b == 0));
+}
+
+#[test]
+fn reset_restores_written_pages_of_anonymous_heap() {
+    reset_restores_written_pages(false);
+}
+
+#[test]
+fn reset_restores_written_pages_of_memfd_heap() {
+    reset_restores_written_pages(true);
+}
+
+#[test]
+fn restore_resets_written_pages() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(1);
+    let mut inst = region
+        .new_instance(growable_module(false))
+        .expect("instance can be created");
+    inst.grow_memory(1).expect("memory can grow");
+    inst.heap_mut()[64 * 1024] = 0x11;
+    let snapshot = inst.snapshot().expect("snapshot can be taken");
+    let snapshotted = inst.heap().to_vec();
+
+    // restoring shrinks a heap that has grown further
+    inst.grow_memory(1).expect("memory can grow");
+    let heap = inst.heap_mut();
+    heap[0] = 0x55;
+    heap[4096] = 0x55;
+    heap[2 * 64 * 1024] = 0x55;
+    inst.restore(&snapshot).expect("snapshot can be restored");
+    assert_eq!(inst.heap(), &snapshotted[..]);
+
+    // and grows a heap that has been reset
+    inst.reset().expect("instance resets");
+    inst.restore(&snapshot).expect("snapshot can be restored");
+    assert_eq!(inst.heap(), &snapshotted[..]);
+}
+
+#[test]
+fn reset_restores_written_pages_of_two_instances() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(2);
+    let mut insts = (0..2)
+        .map(|_| {
+            region
+                .new_instance(growable_module(false))
+                .expect("instance can be created")
+        })
+        .collect::<Vec<_>>();
+    // enough resets of each instance that the region clears the soft-dirty bits in between
+    for i in 0..2 * RESETS_PER_CLEAR_PER_SLOT + 1 {
+        for (n, inst) in insts.iter_mut().enumerate() {
+            let heap = inst.heap_mut();
+            heap[(i + n) % 16 * 4096] = 0x55;
+            heap[4096 + n] = 0x55;
+        }
+        // reset one instance while the other still has written pages, and then the other
+        for inst in insts.iter_mut() {
+            inst.reset().expect("instance resets");
+        }
+        for inst in insts.iter() {
+            assert_eq!(inst.heap(), &initial_heap()[..]);
+        }
+    }
+}
+
+/// Whether the page at `addr` is mapped into the process, according to `/proc/self/pagemap`.
+fn is_present(addr: *const u8) -> bool {
+    use std::os::unix::fs::FileExt;
+    let pagemap = std::fs::File::open("/proc/self/pagemap").expect("pagemap can be opened");
+    let mut entry = [0u8; 8];
+    pagemap
+        .read_exact_at(&mut entry, (addr as usize / 4096 * 8) as u64)
+        .expect("pagemap can be read");
+    u64::from_ne_bytes(entry) & (1 << 63) != 0
+}
+
+/// Clear the soft-dirty bits of the process behind the back of any region, so that the heaps
+/// mapped since the last clear are no longer reported as written in full.
+fn clear_soft_dirty_bits() {
+    std::fs::write("/proc/self/clear_refs", "4").expect("soft-dirty bits can be cleared");
+}
+
+// The tests below need a kernel built with `CONFIG_MEM_SOFT_DIRTY`; run them with `--ignored`
+// where it is.
+
+#[test]
+#[ignore]
+fn reset_keeps_pages_that_were_only_read() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(1);
+    assert!(region.tracks_dirty_pages(), "soft-dirty bits are available");
+    let mut inst = region
+        .new_instance(growable_module(true))
+        .expect("instance can be created");
+    clear_soft_dirty_bits();
+    let page = unsafe { inst.heap().as_ptr().add(4096) };
+    assert_eq!(unsafe { std::ptr::read_volatile(page) }, 0);
+    inst.heap_mut()[0] = 0x55;
+    assert!(is_present(page));
+
+    inst.reset().expect("instance resets");
+    // the page is still mapped from the memfd, while the page that was written is restored
+    assert!(is_present(page));
+    assert_eq!(inst.heap(), &initial_heap()[..]);
+}
+
+#[test]
+#[ignore]
+fn reset_does_not_rewrite_unwritten_data_pages() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(1);
+    assert!(region.tracks_dirty_pages(), "soft-dirty bits are available");
+    let module = MockModuleBuilder::new()
+        .with_heap_spec(HeapSpec {
+            reserved_size: 4 * 1024 * 1024,
+            guard_size: 4 * 1024 * 1024,
+            initial_size: 64 * 1024,
+            max_size: None,
+        })
+        .with_initial_heap(&[0xAA; 2 * 4096])
+        .build();
+    let mut inst = region
+        .new_instance(module)
+        .expect("instance can be created");
+
+    // change the second data page behind the region's back: clearing the soft-dirty bits
+    // directly means that the region does not see that it was written
+    for b in inst.heap_mut()[4096..2 * 4096].iter_mut() {
+        *b = 0xBB;
+    }
+    clear_soft_dirty_bits();
+
+    inst.heap_mut()[0] = 0x55;
+    inst.reset().expect("instance resets");
+    // only the page that was written since the bits were cleared is rewritten
+    assert!(inst.heap()[..4096].iter().all(|b| *b == 0xAA));
+    assert!(inst.heap()[4096..2 * 4096].iter().all(|b| *b == 0xBB));
+}
+
+#[test]
+#[ignore]
+fn reset_keeps_tracking_of_other_instances() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(2);
+    assert!(region.tracks_dirty_pages(), "soft-dirty bits are available");
+    let mut first = region
+        .new_instance(growable_module(true))
+        .expect("instance can be created");
+    let mut second = region
+        .new_instance(growable_module(true))
+        .expect("instance can be created");
+    clear_soft_dirty_bits();
+
+    let page = unsafe { second.heap().as_ptr().add(4096) };
+    assert_eq!(unsafe { std::ptr::read_volatile(page) }, 0);
+    second.heap_mut()[0] = 0x55;
+
+    // resetting the first instance does not stop the tracking of the second, which would reset
+    // it in full and remap the page that was only read
+    first.heap_mut()[0] = 0x55;
+    first.reset().expect("instance resets");
+    second.reset().expect("instance resets");
+    assert!(is_present(page));
+    assert_eq!(first.heap(), &initial_heap()[..]);
+    assert_eq!(second.heap(), &initial_heap()[..]);
+}
+
+#[test]
+#[ignore]
+fn reset_clears_soft_dirty_bits_once_per_batch() {
+    let _lock = DIRTY_TRACKING_LOCK.lock().unwrap();
+    let region = tracking_region(1);
+    assert!(region.tracks_dirty_pages(), "soft-dirty bits are available");
+    let mut inst = region
+        .new_instance(growable_module(true))
+        .expect("instance can be created");
+
+    // the heap was mapped since the last clear, so every page is reset until the region clears
+    // the bits
+    for _ in 0..RESETS_PER_CLEAR_PER_SLOT {
+        inst.heap_mut()[0] = 0x55;
+        inst.reset().expect("instance resets");
+    }
+
+    let page = unsafe { inst.heap().as_ptr().add(4096) };
+    assert_eq!(unsafe { std::ptr::read_volatile(page) }, 0);
+    inst.heap_mut()[0] = 0x55;
+    inst.reset().expect("instance resets");
+    assert!(is_present(page));
+    assert_eq!(inst.heap(), &initial_heap()[..]);
+}
@@ -1,4 +1,4 @@
-use libc::{c_void, ucontext_t, REG_RIP};
+use libc::{c_void, ucontext_t, REG_RBP, REG_RIP, REG_RSP};
 
 #[derive(Clone, Copy, Debug)]
 pub struct UContextPtr(*const ucontext_t);
@@ -15,6 +15,18 @@ impl UContextPtr {
         let mcontext = &unsafe { *(self.0) }.uc_mcontext;
         mcontext.gregs[REG_RIP as usize] as *const _
     }
+
+    #[inline]
+    pub fn get_fp(self) -> *const c_void {
+        let mcontext = &unsafe { *(self.0) }.uc_mcontext;
+        mcontext.gregs[REG_RBP as usize] as *const _
+    }
+
+    #[inline]
+    pub fn get_sp(self) -> *const c_void {
+        let mcontext = &unsafe { *(self.0) }.uc_mcontext;
+        mcontext.gregs[REG_RSP as usize] as *const _
+    }
 }
 
 #[derive(Clone, Copy)]
@@ -136,6 +136,18 @@ impl UContextPtr {
         let mcontext = &unsafe { *(*self.0).uc_mcontext };
         mcontext.ss.rip as *const _
     }
+
+    #[inline]
+    pub fn get_fp(self) -> *const c_void {
+        let mcontext = &unsafe { *(*self.0).uc_mcontext };
+        mcontext.ss.rbp as *const _
+    }
+
+    #[inline]
+    pub fn get_sp(self) -> *const c_void {
+        let mcontext = &unsafe { *(*self.0).uc_mcontext };
+        mcontext.ss.rsp as *const _
+    }
 }
 
 #[derive(Clone, Copy)]
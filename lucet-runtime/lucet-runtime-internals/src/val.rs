@@ -28,7 +28,7 @@ impl Val {
 }
 
 /// Typed values used for passing arguments into guest functions.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Val {
     CPtr(*const c_void),
     /// A WebAssembly linear memory address
@@ -174,6 +174,16 @@ impl UntypedRetVal {
     pub(crate) fn new(gp: u64, fp: __m128) -> UntypedRetVal {
         UntypedRetVal { gp, fp }
     }
+
+    /// Interpret the returned value as a value of type `ty`.
+    pub fn to_val(&self, ty: ValueType) -> Val {
+        match ty {
+            ValueType::I32 => Val::I32(self.as_i32()),
+            ValueType::I64 => Val::I64(self.as_i64()),
+            ValueType::F32 => Val::F32(self.as_f32()),
+            ValueType::F64 => Val::F64(self.as_f64()),
+        }
+    }
 }
 
 macro_rules! impl_from_fp {
@@ -9,12 +9,14 @@ use crate::alloc::instance_heap_offset;
 use crate::context::Context;
 use crate::error::Error;
 use crate::instance::{
-    Instance, InstanceInternal, State, TerminationDetails, CURRENT_INSTANCE, HOST_CTX,
+    Instance, InstanceInternal, State, TerminationDetails, YieldedVal, CURRENT_INSTANCE, HOST_CTX,
 };
+use crate::val::{UntypedRetVal, Val};
 use lucet_module_data::FunctionHandle;
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::borrow::{Borrow, BorrowMut};
 use std::cell::{Ref, RefCell, RefMut};
+use std::thread;
 
 /// An opaque handle to a running instance's context.
 #[derive(Debug)]
@@ -128,10 +130,9 @@ impl Vmctx {
     /// sequence. Since `Vmctx::grow_memory()` takes `&mut self`, heap references cannot live across
     /// it.
     ///
-    /// TODO: There is still an unsound case, though, when a heap reference is held across a call
-    /// back into the guest via `Vmctx::get_func_from_idx()`. That guest code may grow the heap as
-    /// well, causing any outstanding heap references to become invalid. We will address this when
-    /// we rework the interface for calling back into the guest.
+    /// Guest code called with `Vmctx::call_guest()` may grow the heap as well, but that method
+    /// also takes `&mut self`. Calling a function from `Vmctx::get_func_from_idx()` directly
+    /// through its pointer while holding a heap reference remains unsound.
     unsafe fn reconstitute_heap_view_if_needed(&self) {
         let inst = self.instance_mut();
         if inst.heap_mut().len() != self.heap_view.borrow().len() {
@@ -194,6 +195,16 @@ impl Vmctx {
         self.instance_mut().terminate(details)
     }
 
+    /// Check whether the instance's `KillSwitch` has been triggered.
+    ///
+    /// Hostcalls defined with `lucet_hostcalls!` terminate the instance with
+    /// `TerminationDetails::Remote` when they return if this is true. A kill switch interrupts
+    /// system calls that such hostcalls are blocked in, which then fail with `EINTR`; hostcalls
+    /// that block in other ways can poll this in order to return early.
+    pub fn kill_requested(&self) -> bool {
+        self.instance().kill_requested()
+    }
+
     /// Grow the guest memory by the given number of WebAssembly pages.
     ///
     /// On success, returns the number of pages that existed before the call.
@@ -201,6 +212,53 @@ impl Vmctx {
         unsafe { self.instance_mut().grow_memory(additional_pages) }
     }
 
+    /// Suspend the guest, returning `val` to the host as `RunResult::Yielded` from the
+    /// `Instance::run()` or `Instance::resume()` call that is running it.
+    ///
+    /// The guest context is saved, and continues from this point when the host calls
+    /// `Instance::resume()` with a value of type `R`, which becomes the return value of this
+    /// method. `Instance::resume()` rejects values of any other type.
+    ///
+    /// The instance can only be resumed on the thread it yielded on. If it is reset, restored
+    /// from a snapshot, or dropped instead of resumed, this method panics with
+    /// `TerminationDetails::YieldAbandoned` so that the hostcall is unwound, dropping anything in
+    /// scope. A yielded instance dropped on another thread cannot be unwound, and its hostcall
+    /// frames are leaked.
+    ///
+    /// Guest functions called by a hostcall with [`call_guest()`](#method.call_guest) cannot
+    /// yield, as the host would never return to the hostcall. The instance terminates with
+    /// `TerminationDetails::YieldInGuestCall` instead, which `call_guest()` returns to the hostcall.
+    pub fn yield_val<A, R>(&mut self, val: A) -> R
+    where
+        A: Any + 'static + Send + Sync,
+        R: Any + 'static,
+    {
+        let inst = unsafe { self.instance_mut() };
+        if inst.guest_call_depth > 0 {
+            panic!(TerminationDetails::YieldInGuestCall);
+        }
+        inst.state = State::Yielded {
+            val: YieldedVal::new(val),
+            expecting: TypeId::of::<R>(),
+            thread: thread::current().id(),
+        };
+        // the host is not in the hostcall while the instance is yielded, so a `KillSwitch` must
+        // not signal it
+        inst.kill_state.end_hostcall(None);
+        // Save the guest context, and return to the host context that is running the instance.
+        // `Instance::resume()` swaps back here once it has stored the resumed value.
+        HOST_CTX.with(|host_ctx| unsafe { Context::swap(&mut inst.ctx, &*host_ctx.get()) });
+        inst.kill_state.begin_hostcall();
+        // the instance swaps back without a value when it is being reset, restored, or dropped
+        let resumed_val = inst
+            .resumed_val
+            .take()
+            .unwrap_or_else(|| panic!(TerminationDetails::YieldAbandoned));
+        *resumed_val
+            .downcast::<R>()
+            .expect("resumed value has the type checked by `Instance::resume()`")
+    }
+
     /// Return the WebAssembly globals as a slice of `i64`s.
     ///
     /// If the globals are already mutably borrowed by `globals_mut()`, the instance will terminate
@@ -230,10 +288,7 @@ impl Vmctx {
     /// This is useful when a hostcall takes a function pointer as its argument, as WebAssembly uses
     /// table indices as its runtime representation of function pointers.
     ///
-    /// We do not currently reflect function type information into the Rust type system, so callers
-    /// of the returned function must take care to cast it to the correct type before calling. The
-    /// correct type will include the `vmctx` argument, which the caller is responsible for passing
-    /// from its own context.
+    /// The returned function can be run with [`Vmctx::call_guest()`](#method.call_guest).
     ///
     /// ```no_run
     /// use lucet_runtime_internals::{lucet_hostcalls, lucet_hostcall_terminate};
@@ -249,11 +304,10 @@ impl Vmctx {
     ///         operand2: u32,
     ///     ) -> u32 {
     ///         if let Ok(binop) = vmctx.get_func_from_idx(binop_table_idx, binop_func_idx) {
-    ///             let typed_binop = std::mem::transmute::<
-    ///                 usize,
-    ///                 extern "C" fn(*mut lucet_vmctx, u32, u32) -> u32
-    ///             >(binop.ptr.as_usize());
-    ///             unsafe { (typed_binop)(vmctx.as_raw(), operand1, operand2) }
+    ///             match vmctx.call_guest(binop, &[operand1.into(), operand2.into()]) {
+    ///                 Ok(retval) => u32::from(retval),
+    ///                 Err(e) => lucet_hostcall_terminate!("binop failed: {}", e),
+    ///             }
     ///         } else {
     ///             lucet_hostcall_terminate!("invalid function index")
     ///         }
@@ -268,6 +322,38 @@ impl Vmctx {
             .module()
             .get_func_from_idx(table_idx, func_idx)
     }
+
+    /// Run a guest function from a hostcall, and return its result.
+    ///
+    /// This lets hostcalls that take guest callbacks, such as functions from
+    /// [`Vmctx::get_func_from_idx()`](#method.get_func_from_idx), call back into the guest while
+    /// the guest that called the hostcall is suspended in it. The arguments are checked against the
+    /// function's signature, as with [`Instance::run()`](../instance/struct.Instance.html#method.run).
+    ///
+    /// The function runs on the guest stack below the hostcall, with the same signal handling as
+    /// any guest code. Rather than ending the run of the instance, whatever stops the callee early
+    /// is returned to the hostcall, and the calling guest carries on when the hostcall returns:
+    ///
+    /// - A non-fatal fault, including running out of guest stack, is returned as
+    ///   `Error::RuntimeFault`. A fatal fault runs the instance's fatal handler as usual.
+    ///
+    /// - A termination, for example from a panic in a hostcall made by the callee, is returned as
+    ///   `Error::RuntimeTerminated`. To terminate the calling guest as well, the hostcall can
+    ///   `panic!` with the returned `TerminationDetails`.
+    ///
+    /// - Hostcalls made by the callee cannot yield; trying to terminates the callee with
+    ///   `TerminationDetails::YieldInGuestCall`, which is returned as `Error::RuntimeTerminated`.
+    ///
+    /// Guest functions called this way can call hostcalls that call back into the guest in turn,
+    /// up to [`MAX_GUEST_CALL_DEPTH`](../instance/constant.MAX_GUEST_CALL_DEPTH.html) calls deep.
+    /// Deeper calls fail with `Error::LimitsExceeded`.
+    pub fn call_guest(
+        &mut self,
+        func: FunctionHandle,
+        args: &[Val],
+    ) -> Result<UntypedRetVal, Error> {
+        unsafe { self.instance_mut().call_guest_from_hostcall(func, args) }
+    }
 }
 
 /// Get an `Instance` from the `vmctx` pointer.
@@ -0,0 +1,189 @@
+//! Procedural macros for defining `lucet-runtime` hostcalls.
+//!
+//! These are re-exported by `lucet-runtime`, and the code they generate refers to items through
+//! `::lucet_runtime`, so crates using them must depend on `lucet-runtime` rather than on this crate
+//! directly.
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::spanned::Spanned;
+use syn::{parse_macro_input, FnArg, Ident, ItemFn, Type};
+
+/// Define a Lucet hostcall.
+///
+/// The attribute turns an ordinary Rust function whose first argument is `&mut Vmctx` into an
+/// `extern "C"` function that guest code can call, with the same protection as `lucet_hostcalls!`:
+/// panics terminate the instance rather than unwinding into the guest, the instance terminates
+/// when the hostcall returns if its `KillSwitch` has been triggered, and the hostcall runs on a
+/// host stack if the instance has one set.
+///
+/// Arguments of type `GuestPtr<T>`, `GuestSlice<T>`, and `GuestStr` are decoded from the guest's
+/// arguments and checked before the body runs. A `GuestPtr<T>` is passed by the guest as one `u32`
+/// offset, and a `GuestSlice<T>` or `GuestStr` as a `u32` offset followed by a `u32` length. If a
+/// pointer is out of bounds of the heap or misaligned, or a string is not valid UTF-8, the
+/// instance terminates with `TerminationDetails::GuestMemoryError`. Other arguments are passed
+/// through unchanged.
+///
+/// ```ignore
+/// use lucet_runtime::vmctx::Vmctx;
+/// use lucet_runtime::{lucet_hostcall, GuestPtr, GuestStr};
+///
+/// #[lucet_hostcall]
+/// #[no_mangle]
+/// pub fn hostcall_name_len(vmctx: &mut Vmctx, name: GuestStr, out: GuestPtr<u32>) {
+///     out.write(vmctx, name.len());
+/// }
+/// ```
+///
+/// The function may be `unsafe`, and may omit its return type. It must not be generic, and any ABI
+/// given is replaced by `extern "C"`.
+#[proc_macro_attribute]
+pub fn lucet_hostcall(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(Span::call_site(), "#[lucet_hostcall] takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+    let func = parse_macro_input!(item as ItemFn);
+    match hostcall(func) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+/// How a hostcall argument is passed by the guest.
+enum ArgKind {
+    /// Passed through unchanged.
+    Plain,
+    /// A `GuestPtr<T>`, passed as an offset.
+    Ptr,
+    /// A `GuestSlice<T>` or `GuestStr`, passed as an offset and a length.
+    PtrLen,
+}
+
+fn arg_kind(ty: &Type) -> ArgKind {
+    if let Type::Path(path) = ty {
+        if let Some(segment) = path.path.segments.iter().last() {
+            let name = segment.ident.to_string();
+            if name == "GuestPtr" {
+                return ArgKind::Ptr;
+            } else if name == "GuestSlice" || name == "GuestStr" {
+                return ArgKind::PtrLen;
+            }
+        }
+    }
+    ArgKind::Plain
+}
+
+fn hostcall(func: ItemFn) -> Result<TokenStream2, syn::Error> {
+    let ItemFn {
+        attrs,
+        vis,
+        unsafety,
+        ident,
+        decl,
+        block,
+        ..
+    } = func;
+
+    if !decl.generics.params.is_empty() || decl.generics.where_clause.is_some() {
+        return Err(syn::Error::new(
+            decl.generics.span(),
+            "hostcalls cannot be generic",
+        ));
+    }
+    if let Some(ref variadic) = decl.variadic {
+        return Err(syn::Error::new(
+            variadic.span(),
+            "hostcalls cannot be variadic",
+        ));
+    }
+
+    let mut inputs = decl.inputs.iter();
+    match inputs.next() {
+        Some(FnArg::Captured(arg)) => match arg.ty {
+            Type::Reference(ref r) if r.mutability.is_some() => (),
+            _ => {
+                return Err(syn::Error::new(
+                    arg.ty.span(),
+                    "the first argument of a hostcall must be `&mut Vmctx`",
+                ))
+            }
+        },
+        Some(arg) => {
+            return Err(syn::Error::new(
+                arg.span(),
+                "the first argument of a hostcall must be `&mut Vmctx`",
+            ))
+        }
+        None => {
+            return Err(syn::Error::new(
+                ident.span(),
+                "hostcalls must take `&mut Vmctx` as their first argument",
+            ))
+        }
+    }
+
+    let mut raw_params = vec![];
+    let mut decode = vec![];
+    let mut args = vec![];
+    for (i, input) in inputs.enumerate() {
+        let ty = match input {
+            FnArg::Captured(arg) => &arg.ty,
+            FnArg::Ignored(ty) => ty,
+            _ => {
+                return Err(syn::Error::new(
+                    input.span(),
+                    "hostcall arguments must have a type",
+                ))
+            }
+        };
+        let arg = Ident::new(&format!("__arg{}", i), Span::call_site());
+        match arg_kind(ty) {
+            ArgKind::Plain => {
+                raw_params.push(quote!(#arg: #ty));
+            }
+            ArgKind::Ptr => {
+                raw_params.push(quote!(#arg: u32));
+                decode.push(quote! {
+                    let #arg = <#ty>::new(&__vmctx, #arg).unwrap_or_else(|e| {
+                        panic!(::lucet_runtime::TerminationDetails::GuestMemoryError(e))
+                    });
+                });
+            }
+            ArgKind::PtrLen => {
+                let len = Ident::new(&format!("__arg{}_len", i), Span::call_site());
+                raw_params.push(quote!(#arg: u32));
+                raw_params.push(quote!(#len: u32));
+                decode.push(quote! {
+                    let #arg = <#ty>::new(&__vmctx, #arg, #len).unwrap_or_else(|e| {
+                        panic!(::lucet_runtime::TerminationDetails::GuestMemoryError(e))
+                    });
+                });
+            }
+        }
+        args.push(arg);
+    }
+
+    let inputs = &decl.inputs;
+    let output = &decl.output;
+    Ok(quote! {
+        #(#attrs)*
+        #vis unsafe extern "C" fn #ident(
+            __vmctx_raw: *mut ::lucet_runtime::vmctx::lucet_vmctx,
+            #(#raw_params),*
+        ) #output {
+            #[inline(always)]
+            #unsafety fn hostcall_impl(#inputs) #output #block
+
+            ::lucet_runtime::vmctx::run_hostcall_body(__vmctx_raw, move || {
+                let mut __vmctx = ::lucet_runtime::vmctx::Vmctx::from_raw(__vmctx_raw);
+                #(#decode)*
+                hostcall_impl(&mut __vmctx, #(#args),*)
+            })
+        }
+    })
+}
@@ -0,0 +1,13 @@
+(module
+  (func $inner (export "inner") (param i32) (result i32)
+    (if (i32.eqz (get_local 0))
+      (then (unreachable)))
+    (get_local 0)
+  )
+  (func $middle (export "middle") (param i32) (result i32)
+    (i32.add (call $inner (get_local 0)) (i32.const 1))
+  )
+  (func $outer (export "outer") (param i32) (result i32)
+    (i32.add (call $middle (get_local 0)) (i32.const 1))
+  )
+)
@@ -0,0 +1,37 @@
+(module
+  (import "env" "call_guest_test_call" (func $call (param i32 i32) (result i32)))
+  (import "env" "call_guest_test_terminate" (func $terminate))
+  (import "env" "call_guest_test_yield" (func $yield))
+  (memory 1)
+  (table 5 anyfunc)
+  (elem (i32.const 0) $add_one $trap $recurse $terminate_callee $yield_callee)
+  (global $depth (mut i32) (i32.const 0))
+
+  (func $add_one (param i32) (result i32)
+    (i32.add (get_local 0) (i32.const 1))
+  )
+  (func $trap (param i32) (result i32)
+    (unreachable)
+  )
+  ;; calls back into itself through the host, without end
+  (func $recurse (param i32) (result i32)
+    (set_global $depth (i32.add (get_global $depth) (i32.const 1)))
+    (call $call (i32.const 2) (get_local 0))
+  )
+  (func $terminate_callee (param i32) (result i32)
+    (call $terminate)
+    (get_local 0)
+  )
+  (func $yield_callee (param i32) (result i32)
+    (call $yield)
+    (get_local 0)
+  )
+
+  ;; calls the function at `idx` in the table with `x` through the host
+  (func $run (export "run") (param $idx i32) (param $x i32) (result i32)
+    (i32.add (call $call (get_local $idx) (get_local $x)) (i32.const 100))
+  )
+  (func $get_depth (export "get_depth") (result i32)
+    (get_global $depth)
+  )
+)
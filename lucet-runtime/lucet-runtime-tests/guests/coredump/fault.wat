@@ -0,0 +1,13 @@
+(module
+  (global $count (mut i32) (i32.const 0))
+  (global $scale f64 (f64.const 1.5))
+  (memory 1)
+  (func $store (export "store")
+    (i32.store (i32.const 1024) (i32.const 0xfeedface))
+    (set_global $count (i32.const 3))
+  )
+  (func $crash (export "crash")
+    (call $store)
+    (unreachable)
+  )
+)
@@ -0,0 +1,6 @@
+(module
+  (import "env" "double" (func $double (param i32) (result i32)))
+  (func $quadruple (export "quadruple") (param i32) (result i32)
+    (call $double (call $double (get_local 0)))
+  )
+)
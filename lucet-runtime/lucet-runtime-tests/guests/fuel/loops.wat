@@ -0,0 +1,19 @@
+(module
+  (memory 1)
+  (func $count (export "count") (param $n i32) (result i32)
+    (local $i i32)
+    (block
+      (loop
+        (br_if 1 (i32.ge_u (get_local $i) (get_local $n)))
+        (set_local $i (i32.add (get_local $i) (i32.const 1)))
+        (br 0)
+      )
+    )
+    (get_local $i)
+  )
+  (func $spin (export "spin")
+    (loop
+      (br 0)
+    )
+  )
+)
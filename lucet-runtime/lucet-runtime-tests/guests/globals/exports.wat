@@ -0,0 +1,6 @@
+(module
+  (global $flag (export "flag") i32 (i32.const -1))
+  (global $limit (export "limit") (export "max") i64 (i64.const 100))
+  (global $scale (export "scale") f32 (f32.const 2.5))
+  (global $ratio (export "ratio") f64 (f64.const 0.125))
+)
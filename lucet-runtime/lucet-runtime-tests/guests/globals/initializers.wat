@@ -0,0 +1,12 @@
+(module
+  (global $x (import "env" "x") i32)
+  (global $i (mut i32) (i32.const -7))
+  (global $f32 (mut f32) (f32.const 1.5))
+  (global $f64 f64 (f64.const -2.25))
+  (global $copy i32 (get_global $x))
+  (func (export "get_i") (result i32) (get_global $i))
+  (func (export "get_f32") (result f32) (get_global $f32))
+  (func (export "set_f32") (param f32) (set_global $f32 (get_local 0)))
+  (func (export "get_f64") (result f64) (get_global $f64))
+  (func (export "get_copy") (result i32) (get_global $copy))
+)
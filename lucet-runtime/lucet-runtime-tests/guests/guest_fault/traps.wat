@@ -0,0 +1,15 @@
+;; traps raised by compiled code, which the runtime finds in the module's trap table
+
+(module
+ (memory $0 1)
+ (func $unreachable (export "unreachable")
+  (unreachable)
+ )
+ (func $heap_oob (export "heap_oob") (result i32)
+  ;; past the end of the heap, into the guard pages
+  (i32.load (i32.const 0xfffffff0))
+ )
+ (func $div_by_zero (export "div_by_zero") (param i32) (result i32)
+  (i32.div_u (i32.const 1) (get_local 0))
+ )
+)
@@ -0,0 +1,24 @@
+(module
+  (import "env" "add" (func $add (param i32 i32) (result i32)))
+  (import "env" "count" (func $count (result i64)))
+  (import "math" "scale" (func $scale (param f64) (result f64)))
+  (memory 1)
+  (table 1 anyfunc)
+  (elem (i32.const 0) $add)
+  (type $binop (func (param i32 i32) (result i32)))
+
+  (func (export "add") (param i32 i32) (result i32)
+    (call $add (get_local 0) (get_local 1))
+  )
+  ;; calls the import through the function table rather than directly
+  (func (export "add_indirect") (param i32 i32) (result i32)
+    (call_indirect (type $binop) (get_local 0) (get_local 1) (i32.const 0))
+  )
+  (func (export "count_twice") (result i64)
+    (drop (call $count))
+    (call $count)
+  )
+  (func (export "scale") (param f64) (result f64)
+    (call $scale (get_local 0))
+  )
+)
@@ -0,0 +1,11 @@
+(module
+  (memory 1)
+  (func $spin (export "spin")
+    (loop
+      (br 0)
+    )
+  )
+  (func $double (export "double") (param $x i32) (result i32)
+    (i32.mul (get_local $x) (i32.const 2))
+  )
+)
@@ -0,0 +1,7 @@
+(module
+  (memory 1)
+  (data (i32.const 0) "\2a\00\00\00")
+  (func $write (export "write") (param $val i32)
+    (i32.store (i32.const 0) (get_local $val))
+  )
+)
@@ -0,0 +1,16 @@
+(module
+  (global $g (mut i32) (i32.const 0))
+  (memory 1)
+  (func $init (export "init")
+    (i32.store (i32.const 1024) (i32.const 42))
+    (set_global $g (i32.const 7))
+  )
+  (func $bump (export "bump") (result i32)
+    (i32.store (i32.const 1024) (i32.add (i32.load (i32.const 1024)) (i32.const 1)))
+    (set_global $g (i32.add (get_global $g) (i32.const 1)))
+    (i32.add (i32.load (i32.const 1024)) (get_global $g))
+  )
+  (func $trap (export "trap")
+    (unreachable)
+  )
+)
@@ -0,0 +1,87 @@
+#[macro_export]
+macro_rules! backtrace_tests {
+    ( $TestRegion:path ) => {
+        $crate::backtrace_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::{Error, Limits, Region, TrapCode};
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm;
+
+        fn frame_names(details: &lucet_runtime::FaultDetails) -> Vec<&str> {
+            details
+                .backtrace
+                .iter()
+                .map(|frame| frame.name.as_ref().map(String::as_str).unwrap_or(""))
+                .collect()
+        }
+
+        #[test]
+        fn nested_fault_backtrace() {
+            let module =
+                test_module_wasm("backtrace", "nested.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run("outer", &[0u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode, Some(TrapCode::Unreachable));
+                    assert_eq!(
+                        frame_names(&details),
+                        vec!["guest_func_inner", "guest_func_middle", "guest_func_outer"]
+                    );
+                    assert_eq!(details.backtrace[0].addr, details.rip_addr);
+                    for frame in details.backtrace.iter() {
+                        assert!(frame.code_offset > 0);
+                        assert!(frame.wasm_offset.is_some());
+                    }
+
+                    // the faulting instruction is a trap site, which records its wasm offset
+                    assert!(details.wasm_offset.is_some());
+                    assert_eq!(details.backtrace[0].wasm_offset, details.wasm_offset);
+
+                    let msg = details.to_string();
+                    assert!(msg.contains("backtrace:"));
+                    assert!(msg.contains("guest_func_middle"));
+                    assert!(msg.contains(&format!(
+                        "wasm offset {:#x}",
+                        details.wasm_offset.unwrap()
+                    )));
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn backtrace_starts_at_entrypoint() {
+            let module =
+                test_module_wasm("backtrace", "nested.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            // the backtrace only covers the guest functions that were running
+            match inst.run("inner", &[0u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(frame_names(&details), vec!["guest_func_inner"]);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // after a reset, a later fault gets a fresh backtrace
+            inst.reset().expect("instance resets");
+            match inst.run("middle", &[0u32.into()]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(
+                        frame_names(&details),
+                        vec!["guest_func_inner", "guest_func_middle"]
+                    );
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
@@ -1,15 +1,173 @@
 use failure::Error;
-use lucet_runtime_internals::module::DlModule;
+use lazy_static::lazy_static;
+use lucet_runtime_internals::module::{DlModule, ElfImports, ElfModule};
 use lucet_wasi_sdk::{CompileOpts, Link, LinkOpt, LinkOpts};
-use lucetc::{Bindings, Lucetc, LucetcOpts};
+use lucetc::{Bindings, ImportLinking, Lucetc, LucetcOpts};
+use std::fs::File;
+use std::io::prelude::*;
+use std::os::raw::c_void;
 use std::path::{Path, PathBuf};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use tempfile::TempDir;
 
-pub fn test_module_c(dir: &str, cfile: &str) -> Result<Arc<DlModule>, Error> {
-    let c_path = guest_file(dir, cfile);
-    let bindings_path = guest_file(dir, "bindings.json");
-    c_test(c_path, bindings_path)
+pub use self::dl::{
+    c_test, test_module_c, test_module_wasm, test_module_wasm_fuel, test_module_wasm_import_table,
+    test_module_wasm_interruptible, wasm_test,
+};
+
+/// Define the functions that build test modules and load them with `$load`.
+///
+/// Each loader gets a module of these, so that test suites can be run against each of the module
+/// implementations by choosing which module they import the functions from.
+macro_rules! loader_helpers {
+    ( $Module:ty, $load:path ) => {
+        use super::*;
+
+        pub fn test_module_c(dir: &str, cfile: &str) -> Result<Arc<$Module>, Error> {
+            let c_path = guest_file(dir, cfile);
+            let bindings_path = guest_file(dir, "bindings.json");
+            c_test(c_path, bindings_path)
+        }
+
+        pub fn c_test<P, Q>(c_file: P, bindings_file: Q) -> Result<Arc<$Module>, Error>
+        where
+            P: AsRef<Path>,
+            Q: AsRef<Path>,
+        {
+            let workdir = TempDir::new().expect("create working directory");
+
+            let so_file = c_so_file(c_file, bindings_file, &workdir)?;
+
+            Ok($load(so_file)?)
+        }
+
+        pub fn test_module_wasm(dir: &str, wasmfile: &str) -> Result<Arc<$Module>, Error> {
+            let wasm_path = guest_file(dir, wasmfile);
+            let bindings_path = guest_file(dir, "bindings.json");
+            wasm_test(wasm_path, bindings_path)
+        }
+
+        pub fn wasm_test<P, Q>(wasm_file: P, bindings_file: Q) -> Result<Arc<$Module>, Error>
+        where
+            P: AsRef<Path>,
+            Q: AsRef<Path>,
+        {
+            let bindings = Bindings::from_file(&bindings_file)?;
+
+            let native_build = Lucetc::new(wasm_file).with_bindings(bindings);
+
+            native_test(native_build)
+        }
+
+        /// Like `test_module_wasm`, but with the generated code instrumented for fuel metering.
+        pub fn test_module_wasm_fuel(dir: &str, wasmfile: &str) -> Result<Arc<$Module>, Error> {
+            let wasm_path = guest_file(dir, wasmfile);
+            let bindings = Bindings::from_file(guest_file(dir, "bindings.json"))?;
+
+            let native_build = Lucetc::new(wasm_path)
+                .with_bindings(bindings)
+                .with_fuel_metering(true);
+
+            native_test(native_build)
+        }
+
+        /// Like `test_module_wasm`, but with the generated code instrumented with interrupt
+        /// checks.
+        pub fn test_module_wasm_interruptible(
+            dir: &str,
+            wasmfile: &str,
+        ) -> Result<Arc<$Module>, Error> {
+            let wasm_path = guest_file(dir, wasmfile);
+            let bindings = Bindings::from_file(guest_file(dir, "bindings.json"))?;
+
+            let native_build = Lucetc::new(wasm_path)
+                .with_bindings(bindings)
+                .with_interrupt_checks(true);
+
+            native_test(native_build)
+        }
+
+        /// Like `test_module_wasm`, but with the imports linked through an import table, so no
+        /// bindings are needed.
+        pub fn test_module_wasm_import_table(
+            dir: &str,
+            wasmfile: &str,
+        ) -> Result<Arc<$Module>, Error> {
+            let wasm_path = guest_file(dir, wasmfile);
+
+            let native_build = Lucetc::new(wasm_path).with_import_linking(ImportLinking::Table);
+
+            native_test(native_build)
+        }
+
+        /// Build a module from WebAssembly text generated by the test, rather than from a file.
+        pub fn wat_source_test(source: &str) -> Result<Arc<$Module>, Error> {
+            let workdir = TempDir::new().expect("create working directory");
+
+            let wasm_path = workdir.path().join("out.wasm");
+
+            let mut wasm_file = File::create(&wasm_path)?;
+            wasm_file.write_all(source.as_bytes())?;
+
+            native_test(Lucetc::new(wasm_path))
+        }
+
+        fn native_test(native_build: Lucetc) -> Result<Arc<$Module>, Error> {
+            let workdir = TempDir::new().expect("create working directory");
+
+            let so_file = workdir.path().join("out.so");
+
+            native_build.shared_object_file(so_file.clone())?;
+
+            Ok($load(so_file)?)
+        }
+    };
+}
+
+/// Test modules loaded with `DlModule`.
+pub mod dl {
+    loader_helpers!(DlModule, DlModule::load);
+}
+
+/// Test modules loaded with `DlModule`, with their initial heaps kept in a memfd that instance
+/// heaps are mapped from copy-on-write. Only available on Linux.
+pub mod dl_heap_memfd {
+    loader_helpers!(DlModule, DlModule::load_with_heap_memfd);
+}
+
+/// Test modules loaded with `ElfModule`, with their imports resolved against the test executable
+/// like those of a `DlModule`.
+pub mod elf {
+    loader_helpers!(ElfModule, load_elf);
+}
+
+/// Test modules loaded with `ElfModule`, with their imports resolved only through an explicit
+/// `ElfImports` table: the functions every table has, and the hostcalls added with
+/// `add_elf_table_import()`.
+pub mod elf_table {
+    loader_helpers!(ElfModule, load_elf_table);
+}
+
+fn load_elf(so_file: PathBuf) -> Result<Arc<ElfModule>, lucet_runtime_internals::error::Error> {
+    ElfModule::load(so_file, &ElfImports::from_host_process())
+}
+
+lazy_static! {
+    static ref ELF_TABLE_IMPORTS: Mutex<ElfImports> = Mutex::new(ElfImports::new());
+}
+
+/// Add a hostcall to the table that modules built with the `elf_table` helpers are loaded with.
+///
+/// Test suites that import hostcalls add them before building their modules, as those helpers do
+/// not look for them in the test executable.
+pub fn add_elf_table_import(name: &str, addr: *const c_void) {
+    ELF_TABLE_IMPORTS.lock().unwrap().insert(name, addr);
+}
+
+fn load_elf_table(
+    so_file: PathBuf,
+) -> Result<Arc<ElfModule>, lucet_runtime_internals::error::Error> {
+    ElfModule::load(so_file, &ELF_TABLE_IMPORTS.lock().unwrap())
 }
 
 pub fn guest_file(dir: &str, fname: &str) -> PathBuf {
@@ -22,13 +180,28 @@ pub fn guest_file(dir: &str, fname: &str) -> PathBuf {
     p
 }
 
-pub fn c_test<P, Q>(c_file: P, bindings_file: Q) -> Result<Arc<DlModule>, Error>
+/// Like `test_module_wasm`, but returning the bytes of the shared object rather than loading it.
+pub fn test_module_wasm_bytes(dir: &str, wasmfile: &str) -> Result<Vec<u8>, Error> {
+    let wasm_path = guest_file(dir, wasmfile);
+    let bindings = Bindings::from_file(guest_file(dir, "bindings.json"))?;
+
+    let native_build = Lucetc::new(wasm_path).with_bindings(bindings);
+
+    let workdir = TempDir::new().expect("create working directory");
+
+    let so_file = workdir.path().join("out.so");
+
+    native_build.shared_object_file(so_file.clone())?;
+
+    Ok(std::fs::read(so_file)?)
+}
+
+/// Compile a C file to a shared object in `workdir`.
+fn c_so_file<P, Q>(c_file: P, bindings_file: Q, workdir: &TempDir) -> Result<PathBuf, Error>
 where
     P: AsRef<Path>,
     Q: AsRef<Path>,
 {
-    let workdir = TempDir::new().expect("create working directory");
-
     let wasm_build = Link::new(&[c_file])
         .with_cflag("-nostartfiles")
         .with_link_opt(LinkOpt::NoDefaultEntryPoint)
@@ -47,33 +220,5 @@ where
 
     native_build.shared_object_file(so_file.clone())?;
 
-    let dlmodule = DlModule::load(so_file)?;
-
-    Ok(dlmodule)
-}
-
-pub fn test_module_wasm(dir: &str, wasmfile: &str) -> Result<Arc<DlModule>, Error> {
-    let wasm_path = guest_file(dir, wasmfile);
-    let bindings_path = guest_file(dir, "bindings.json");
-    wasm_test(wasm_path, bindings_path)
-}
-
-pub fn wasm_test<P, Q>(wasm_file: P, bindings_file: Q) -> Result<Arc<DlModule>, Error>
-where
-    P: AsRef<Path>,
-    Q: AsRef<Path>,
-{
-    let workdir = TempDir::new().expect("create working directory");
-
-    let bindings = Bindings::from_file(&bindings_file)?;
-
-    let native_build = Lucetc::new(wasm_file).with_bindings(bindings);
-
-    let so_file = workdir.path().join("out.so");
-
-    native_build.shared_object_file(so_file.clone())?;
-
-    let dlmodule = DlModule::load(so_file)?;
-
-    Ok(dlmodule)
+    Ok(so_file)
 }
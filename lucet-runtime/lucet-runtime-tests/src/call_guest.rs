@@ -0,0 +1,239 @@
+#[macro_export]
+macro_rules! call_guest_tests {
+    ( $TestRegion:path ) => {
+        $crate::call_guest_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::vmctx::MAX_GUEST_CALL_DEPTH;
+        use lucet_runtime::{
+            lucet_hostcall_terminate, lucet_hostcalls, Error, Limits, Region, TerminationDetails,
+            TrapCode,
+        };
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm;
+
+        /// Returned to the guest by `call_guest_test_call` when the call fails.
+        const FAILED: u32 = 0xFFFF;
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn call_guest_test_call(
+                &mut vmctx,
+                table_idx: u32,
+                x: u32,
+            ) -> u32 {
+                let func = vmctx
+                    .get_func_from_idx(0, table_idx)
+                    .expect("can get function by index");
+                match vmctx.call_guest(func, &[x.into()]) {
+                    Ok(retval) => u32::from(retval),
+                    // terminate the calling guest too
+                    Err(Error::RuntimeTerminated(details)) => panic!(details),
+                    Err(e) => {
+                        vmctx.get_embed_ctx_mut::<Vec<Error>>().push(e);
+                        FAILED
+                    }
+                }
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn call_guest_test_terminate(
+                &mut _vmctx,
+            ) -> () {
+                lucet_hostcall_terminate!("terminated by callee");
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn call_guest_test_yield(
+                &mut vmctx,
+            ) -> () {
+                vmctx.yield_val::<(), ()>(());
+            }
+        }
+
+        #[test]
+        fn call_guest_from_hostcall() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+
+            let retval = inst
+                .run("run", &[0u32.into(), 41u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 42 + 100);
+            assert!(inst
+                .get_embed_ctx::<Vec<Error>>()
+                .unwrap()
+                .unwrap()
+                .is_empty());
+        }
+
+        #[test]
+        fn call_guest_fault_is_returned() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+
+            // the fault goes to the hostcall, and the calling guest carries on
+            let retval = inst
+                .run("run", &[1u32.into(), 0u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), FAILED + 100);
+            match inst
+                .get_embed_ctx::<Vec<Error>>()
+                .unwrap()
+                .unwrap()
+                .as_slice()
+            {
+                [Error::RuntimeFault(details)] => {
+                    assert_eq!(details.trapcode, Some(TrapCode::Unreachable));
+                    assert!(!details.fatal);
+                }
+                errs => panic!("unexpected errors: {:?}", errs),
+            }
+
+            // the instance is still usable
+            let retval = inst
+                .run("run", &[0u32.into(), 1u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 2 + 100);
+        }
+
+        #[test]
+        fn call_guest_from_host_stack() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+            inst.set_hostcall_stack_size(Some(1024 * 1024))
+                .expect("hostcall stack size can be set");
+
+            let retval = inst
+                .run("run", &[0u32.into(), 41u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 42 + 100);
+
+            // the callee's fault returns to the hostcall on the host stack
+            let retval = inst
+                .run("run", &[1u32.into(), 0u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), FAILED + 100);
+
+            let retval = inst
+                .run("run", &[0u32.into(), 1u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 2 + 100);
+        }
+
+        #[test]
+        fn call_guest_recursion_is_bounded() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            // each nested call takes some guest stack for the hostcall and runtime frames
+            let limits = Limits {
+                stack_size: 2 * 1024 * 1024,
+                ..Limits::default()
+            };
+            let region = TestRegion::create(1, &limits).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+
+            let retval = inst
+                .run("run", &[2u32.into(), 0u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), FAILED + 100);
+            match inst
+                .get_embed_ctx::<Vec<Error>>()
+                .unwrap()
+                .unwrap()
+                .as_slice()
+            {
+                [Error::LimitsExceeded(_)] => (),
+                errs => panic!("unexpected errors: {:?}", errs),
+            }
+
+            let depth = inst
+                .run("get_depth", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(depth), MAX_GUEST_CALL_DEPTH);
+        }
+
+        #[test]
+        fn call_guest_termination_is_returned() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+
+            // the hostcall passes the callee's termination on to the calling guest
+            match inst.run("run", &[3u32.into(), 0u32.into()]) {
+                Err(Error::RuntimeTerminated(details)) => {
+                    assert_eq!(
+                        *details
+                            .provided_details()
+                            .expect("user provided termination reason")
+                            .downcast_ref::<&'static str>()
+                            .expect("error was static str"),
+                        "terminated by callee"
+                    );
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn call_guest_cannot_yield() {
+            let module =
+                test_module_wasm("call_guest", "callback.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(Vec::<Error>::new())
+                .build()
+                .expect("instance can be created");
+
+            // the callee is terminated rather than yielding past the hostcall that called it, and
+            // the hostcall passes the termination on to the calling guest
+            match inst.run("run", &[4u32.into(), 0u32.into()]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::YieldInGuestCall)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run("run", &[0u32.into(), 1u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 102);
+        }
+    };
+}
@@ -0,0 +1,198 @@
+pub use tempfile::TempDir;
+
+/// A section of a core dump: its id, its name if it is a custom section, and its contents after
+/// the name.
+pub type Section = (u8, Option<String>, Vec<u8>);
+
+fn read_leb_u32(buf: &[u8], pos: &mut usize) -> u32 {
+    let mut result = 0;
+    let mut shift = 0;
+    loop {
+        let byte = buf[*pos];
+        *pos += 1;
+        result |= ((byte & 0x7F) as u32) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return result;
+        }
+    }
+}
+
+/// Split a core dump into its sections, checking its header.
+pub fn coredump_sections(dump: &[u8]) -> Vec<Section> {
+    assert_eq!(
+        &dump[0..8],
+        b"\0asm\x01\0\0\0",
+        "core dump has a wasm header"
+    );
+    let mut sections = vec![];
+    let mut pos = 8;
+    while pos < dump.len() {
+        let id = dump[pos];
+        pos += 1;
+        let len = read_leb_u32(dump, &mut pos) as usize;
+        let end = pos + len;
+        let name = if id == 0 {
+            let name_len = read_leb_u32(dump, &mut pos) as usize;
+            let name = String::from_utf8(dump[pos..pos + name_len].to_vec()).unwrap();
+            pos += name_len;
+            Some(name)
+        } else {
+            None
+        };
+        sections.push((id, name, dump[pos..end].to_vec()));
+        pos = end;
+    }
+    sections
+}
+
+#[macro_export]
+macro_rules! coredump_tests {
+    ( $TestRegion:path ) => {
+        $crate::coredump_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::{Error, Limits, Region, TrapCode};
+        use std::fs;
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm;
+        use $crate::coredump::{coredump_sections, Section, TempDir};
+
+        fn section<'a>(sections: &'a [Section], id: u8, name: Option<&str>) -> Option<&'a [u8]> {
+            sections
+                .iter()
+                .find(|(sid, sname, _)| *sid == id && sname.as_ref().map(String::as_str) == name)
+                .map(|(_, _, contents)| contents.as_slice())
+        }
+
+        #[test]
+        fn coredump_of_fault() {
+            let module =
+                test_module_wasm("coredump", "fault.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run("crash", &[]) {
+                Err(Error::RuntimeFault(details)) => {
+                    assert_eq!(details.trapcode, Some(TrapCode::Unreachable));
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            let workdir = TempDir::new().expect("create working directory");
+            let path = workdir.path().join("crash.core");
+            inst.write_coredump(&path)
+                .expect("core dump can be written");
+            let dump = fs::read(&path).expect("core dump can be read");
+            let sections = coredump_sections(&dump);
+
+            let names = sections
+                .iter()
+                .map(|(id, name, _)| name.clone().unwrap_or_else(|| id.to_string()))
+                .collect::<Vec<_>>();
+            assert_eq!(
+                names,
+                vec![
+                    "core",
+                    "coremodules",
+                    "coreinstances",
+                    "corestack",
+                    "5",
+                    "6",
+                    "11",
+                    "lucet-fault"
+                ]
+            );
+
+            // one frame, for `crash`, whose wasm offset is known
+            let corestack = section(&sections, 0, Some("corestack")).unwrap();
+            assert_eq!(&corestack[0..6], b"\x00\x04main");
+            assert_eq!(corestack[6], 1);
+            assert_eq!(&corestack[7..10], &[0x00, 0x00, 0x01]);
+
+            // the heap is one page, holding the value stored before the trap
+            let memory = section(&sections, 5, None).unwrap();
+            assert_eq!(memory, &[0x01, 0x00, 0x01]);
+            let data = section(&sections, 11, None).unwrap();
+            assert_eq!(&data[0..4], &[0x01, 0x00, 0x41, 0x00]);
+            assert!(data.windows(4).any(|w| w == [0xce, 0xfa, 0xed, 0xfe]));
+
+            // `(mut i32) 3` and `f64 1.5`
+            let globals = section(&sections, 6, None).unwrap();
+            let mut expected = vec![0x02, 0x7F, 0x01, 0x41, 0x03, 0x0B, 0x7C, 0x00, 0x44];
+            expected.extend_from_slice(&1.5f64.to_bits().to_le_bytes());
+            expected.push(0x0B);
+            assert_eq!(globals, expected.as_slice());
+
+            let fault = section(&sections, 0, Some("lucet-fault")).unwrap();
+            assert_eq!(fault.len(), 17);
+            assert_eq!(fault[0], 0);
+            assert_eq!(&fault[1..5], &(TrapCode::Unreachable as u32).to_le_bytes());
+        }
+
+        #[test]
+        fn coredump_of_ready_instance() {
+            let module =
+                test_module_wasm("coredump", "fault.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let workdir = TempDir::new().expect("create working directory");
+            let path = workdir.path().join("ready.core");
+            inst.write_coredump(&path)
+                .expect("core dump can be written");
+            let sections = coredump_sections(&fs::read(&path).expect("core dump can be read"));
+
+            // no frames, no fault, and an all-zero heap
+            let corestack = section(&sections, 0, Some("corestack")).unwrap();
+            assert_eq!(corestack, b"\x00\x04main\x00");
+            assert!(section(&sections, 0, Some("lucet-fault")).is_none());
+            assert_eq!(section(&sections, 11, None).unwrap(), &[0x00]);
+        }
+
+        #[test]
+        fn coredump_dir_on_fault() {
+            let module =
+                test_module_wasm("coredump", "fault.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let workdir = TempDir::new().expect("create working directory");
+            inst.set_coredump_dir(Some(workdir.path().to_owned()));
+
+            // no dump is written for a successful run
+            inst.run("store", &[]).expect("instance runs");
+            assert_eq!(fs::read_dir(workdir.path()).unwrap().count(), 0);
+
+            match inst.run("crash", &[]) {
+                Err(Error::RuntimeFault(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            let dumps = fs::read_dir(workdir.path())
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .collect::<Vec<_>>();
+            assert_eq!(dumps.len(), 1);
+            let file_name = dumps[0].file_name().unwrap().to_str().unwrap();
+            assert!(file_name.starts_with(&format!("lucet-{}-", std::process::id())));
+            assert!(file_name.ends_with(".core"));
+
+            let sections = coredump_sections(&fs::read(&dumps[0]).unwrap());
+            assert!(section(&sections, 0, Some("lucet-fault")).is_some());
+
+            // the setting survives a reset, and a later fault gets its own dump
+            inst.reset().expect("instance resets");
+            match inst.run("crash", &[]) {
+                Err(Error::RuntimeFault(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            assert_eq!(fs::read_dir(workdir.path()).unwrap().count(), 2);
+        }
+    };
+}
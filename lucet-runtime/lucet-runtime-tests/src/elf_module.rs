@@ -0,0 +1,212 @@
+#[macro_export]
+macro_rules! elf_module_tests {
+    ( $TestRegion:path ) => {
+        use lucet_runtime::vmctx::Vmctx;
+        use lucet_runtime::{lucet_hostcall, ElfImports, ElfModule, Error, Limits, Region};
+        use $TestRegion as TestRegion;
+        use $crate::build::test_module_wasm_bytes;
+
+        /// Not exported from the test executable, so only reachable through an explicit import.
+        #[lucet_hostcall]
+        pub fn elf_module_test_double(_vmctx: &mut Vmctx, x: u32) -> u32 {
+            x * 2
+        }
+
+        #[test]
+        fn elf_module_explicit_imports() {
+            let so_bytes =
+                test_module_wasm_bytes("elf_module", "imports.wat").expect("build module");
+            let imports = ElfImports::new().with_symbol(
+                "elf_module_test_double",
+                elf_module_test_double as *const libc::c_void,
+            );
+            let module =
+                ElfModule::load_from_bytes(&so_bytes, &imports).expect("module can be loaded");
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let retval = inst
+                .run("quadruple", &[3u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 12);
+        }
+
+        #[test]
+        fn elf_module_missing_import() {
+            let so_bytes =
+                test_module_wasm_bytes("elf_module", "imports.wat").expect("build module");
+            match ElfModule::load_from_bytes(&so_bytes, &ElfImports::new()) {
+                Err(Error::SymbolNotFound(sym)) => assert_eq!(sym, "elf_module_test_double"),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn elf_module_load_nonexistent() {
+            match ElfModule::load("/non/existent/file", &ElfImports::new()) {
+                Err(Error::DlError(_)) => (),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn elf_module_load_invalid() {
+            match ElfModule::load_from_bytes(b"not a shared object", &ElfImports::new()) {
+                Err(Error::ModuleError(_)) => (),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn elf_module_load_corrupted_headers() {
+            let so_bytes =
+                test_module_wasm_bytes("elf_module", "imports.wat").expect("build module");
+            let read_u16 = |offset: usize| {
+                let mut buf = [0; 2];
+                buf.copy_from_slice(&so_bytes[offset..offset + 2]);
+                u16::from_le_bytes(buf)
+            };
+            let read_u32 = |offset: usize| {
+                let mut buf = [0; 4];
+                buf.copy_from_slice(&so_bytes[offset..offset + 4]);
+                u32::from_le_bytes(buf)
+            };
+            let read_u64 = |offset: usize| {
+                let mut buf = [0; 8];
+                buf.copy_from_slice(&so_bytes[offset..offset + 8]);
+                u64::from_le_bytes(buf)
+            };
+            let corrupt = |offset: usize, val: u64| {
+                let mut bytes = so_bytes.clone();
+                bytes[offset..offset + 8].copy_from_slice(&val.to_le_bytes());
+                bytes
+            };
+
+            // a loadable segment whose end, rounded up to a page, is past the end of the address
+            // space
+            let phoff = read_u64(32) as usize;
+            let phentsize = read_u16(54) as usize;
+            let load_ph = (0..read_u16(56) as usize)
+                .map(|i| phoff + i * phentsize)
+                // PT_LOAD
+                .find(|ph| read_u32(*ph) == 1)
+                .expect("module has a loadable segment");
+            let vaddr = read_u64(load_ph + 16);
+
+            let corrupted = vec![
+                // program header table at the end of the address space
+                corrupt(32, std::u64::MAX),
+                // section header table at the end of the address space
+                corrupt(40, std::u64::MAX),
+                corrupt(load_ph + 40, std::u64::MAX - vaddr),
+            ];
+            for bytes in corrupted {
+                let imports = ElfImports::new().with_symbol(
+                    "elf_module_test_double",
+                    elf_module_test_double as *const libc::c_void,
+                );
+                match ElfModule::load_from_bytes(&bytes, &imports) {
+                    Err(Error::ModuleError(_)) => (),
+                    res => panic!("unexpected result: {:?}", res.map(|_| ())),
+                }
+            }
+        }
+
+        // The suites that load modules, run against modules loaded by `ElfModule`
+
+        mod backtrace {
+            $crate::backtrace_tests!($TestRegion, elf);
+        }
+
+        mod call_guest {
+            $crate::call_guest_tests!($TestRegion, elf);
+        }
+
+        mod coredump {
+            $crate::coredump_tests!($TestRegion, elf);
+        }
+
+        mod entrypoint {
+            $crate::entrypoint_tests!($TestRegion, elf);
+        }
+
+        mod fuel {
+            $crate::fuel_tests!($TestRegion, elf);
+        }
+
+        mod globals {
+            $crate::globals_tests!($TestRegion, elf);
+        }
+
+        mod guest_fault {
+            $crate::guest_fault_tests!($TestRegion, elf);
+        }
+
+        mod guest_memory {
+            $crate::guest_memory_tests!($TestRegion, elf);
+        }
+
+        mod host {
+            $crate::host_tests!($TestRegion, elf);
+        }
+
+        mod host_functions {
+            $crate::host_functions_tests!($TestRegion, elf);
+        }
+
+        mod host_stack {
+            $crate::host_stack_tests!($TestRegion, elf);
+        }
+
+        mod hostcall_macro {
+            $crate::hostcall_macro_tests!($TestRegion, elf);
+        }
+
+        mod kill_switch {
+            $crate::kill_switch_tests!($TestRegion, elf);
+        }
+
+        mod memory {
+            $crate::memory_tests!($TestRegion, elf);
+        }
+
+        mod resource_usage {
+            $crate::resource_usage_tests!($TestRegion, elf);
+        }
+
+        mod snapshot {
+            $crate::snapshot_tests!($TestRegion, elf);
+        }
+
+        mod stack {
+            $crate::stack_tests!($TestRegion, elf);
+        }
+
+        mod start {
+            $crate::start_tests!($TestRegion, elf);
+        }
+
+        mod strcmp {
+            $crate::strcmp_tests!($TestRegion, elf);
+        }
+
+        mod yield_resume {
+            $crate::yield_resume_tests!($TestRegion, elf);
+        }
+
+        // Suites run against modules loaded with an explicit import table, rather than one that
+        // falls back to the symbols of the test executable
+
+        mod host_table {
+            $crate::host_tests!($TestRegion, elf_table);
+        }
+
+        mod memory_table {
+            $crate::memory_tests!($TestRegion, elf_table);
+        }
+    };
+}
@@ -1,14 +1,9 @@
-use crate::build::test_module_wasm;
 use crate::helpers::{MockExportBuilder, MockModuleBuilder};
 use lucet_module_data::{lucet_signature, FunctionPointer};
 use lucet_runtime_internals::module::Module;
 use lucet_runtime_internals::vmctx::lucet_vmctx;
 use std::sync::Arc;
 
-pub fn wat_calculator_module() -> Arc<dyn Module> {
-    test_module_wasm("entrypoint", "calculator.wat").expect("build and load module")
-}
-
 pub fn mock_calculator_module() -> Arc<dyn Module> {
     extern "C" fn add_2(_vmctx: *mut lucet_vmctx, arg0: u64, arg1: u64) -> u64 {
         arg0 + arg1
@@ -176,14 +171,20 @@ pub fn mock_calculator_module() -> Arc<dyn Module> {
 #[macro_export]
 macro_rules! entrypoint_tests {
     ( $TestRegion:path ) => {
+        $crate::entrypoint_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use libc::c_void;
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
-        use lucet_runtime::{
-            lucet_hostcalls, DlModule, Error, Limits, Module, Region, Val, WASM_PAGE_SIZE,
-        };
+        use lucet_runtime::{lucet_hostcalls, Error, Limits, Module, Region, Val, WASM_PAGE_SIZE};
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::entrypoint::{mock_calculator_module, wat_calculator_module};
+        use $crate::build::$loader::test_module_wasm;
+        use $crate::entrypoint::mock_calculator_module;
+
+        fn wat_calculator_module() -> Arc<dyn Module> {
+            test_module_wasm("entrypoint", "calculator.wat").expect("build and load module")
+        }
 
         lucet_hostcalls! {
             #[no_mangle]
@@ -211,7 +212,8 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("add_2", &[123u64.into(), 456u64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(u64::from(retval), 123u64 + 456);
         }
@@ -254,7 +256,8 @@ macro_rules! entrypoint_tests {
                         10u64.into(),
                     ],
                 )
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(u64::from(retval), 1 + 2 + 3 + 4 + 5 + 6 + 7 + 8 + 9 + 10);
         }
@@ -277,7 +280,8 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("mul_2", &[123u64.into(), 456u64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(u64::from(retval), 123 * 456);
         }
@@ -295,13 +299,15 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("add_2", &[111u64.into(), 222u64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(u64::from(retval), 111 + 222);
 
             let retval = inst
                 .run("mul_2", &[333u64.into(), 444u64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(u64::from(retval), 333 * 444);
         }
@@ -345,7 +351,8 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("add_f32_2", &[(-6.9f32).into(), 4.2f32.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(f32::from(retval), -6.9 + 4.2);
         }
@@ -366,7 +373,8 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("add_f64_2", &[(-6.9f64).into(), 4.2f64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(f64::from(retval), -6.9 + 4.2);
         }
@@ -401,7 +409,8 @@ macro_rules! entrypoint_tests {
                         1.0f32.into(),
                     ],
                 )
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(
                 f32::from(retval),
@@ -439,7 +448,8 @@ macro_rules! entrypoint_tests {
                         1.0f64.into(),
                     ],
                 )
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(
                 f64::from(retval),
@@ -484,7 +494,8 @@ macro_rules! entrypoint_tests {
                         19u64.into(),
                     ],
                 )
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
 
             assert_eq!(
                 f64::from(retval),
@@ -585,7 +596,160 @@ macro_rules! entrypoint_tests {
             }
         }
 
-        use $crate::build::test_module_c;
+        #[test]
+        fn mock_typed_return_values() {
+            typed_return_values(mock_calculator_module())
+        }
+
+        #[test]
+        fn wat_typed_return_values() {
+            typed_return_values(wat_calculator_module())
+        }
+
+        fn typed_return_values(module: Arc<dyn Module>) {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let val = inst
+                .run("add_2", &[123u64.into(), 456u64.into()])
+                .expect("instance runs")
+                .unwrap_returned_val();
+            assert_eq!(val, Some(Val::I64(123 + 456)));
+
+            let val = inst
+                .run("add_f32_2", &[(-6.9f32).into(), 4.2f32.into()])
+                .expect("instance runs")
+                .unwrap_returned_val();
+            assert_eq!(val, Some(Val::F32(-6.9 + 4.2)));
+
+            let val = inst
+                .run("add_f64_2", &[(-6.9f64).into(), 4.2f64.into()])
+                .expect("instance runs")
+                .unwrap_returned_val();
+            assert_eq!(val, Some(Val::F64(-6.9 + 4.2)));
+        }
+
+        #[test]
+        fn mock_typed_func_calls() {
+            typed_func_calls(mock_calculator_module())
+        }
+
+        #[test]
+        fn wat_typed_func_calls() {
+            typed_func_calls(wat_calculator_module())
+        }
+
+        fn typed_func_calls(module: Arc<dyn Module>) {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let add_2 = inst
+                .typed_func::<(u64, u64), u64>("add_2")
+                .expect("signature matches");
+            let mul_2 = inst
+                .typed_func::<(i64, i64), i64>("mul_2")
+                .expect("signature matches");
+            let add_f64_2 = inst
+                .typed_func::<(f64, f64), f64>("add_f64_2")
+                .expect("signature matches");
+
+            for i in 0..4u64 {
+                let sum = add_2.call(&mut inst, (i, 456)).expect("instance runs");
+                assert_eq!(sum, i + 456);
+            }
+            let product = mul_2.call(&mut inst, (12, 34)).expect("instance runs");
+            assert_eq!(product, 12 * 34);
+            let sum = add_f64_2
+                .call(&mut inst, (-6.9, 4.2))
+                .expect("instance runs");
+            assert_eq!(sum, -6.9 + 4.2);
+
+            // the instance can still be used directly between typed calls
+            inst.reset().expect("instance resets");
+            let sum = add_2.call(&mut inst, (123, 456)).expect("instance runs");
+            assert_eq!(sum, 123 + 456);
+        }
+
+        #[test]
+        fn mock_typed_func_signature_mismatch() {
+            typed_func_signature_mismatch(mock_calculator_module())
+        }
+
+        #[test]
+        fn wat_typed_func_signature_mismatch() {
+            typed_func_signature_mismatch(wat_calculator_module())
+        }
+
+        fn typed_func_signature_mismatch(module: Arc<dyn Module>) {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            // wrong argument types
+            match inst.typed_func::<(i32, i64), u64>("add_2") {
+                Err(Error::InvalidArgument(err)) => {
+                    assert_eq!(err, "entrypoint function signature mismatch")
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("typed function created with the wrong argument types"),
+            }
+
+            // wrong number of arguments
+            match inst.typed_func::<(u64,), u64>("add_2") {
+                Err(Error::InvalidArgument(err)) => {
+                    assert_eq!(err, "entrypoint function signature mismatch")
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("typed function created with too few arguments"),
+            }
+
+            // wrong return type
+            match inst.typed_func::<(u64, u64), f64>("add_2") {
+                Err(Error::InvalidArgument(err)) => {
+                    assert_eq!(err, "entrypoint function signature mismatch")
+                }
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("typed function created with the wrong return type"),
+            }
+
+            match inst.typed_func::<(u64, u64), ()>("add_2") {
+                Err(Error::InvalidArgument(_)) => (),
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("typed function created without a return type"),
+            }
+
+            match inst.typed_func::<(u64, u64), u64>("invalid") {
+                Err(Error::SymbolNotFound(sym)) => assert_eq!(sym, "invalid"),
+                Err(e) => panic!("unexpected error: {}", e),
+                Ok(_) => panic!("typed function created for a missing export"),
+            }
+        }
+
+        #[test]
+        fn typed_func_other_module() {
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(mock_calculator_module())
+                .expect("instance can be created");
+            let mut other = region
+                .new_instance(wat_calculator_module())
+                .expect("instance can be created");
+
+            let add_2 = inst
+                .typed_func::<(u64, u64), u64>("add_2")
+                .expect("signature matches");
+            match add_2.call(&mut other, (123, 456)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        use $crate::build::$loader::test_module_c;
         const TEST_REGION_INIT_VAL: libc::c_int = 123;
         const TEST_REGION_SIZE: libc::size_t = 4;
 
@@ -865,7 +1029,8 @@ macro_rules! entrypoint_tests {
 
             let retval = inst
                 .run("callback_entrypoint", &[0u64.into()])
-                .expect("instance runs");
+                .expect("instance runs")
+                .unwrap_returned();
             assert_eq!(u64::from(retval), 3);
         }
     };
@@ -0,0 +1,109 @@
+#[macro_export]
+macro_rules! fuel_tests {
+    ( $TestRegion:path ) => {
+        $crate::fuel_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::{Error, Limits, Region, TrapCode};
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::{test_module_wasm, test_module_wasm_fuel};
+        use $crate::helpers::test_nonex;
+
+        #[test]
+        fn unlimited_by_default() {
+            test_nonex(|| {
+                let module =
+                    test_module_wasm_fuel("fuel", "loops.wat").expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                let retval = inst
+                    .run("count", &[100i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 100);
+                assert!(inst.fuel_consumed() > 100);
+            });
+        }
+
+        #[test]
+        fn sufficient_fuel() {
+            test_nonex(|| {
+                let module =
+                    test_module_wasm_fuel("fuel", "loops.wat").expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                inst.set_fuel(1000);
+                let retval = inst
+                    .run("count", &[10i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 10);
+
+                // one unit for the function entry, and one for each time the loop header is
+                // reached, including the final time when the loop exits
+                let consumed = inst.fuel_consumed();
+                assert!(consumed >= 12 && consumed < 1000);
+            });
+        }
+
+        #[test]
+        fn fuel_exhausted() {
+            test_nonex(|| {
+                let module =
+                    test_module_wasm_fuel("fuel", "loops.wat").expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                inst.set_fuel(1000);
+                match inst.run("spin", &[]) {
+                    Err(Error::RuntimeFault(details)) => {
+                        assert_eq!(details.trapcode, Some(TrapCode::FuelExhausted));
+                        assert!(!details.fatal);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                assert_eq!(inst.fuel_consumed(), 1000);
+
+                // after refueling, the instance can run again
+                inst.set_fuel(1000);
+                let retval = inst
+                    .run("count", &[10i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 10);
+            });
+        }
+
+        #[test]
+        fn fuel_ignored_without_metering() {
+            test_nonex(|| {
+                let module =
+                    test_module_wasm("fuel", "loops.wat").expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                inst.set_fuel(1);
+                let retval = inst
+                    .run("count", &[10i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 10);
+                assert_eq!(inst.fuel_consumed(), 0);
+            });
+        }
+    };
+}
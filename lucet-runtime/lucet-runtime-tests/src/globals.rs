@@ -1,12 +1,15 @@
 #[macro_export]
 macro_rules! globals_tests {
     ( $TestRegion:path ) => {
+        $crate::globals_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use lucet_module_data::{lucet_signature, FunctionPointer};
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
-        use lucet_runtime::{Error, Limits, Module, Region};
+        use lucet_runtime::{Error, Limits, Module, Region, Val};
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_wasm;
+        use $crate::build::$loader::test_module_wasm;
         use $crate::helpers::{MockExportBuilder, MockModuleBuilder};
 
         #[test]
@@ -50,16 +53,144 @@ macro_rules! globals_tests {
         }
 
         #[test]
-        fn reject_import() {
+        fn reject_missing_import() {
             let module = mock_import_module();
             let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
             match region.new_instance(module) {
                 Ok(_) => panic!("instance creation should not succeed"),
-                Err(Error::Unsupported(_)) => (),
+                Err(Error::SymbolNotFound(_)) => (),
                 Err(e) => panic!("unexpected error: {}", e),
             }
         }
 
+        #[test]
+        fn reject_unknown_import() {
+            let module = mock_import_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let res = region
+                .new_instance_builder(module)
+                .with_global_import("something", "else", Val::I64(1))
+                .with_global_import("something", "different", Val::I64(2))
+                .build();
+            match res {
+                Ok(_) => panic!("instance creation should not succeed"),
+                Err(Error::InvalidArgument(_)) => (),
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+        }
+
+        #[test]
+        fn provided_import() {
+            let module = mock_import_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_global_import("something", "else", Val::I64(-42))
+                .build()
+                .expect("instance can be created");
+            assert_eq!(inst.globals()[0], -42);
+
+            inst.globals_mut()[0] = 7;
+            inst.reset().expect("instance resets");
+            assert_eq!(inst.globals()[0], -42);
+        }
+
+        #[test]
+        fn imported_global_in_start() {
+            let module =
+                test_module_wasm("globals", "import.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_global_import("env", "x", Val::I32(1234))
+                .build()
+                .expect("instance can be created");
+
+            // the start function stores the imported global at address 0
+            let heap_u32 = inst.heap_u32();
+            assert_eq!(heap_u32[0], 1234);
+
+            inst.heap_u32_mut()[0] = 0;
+            inst.reset().expect("instance resets");
+            let heap_u32 = inst.heap_u32();
+            assert_eq!(heap_u32[0], 1234);
+        }
+
+        #[test]
+        fn typed_initializers() {
+            let module = test_module_wasm("globals", "initializers.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_global_import("env", "x", Val::I32(99))
+                .build()
+                .expect("instance can be created");
+
+            let retval = inst.run("get_i", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(i32::from(retval), -7);
+            let retval = inst.run("get_f32", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(f32::from(retval), 1.5);
+            let retval = inst.run("get_f64", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(f64::from(retval), -2.25);
+            let retval = inst.run("get_copy", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(i32::from(retval), 99);
+
+            inst.run("set_f32", &[0.25f32.into()]).expect("instance runs");
+            let retval = inst.run("get_f32", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(f32::from(retval), 0.25);
+
+            inst.reset().expect("instance resets");
+            let retval = inst.run("get_f32", &[]).expect("instance runs").unwrap_returned();
+            assert_eq!(f32::from(retval), 1.5);
+        }
+
+        #[test]
+        fn get_exported_globals() {
+            let module =
+                test_module_wasm("globals", "exports.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.get_global("flag") {
+                Ok(Val::I32(-1)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match (inst.get_global("limit"), inst.get_global("max")) {
+                (Ok(Val::I64(100)), Ok(Val::I64(100))) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.get_global("scale") {
+                Ok(Val::F32(v)) if v == 2.5 => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.get_global("ratio") {
+                Ok(Val::F64(v)) if v == 0.125 => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.get_global("nonexistent") {
+                Err(Error::SymbolNotFound(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn reject_set_immutable_global() {
+            let module =
+                test_module_wasm("globals", "exports.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.set_global("flag", Val::I32(1)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
         fn mock_globals_module() -> Arc<dyn Module> {
             extern "C" {
                 fn lucet_vmctx_get_globals(vmctx: *mut lucet_vmctx) -> *mut i64;
@@ -98,6 +229,54 @@ macro_rules! globals_tests {
                 .build()
         }
 
+        #[test]
+        fn set_exported_global() {
+            let module = MockModuleBuilder::new()
+                .with_exported_global(0, -1, "global0")
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "get_global0",
+                        FunctionPointer::from_usize(get_exported_global0 as usize),
+                    )
+                    .with_sig(lucet_signature!(() -> I64)),
+                )
+                .build();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.set_global("global0", Val::I64(666))
+                .expect("global can be set");
+            match inst.get_global("global0") {
+                Ok(Val::I64(666)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            let retval = inst
+                .run("get_global0", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(i64::from(retval), 666);
+
+            match inst.set_global("global0", Val::F64(1.0)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            inst.reset().expect("instance resets");
+            match inst.get_global("global0") {
+                Ok(Val::I64(-1)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        unsafe extern "C" fn get_exported_global0(vmctx: *mut lucet_vmctx) -> i64 {
+            extern "C" {
+                fn lucet_vmctx_get_globals(vmctx: *mut lucet_vmctx) -> *mut i64;
+            }
+            *lucet_vmctx_get_globals(vmctx)
+        }
+
         /* replace with use of instance public api to make sure defined globals are initialized
          * correctly
          */
@@ -121,7 +300,7 @@ macro_rules! globals_tests {
                 .new_instance(module)
                 .expect("instance can be created");
 
-            let retval = inst.run("get_global0", &[]).expect("instance runs");
+            let retval = inst.run("get_global0", &[]).expect("instance runs").unwrap_returned();
             assert_eq!(i64::from(retval), -1);
         }
 
@@ -133,10 +312,10 @@ macro_rules! globals_tests {
                 .new_instance(module)
                 .expect("instance can be created");
 
-            let retval = inst.run("get_global0", &[]).expect("instance runs");
+            let retval = inst.run("get_global0", &[]).expect("instance runs").unwrap_returned();
             assert_eq!(i64::from(retval), -1);
 
-            let retval = inst.run("get_global1", &[]).expect("instance runs");
+            let retval = inst.run("get_global1", &[]).expect("instance runs").unwrap_returned();
             assert_eq!(i64::from(retval), 420);
         }
 
@@ -151,7 +330,7 @@ macro_rules! globals_tests {
             inst.run("set_global0", &[666i64.into()])
                 .expect("instance runs");
 
-            let retval = inst.run("get_global0", &[]).expect("instance runs");
+            let retval = inst.run("get_global0", &[]).expect("instance runs").unwrap_returned();
             assert_eq!(i64::from(retval), 666);
         }
     };
@@ -1,5 +1,5 @@
 use crate::helpers::{MockExportBuilder, MockModuleBuilder};
-use lucet_module_data::{FunctionPointer, TrapCode, TrapSite};
+use lucet_module_data::{FunctionPointer, TrapCode, TrapSite, WasmOffset};
 use lucet_runtime_internals::module::Module;
 use lucet_runtime_internals::vmctx::lucet_vmctx;
 use std::sync::Arc;
@@ -76,11 +76,14 @@ pub fn mock_traps_module() -> Arc<dyn Module> {
     static ILLEGAL_INSTR_TRAPS: &'static [TrapSite] = &[TrapSite {
         offset: 8,
         code: TrapCode::BadSignature,
+        wasm_offset: WasmOffset::UNKNOWN,
     }];
 
+    // These functions are not compiled from wasm, so the wasm offset is made up.
     static OOB_TRAPS: &'static [TrapSite] = &[TrapSite {
         offset: 29,
         code: TrapCode::HeapOutOfBounds,
+        wasm_offset: 0x1a3,
     }];
 
     MockModuleBuilder::new()
@@ -123,6 +126,9 @@ pub fn mock_traps_module() -> Arc<dyn Module> {
 #[macro_export]
 macro_rules! guest_fault_tests {
     ( $TestRegion:path ) => {
+        $crate::guest_fault_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use lazy_static::lazy_static;
         use libc::{c_void, siginfo_t, SIGSEGV};
         use lucet_module_data::FunctionPointer;
@@ -138,6 +144,7 @@ macro_rules! guest_fault_tests {
         use std::ptr;
         use std::sync::{Arc, Mutex};
         use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm;
         use $crate::guest_fault::mock_traps_module;
         use $crate::helpers::{test_ex, test_nonex, MockExportBuilder, MockModuleBuilder};
 
@@ -194,7 +201,10 @@ macro_rules! guest_fault_tests {
         }
 
         fn run_onetwothree(inst: &mut Instance) {
-            let retval = inst.run("onetwothree", &[]).expect("instance runs");
+            let retval = inst
+                .run("onetwothree", &[])
+                .expect("instance runs")
+                .unwrap_returned();
             assert_eq!(libc::c_int::from(retval), 123);
         }
 
@@ -211,6 +221,7 @@ macro_rules! guest_fault_tests {
                 match inst.run("illegal_instr", &[]) {
                     Err(Error::RuntimeFault(details)) => {
                         assert_eq!(details.trapcode, Some(TrapCode::BadSignature));
+                        assert_eq!(details.wasm_offset, None);
                     }
                     res => panic!("unexpected result: {:?}", res),
                 }
@@ -235,6 +246,7 @@ macro_rules! guest_fault_tests {
                 match inst.run("oob", &[]) {
                     Err(Error::RuntimeFault(details)) => {
                         assert_eq!(details.trapcode, Some(TrapCode::HeapOutOfBounds));
+                        assert_eq!(details.wasm_offset, Some(0x1a3));
                     }
                     res => panic!("unexpected result: {:?}", res),
                 }
@@ -246,6 +258,42 @@ macro_rules! guest_fault_tests {
             });
         }
 
+        #[test]
+        fn compiled_traps() {
+            test_nonex(|| {
+                // unlike the mock module, the faults of a compiled module are found through the
+                // trap table of the loaded module
+                let module = test_module_wasm("guest_fault", "traps.wat")
+                    .expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                for (func, args, trapcode) in &[
+                    ("unreachable", vec![], TrapCode::Unreachable),
+                    ("heap_oob", vec![], TrapCode::HeapOutOfBounds),
+                    ("div_by_zero", vec![0u32.into()], TrapCode::IntegerDivByZero),
+                ] {
+                    match inst.run(func, args) {
+                        Err(Error::RuntimeFault(details)) => {
+                            assert_eq!(details.trapcode, Some(*trapcode));
+                            assert_eq!(details.fatal, false);
+                        }
+                        res => panic!("unexpected result: {:?}", res),
+                    }
+                    inst.reset().expect("instance resets");
+                }
+
+                let retval = inst
+                    .run("div_by_zero", &[1u32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(u32::from(retval), 1);
+            });
+        }
+
         #[test]
         fn hostcall_error() {
             test_nonex(|| {
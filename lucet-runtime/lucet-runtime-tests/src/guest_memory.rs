@@ -0,0 +1,248 @@
+#[macro_export]
+macro_rules! guest_memory_tests {
+    ( $TestRegion:path ) => {
+        $crate::guest_memory_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
+        use lucet_runtime::{
+            lucet_hostcall, Error, GuestMemoryError, GuestPtr, GuestSlice, GuestType, Limits,
+            Module, Region, TerminationDetails, Val,
+        };
+        use std::cell::Cell;
+        use std::sync::Arc;
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{HeapSpec, MockExportBuilder, MockModuleBuilder};
+
+        #[repr(C)]
+        #[derive(Clone, Copy)]
+        struct Pair {
+            a: u32,
+            b: u32,
+        }
+
+        unsafe impl GuestType for Pair {}
+
+        /// Swap the fields of each pair, and return the sum of all of them.
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn guest_memory_test_swap_pairs(vmctx: &mut Vmctx, pairs: GuestSlice<Pair>) -> u64 {
+            let mut sum = 0;
+            for p in pairs.iter() {
+                let Pair { a, b } = p.read(vmctx);
+                p.write(vmctx, Pair { a: b, b: a });
+                sum += a as u64 + b as u64;
+            }
+            sum
+        }
+
+        /// Reverse the elements of a slice.
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn guest_memory_test_reverse(vmctx: &mut Vmctx, xs: GuestSlice<u16>) {
+            let mut elems = xs.to_vec(vmctx);
+            elems.reverse();
+            xs.copy_from_slice(vmctx, &elems);
+        }
+
+        /// Read a `u64`, returning an error code rather than terminating if the pointer is bad.
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn guest_memory_test_try_read(vmctx: &mut Vmctx, ptr: u32) -> u64 {
+            match GuestPtr::<u64>::new(vmctx, ptr) {
+                Ok(p) => p.read(vmctx),
+                Err(GuestMemoryError::OutOfBounds { .. }) => 1,
+                Err(GuestMemoryError::Misaligned { .. }) => 2,
+                Err(GuestMemoryError::InvalidUtf8 { .. }) => 3,
+            }
+        }
+
+        thread_local! {
+            static KEPT_PTR: Cell<Option<GuestPtr<u64>>> = Cell::new(None);
+        }
+
+        /// Keep a pointer past the end of the hostcall, which hostcalls should not do.
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn guest_memory_test_keep_ptr(_vmctx: &mut Vmctx, ptr: GuestPtr<u64>) {
+            KEPT_PTR.with(|kept| kept.set(Some(ptr)));
+        }
+
+        /// Read through the pointer kept by an earlier hostcall.
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn guest_memory_test_read_kept_ptr(vmctx: &mut Vmctx) -> u64 {
+            let ptr = KEPT_PTR
+                .with(|kept| kept.get())
+                .expect("a pointer was kept");
+            ptr.read(vmctx)
+        }
+
+        extern "C" fn guest_swap_pairs(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn guest_memory_test_swap_pairs(
+                    vmctx: *mut lucet_vmctx,
+                    ptr: u32,
+                    len: u32,
+                ) -> u64;
+            }
+            unsafe { guest_memory_test_swap_pairs(vmctx, ptr, len) }
+        }
+
+        extern "C" fn guest_reverse(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) {
+            extern "C" {
+                // actually is defined in this file
+                fn guest_memory_test_reverse(vmctx: *mut lucet_vmctx, ptr: u32, len: u32);
+            }
+            unsafe { guest_memory_test_reverse(vmctx, ptr, len) }
+        }
+
+        extern "C" fn guest_try_read(vmctx: *mut lucet_vmctx, ptr: u32) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn guest_memory_test_try_read(vmctx: *mut lucet_vmctx, ptr: u32) -> u64;
+            }
+            unsafe { guest_memory_test_try_read(vmctx, ptr) }
+        }
+
+        extern "C" fn guest_keep_ptr(vmctx: *mut lucet_vmctx, ptr: u32) {
+            extern "C" {
+                // actually is defined in this file
+                fn guest_memory_test_keep_ptr(vmctx: *mut lucet_vmctx, ptr: u32);
+            }
+            unsafe { guest_memory_test_keep_ptr(vmctx, ptr) }
+        }
+
+        extern "C" fn guest_read_kept_ptr(vmctx: *mut lucet_vmctx) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn guest_memory_test_read_kept_ptr(vmctx: *mut lucet_vmctx) -> u64;
+            }
+            unsafe { guest_memory_test_read_kept_ptr(vmctx) }
+        }
+
+        fn guest_memory_module() -> Arc<dyn Module> {
+            MockModuleBuilder::new()
+                .with_heap_spec(HeapSpec {
+                    reserved_size: 4 * 1024 * 1024,
+                    guard_size: 4 * 1024 * 1024,
+                    initial_size: 64 * 1024,
+                    max_size: None,
+                })
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "swap_pairs",
+                        FunctionPointer::from_usize(guest_swap_pairs as usize),
+                    )
+                    .with_sig(lucet_signature!((I32, I32) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "reverse",
+                        FunctionPointer::from_usize(guest_reverse as usize),
+                    )
+                    .with_sig(lucet_signature!((I32, I32) -> ())),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "try_read",
+                        FunctionPointer::from_usize(guest_try_read as usize),
+                    )
+                    .with_sig(lucet_signature!((I32) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "keep_ptr",
+                        FunctionPointer::from_usize(guest_keep_ptr as usize),
+                    )
+                    .with_sig(lucet_signature!((I32) -> ())),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "read_kept_ptr",
+                        FunctionPointer::from_usize(guest_read_kept_ptr as usize),
+                    )
+                    .with_sig(lucet_signature!(() -> I64)),
+                )
+                .build()
+        }
+
+        #[test]
+        fn guest_memory_struct_array() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(guest_memory_module())
+                .expect("instance can be created");
+
+            inst.heap_u32_mut()[4..10].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+
+            let retval = inst
+                .run("swap_pairs", &[Val::GuestPtr(16), 3u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 21);
+            assert_eq!(&inst.heap_u32()[4..10], &[2, 1, 4, 3, 6, 5]);
+        }
+
+        #[test]
+        fn guest_memory_copy_slice() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(guest_memory_module())
+                .expect("instance can be created");
+
+            inst.heap_mut()[8..14].copy_from_slice(&[1, 0, 2, 0, 3, 0]);
+
+            inst.run("reverse", &[Val::GuestPtr(8), 3u32.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[8..14], &[3, 0, 2, 0, 1, 0]);
+        }
+
+        #[test]
+        fn guest_memory_errors_are_values() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(guest_memory_module())
+                .expect("instance can be created");
+            let heap_len = inst.heap().len() as u32;
+
+            inst.heap_mut()[8..16].copy_from_slice(&0x1234u64.to_le_bytes());
+
+            let mut try_read = |ptr: u32| {
+                u64::from(
+                    inst.run("try_read", &[Val::GuestPtr(ptr)])
+                        .expect("instance runs")
+                        .unwrap_returned(),
+                )
+            };
+            assert_eq!(try_read(8), 0x1234);
+            assert_eq!(try_read(heap_len - 4), 2);
+            assert_eq!(try_read(heap_len), 1);
+            assert_eq!(try_read(std::u32::MAX - 7), 1);
+        }
+
+        #[test]
+        fn guest_memory_ptr_checked_again_after_reset() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(guest_memory_module())
+                .expect("instance can be created");
+
+            inst.grow_memory(1).expect("memory can grow");
+            let ptr = inst.heap().len() as u32 - 8;
+            inst.run("keep_ptr", &[Val::GuestPtr(ptr)])
+                .expect("instance runs");
+
+            // the heap shrinks back to its initial size, leaving the kept pointer out of bounds
+            inst.reset().expect("instance resets");
+            match inst.run("read_kept_ptr", &[]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::GuestMemoryError(
+                    GuestMemoryError::OutOfBounds { offset, .. },
+                ))) => assert_eq!(offset, ptr),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
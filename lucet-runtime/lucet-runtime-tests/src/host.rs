@@ -1,17 +1,21 @@
 #[macro_export]
 macro_rules! host_tests {
     ( $TestRegion:path ) => {
+        $crate::host_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use lazy_static::lazy_static;
         use libc::c_void;
         use lucet_module_data::FunctionPointer;
         use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
         use lucet_runtime::{
-            lucet_hostcall_terminate, lucet_hostcalls, DlModule, Error, Limits, Region,
+            lucet_hostcall_terminate, lucet_hostcalls, DlModule, Error, Limits, Module, Region,
             TerminationDetails, TrapCode,
         };
         use std::sync::{Arc, Mutex};
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_c;
+        use $crate::build::$loader;
+        use $crate::build::test_module_wasm_bytes;
         use $crate::helpers::{MockExportBuilder, MockModuleBuilder};
         #[test]
         fn load_module() {
@@ -24,6 +28,42 @@ macro_rules! host_tests {
             assert!(module.is_err());
         }
 
+        #[test]
+        fn load_module_from_bytes() {
+            let calculator =
+                test_module_wasm_bytes("entrypoint", "calculator.wat").expect("build module");
+            let loops = test_module_wasm_bytes("fuel", "loops.wat").expect("build module");
+            // load both at once, so that they are loaded from different in-memory files
+            let calculator = DlModule::load_from_bytes(&calculator).expect("module can be loaded");
+            let loops = DlModule::load_from_bytes(&loops).expect("module can be loaded");
+
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut calculator_inst = region
+                .new_instance(calculator)
+                .expect("instance can be created");
+            let mut loops_inst = region.new_instance(loops).expect("instance can be created");
+
+            let retval = calculator_inst
+                .run("add_2", &[123u64.into(), 456u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 123 + 456);
+
+            let retval = loops_inst
+                .run("count", &[10u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 10);
+        }
+
+        #[test]
+        fn load_invalid_module_from_bytes() {
+            match DlModule::load_from_bytes(b"not a shared object") {
+                Err(Error::DlError(_)) => (),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
         const ERROR_MESSAGE: &'static str = "hostcall_test_func_hostcall_error";
 
         lazy_static! {
@@ -101,6 +141,44 @@ macro_rules! host_tests {
 
                 res
             }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn hostcall_panic(
+                &mut _vmctx,
+            ) -> () {
+                panic!("hostcall panicked with {}", 42);
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn hostcall_panic_any(
+                &mut _vmctx,
+            ) -> () {
+                // skips the panic hook, so the location is not known
+                std::panic::resume_unwind(Box::new(42u32));
+            }
+        }
+
+        /// Build and load a C test module, first adding the hostcalls that the modules import to
+        /// the table used by the `elf_table` loader.
+        fn test_module_c(dir: &str, cfile: &str) -> Result<Arc<dyn Module>, failure::Error> {
+            let hostcalls: &[(&str, *const c_void)] = &[
+                (
+                    "hostcall_test_func_hello",
+                    hostcall_test_func_hello as *const c_void,
+                ),
+                (
+                    "hostcall_test_func_hostcall_error",
+                    hostcall_test_func_hostcall_error as *const c_void,
+                ),
+                (
+                    "hostcall_test_func_hostcall_error_unwind",
+                    hostcall_test_func_hostcall_error_unwind as *const c_void,
+                ),
+            ];
+            for (name, addr) in hostcalls {
+                $crate::build::add_elf_table_import(name, *addr);
+            }
+            Ok($loader::test_module_c(dir, cfile)?)
         }
 
         #[test]
@@ -293,8 +371,92 @@ macro_rules! host_tests {
                 .new_instance(module)
                 .expect("instance can be created");
 
-            let retval = inst.run("f", &[]).expect("instance runs");
+            let retval = inst.run("f", &[]).expect("instance runs").unwrap_returned();
             assert_eq!(bool::from(retval), true);
         }
+
+        #[test]
+        fn run_hostcall_panic() {
+            extern "C" {
+                fn hostcall_panic(vmctx: *mut lucet_vmctx);
+            }
+
+            unsafe extern "C" fn f(vmctx: *mut lucet_vmctx) {
+                hostcall_panic(vmctx);
+            }
+
+            let module = MockModuleBuilder::new()
+                .with_export_func(MockExportBuilder::new(
+                    "f",
+                    FunctionPointer::from_usize(f as usize),
+                ))
+                .build();
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run("f", &[]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::HostPanic {
+                    message,
+                    location,
+                })) => {
+                    assert_eq!(message, "hostcall panicked with 42");
+                    let location = location.expect("panic location is known");
+                    assert!(location.starts_with(file!()));
+                }
+                res => {
+                    panic!("unexpected result: {:?}", res);
+                }
+            }
+
+            // the host keeps running, and the instance can be reset and run again
+            inst.reset().expect("instance resets");
+            match inst.run("f", &[]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::HostPanic { .. })) => (),
+                res => {
+                    panic!("unexpected result: {:?}", res);
+                }
+            }
+        }
+
+        #[test]
+        fn run_hostcall_panic_any() {
+            extern "C" {
+                fn hostcall_panic_any(vmctx: *mut lucet_vmctx);
+            }
+
+            unsafe extern "C" fn f(vmctx: *mut lucet_vmctx) {
+                hostcall_panic_any(vmctx);
+            }
+
+            let module = MockModuleBuilder::new()
+                .with_export_func(MockExportBuilder::new(
+                    "f",
+                    FunctionPointer::from_usize(f as usize),
+                ))
+                .build();
+
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run("f", &[]) {
+                Err(Error::RuntimeTerminated(details)) => {
+                    assert_eq!(
+                        details,
+                        TerminationDetails::HostPanic {
+                            message: "Box<Any>".to_owned(),
+                            location: None,
+                        }
+                    );
+                }
+                res => {
+                    panic!("unexpected result: {:?}", res);
+                }
+            }
+        }
     };
 }
@@ -0,0 +1,213 @@
+#[macro_export]
+macro_rules! host_functions_tests {
+    ( $TestRegion:path ) => {
+        $crate::host_functions_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::Vmctx;
+        use lucet_runtime::{
+            lucet_hostcall, Error, HostFunction, HostFunctionRegistry, Limits, Region,
+            TerminationDetails,
+        };
+        use std::sync::atomic::{AtomicU64, Ordering};
+        use std::sync::Arc;
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm_import_table;
+
+        /// Unlike the hostcalls in other tests, this is not exported from the test executable; it
+        /// is only reachable through the import table.
+        #[lucet_hostcall]
+        pub fn host_functions_test_sub(_vmctx: &mut Vmctx, x: u32, y: u32) -> u32 {
+            x.wrapping_sub(y)
+        }
+
+        /// The functions for the imports of `imports.wat`, with `add` implemented by `add`.
+        fn registry<F>(add: F) -> HostFunctionRegistry
+        where
+            F: Fn(&mut Vmctx, u32, u32) -> u32 + Send + Sync + 'static,
+        {
+            HostFunctionRegistry::new()
+                .with_function("env", "add", HostFunction::new(add))
+                .with_function("env", "count", HostFunction::new(|_vmctx: &mut Vmctx| 0u64))
+                .with_function(
+                    "math",
+                    "scale",
+                    HostFunction::new(|_vmctx: &mut Vmctx, x: f64| x * 2.0),
+                )
+        }
+
+        #[test]
+        fn host_functions_closures() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let counter = Arc::new(AtomicU64::new(0));
+            let counter_inner = counter.clone();
+            let factor = 1.5;
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, x: u32, y: u32| x + y))
+                .with_host_function(
+                    "env",
+                    "count",
+                    HostFunction::new(move |_vmctx: &mut Vmctx| {
+                        counter_inner.fetch_add(1, Ordering::SeqCst) + 1
+                    }),
+                )
+                .with_host_function(
+                    "math",
+                    "scale",
+                    HostFunction::new(move |_vmctx: &mut Vmctx, x: f64| x * factor),
+                )
+                .build()
+                .expect("instance can be created");
+
+            let retval = inst
+                .run("add", &[2u32.into(), 3u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 5);
+
+            let retval = inst
+                .run("add_indirect", &[4u32.into(), 5u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 9);
+
+            let retval = inst
+                .run("count_twice", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 2);
+            assert_eq!(counter.load(Ordering::SeqCst), 2);
+
+            let retval = inst
+                .run("scale", &[4.0f64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(f64::from(retval), 6.0);
+        }
+
+        #[test]
+        fn host_functions_per_instance() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+
+            let mut add_inst = region
+                .new_instance_builder(module.clone())
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, x: u32, y: u32| x + y))
+                .build()
+                .expect("instance can be created");
+            let mut mul_inst = region
+                .new_instance_builder(module)
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, x: u32, y: u32| x * y))
+                .build()
+                .expect("instance can be created");
+
+            let retval = add_inst
+                .run("add", &[6u32.into(), 7u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 13);
+
+            let retval = mul_inst
+                .run("add", &[6u32.into(), 7u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 42);
+        }
+
+        #[test]
+        fn host_functions_raw() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let sub = unsafe {
+                HostFunction::from_raw(
+                    FunctionPointer::from_usize(host_functions_test_sub as usize),
+                    lucet_signature!((I32, I32) -> I32),
+                )
+            };
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, x: u32, y: u32| x + y))
+                .with_host_function("env", "add", sub)
+                .build()
+                .expect("instance can be created");
+
+            let retval = inst
+                .run("add_indirect", &[10u32.into(), 3u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 7);
+        }
+
+        #[test]
+        fn host_functions_missing() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let funcs = HostFunctionRegistry::new().with_function(
+                "env",
+                "add",
+                HostFunction::new(|_vmctx: &mut Vmctx, x: u32, y: u32| x + y),
+            );
+            match region
+                .new_instance_builder(module)
+                .with_host_functions(&funcs)
+                .build()
+            {
+                Err(Error::SymbolNotFound(sym)) => assert_eq!(sym, "function import env::count"),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn host_functions_signature_mismatch() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            match region
+                .new_instance_builder(module)
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, x: u32, y: u32| x + y))
+                .with_host_function(
+                    "math",
+                    "scale",
+                    HostFunction::new(|_vmctx: &mut Vmctx, x: f32| x * 2.0),
+                )
+                .build()
+            {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res.map(|_| ())),
+            }
+        }
+
+        #[test]
+        fn host_functions_panic() {
+            let module = test_module_wasm_import_table("host_functions", "imports.wat")
+                .expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_host_functions(&registry(|_vmctx: &mut Vmctx, _x: u32, _y: u32| -> u32 {
+                    panic!("host function panicked")
+                }))
+                .build()
+                .expect("instance can be created");
+
+            match inst.run("add", &[1u32.into(), 2u32.into()]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::HostPanic { message, .. })) => {
+                    assert_eq!(message, "host function panicked");
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
@@ -0,0 +1,236 @@
+#[macro_export]
+macro_rules! host_stack_tests {
+    ( $TestRegion:path ) => {
+        $crate::host_stack_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{
+            lucet_hostcalls, Error, Limits, Module, Region, RunResult, TerminationDetails,
+        };
+        use std::sync::Arc;
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{MockExportBuilder, MockModuleBuilder};
+
+        /// Use about `kib` KiB of stack, one frame per KiB.
+        #[inline(never)]
+        fn use_stack(kib: u64) -> u64 {
+            let mut buf = [0u8; 1024];
+            unsafe { std::ptr::write_volatile(&mut buf[0], kib as u8) };
+            if kib == 0 {
+                0
+            } else {
+                use_stack(kib - 1) + unsafe { std::ptr::read_volatile(&buf[0]) } as u64
+            }
+        }
+
+        fn expected(kib: u64) -> u64 {
+            (1..=kib).map(|k| k as u8 as u64).sum()
+        }
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn host_stack_test_use_stack(
+                &mut _vmctx,
+                kib: u64,
+            ) -> u64 {
+                use_stack(kib)
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn host_stack_test_yield(
+                &mut vmctx,
+                kib: u64,
+            ) -> u64 {
+                let resumed = vmctx.yield_val::<u64, u64>(use_stack(kib));
+                resumed + use_stack(kib)
+            }
+        }
+
+        extern "C" fn guest_use_stack(vmctx: *mut lucet_vmctx, kib: u64) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn host_stack_test_use_stack(vmctx: *mut lucet_vmctx, kib: u64) -> u64;
+            }
+            unsafe { host_stack_test_use_stack(vmctx, kib) }
+        }
+
+        extern "C" fn guest_yield(vmctx: *mut lucet_vmctx, kib: u64) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn host_stack_test_yield(vmctx: *mut lucet_vmctx, kib: u64) -> u64;
+            }
+            unsafe { host_stack_test_yield(vmctx, kib) }
+        }
+
+        fn host_stack_module() -> Arc<dyn Module> {
+            MockModuleBuilder::new()
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "use_stack",
+                        FunctionPointer::from_usize(guest_use_stack as usize),
+                    )
+                    .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "yield",
+                        FunctionPointer::from_usize(guest_yield as usize),
+                    )
+                    .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .build()
+        }
+
+        fn assert_host_stack_overflow(res: Result<RunResult, Error>) {
+            match res {
+                Err(Error::RuntimeTerminated(details)) => {
+                    assert_eq!(details, TerminationDetails::HostStackOverflow);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn hostcall_overflows_guest_stack() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+
+            // the default guest stack is 128 KiB
+            assert_host_stack_overflow(inst.run("use_stack", &[1024u64.into()]));
+
+            // the instance runs normally again after a reset
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run("use_stack", &[16u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(16));
+        }
+
+        #[test]
+        fn hostcall_runs_on_host_stack() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+            inst.set_hostcall_stack_size(Some(4 * 1024 * 1024))
+                .expect("hostcall stack size can be set");
+
+            let retval = inst
+                .run("use_stack", &[1024u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(1024));
+
+            // the setting survives a reset
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run("use_stack", &[1024u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(1024));
+        }
+
+        #[test]
+        fn hostcall_overflows_host_stack() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+            inst.set_hostcall_stack_size(Some(64 * 1024))
+                .expect("hostcall stack size can be set");
+
+            assert_host_stack_overflow(inst.run("use_stack", &[1024u64.into()]));
+
+            inst.reset().expect("instance resets");
+            let retval = inst
+                .run("use_stack", &[16u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(16));
+        }
+
+        #[test]
+        fn hostcall_yields_from_host_stack() {
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+            inst.set_hostcall_stack_size(Some(4 * 1024 * 1024))
+                .expect("hostcall stack size can be set");
+            let mut other = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+            other
+                .set_hostcall_stack_size(Some(4 * 1024 * 1024))
+                .expect("hostcall stack size can be set");
+
+            let yielded = inst
+                .run("yield", &[512u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+            assert_eq!(yielded.downcast_ref::<u64>(), Some(&expected(512)));
+
+            // another instance on the same thread doesn't disturb the yielded hostcall's frames
+            let retval = other
+                .run("use_stack", &[1024u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(1024));
+
+            let retval = inst
+                .resume(1u64)
+                .expect("instance resumes")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 1 + expected(512));
+        }
+
+        #[test]
+        fn restore_abandons_yielded_hostcall() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+            inst.set_hostcall_stack_size(Some(4 * 1024 * 1024))
+                .expect("hostcall stack size can be set");
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+
+            inst.run("yield", &[512u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+            inst.restore(&snapshot).expect("snapshot can be restored");
+
+            // the yielded hostcall and the host stack it held are gone
+            match inst.resume(1u64) {
+                Err(Error::InstanceNotYielded) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            let retval = inst
+                .run("use_stack", &[1024u64.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), expected(1024));
+        }
+
+        #[test]
+        fn hostcall_stack_size_is_checked() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(host_stack_module())
+                .expect("instance can be created");
+
+            match inst.set_hostcall_stack_size(Some(1000)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.set_hostcall_stack_size(Some(0)) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
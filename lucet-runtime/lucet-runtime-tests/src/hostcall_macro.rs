@@ -0,0 +1,247 @@
+#[macro_export]
+macro_rules! hostcall_macro_tests {
+    ( $TestRegion:path ) => {
+        $crate::hostcall_macro_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
+        use lucet_runtime::{
+            lucet_hostcall, Error, GuestMemoryError, GuestPtr, GuestSlice, GuestStr, Limits,
+            Module, Region, RunResult, TerminationDetails, Val,
+        };
+        use std::sync::Arc;
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{MockExportBuilder, MockModuleBuilder};
+
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn hostcall_macro_test_char_count(vmctx: &mut Vmctx, s: GuestStr) -> u64 {
+            s.as_str(vmctx).expect("string is still valid").chars().count() as u64
+        }
+
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn hostcall_macro_test_sum(vmctx: &mut Vmctx, xs: GuestSlice<u32>) -> u64 {
+            xs.as_slice(vmctx).iter().map(|&x| x as u64).sum()
+        }
+
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn hostcall_macro_test_write(vmctx: &mut Vmctx, out: GuestPtr<u64>, val: u64) {
+            out.write(vmctx, val);
+        }
+
+        #[lucet_hostcall]
+        #[no_mangle]
+        pub fn hostcall_macro_test_panic(vmctx: &mut Vmctx, msg: GuestStr) {
+            panic!("{}", &*msg.as_str(vmctx).expect("string is still valid"));
+        }
+
+        extern "C" fn guest_char_count(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn hostcall_macro_test_char_count(
+                    vmctx: *mut lucet_vmctx,
+                    ptr: u32,
+                    len: u32,
+                ) -> u64;
+            }
+            unsafe { hostcall_macro_test_char_count(vmctx, ptr, len) }
+        }
+
+        extern "C" fn guest_sum(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn hostcall_macro_test_sum(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) -> u64;
+            }
+            unsafe { hostcall_macro_test_sum(vmctx, ptr, len) }
+        }
+
+        extern "C" fn guest_write(vmctx: *mut lucet_vmctx, ptr: u32, val: u64) {
+            extern "C" {
+                // actually is defined in this file
+                fn hostcall_macro_test_write(vmctx: *mut lucet_vmctx, ptr: u32, val: u64);
+            }
+            unsafe { hostcall_macro_test_write(vmctx, ptr, val) }
+        }
+
+        extern "C" fn guest_panic(vmctx: *mut lucet_vmctx, ptr: u32, len: u32) {
+            extern "C" {
+                // actually is defined in this file
+                fn hostcall_macro_test_panic(vmctx: *mut lucet_vmctx, ptr: u32, len: u32);
+            }
+            unsafe { hostcall_macro_test_panic(vmctx, ptr, len) }
+        }
+
+        fn hostcall_macro_module() -> Arc<dyn Module> {
+            MockModuleBuilder::new()
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "char_count",
+                        FunctionPointer::from_usize(guest_char_count as usize),
+                    )
+                    .with_sig(lucet_signature!((I32, I32) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new("sum", FunctionPointer::from_usize(guest_sum as usize))
+                        .with_sig(lucet_signature!((I32, I32) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "write",
+                        FunctionPointer::from_usize(guest_write as usize),
+                    )
+                    .with_sig(lucet_signature!((I32, I64) -> ())),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "panic",
+                        FunctionPointer::from_usize(guest_panic as usize),
+                    )
+                    .with_sig(lucet_signature!((I32, I32) -> ())),
+                )
+                .build()
+        }
+
+        fn assert_guest_memory_error(res: Result<RunResult, Error>, expected: GuestMemoryError) {
+            match res {
+                Err(Error::RuntimeTerminated(TerminationDetails::GuestMemoryError(e))) => {
+                    assert_eq!(e, expected);
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn hostcall_macro_str() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            let s = "héllo".as_bytes();
+            inst.heap_mut()[16..16 + s.len()].copy_from_slice(s);
+
+            let retval = inst
+                .run("char_count", &[Val::GuestPtr(16), (s.len() as u32).into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 5);
+        }
+
+        #[test]
+        fn hostcall_macro_slice() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            for (i, x) in inst.heap_u32_mut()[4..8].iter_mut().enumerate() {
+                *x = i as u32 + 1;
+            }
+
+            let retval = inst
+                .run("sum", &[Val::GuestPtr(16), 4u32.into()])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 1 + 2 + 3 + 4);
+        }
+
+        #[test]
+        fn hostcall_macro_ptr() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            inst.run("write", &[Val::GuestPtr(8), 0xAABB_CCDD_EEFFu64.into()])
+                .expect("instance runs");
+            assert_eq!(&inst.heap()[8..16], &0xAABB_CCDD_EEFFu64.to_le_bytes());
+        }
+
+        #[test]
+        fn hostcall_macro_out_of_bounds() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+            let heap_len = inst.heap().len() as u32;
+
+            assert_guest_memory_error(
+                inst.run("sum", &[Val::GuestPtr(heap_len - 8), 4u32.into()]),
+                GuestMemoryError::OutOfBounds {
+                    offset: heap_len - 8,
+                    len: 16,
+                },
+            );
+
+            inst.reset().expect("instance resets");
+            assert_guest_memory_error(
+                inst.run("write", &[Val::GuestPtr(heap_len), 0u64.into()]),
+                GuestMemoryError::OutOfBounds {
+                    offset: heap_len,
+                    len: 8,
+                },
+            );
+
+            inst.reset().expect("instance resets");
+            assert_guest_memory_error(
+                inst.run("char_count", &[Val::GuestPtr(16), std::u32::MAX.into()]),
+                GuestMemoryError::OutOfBounds {
+                    offset: 16,
+                    len: std::u32::MAX as usize,
+                },
+            );
+        }
+
+        #[test]
+        fn hostcall_macro_misaligned() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            assert_guest_memory_error(
+                inst.run("write", &[Val::GuestPtr(4), 0u64.into()]),
+                GuestMemoryError::Misaligned {
+                    offset: 4,
+                    align: std::mem::align_of::<u64>(),
+                },
+            );
+        }
+
+        #[test]
+        fn hostcall_macro_invalid_utf8() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            inst.heap_mut()[16..18].copy_from_slice(&[0xC3, 0x28]);
+
+            assert_guest_memory_error(
+                inst.run("char_count", &[Val::GuestPtr(16), 2u32.into()]),
+                GuestMemoryError::InvalidUtf8 { offset: 16 },
+            );
+        }
+
+        #[test]
+        fn hostcall_macro_panic() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(hostcall_macro_module())
+                .expect("instance can be created");
+
+            let msg = "hostcall panicked".as_bytes();
+            inst.heap_mut()[16..16 + msg.len()].copy_from_slice(msg);
+
+            match inst.run("panic", &[Val::GuestPtr(16), (msg.len() as u32).into()]) {
+                Err(Error::RuntimeTerminated(TerminationDetails::HostPanic { message, .. })) => {
+                    assert_eq!(message, "hostcall panicked");
+                }
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
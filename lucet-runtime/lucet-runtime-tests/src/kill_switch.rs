@@ -0,0 +1,214 @@
+#[macro_export]
+macro_rules! kill_switch_tests {
+    ( $TestRegion:path ) => {
+        $crate::kill_switch_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::FunctionPointer;
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{lucet_hostcalls, Error, Limits, Region, TerminationDetails};
+        use std::io::Read;
+        use std::os::unix::net::UnixStream;
+        use std::thread;
+        use std::time::Duration;
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::{test_module_wasm, test_module_wasm_interruptible};
+        use $crate::helpers::{test_nonex, MockExportBuilder, MockModuleBuilder};
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn kill_switch_test_wait(
+                &mut vmctx,
+            ) -> () {
+                while !vmctx.kill_requested() {
+                    thread::yield_now();
+                }
+            }
+        }
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn kill_switch_test_block(
+                &mut vmctx,
+            ) -> () {
+                // nothing is ever written to the other end, so this only returns once it is
+                // interrupted
+                let stream = vmctx.get_embed_ctx::<UnixStream>();
+                let mut buf = [0u8; 1];
+                let res = (&*stream).read(&mut buf);
+                assert!(res.is_err(), "read was interrupted");
+            }
+        }
+
+        extern "C" fn block_main(vmctx: *mut lucet_vmctx) {
+            extern "C" {
+                // actually is defined in this file
+                fn kill_switch_test_block(vmctx: *mut lucet_vmctx);
+            }
+            unsafe { kill_switch_test_block(vmctx) }
+        }
+
+        extern "C" fn wait_main(vmctx: *mut lucet_vmctx) {
+            extern "C" {
+                // actually is defined in this file
+                fn kill_switch_test_wait(vmctx: *mut lucet_vmctx);
+            }
+            unsafe { kill_switch_test_wait(vmctx) }
+        }
+
+        fn trigger_later(kill_switch: lucet_runtime::KillSwitch) -> thread::JoinHandle<bool> {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(10));
+                kill_switch.terminate()
+            })
+        }
+
+        #[test]
+        fn terminate_in_guest() {
+            test_nonex(|| {
+                let module = test_module_wasm_interruptible("kill_switch", "spin.wat")
+                    .expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                let killer = trigger_later(inst.kill_switch());
+                match inst.run("spin", &[]) {
+                    Err(Error::RuntimeTerminated(details)) => {
+                        assert_eq!(details, TerminationDetails::Remote);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                assert!(killer.join().expect("killer thread joins"));
+
+                // after a reset, the instance runs normally again
+                inst.reset().expect("instance resets");
+                let retval = inst
+                    .run("double", &[21i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 42);
+            });
+        }
+
+        #[test]
+        fn terminate_in_hostcall() {
+            test_nonex(|| {
+                let module = MockModuleBuilder::new()
+                    .with_export_func(MockExportBuilder::new(
+                        "wait_main",
+                        FunctionPointer::from_usize(wait_main as usize),
+                    ))
+                    .build();
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                let killer = trigger_later(inst.kill_switch());
+                match inst.run("wait_main", &[]) {
+                    Err(Error::RuntimeTerminated(details)) => {
+                        assert_eq!(details, TerminationDetails::Remote);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                assert!(killer.join().expect("killer thread joins"));
+            });
+        }
+
+        #[test]
+        fn terminate_in_blocked_hostcall() {
+            test_nonex(|| {
+                let module = MockModuleBuilder::new()
+                    .with_export_func(MockExportBuilder::new(
+                        "block_main",
+                        FunctionPointer::from_usize(block_main as usize),
+                    ))
+                    .build();
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let (stream, _writer) = UnixStream::pair().expect("socket pair can be created");
+                let mut inst = region
+                    .new_instance_builder(module)
+                    .with_embed_ctx(stream)
+                    .build()
+                    .expect("instance can be created");
+
+                let killer = trigger_later(inst.kill_switch());
+                match inst.run("block_main", &[]) {
+                    Err(Error::RuntimeTerminated(details)) => {
+                        assert_eq!(details, TerminationDetails::Remote);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                assert!(killer.join().expect("killer thread joins"));
+            });
+        }
+
+        #[test]
+        fn terminate_before_run() {
+            test_nonex(|| {
+                let module = test_module_wasm_interruptible("kill_switch", "spin.wat")
+                    .expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                assert!(inst.kill_switch().terminate());
+                match inst.run("double", &[21i32.into()]) {
+                    Err(Error::RuntimeTerminated(details)) => {
+                        assert_eq!(details, TerminationDetails::Remote);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+            });
+        }
+
+        #[test]
+        fn terminate_before_run_without_interrupt_checks() {
+            test_nonex(|| {
+                let module = test_module_wasm("kill_switch", "spin.wat")
+                    .expect("module compiled and loaded");
+                let region =
+                    TestRegion::create(1, &Limits::default()).expect("region can be created");
+                let mut inst = region
+                    .new_instance(module)
+                    .expect("instance can be created");
+
+                // the guest never checks the kill switch, so the run has to stop before it starts
+                assert!(inst.kill_switch().terminate());
+                match inst.run("double", &[21i32.into()]) {
+                    Err(Error::RuntimeTerminated(details)) => {
+                        assert_eq!(details, TerminationDetails::Remote);
+                    }
+                    res => panic!("unexpected result: {:?}", res),
+                }
+
+                inst.reset().expect("instance resets");
+                let retval = inst
+                    .run("double", &[21i32.into()])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(i32::from(retval), 42);
+            });
+        }
+
+        #[test]
+        fn terminate_after_drop() {
+            let module = MockModuleBuilder::new().build();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let kill_switch = inst.kill_switch();
+            drop(inst);
+            assert!(!kill_switch.terminate());
+        }
+    };
+}
@@ -1,10 +1,23 @@
+pub mod backtrace;
 pub mod build;
+pub mod call_guest;
+pub mod coredump;
+pub mod elf_module;
 pub mod entrypoint;
+pub mod fuel;
 pub mod globals;
 pub mod guest_fault;
+pub mod guest_memory;
 pub mod helpers;
 pub mod host;
+pub mod host_functions;
+pub mod host_stack;
+pub mod hostcall_macro;
+pub mod kill_switch;
 pub mod memory;
+pub mod resource_usage;
+pub mod snapshot;
 pub mod stack;
 pub mod start;
 pub mod strcmp;
+pub mod yield_resume;
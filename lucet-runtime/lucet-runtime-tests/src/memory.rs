@@ -1,11 +1,14 @@
 #[macro_export]
 macro_rules! memory_tests {
     ( $TestRegion:path ) => {
+        $crate::memory_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use lazy_static::lazy_static;
-        use lucet_runtime::{DlModule, Limits, Region};
+        use lucet_runtime::{Limits, Region};
         use std::sync::Mutex;
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_wasm;
+        use $crate::build::$loader::test_module_wasm;
 
         #[test]
         fn current_memory_hostcall() {
@@ -16,7 +19,10 @@ macro_rules! memory_tests {
                 .new_instance(module)
                 .expect("instance can be created");
 
-            let retval = inst.run("main", &[]).expect("instance runs");
+            let retval = inst
+                .run("main", &[])
+                .expect("instance runs")
+                .unwrap_returned();
             assert_eq!(u32::from(retval), 4);
         }
 
@@ -38,5 +44,34 @@ macro_rules! memory_tests {
             // guest then puts the result of the current memory call in heap[4] (indexed by bytes)
             assert_eq!(heap[1], 5);
         }
+
+        #[test]
+        fn guest_writes_are_private_to_instance() {
+            let module = test_module_wasm("memory", "data_segment.wat")
+                .expect("compile and load data_segment.wasm");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let mut written = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+            let other = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+
+            written.run("write", &[7i32.into()]).expect("instance runs");
+
+            // the write is only visible in the instance that made it
+            assert_eq!(written.heap_u32()[0], 7);
+            assert_eq!(other.heap_u32()[0], 42);
+
+            // and did not reach the initial heap of the module
+            drop(other);
+            let new = region
+                .new_instance(module)
+                .expect("instance can be created");
+            assert_eq!(new.heap_u32()[0], 42);
+
+            written.reset().expect("instance resets");
+            assert_eq!(written.heap_u32()[0], 42);
+        }
     };
 }
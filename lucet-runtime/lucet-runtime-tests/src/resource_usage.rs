@@ -0,0 +1,176 @@
+#[macro_export]
+macro_rules! resource_usage_tests {
+    ( $TestRegion:path ) => {
+        $crate::resource_usage_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::lucet_vmctx;
+        use lucet_runtime::{lucet_hostcalls, Limits, Module, Region, WASM_PAGE_SIZE};
+        use std::sync::Arc;
+        use std::time::Duration;
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{HeapSpec, MockExportBuilder, MockModuleBuilder};
+
+        /// Use about `kib` KiB of stack, one frame per KiB.
+        #[inline(never)]
+        fn use_stack(kib: u64) -> u64 {
+            let mut buf = [0u8; 1024];
+            unsafe { std::ptr::write_volatile(&mut buf[0], kib as u8) };
+            if kib == 0 {
+                0
+            } else {
+                use_stack(kib - 1) + unsafe { std::ptr::read_volatile(&buf[0]) } as u64
+            }
+        }
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn resource_usage_test_yield(
+                &mut vmctx,
+            ) -> () {
+                vmctx.yield_val::<(), ()>(());
+            }
+        }
+
+        extern "C" fn guest_use_stack(_vmctx: *mut lucet_vmctx, kib: u64) -> u64 {
+            use_stack(kib)
+        }
+
+        extern "C" fn guest_spin(_vmctx: *mut lucet_vmctx, iterations: u64) -> u64 {
+            let mut acc = 0u64;
+            for i in 0..iterations {
+                unsafe { std::ptr::write_volatile(&mut acc, acc.wrapping_add(i)) };
+            }
+            acc
+        }
+
+        extern "C" fn guest_yield(vmctx: *mut lucet_vmctx) {
+            extern "C" {
+                // actually is defined in this file
+                fn resource_usage_test_yield(vmctx: *mut lucet_vmctx);
+            }
+            unsafe { resource_usage_test_yield(vmctx) }
+        }
+
+        fn resource_usage_module() -> Arc<dyn Module> {
+            MockModuleBuilder::new()
+                .with_heap_spec(HeapSpec {
+                    reserved_size: 4 * 1024 * 1024,
+                    guard_size: 4 * 1024 * 1024,
+                    initial_size: 64 * 1024,
+                    max_size: None,
+                })
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "use_stack",
+                        FunctionPointer::from_usize(guest_use_stack as usize),
+                    )
+                    .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new("spin", FunctionPointer::from_usize(guest_spin as usize))
+                        .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .with_export_func(MockExportBuilder::new(
+                    "yield",
+                    FunctionPointer::from_usize(guest_yield as usize),
+                ))
+                .build()
+        }
+
+        #[test]
+        fn resource_usage_of_new_instance() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(resource_usage_module())
+                .expect("instance can be created");
+
+            let usage = inst.resource_usage();
+            assert_eq!(usage.heap_size, 64 * 1024);
+            assert_eq!(usage.heap_high_water, 64 * 1024);
+            assert_eq!(usage.stack_high_water, 0);
+            assert_eq!(usage.runs, 0);
+            assert_eq!(usage.guest_cpu_time, Duration::default());
+        }
+
+        #[test]
+        fn resource_usage_counts_runs_and_cpu_time() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(resource_usage_module())
+                .expect("instance can be created");
+
+            inst.run("spin", &[1_000_000u64.into()])
+                .expect("instance runs");
+            let first = inst.resource_usage();
+            assert_eq!(first.runs, 1);
+            assert!(first.guest_cpu_time > Duration::default());
+
+            inst.run("spin", &[1_000_000u64.into()])
+                .expect("instance runs");
+            let second = inst.resource_usage();
+            assert_eq!(second.runs, 2);
+            assert!(second.guest_cpu_time > first.guest_cpu_time);
+
+            // the usage is kept when the instance is reset
+            inst.reset().expect("instance resets");
+            assert_eq!(inst.resource_usage().runs, 2);
+        }
+
+        #[test]
+        fn resource_usage_resume_continues_run() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(resource_usage_module())
+                .expect("instance can be created");
+
+            inst.run("yield", &[])
+                .expect("instance runs")
+                .unwrap_yielded();
+            inst.resume(()).expect("instance resumes").unwrap_returned();
+            assert_eq!(inst.resource_usage().runs, 1);
+        }
+
+        #[test]
+        fn resource_usage_stack_high_water() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(resource_usage_module())
+                .expect("instance can be created");
+
+            inst.run("use_stack", &[32u64.into()])
+                .expect("instance runs");
+            let high_water = inst.resource_usage().stack_high_water;
+            assert!(high_water >= 32 * 1024);
+            assert!(high_water <= Limits::default().stack_size);
+
+            // a shallower run doesn't lower the mark
+            inst.run("use_stack", &[1u64.into()])
+                .expect("instance runs");
+            assert_eq!(inst.resource_usage().stack_high_water, high_water);
+        }
+
+        #[test]
+        fn resource_usage_heap_high_water() {
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(resource_usage_module())
+                .expect("instance can be created");
+
+            inst.grow_memory(2).expect("memory can grow");
+            let usage = inst.resource_usage();
+            assert_eq!(usage.heap_size, 64 * 1024 + 2 * WASM_PAGE_SIZE as usize);
+            assert_eq!(usage.heap_high_water, usage.heap_size);
+
+            // resetting shrinks the heap, but not the high-water mark
+            inst.reset().expect("instance resets");
+            let usage = inst.resource_usage();
+            assert_eq!(usage.heap_size, 64 * 1024);
+            assert_eq!(
+                usage.heap_high_water,
+                64 * 1024 + 2 * WASM_PAGE_SIZE as usize
+            );
+        }
+    };
+}
@@ -0,0 +1,134 @@
+#[macro_export]
+macro_rules! snapshot_tests {
+    ( $TestRegion:path ) => {
+        $crate::snapshot_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::{Error, Limits, Region, WASM_PAGE_SIZE};
+        use $TestRegion as TestRegion;
+        use $crate::build::$loader::test_module_wasm;
+        use $crate::helpers::MockModuleBuilder;
+
+        #[test]
+        fn restore_same_instance() {
+            let module =
+                test_module_wasm("snapshot", "counter.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run("init", &[]).expect("instance runs");
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+
+            let retval = inst
+                .run("bump", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 43 + 8);
+            let retval = inst
+                .run("bump", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 44 + 9);
+
+            inst.restore(&snapshot).expect("snapshot can be restored");
+            let retval = inst
+                .run("bump", &[])
+                .expect("instance runs")
+                .unwrap_returned();
+            assert_eq!(u32::from(retval), 43 + 8);
+        }
+
+        #[test]
+        fn restore_other_instances() {
+            let module =
+                test_module_wasm("snapshot", "counter.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(3, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module.clone())
+                .expect("instance can be created");
+            inst.run("init", &[]).expect("instance runs");
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+
+            for _ in 0..2 {
+                let mut stamped = region
+                    .new_instance(module.clone())
+                    .expect("instance can be created");
+                stamped
+                    .restore(&snapshot)
+                    .expect("snapshot can be restored");
+                assert_eq!(stamped.globals()[0], 7);
+                let retval = stamped
+                    .run("bump", &[])
+                    .expect("instance runs")
+                    .unwrap_returned();
+                assert_eq!(u32::from(retval), 43 + 8);
+            }
+        }
+
+        #[test]
+        fn restore_heap_len() {
+            let module =
+                test_module_wasm("snapshot", "counter.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.grow_memory(1).expect("memory grows");
+            inst.heap_mut()[WASM_PAGE_SIZE as usize] = 0xAA;
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+            assert_eq!(snapshot.heap_len(), 2 * WASM_PAGE_SIZE as usize);
+
+            inst.reset().expect("instance resets");
+            assert_eq!(inst.heap().len(), WASM_PAGE_SIZE as usize);
+
+            inst.restore(&snapshot).expect("snapshot can be restored");
+            assert_eq!(inst.heap().len(), 2 * WASM_PAGE_SIZE as usize);
+            assert_eq!(inst.heap()[WASM_PAGE_SIZE as usize], 0xAA);
+        }
+
+        #[test]
+        fn snapshot_requires_ready() {
+            let module =
+                test_module_wasm("snapshot", "counter.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.run("trap", &[]) {
+                Err(Error::RuntimeFault(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+            match inst.snapshot() {
+                Err(Error::InvalidArgument(_)) => (),
+                Ok(_) => panic!("snapshot taken of faulted instance"),
+                Err(e) => panic!("unexpected error: {}", e),
+            }
+
+            inst.reset().expect("instance resets");
+            inst.snapshot().expect("snapshot can be taken");
+        }
+
+        #[test]
+        fn restore_other_module() {
+            let module =
+                test_module_wasm("snapshot", "counter.wat").expect("module compiled and loaded");
+            let region = TestRegion::create(2, &Limits::default()).expect("region can be created");
+            let inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+
+            let mut other = region
+                .new_instance(MockModuleBuilder::new().build())
+                .expect("instance can be created");
+            match other.restore(&snapshot) {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+    };
+}
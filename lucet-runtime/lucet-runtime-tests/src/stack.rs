@@ -1,31 +1,4 @@
-use failure::Error;
-use lucet_runtime_internals::module::DlModule;
-use lucetc::Lucetc;
-use std::fs::File;
-use std::io::prelude::*;
-use std::sync::Arc;
-use tempfile::TempDir;
-
-pub fn stack_testcase(num_locals: usize) -> Result<Arc<DlModule>, Error> {
-    let workdir = TempDir::new().expect("create working directory");
-
-    let wasm_path = workdir.path().join("out.wasm");
-
-    let mut wasm_file = File::create(&wasm_path)?;
-    wasm_file.write_all(generate_test_wat(num_locals).as_bytes())?;
-
-    let native_build = Lucetc::new(wasm_path);
-
-    let so_file = workdir.path().join("out.so");
-
-    native_build.shared_object_file(so_file.clone())?;
-
-    let dlmodule = DlModule::load(so_file)?;
-
-    Ok(dlmodule)
-}
-
-fn generate_test_wat(num_locals: usize) -> String {
+pub fn generate_test_wat(num_locals: usize) -> String {
     assert!(num_locals > 2);
 
     let mut module =
@@ -83,27 +56,36 @@ fn generate_test_wat(num_locals: usize) -> String {
 #[macro_export]
 macro_rules! stack_tests {
     ( $TestRegion:path ) => {
+        $crate::stack_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use lucet_runtime::{
-            DlModule, Error, InstanceHandle, Limits, Region, TrapCode, UntypedRetVal, Val,
+            Error, InstanceHandle, Limits, Module, Region, TrapCode, UntypedRetVal, Val,
         };
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::stack::stack_testcase;
+        use $crate::build::$loader::wat_source_test;
+        use $crate::stack::generate_test_wat;
+
+        fn stack_testcase(num_locals: usize) -> Result<Arc<dyn Module>, failure::Error> {
+            Ok(wat_source_test(&generate_test_wat(num_locals))?)
+        }
 
-        fn run(module: Arc<DlModule>, recursion_depth: i32) -> Result<UntypedRetVal, Error> {
+        fn run(module: Arc<dyn Module>, recursion_depth: i32) -> Result<UntypedRetVal, Error> {
             let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
             let mut inst = region
                 .new_instance(module)
                 .expect("instance can be created");
 
             inst.run("localpalooza", &[recursion_depth.into()])
+                .and_then(|rr| rr.returned())
         }
 
-        fn expect_ok(module: Arc<DlModule>, recursion_depth: i32) {
+        fn expect_ok(module: Arc<dyn Module>, recursion_depth: i32) {
             assert!(run(module, recursion_depth).is_ok());
         }
 
-        fn expect_stack_overflow(module: Arc<DlModule>, recursion_depth: i32, probestack: bool) {
+        fn expect_stack_overflow(module: Arc<dyn Module>, recursion_depth: i32, probestack: bool) {
             match run(module, recursion_depth) {
                 Err(Error::RuntimeFault(details)) => {
                     // We should get a nonfatal trap due to the stack overflow.
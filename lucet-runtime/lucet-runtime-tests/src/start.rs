@@ -1,10 +1,13 @@
 #[macro_export]
 macro_rules! start_tests {
     ( $TestRegion:path ) => {
-        use lucet_runtime::{DlModule, Limits, Region};
+        $crate::start_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_runtime::{Limits, Region};
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_wasm;
+        use $crate::build::$loader::test_module_wasm;
 
         #[test]
         fn global_init() {
@@ -1,13 +1,16 @@
 #[macro_export]
 macro_rules! strcmp_tests {
     ( $TestRegion:path ) => {
+        $crate::strcmp_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
         use libc::{c_char, c_int, c_void, strcmp, uint64_t};
         use lucet_runtime::vmctx::lucet_vmctx;
         use lucet_runtime::{lucet_hostcalls, Error, Limits, Region, Val, WASM_PAGE_SIZE};
         use std::ffi::CString;
         use std::sync::Arc;
         use $TestRegion as TestRegion;
-        use $crate::build::test_module_c;
+        use $crate::build::$loader::test_module_c;
 
         lucet_hostcalls! {
             #[no_mangle]
@@ -48,7 +51,8 @@ macro_rules! strcmp_tests {
                     "run_strcmp",
                     &[Val::GuestPtr(s1_ptr as u32), Val::GuestPtr(s2_ptr as u32)],
                 )
-                .expect("instance runs"),
+                .expect("instance runs")
+                .unwrap_returned(),
             );
 
             let host_strcmp_res =
@@ -0,0 +1,281 @@
+#[macro_export]
+macro_rules! yield_resume_tests {
+    ( $TestRegion:path ) => {
+        $crate::yield_resume_tests!($TestRegion, dl);
+    };
+    ( $TestRegion:path, $loader:ident ) => {
+        use lucet_module_data::{lucet_signature, FunctionPointer};
+        use lucet_runtime::vmctx::{lucet_vmctx, Vmctx};
+        use lucet_runtime::{lucet_hostcalls, Error, Limits, Module, Region};
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+        use std::thread;
+        use $TestRegion as TestRegion;
+        use $crate::helpers::{MockExportBuilder, MockModuleBuilder};
+
+        lucet_hostcalls! {
+            #[no_mangle]
+            pub unsafe extern "C" fn yield_resume_test_ask(
+                &mut vmctx,
+                question: u64,
+            ) -> u64 {
+                vmctx.yield_val::<u64, u64>(question)
+            }
+
+            #[no_mangle]
+            pub unsafe extern "C" fn yield_resume_test_ask_holding(
+                &mut vmctx,
+                question: u64,
+            ) -> u64 {
+                // hold a borrow of the embedder context and a guard across the yield, both of
+                // which must be released if the hostcall is unwound
+                let other = Vmctx::from_raw(vmctx.as_raw());
+                let held = other.get_embed_ctx_mut::<Arc<AtomicBool>>();
+                let _guard = SetOnDrop(held.clone());
+                vmctx.yield_val::<u64, u64>(question)
+            }
+        }
+
+        struct SetOnDrop(Arc<AtomicBool>);
+
+        impl Drop for SetOnDrop {
+            fn drop(&mut self) {
+                self.0.store(true, Ordering::SeqCst);
+            }
+        }
+
+        extern "C" fn ask_twice(vmctx: *mut lucet_vmctx, x: u64) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn yield_resume_test_ask(vmctx: *mut lucet_vmctx, question: u64) -> u64;
+            }
+            unsafe {
+                let a = yield_resume_test_ask(vmctx, x);
+                let b = yield_resume_test_ask(vmctx, a + 1);
+                a + b
+            }
+        }
+
+        extern "C" fn ask_holding(vmctx: *mut lucet_vmctx, x: u64) -> u64 {
+            extern "C" {
+                // actually is defined in this file
+                fn yield_resume_test_ask_holding(vmctx: *mut lucet_vmctx, question: u64) -> u64;
+            }
+            unsafe { yield_resume_test_ask_holding(vmctx, x) }
+        }
+
+        fn ask_module() -> Arc<dyn Module> {
+            MockModuleBuilder::new()
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "ask_twice",
+                        FunctionPointer::from_usize(ask_twice as usize),
+                    )
+                    .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .with_export_func(
+                    MockExportBuilder::new(
+                        "ask_holding",
+                        FunctionPointer::from_usize(ask_holding as usize),
+                    )
+                    .with_sig(lucet_signature!((I64) -> I64)),
+                )
+                .build()
+        }
+
+        #[test]
+        fn yield_and_resume() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            let yielded = inst
+                .run("ask_twice", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+            assert_eq!(yielded.downcast_ref::<u64>(), Some(&5));
+
+            let yielded = inst
+                .resume(10u64)
+                .expect("instance resumes")
+                .unwrap_yielded();
+            assert_eq!(yielded.downcast_ref::<u64>(), Some(&11));
+
+            let retval = inst
+                .resume(20u64)
+                .expect("instance resumes")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 30);
+        }
+
+        #[test]
+        fn resume_wrong_type() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run("ask_twice", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+
+            match inst.resume("not a u64") {
+                Err(Error::InvalidArgument(_)) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+
+            // the instance is still waiting for a value of the right type
+            inst.resume(1u64)
+                .expect("instance resumes")
+                .unwrap_yielded();
+        }
+
+        #[test]
+        fn resume_not_yielded() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            match inst.resume(0u64) {
+                Err(Error::InstanceNotYielded) => (),
+                res => panic!("unexpected result: {:?}", res),
+            }
+        }
+
+        #[test]
+        fn reset_after_yield() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run("ask_twice", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+
+            // a yielded instance must be resumed or reset before it can run again
+            assert!(inst.run("ask_twice", &[7u64.into()]).is_err());
+
+            inst.reset().expect("instance resets");
+            let yielded = inst
+                .run("ask_twice", &[7u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+            assert_eq!(yielded.downcast_ref::<u64>(), Some(&7));
+        }
+
+        #[test]
+        fn resume_on_other_thread() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let mut inst = region
+                .new_instance(module)
+                .expect("instance can be created");
+
+            inst.run("ask_twice", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+
+            let mut inst = thread::spawn(move || {
+                match inst.resume(10u64) {
+                    Err(Error::InvalidArgument(_)) => (),
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                match inst.reset() {
+                    Err(Error::InvalidArgument(_)) => (),
+                    res => panic!("unexpected result: {:?}", res),
+                }
+                inst
+            })
+            .join()
+            .expect("thread joins");
+
+            // the instance can still be resumed on the thread it yielded on
+            inst.resume(10u64)
+                .expect("instance resumes")
+                .unwrap_yielded();
+            let retval = inst
+                .resume(20u64)
+                .expect("instance resumes")
+                .unwrap_returned();
+            assert_eq!(u64::from(retval), 30);
+        }
+
+        #[test]
+        fn reset_unwinds_yielded_hostcall() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let dropped = Arc::new(AtomicBool::new(false));
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(dropped.clone())
+                .build()
+                .expect("instance can be created");
+
+            inst.run("ask_holding", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+            assert!(!dropped.load(Ordering::SeqCst));
+
+            inst.reset().expect("instance resets");
+            assert!(dropped.load(Ordering::SeqCst));
+            match inst.get_embed_ctx_mut::<Arc<AtomicBool>>() {
+                Some(Ok(_)) => (),
+                _ => panic!("embedder context is no longer borrowed"),
+            }
+
+            inst.run("ask_twice", &[7u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+        }
+
+        #[test]
+        fn restore_unwinds_yielded_hostcall() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let dropped = Arc::new(AtomicBool::new(false));
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(dropped.clone())
+                .build()
+                .expect("instance can be created");
+            let snapshot = inst.snapshot().expect("snapshot can be taken");
+
+            inst.run("ask_holding", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+
+            inst.restore(&snapshot).expect("instance restores");
+            assert!(dropped.load(Ordering::SeqCst));
+
+            inst.run("ask_twice", &[7u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+        }
+
+        #[test]
+        fn drop_unwinds_yielded_hostcall() {
+            let module = ask_module();
+            let region = TestRegion::create(1, &Limits::default()).expect("region can be created");
+            let dropped = Arc::new(AtomicBool::new(false));
+            let mut inst = region
+                .new_instance_builder(module)
+                .with_embed_ctx(dropped.clone())
+                .build()
+                .expect("instance can be created");
+
+            inst.run("ask_holding", &[5u64.into()])
+                .expect("instance runs")
+                .unwrap_yielded();
+
+            drop(inst);
+            assert!(dropped.load(Ordering::SeqCst));
+        }
+    };
+}
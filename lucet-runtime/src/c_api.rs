@@ -1,12 +1,14 @@
-use crate::{DlModule, Instance, Limits, MmapRegion, Module, Region};
+use crate::{
+    DlModule, HostFunction, HostFunctionRegistry, Instance, KillSwitch, Limits, MmapRegion, Module,
+    Region,
+};
 use libc::{c_char, c_int, c_void};
-use lucet_module_data::TrapCode;
+use lucet_module_data::{FunctionPointer, Signature, TrapCode, ValueType};
 use lucet_runtime_internals::c_api::*;
 use lucet_runtime_internals::instance::{
     instance_handle_from_raw, instance_handle_to_raw, InstanceInternal,
 };
 use lucet_runtime_internals::vmctx::VmctxInternal;
-use lucet_runtime_internals::WASM_PAGE_SIZE;
 use lucet_runtime_internals::{
     assert_nonnull, lucet_hostcall_terminate, lucet_hostcalls, with_ffi_arcs,
 };
@@ -48,6 +50,8 @@ pub extern "C" fn lucet_error_name(e: c_int) -> *const c_char {
             Dl => "lucet_error_dl\0".as_ptr() as _,
             Internal => "lucet_error_internal\0".as_ptr() as _,
             Unsupported => "lucet_error_unsupported\0".as_ptr() as _,
+            InstanceNotReturned => "lucet_error_instance_not_returned\0".as_ptr() as _,
+            InstanceNotYielded => "lucet_error_instance_not_yielded\0".as_ptr() as _,
         }
     } else {
         "!!! error: unknown lucet_error variant\0".as_ptr() as _
@@ -63,6 +67,7 @@ pub extern "C" fn lucet_state_tag_name(tag: libc::c_int) -> *const c_char {
             Running => "lucet_state_tag_running\0".as_ptr() as _,
             Fault => "lucet_state_tag_fault\0".as_ptr() as _,
             Terminated => "lucet_state_tag_terminated\0".as_ptr() as _,
+            Yielded => "lucet_state_tag_yielded\0".as_ptr() as _,
         }
     } else {
         "!!! unknown lucet_state_tag variant!\0".as_ptr() as _
@@ -95,6 +100,24 @@ pub unsafe extern "C" fn lucet_region_release(region: *const lucet_region) {
     Arc::from_raw(region as *const Arc<dyn Region>);
 }
 
+/// Get how many instances the region holds, and how much memory they use.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_region_stats(
+    region: *const lucet_region,
+    stats_out: *mut lucet_region_stats,
+) -> lucet_error {
+    assert_nonnull!(stats_out);
+    with_ffi_arcs!([region: dyn Region], {
+        region
+            .stats()
+            .map(|stats| {
+                stats_out.write((&stats).into());
+                lucet_error::Ok
+            })
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
 // omg this naming convention might not scale
 #[no_mangle]
 pub unsafe extern "C" fn lucet_region_new_instance_with_ctx(
@@ -126,6 +149,111 @@ pub unsafe extern "C" fn lucet_region_new_instance(
     lucet_region_new_instance_with_ctx(region, module, ptr::null_mut(), inst_out)
 }
 
+/// Create an instance of a module compiled to link its imports through an import table, calling
+/// the functions in `funcs` for its imports.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_region_new_instance_with_host_functions(
+    region: *const lucet_region,
+    module: *const lucet_dl_module,
+    embed_ctx: *mut c_void,
+    funcs: *const lucet_host_functions,
+    inst_out: *mut *mut lucet_instance,
+) -> lucet_error {
+    assert_nonnull!(funcs);
+    assert_nonnull!(inst_out);
+    let funcs = &*(funcs as *const HostFunctionRegistry);
+    with_ffi_arcs!([region: dyn Region, module: DlModule], {
+        region
+            .new_instance_builder(module.clone() as Arc<dyn Module>)
+            .with_embed_ctx(embed_ctx)
+            .with_host_functions(funcs)
+            .build()
+            .map(|i| {
+                inst_out.write(instance_handle_to_raw(i) as _);
+                lucet_error::Ok
+            })
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_host_functions_create(
+    funcs_out: *mut *mut lucet_host_functions,
+) -> lucet_error {
+    assert_nonnull!(funcs_out);
+    funcs_out.write(Box::into_raw(Box::new(HostFunctionRegistry::new())) as _);
+    lucet_error::Ok
+}
+
+/// Add the function `func` for the import `module.name`.
+///
+/// `func` takes a `struct lucet_vmctx *` followed by `params_len` arguments of the types in
+/// `params`, and returns a value of type `*ret`, or nothing if `ret` is null. Only the types that
+/// WebAssembly values can be passed as are accepted.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_host_functions_add(
+    funcs: *mut lucet_host_functions,
+    module: *const c_char,
+    name: *const c_char,
+    func: *const c_void,
+    params_len: usize,
+    params: *const lucet_val::lucet_val_type,
+    ret: *const lucet_val::lucet_val_type,
+) -> lucet_error {
+    assert_nonnull!(funcs);
+    assert_nonnull!(module);
+    assert_nonnull!(name);
+    assert_nonnull!(func);
+    if params_len != 0 && params.is_null() {
+        return lucet_error::InvalidArgument;
+    }
+    let param_types = if params_len == 0 {
+        Some(vec![])
+    } else {
+        std::slice::from_raw_parts(params, params_len)
+            .iter()
+            .map(|ty| wasm_value_type(*ty))
+            .collect()
+    };
+    let ret_ty = match ret.as_ref() {
+        Some(ty) => wasm_value_type(*ty).map(Some),
+        None => Some(None),
+    };
+    let signature = match (param_types, ret_ty) {
+        (Some(params), Some(ret_ty)) => Signature { params, ret_ty },
+        _ => return lucet_error::InvalidArgument,
+    };
+    let (module, name) = match (
+        CStr::from_ptr(module).to_str(),
+        CStr::from_ptr(name).to_str(),
+    ) {
+        (Ok(module), Ok(name)) => (module, name),
+        _ => return lucet_error::InvalidArgument,
+    };
+    let func = HostFunction::from_raw(FunctionPointer::from_usize(func as usize), signature);
+    (*(funcs as *mut HostFunctionRegistry)).insert(module, name, func);
+    lucet_error::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_host_functions_release(funcs: *mut lucet_host_functions) {
+    if !funcs.is_null() {
+        Box::from_raw(funcs as *mut HostFunctionRegistry);
+    }
+}
+
+/// The type that values of type `ty` are passed to and from guests as, if they can be.
+fn wasm_value_type(ty: lucet_val::lucet_val_type) -> Option<ValueType> {
+    use lucet_runtime_internals::c_api::lucet_val::lucet_val_type::*;
+    match ty {
+        GuestPtr | U8 | U16 | U32 | I8 | I16 | I32 | Bool => Some(ValueType::I32),
+        U64 | I64 => Some(ValueType::I64),
+        F32 => Some(ValueType::F32),
+        F64 => Some(ValueType::F64),
+        C_Ptr | USize | ISize => None,
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_dl_module_load(
     path: *const c_char,
@@ -141,6 +269,25 @@ pub unsafe extern "C" fn lucet_dl_module_load(
         .unwrap_or_else(|e| e.into())
 }
 
+/// Load a module from the `len` bytes of a shared object at `bytes`, which are copied and need not
+/// outlive the module.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_dl_module_load_from_bytes(
+    bytes: *const u8,
+    len: usize,
+    mod_out: *mut *mut lucet_dl_module,
+) -> lucet_error {
+    assert_nonnull!(bytes);
+    assert_nonnull!(mod_out);
+    let so_bytes = std::slice::from_raw_parts(bytes, len);
+    DlModule::load_from_bytes(so_bytes)
+        .map(|m| {
+            mod_out.write(Arc::into_raw(m) as _);
+            lucet_error::Ok
+        })
+        .unwrap_or_else(|e| e.into())
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_dl_module_release(module: *const lucet_dl_module) {
     Arc::from_raw(module as *const DlModule);
@@ -208,6 +355,20 @@ pub unsafe extern "C" fn lucet_instance_run_func_idx(
     })
 }
 
+/// Resume an instance that yielded with `lucet_vmctx_yield`, which returns `val` to the yielding
+/// hostcall.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_resume(
+    inst: *mut lucet_instance,
+    val: *mut c_void,
+) -> lucet_error {
+    with_instance_ptr!(inst, {
+        inst.resume(CYieldedVal { val })
+            .map(|_| lucet_error::Ok)
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_state(
     inst: *const lucet_instance,
@@ -248,6 +409,37 @@ pub unsafe extern "C" fn lucet_instance_release(inst: *mut lucet_instance) {
     instance_handle_from_raw(inst as *mut Instance, true);
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_kill_switch(
+    inst: *mut lucet_instance,
+    kill_switch_out: *mut *mut lucet_kill_switch,
+) -> lucet_error {
+    assert_nonnull!(inst);
+    assert_nonnull!(kill_switch_out);
+    // an `InstanceHandle` is needed to get a kill switch; borrow one without taking ownership
+    let handle = instance_handle_from_raw(inst as *mut Instance, false);
+    let kill_switch = handle.kill_switch();
+    kill_switch_out.write(Box::into_raw(Box::new(kill_switch)) as _);
+    lucet_error::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_kill_switch_terminate(
+    kill_switch: *const lucet_kill_switch,
+) -> bool {
+    if kill_switch.is_null() {
+        return false;
+    }
+    (*(kill_switch as *const KillSwitch)).terminate()
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_kill_switch_release(kill_switch: *mut lucet_kill_switch) {
+    if !kill_switch.is_null() {
+        Box::from_raw(kill_switch as *mut KillSwitch);
+    }
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_heap(inst: *mut lucet_instance) -> *mut u8 {
     with_instance_ptr_unchecked!(inst, { inst.heap_mut().as_mut_ptr() })
@@ -286,6 +478,81 @@ pub unsafe extern "C" fn lucet_instance_grow_heap(
     })
 }
 
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_set_fuel(
+    inst: *mut lucet_instance,
+    fuel: u64,
+) -> lucet_error {
+    with_instance_ptr!(inst, {
+        inst.set_fuel(fuel);
+    });
+    lucet_error::Ok
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_fuel_consumed(inst: *const lucet_instance) -> u64 {
+    with_instance_ptr_unchecked!(inst, { inst.fuel_consumed() })
+}
+
+/// Get the resources the instance has used since it was created.
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_resource_usage(
+    inst: *const lucet_instance,
+    usage_out: *mut lucet_resource_usage,
+) -> lucet_error {
+    assert_nonnull!(usage_out);
+    with_instance_ptr!(inst, {
+        usage_out.write((&inst.resource_usage()).into());
+        lucet_error::Ok
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_get_global(
+    inst: *const lucet_instance,
+    name: *const c_char,
+    val_out: *mut lucet_val::lucet_val,
+) -> lucet_error {
+    assert_nonnull!(name);
+    assert_nonnull!(val_out);
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name_str) => name_str,
+        Err(_) => {
+            return lucet_error::SymbolNotFound;
+        }
+    };
+    with_instance_ptr!(inst, {
+        match inst.get_global(name) {
+            Ok(val) => {
+                val_out.write(val.into());
+                lucet_error::Ok
+            }
+            Err(e) => e.into(),
+        }
+    })
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn lucet_instance_set_global(
+    inst: *mut lucet_instance,
+    name: *const c_char,
+    val: *const lucet_val::lucet_val,
+) -> lucet_error {
+    assert_nonnull!(name);
+    assert_nonnull!(val);
+    let name = match CStr::from_ptr(name).to_str() {
+        Ok(name_str) => name_str,
+        Err(_) => {
+            return lucet_error::SymbolNotFound;
+        }
+    };
+    with_instance_ptr!(inst, {
+        inst.set_global(name, (&*val).into())
+            .map(|_| lucet_error::Ok)
+            .unwrap_or_else(|e| e.into())
+    })
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn lucet_instance_embed_ctx(inst: *mut lucet_instance) -> *mut c_void {
     with_instance_ptr_unchecked!(inst, {
@@ -384,29 +651,6 @@ lucet_hostcalls! {
         vmctx.instance().alloc().slot().globals as *mut i64
     }
 
-    #[no_mangle]
-    /// Get the number of WebAssembly pages currently in the heap.
-    pub unsafe extern "C" fn lucet_vmctx_current_memory(
-        &mut vmctx,
-    ) -> libc::uint32_t {
-        vmctx.instance().alloc().heap_len() as u32 / WASM_PAGE_SIZE
-    }
-
-    #[no_mangle]
-    /// Grows the guest heap by the given number of WebAssembly pages.
-    ///
-    /// On success, returns the number of pages that existed before the call. On failure, returns `-1`.
-    pub unsafe extern "C" fn lucet_vmctx_grow_memory(
-        &mut vmctx,
-        additional_pages: libc::uint32_t,
-    ) -> libc::int32_t {
-        if let Ok(old_pages) = vmctx.instance_mut().grow_memory(additional_pages) {
-            old_pages as libc::int32_t
-        } else {
-            -1
-        }
-    }
-
     #[no_mangle]
     /// Check if a memory region is inside the instance heap.
     pub unsafe extern "C" fn lucet_vmctx_check_heap(
@@ -438,6 +682,18 @@ lucet_hostcalls! {
         lucet_hostcall_terminate!(CTerminationDetails { details});
     }
 
+    #[no_mangle]
+    /// Suspend the guest, making the state of the instance `lucet_state_tag_yielded` with `val` as
+    /// the yielded value.
+    ///
+    /// Returns the value passed to `lucet_instance_resume` once the instance is resumed.
+    pub unsafe extern "C" fn lucet_vmctx_yield(
+        &mut vmctx,
+        val: *mut c_void,
+    ) -> *mut c_void {
+        vmctx.yield_val::<_, CYieldedVal>(CYieldedVal { val }).val
+    }
+
     #[no_mangle]
     /// Get the delegate object for the current instance.
     ///
@@ -23,7 +23,9 @@
 //! [`InstanceHandle`](struct.InstanceHandle.html) smart pointer.
 //!
 //! - [`Region`](trait.Region.html): the memory from which instances are created. This crate
-//! includes [`MmapRegion`](struct.MmapRegion.html), an implementation backed by `mmap`.
+//! includes [`MmapRegion`](struct.MmapRegion.html), an implementation backed by `mmap`, and
+//! [`OnDemandMmapRegion`](struct.OnDemandMmapRegion.html), which only maps the memory for an
+//! instance while it exists.
 //!
 //! - [`Limits`](struct.Limits.html): upper bounds for the resources a Lucet instance may
 //! consume. These may be larger or smaller than the limits described in the WebAssembly module
@@ -31,12 +33,18 @@
 //!
 //! - [`Module`](trait.Module.html): the read-only parts of a Lucet program, including its code and
 //! initial heap configuration. This crate includes [`DlModule`](struct.DlModule.html), an
-//! implementation backed by dynamic loading of shared objects.
+//! implementation backed by dynamic loading of shared objects, and
+//! [`ElfModule`](struct.ElfModule.html), which loads the same shared objects without the dynamic
+//! linker and resolves their imports from an explicit table.
 //!
 //! - [`Val`](enum.Val.html): an enum describing values in WebAssembly, used to provide
 //! arguments. These can be created using `From` implementations of primitive types, for example
 //! `5u64.into()` in the example below.
 //!
+//! - [`RunResult`](enum.RunResult.html): the outcome of running a guest function, which either
+//! returned or was suspended by a hostcall. `unwrap_returned()` gets the return value when the
+//! guest is known not to yield, as in the example below.
+//!
 //! - [`UntypedRetVal`](struct.UntypedRetVal.html): values returned from WebAssembly
 //! functions. These must be interpreted at the correct type by the user via `From` implementations
 //! or `retval.as_T()` methods, for example `u64::from(retval)` in the example below.
@@ -53,7 +61,7 @@
 //! let region = MmapRegion::create(1, &Limits::default()).unwrap();
 //! let mut inst = region.new_instance(module).unwrap();
 //!
-//! let retval = inst.run("factorial", &[5u64.into()]).unwrap();
+//! let retval = inst.run("factorial", &[5u64.into()]).unwrap().unwrap_returned();
 //! assert_eq!(u64::from(retval), 120u64);
 //! ```
 //!
@@ -133,6 +141,67 @@
 //! unsafe { Box::from_raw(foreign_ctx) };
 //! ```
 //!
+//! ### Host Functions
+//!
+//! Modules compiled by `lucetc` with `--import-linking table` call their imports through a table
+//! in each instance, rather than through symbols that the dynamic linker resolves against the host
+//! executable. The functions in the table are given when the instance is built, and can be Rust
+//! closures, so each instance can have its own state without going through the embedder context:
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, HostFunction, Limits, MmapRegion, Region};
+//! use lucet_runtime::vmctx::Vmctx;
+//!
+//! let module = DlModule::load("/my/lucet/module.so").unwrap();
+//! let region = MmapRegion::create(1, &Limits::default()).unwrap();
+//! let offset = 42;
+//! let mut inst = region
+//!     .new_instance_builder(module)
+//!     .with_host_function(
+//!         "env",
+//!         "add_offset",
+//!         HostFunction::new(move |_vmctx: &mut Vmctx, x: u32| x + offset),
+//!     )
+//!     .build()
+//!     .unwrap();
+//!
+//! inst.run("main", &[]).unwrap();
+//! ```
+//!
+//! Building the instance fails if the module imports a function that was not given, or that was
+//! given with a different signature.
+//!
+//! ## Yielding and Resuming
+//!
+//! A hostcall can suspend the guest with
+//! [`Vmctx::yield_val()`](vmctx/struct.Vmctx.html#method.yield_val), which makes the pending call
+//! to `Instance::run()` return `RunResult::Yielded` with the value the hostcall provides. The
+//! embedder can then do other work, and later continue the guest with `Instance::resume()`, whose
+//! argument becomes the return value of `yield_val()`:
+//!
+//! ```no_run
+//! use lucet_runtime::{DlModule, Limits, MmapRegion, Region, lucet_hostcalls};
+//! use lucet_runtime::vmctx::{Vmctx, lucet_vmctx};
+//!
+//! lucet_hostcalls! {
+//!     #[no_mangle]
+//!     pub unsafe extern "C" fn ask_host(
+//!         &mut vmctx,
+//!         question: u32,
+//!     ) -> u32 {
+//!         vmctx.yield_val::<u32, u32>(question)
+//!     }
+//! }
+//!
+//! let module = DlModule::load("/my/lucet/module.so").unwrap();
+//! let region = MmapRegion::create(1, &Limits::default()).unwrap();
+//! let mut inst = region.new_instance(module).unwrap();
+//!
+//! let yielded = inst.run("call_ask_host", &[]).unwrap().unwrap_yielded();
+//! let question = *yielded.downcast_ref::<u32>().unwrap();
+//! let retval = inst.resume(question * 2).unwrap().unwrap_returned();
+//! ```
+//!
 //! ## Custom Signal Handlers
 //!
 //! Since Lucet programs are run as native machine code, signals such as `SIGSEGV` and `SIGFPE` can
@@ -203,14 +272,21 @@ pub mod c_api;
 pub use lucet_module_data::TrapCode;
 pub use lucet_runtime_internals::alloc::Limits;
 pub use lucet_runtime_internals::error::Error;
+pub use lucet_runtime_internals::guest_memory::{
+    GuestMemoryError, GuestPtr, GuestSlice, GuestStr, GuestType,
+};
+pub use lucet_runtime_internals::host_functions::{HostClosure, HostFunction, HostFunctionRegistry};
 pub use lucet_runtime_internals::instance::{
-    FaultDetails, Instance, InstanceHandle, SignalBehavior, TerminationDetails,
+    hostcall_panic_policy, set_hostcall_panic_policy, FaultDetails, Frame, HostcallPanicPolicy,
+    Instance, InstanceHandle, InstanceSnapshot, KillSwitch, ResourceUsage, RunResult,
+    SignalBehavior, TerminationDetails, TypedArgs, TypedFunc, TypedRet, TypedVal, YieldedVal,
 };
-pub use lucet_runtime_internals::module::{DlModule, Module};
-pub use lucet_runtime_internals::region::mmap::MmapRegion;
-pub use lucet_runtime_internals::region::{InstanceBuilder, Region, RegionCreate};
+pub use lucet_runtime_internals::module::{DlModule, ElfImports, ElfModule, Module};
+pub use lucet_runtime_internals::region::mmap::{MmapRegion, MmapRegionBuilder, OnDemandMmapRegion};
+pub use lucet_runtime_internals::region::{InstanceBuilder, Region, RegionCreate, RegionStats};
 pub use lucet_runtime_internals::val::{UntypedRetVal, Val};
 pub use lucet_runtime_internals::{lucet_hostcall_terminate, lucet_hostcalls, WASM_PAGE_SIZE};
+pub use lucet_runtime_macros::lucet_hostcall;
 
 pub mod vmctx {
     //! Functions for manipulating instances from hostcalls.
@@ -225,7 +301,11 @@ pub mod vmctx {
     //! All of the `Vmctx` methods will panic if the `Vmctx` was not created from a valid pointer
     //! associated with a running instance. This should never occur if run in guest code on the
     //! pointer argument inserted by the compiler.
+    pub use lucet_runtime_internals::instance::MAX_GUEST_CALL_DEPTH;
     pub use lucet_runtime_internals::vmctx::{lucet_vmctx, Vmctx};
+
+    #[doc(hidden)]
+    pub use lucet_runtime_internals::hostcall_macros::run_hostcall_body;
 }
 
 /// Call this if you're having trouble with `lucet_*` symbols not being exported.
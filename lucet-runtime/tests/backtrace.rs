@@ -0,0 +1,3 @@
+use lucet_runtime_tests::backtrace_tests;
+
+backtrace_tests!(lucet_runtime::MmapRegion);
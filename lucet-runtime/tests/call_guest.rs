@@ -0,0 +1,3 @@
+use lucet_runtime_tests::call_guest_tests;
+
+call_guest_tests!(lucet_runtime::MmapRegion);
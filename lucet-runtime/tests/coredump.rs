@@ -0,0 +1,3 @@
+use lucet_runtime_tests::coredump_tests;
+
+coredump_tests!(lucet_runtime::MmapRegion);
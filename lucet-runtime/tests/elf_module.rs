@@ -0,0 +1,3 @@
+use lucet_runtime_tests::elf_module_tests;
+
+elf_module_tests!(lucet_runtime::MmapRegion);
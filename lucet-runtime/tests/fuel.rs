@@ -0,0 +1,3 @@
+use lucet_runtime_tests::fuel_tests;
+
+fuel_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::guest_memory_tests;
+
+guest_memory_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::host_functions_tests;
+
+host_functions_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::host_stack_tests;
+
+host_stack_tests!(lucet_runtime::MmapRegion);
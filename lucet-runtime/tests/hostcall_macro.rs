@@ -0,0 +1,3 @@
+use lucet_runtime_tests::hostcall_macro_tests;
+
+hostcall_macro_tests!(lucet_runtime::MmapRegion);
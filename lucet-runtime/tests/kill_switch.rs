@@ -0,0 +1,3 @@
+use lucet_runtime_tests::kill_switch_tests;
+
+kill_switch_tests!(lucet_runtime::MmapRegion);
@@ -1,3 +1,10 @@
 use lucet_runtime_tests::memory_tests;
 
 memory_tests!(lucet_runtime::MmapRegion);
+
+#[cfg(target_os = "linux")]
+mod heap_memfd {
+    use lucet_runtime_tests::memory_tests;
+
+    memory_tests!(lucet_runtime::MmapRegion, dl_heap_memfd);
+}
@@ -0,0 +1,41 @@
+//! The suites that create instances, run against a region that maps its slots on demand.
+
+mod entrypoint {
+    lucet_runtime_tests::entrypoint_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod globals {
+    lucet_runtime_tests::globals_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod guest_fault {
+    lucet_runtime_tests::guest_fault_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod host {
+    lucet_runtime_tests::host_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod host_functions {
+    lucet_runtime_tests::host_functions_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod memory {
+    lucet_runtime_tests::memory_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod snapshot {
+    lucet_runtime_tests::snapshot_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod stack {
+    lucet_runtime_tests::stack_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod start {
+    lucet_runtime_tests::start_tests!(lucet_runtime::OnDemandMmapRegion);
+}
+
+mod strcmp {
+    lucet_runtime_tests::strcmp_tests!(lucet_runtime::OnDemandMmapRegion);
+}
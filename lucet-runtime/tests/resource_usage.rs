@@ -0,0 +1,3 @@
+use lucet_runtime_tests::resource_usage_tests;
+
+resource_usage_tests!(lucet_runtime::MmapRegion);
@@ -0,0 +1,3 @@
+use lucet_runtime_tests::snapshot_tests;
+
+snapshot_tests!(lucet_runtime::MmapRegion);
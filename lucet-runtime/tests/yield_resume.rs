@@ -0,0 +1,3 @@
+use lucet_runtime_tests::yield_resume_tests;
+
+yield_resume_tests!(lucet_runtime::MmapRegion);
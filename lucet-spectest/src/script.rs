@@ -1,7 +1,7 @@
 use crate::bindings;
 use failure::{format_err, Error, Fail};
 use lucet_runtime::{self, MmapRegion, Module as LucetModule, Region, UntypedRetVal, Val};
-use lucetc::{Compiler, HeapSettings, LucetcError, LucetcErrorKind, OptLevel};
+use lucetc::{Compiler, CompilerSettings, HeapSettings, LucetcError, LucetcErrorKind, OptLevel};
 use std::io;
 use std::process::Command;
 use std::sync::Arc;
@@ -67,8 +67,14 @@ impl ScriptEnv {
     }
     pub fn instantiate(&mut self, module: &[u8], name: &Option<String>) -> Result<(), ScriptError> {
         let bindings = bindings::spec_test_bindings();
-        let compiler = Compiler::new(module, OptLevel::Best, &bindings, HeapSettings::default())
-            .map_err(program_error)?;
+        let compiler = Compiler::new(
+            module,
+            OptLevel::Best,
+            &bindings,
+            HeapSettings::default(),
+            CompilerSettings::default(),
+        )
+        .map_err(program_error)?;
 
         let dir = tempfile::Builder::new().prefix("codegen").tempdir()?;
         let objfile_path = dir.path().join("a.o");
@@ -160,6 +166,7 @@ impl ScriptEnv {
     ) -> Result<UntypedRetVal, ScriptError> {
         let (_, ref mut inst) = self.instance_named_mut(name)?;
         inst.run(field, &args)
+            .and_then(|rr| rr.returned())
             .map_err(|e| ScriptError::RuntimeError(e))
     }
 
@@ -10,7 +10,6 @@ use super::fs_helpers::*;
 use lucet_runtime::vmctx::Vmctx;
 
 use nix::libc::{self, c_long, c_void, off_t};
-use std::ffi::OsStr;
 use std::os::unix::prelude::{FromRawFd, OsStrExt};
 
 pub fn wasi_fd_close(vmctx: &mut Vmctx, fd: wasm32::__wasi_fd_t) -> wasm32::__wasi_errno_t {
@@ -387,8 +386,8 @@ pub fn wasi_path_open(
         nix_all_oflags.remove(OFlag::O_WRONLY);
         nix_all_oflags.insert(OFlag::O_RDONLY);
     }
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
 
@@ -519,8 +518,8 @@ pub fn wasi_path_filestat_get(
 
     let dirfd = dec_fd(dirfd);
     let dirflags = dec_lookupflags(dirflags);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_FILESTAT_GET;
@@ -555,8 +554,8 @@ pub fn wasi_path_create_directory(
     use nix::libc::mkdirat;
 
     let dirfd = dec_fd(dirfd);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_CREATE_DIRECTORY;
@@ -585,8 +584,8 @@ pub fn wasi_path_unlink_file(
     use nix::libc::unlinkat;
 
     let dirfd = dec_fd(dirfd);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_UNLINK_FILE;
@@ -875,8 +874,8 @@ pub fn wasi_path_filestat_set_times(
 
     let dirfd = dec_fd(dirfd);
     let dirflags = dec_lookupflags(dirflags);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_FILESTAT_SET_TIMES;
@@ -1048,11 +1047,11 @@ pub fn wasi_fd_readdir(
         Ok(fe) => fe,
         Err(e) => return enc_errno(e),
     };
-    let host_buf = match dec_slice_of::<u8>(vmctx, buf, buf_len) {
+    let mut host_buf = match dec_slice_of_mut::<u8>(vmctx, buf, buf_len) {
         Ok(host_buf) => host_buf,
         Err(e) => return enc_errno(e),
     };
-    let host_buf_ptr = host_buf.as_ptr();
+    let host_buf_ptr = host_buf.as_mut_ptr();
     let host_buf_len = host_buf.len();
     let dir = unsafe { fdopendir(fe.fd_object.rawfd) };
     if dir.is_null() {
@@ -1101,6 +1100,7 @@ pub fn wasi_fd_readdir(
         left -= required_space;
     }
     let host_bufused = host_buf_len - left;
+    drop(host_buf);
     unsafe {
         enc_usize_byref(vmctx, bufused, host_bufused)
             .map(|_| wasm32::__WASI_ESUCCESS)
@@ -1147,12 +1147,12 @@ pub fn wasi_path_link(
 
     let old_dirfd = dec_fd(old_dirfd);
     let new_dirfd = dec_fd(new_dirfd);
-    let old_path = match dec_slice_of::<u8>(vmctx, old_path_ptr, old_path_len) {
-        Ok(old_path_bytes) => OsStr::from_bytes(old_path_bytes),
+    let old_path = match dec_path(vmctx, old_path_ptr, old_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
-    let new_path = match dec_slice_of::<u8>(vmctx, new_path_ptr, new_path_len) {
-        Ok(new_path_bytes) => OsStr::from_bytes(new_path_bytes),
+    let new_path = match dec_path(vmctx, new_path_ptr, new_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_LINK_SOURCE;
@@ -1209,8 +1209,8 @@ pub fn wasi_path_readlink(
         Err(e) => return enc_errno(e),
     };
     let dirfd = dec_fd(dirfd);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_READLINK;
@@ -1227,6 +1227,7 @@ pub fn wasi_path_readlink(
         Ok(target_path) => target_path,
     };
     let host_bufused = target_path.len();
+    drop(buf);
     match enc_usize_byref(vmctx, bufused, host_bufused) {
         Ok(_) => {}
         Err(e) => return enc_errno(e),
@@ -1244,8 +1245,8 @@ pub fn wasi_path_remove_directory(
     use nix::libc::{unlinkat, AT_REMOVEDIR};
 
     let dirfd = dec_fd(dirfd);
-    let path = match dec_slice_of::<u8>(vmctx, path_ptr, path_len) {
-        Ok(path_bytes) => OsStr::from_bytes(path_bytes),
+    let path = match dec_path(vmctx, path_ptr, path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_REMOVE_DIRECTORY;
@@ -1277,12 +1278,12 @@ pub fn wasi_path_rename(
 
     let old_dirfd = dec_fd(old_dirfd);
     let new_dirfd = dec_fd(new_dirfd);
-    let old_path = match dec_slice_of::<u8>(vmctx, old_path_ptr, old_path_len) {
-        Ok(old_path_bytes) => OsStr::from_bytes(old_path_bytes),
+    let old_path = match dec_path(vmctx, old_path_ptr, old_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
-    let new_path = match dec_slice_of::<u8>(vmctx, new_path_ptr, new_path_len) {
-        Ok(new_path_bytes) => OsStr::from_bytes(new_path_bytes),
+    let new_path = match dec_path(vmctx, new_path_ptr, new_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_RENAME_SOURCE;
@@ -1330,12 +1331,12 @@ pub fn wasi_path_symlink(
     use nix::libc::symlinkat;
 
     let dirfd = dec_fd(dirfd);
-    let old_path = match dec_slice_of::<u8>(vmctx, old_path_ptr, old_path_len) {
-        Ok(old_path_bytes) => OsStr::from_bytes(old_path_bytes),
+    let old_path = match dec_path(vmctx, old_path_ptr, old_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
-    let new_path = match dec_slice_of::<u8>(vmctx, new_path_ptr, new_path_len) {
-        Ok(new_path_bytes) => OsStr::from_bytes(new_path_bytes),
+    let new_path = match dec_path(vmctx, new_path_ptr, new_path_len) {
+        Ok(path) => path,
         Err(e) => return enc_errno(e),
     };
     let rights = host::__WASI_RIGHT_PATH_SYMLINK;
@@ -262,11 +262,11 @@ pub fn wasi_random_get(
 ) -> wasm32::__wasi_errno_t {
     use rand::{thread_rng, RngCore};
 
-    let buf = match dec_slice_of_mut::<u8>(vmctx, buf_ptr, buf_len) {
+    let mut buf = match dec_slice_of_mut::<u8>(vmctx, buf_ptr, buf_len) {
         Ok(buf) => buf,
         Err(e) => return enc_errno(e),
     };
-    thread_rng().fill_bytes(buf);
+    thread_rng().fill_bytes(&mut buf);
 
     return wasm32::__WASI_ESUCCESS;
 }
@@ -287,7 +287,7 @@ fn _wasi_poll_oneoff_handle_timeout_event(
                 },
             },
         };
-        output_slice[0] = enc_event(output_event);
+        enc_event(&mut output_slice[0], output_event);
         1
     } else {
         // shouldn't happen
@@ -367,7 +367,7 @@ fn _wasi_poll_oneoff_handle_fd_event<'t>(
         } else {
             continue;
         };
-        *output_slice_cur.next().unwrap() = enc_event(output_event);
+        enc_event(output_slice_cur.next().unwrap(), output_event);
         revents_count += 1;
     }
     revents_count
@@ -385,14 +385,15 @@ pub fn wasi_poll_oneoff(
     }
     enc_pointee(vmctx, nevents, 0).unwrap();
 
-    let input_slice =
-        dec_slice_of::<wasm32::__wasi_subscription_t>(vmctx, input, nsubscriptions).unwrap();
+    let input: Vec<_> = dec_slice_of::<wasm32::__wasi_subscription_t>(vmctx, input, nsubscriptions)
+        .unwrap()
+        .iter()
+        .map(|x| dec_subscription(x))
+        .collect();
 
-    let output_slice =
+    let mut output_slice =
         dec_slice_of_mut::<wasm32::__wasi_event_t>(vmctx, output, nsubscriptions).unwrap();
 
-    let input: Vec<_> = input_slice.iter().map(|x| dec_subscription(x)).collect();
-
     let timeout = input
         .iter()
         .filter_map(|event| match event {
@@ -454,11 +455,12 @@ pub fn wasi_poll_oneoff(
         }
     };
     let events_count = if ready == 0 {
-        _wasi_poll_oneoff_handle_timeout_event(output_slice, timeout)
+        _wasi_poll_oneoff_handle_timeout_event(&mut output_slice, timeout)
     } else {
         let events = fd_events.iter().zip(poll_fds.iter()).take(ready);
-        _wasi_poll_oneoff_handle_fd_event(output_slice, events)
+        _wasi_poll_oneoff_handle_fd_event(&mut output_slice, events)
     };
+    drop(output_slice);
     if let Err(e) = enc_pointee(vmctx, nevents, events_count) {
         return enc_errno(e);
     }
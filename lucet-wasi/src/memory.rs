@@ -13,143 +13,117 @@ use crate::{host, wasm32};
 use cast;
 use cast::From as _0;
 use lucet_runtime::vmctx::Vmctx;
-use std::mem::{align_of, size_of};
-use std::slice;
-
-macro_rules! bail_errno {
-    ( $errno:ident ) => {
-        return Err(host::$errno as host::__wasi_errno_t);
+use lucet_runtime::{GuestMemoryError, GuestPtr, GuestSlice, GuestType};
+use std::cell::{Ref, RefMut};
+use std::ffi::OsString;
+use std::os::unix::ffi::OsStringExt;
+use std::ptr;
+
+// The wasm32 structs are `repr(C)` plain-old-data, valid for any bytes. Some of them also have
+// implicit padding, which assigning a whole value would fill with whatever the host had there, so
+// the structs that hostcalls write to the guest are encoded one field at a time over zeroed memory.
+unsafe impl GuestType for wasm32::__wasi_ciovec_t {}
+unsafe impl GuestType for wasm32::__wasi_event_t {}
+unsafe impl GuestType for wasm32::__wasi_fdstat_t {}
+unsafe impl GuestType for wasm32::__wasi_filestat_t {}
+unsafe impl GuestType for wasm32::__wasi_iovec_t {}
+unsafe impl GuestType for wasm32::__wasi_prestat_t {}
+unsafe impl GuestType for wasm32::__wasi_subscription_t {}
+
+/// The WASI error for a guest pointer that failed its checks.
+pub fn errno_from_guest_memory(e: GuestMemoryError) -> host::__wasi_errno_t {
+    let errno = match e {
+        GuestMemoryError::OutOfBounds { .. } => host::__WASI_EFAULT,
+        GuestMemoryError::Misaligned { .. } => host::__WASI_EINVAL,
+        GuestMemoryError::InvalidUtf8 { .. } => host::__WASI_EILSEQ,
     };
+    errno as host::__wasi_errno_t
 }
 
-pub fn dec_ptr(
-    vmctx: &Vmctx,
-    ptr: wasm32::uintptr_t,
-    len: usize,
-) -> Result<*const u8, host::__wasi_errno_t> {
-    let heap = vmctx.heap();
-
-    // check for overflow
-    let checked_len = (ptr as usize)
-        .checked_add(len)
-        .ok_or(host::__WASI_EFAULT as host::__wasi_errno_t)?;
-    if checked_len > heap.len() {
-        bail_errno!(__WASI_EFAULT);
-    }
-    // translate the pointer
-    Ok(unsafe { heap.as_ptr().offset(ptr as isize) })
-}
-
-pub fn dec_ptr_mut(
+pub fn dec_pointee<T: GuestType>(
     vmctx: &Vmctx,
     ptr: wasm32::uintptr_t,
-    len: usize,
-) -> Result<*mut u8, host::__wasi_errno_t> {
-    let mut heap = vmctx.heap_mut();
-
-    // check for overflow
-    let checked_len = (ptr as usize)
-        .checked_add(len)
-        .ok_or(host::__WASI_EFAULT as host::__wasi_errno_t)?;
-    if checked_len > heap.len() {
-        bail_errno!(__WASI_EFAULT);
-    }
-    // translate the pointer
-    Ok(unsafe { heap.as_mut_ptr().offset(ptr as isize) })
+) -> Result<T, host::__wasi_errno_t> {
+    GuestPtr::<T>::new(vmctx, ptr)
+        .map(|p| p.read(vmctx))
+        .map_err(errno_from_guest_memory)
 }
 
-pub fn dec_ptr_to<T>(
+pub fn enc_pointee<T: GuestType>(
     vmctx: &Vmctx,
     ptr: wasm32::uintptr_t,
-) -> Result<*const T, host::__wasi_errno_t> {
-    // check that the ptr is aligned
-    if ptr as usize % align_of::<T>() != 0 {
-        bail_errno!(__WASI_EINVAL);
-    }
-    dec_ptr(vmctx, ptr, size_of::<T>()).map(|p| p as *const T)
+    t: T,
+) -> Result<(), host::__wasi_errno_t> {
+    GuestPtr::<T>::new(vmctx, ptr)
+        .map(|p| p.write(vmctx, t))
+        .map_err(errno_from_guest_memory)
 }
 
-pub fn dec_ptr_to_mut<T>(
+/// Encode a struct into the guest heap with `enc`, which is passed the struct to set the fields of.
+pub fn enc_pointee_fields<T: GuestType>(
     vmctx: &Vmctx,
     ptr: wasm32::uintptr_t,
-) -> Result<*mut T, host::__wasi_errno_t> {
-    // check that the ptr is aligned
-    if ptr as usize % align_of::<T>() != 0 {
-        bail_errno!(__WASI_EINVAL);
-    }
-    dec_ptr_mut(vmctx, ptr, size_of::<T>()).map(|p| p as *mut T)
+    enc: impl FnOnce(&mut T) -> Result<(), host::__wasi_errno_t>,
+) -> Result<(), host::__wasi_errno_t> {
+    let mut pointee = dec_slice_of_mut::<T>(vmctx, ptr, 1)?;
+    enc(&mut pointee[0])
 }
 
-pub fn dec_pointee<T>(vmctx: &Vmctx, ptr: wasm32::uintptr_t) -> Result<T, host::__wasi_errno_t> {
-    dec_ptr_to::<T>(vmctx, ptr).map(|p| unsafe { p.read() })
+/// Zero all of the bytes of `t`, including its implicit padding, so that its fields can be set.
+fn zeroed<T: GuestType>(t: &mut T) -> &mut T {
+    unsafe { ptr::write_bytes(t as *mut T, 0, 1) };
+    t
 }
 
-pub fn enc_pointee<T>(
+fn check_slice_of<T: GuestType>(
     vmctx: &Vmctx,
-    ptr: wasm32::uintptr_t,
-    t: T,
-) -> Result<(), host::__wasi_errno_t> {
-    dec_ptr_to_mut::<T>(vmctx, ptr).map(|p| unsafe { p.write(t) })
-}
-
-fn check_slice_of<T>(
     ptr: wasm32::uintptr_t,
     len: wasm32::size_t,
-) -> Result<(usize, usize), host::__wasi_errno_t> {
-    // check alignment, and that length doesn't overflow
-    if ptr as usize % align_of::<T>() != 0 {
-        bail_errno!(__WASI_EINVAL);
-    }
-    let len = dec_usize(len);
-    let len_bytes = if let Some(len) = size_of::<T>().checked_mul(len) {
-        len
-    } else {
-        bail_errno!(__WASI_EOVERFLOW);
-    };
-    Ok((len, len_bytes))
+) -> Result<GuestSlice<T>, host::__wasi_errno_t> {
+    GuestSlice::new(vmctx, ptr, dec_u32(len)).map_err(errno_from_guest_memory)
 }
 
-pub fn dec_slice_of<'vmctx, T>(
+/// Borrow a slice from the guest heap.
+///
+/// The heap stays borrowed until the returned value is dropped, so drop it before encoding any
+/// results into the heap.
+pub fn dec_slice_of<'vmctx, T: GuestType>(
     vmctx: &'vmctx Vmctx,
     ptr: wasm32::uintptr_t,
     len: wasm32::size_t,
-) -> Result<&'vmctx [T], host::__wasi_errno_t> {
-    let (len, len_bytes) = check_slice_of::<T>(ptr, len)?;
-    let ptr = dec_ptr(vmctx, ptr, len_bytes)? as *const T;
-    Ok(unsafe { slice::from_raw_parts(ptr, len) })
+) -> Result<Ref<'vmctx, [T]>, host::__wasi_errno_t> {
+    check_slice_of::<T>(vmctx, ptr, len).map(|slice| slice.as_slice(vmctx))
 }
 
-pub fn dec_slice_of_mut<'vmctx, T>(
+/// Mutably borrow a slice from the guest heap.
+///
+/// The heap stays borrowed until the returned value is dropped, so drop it before encoding any
+/// results into the heap.
+pub fn dec_slice_of_mut<'vmctx, T: GuestType>(
     vmctx: &'vmctx Vmctx,
     ptr: wasm32::uintptr_t,
     len: wasm32::size_t,
-) -> Result<&'vmctx mut [T], host::__wasi_errno_t> {
-    let (len, len_bytes) = check_slice_of::<T>(ptr, len)?;
-    let ptr = dec_ptr_mut(vmctx, ptr, len_bytes)? as *mut T;
-    Ok(unsafe { slice::from_raw_parts_mut(ptr, len) })
+) -> Result<RefMut<'vmctx, [T]>, host::__wasi_errno_t> {
+    check_slice_of::<T>(vmctx, ptr, len).map(|slice| slice.as_slice_mut(vmctx))
 }
 
-pub fn enc_slice_of<T>(
+pub fn enc_slice_of<T: GuestType>(
     vmctx: &Vmctx,
     slice: &[T],
     ptr: wasm32::uintptr_t,
 ) -> Result<(), host::__wasi_errno_t> {
-    // check alignment
-    if ptr as usize % align_of::<T>() != 0 {
-        return Err(host::__WASI_EINVAL as host::__wasi_errno_t);
-    }
-    // check that length doesn't overflow
-    let len_bytes = if let Some(len) = size_of::<T>().checked_mul(slice.len()) {
-        len
-    } else {
-        return Err(host::__WASI_EOVERFLOW as host::__wasi_errno_t);
-    };
-
-    // get the pointer into guest memory, and copy the bytes
-    let ptr = dec_ptr(vmctx, ptr, len_bytes)? as *mut libc::c_void;
-    unsafe { libc::memcpy(ptr, slice.as_ptr() as *const libc::c_void, len_bytes) };
+    let len = wasm32::size_t::cast(slice.len())
+        .map_err(|_| host::__WASI_EOVERFLOW as host::__wasi_errno_t)?;
+    check_slice_of::<T>(vmctx, ptr, enc_u32(len)).map(|dst| dst.copy_from_slice(vmctx, slice))
+}
 
-    Ok(())
+/// Copy a path out of the guest heap.
+pub fn dec_path(
+    vmctx: &Vmctx,
+    ptr: wasm32::uintptr_t,
+    len: wasm32::size_t,
+) -> Result<OsString, host::__wasi_errno_t> {
+    check_slice_of::<u8>(vmctx, ptr, len).map(|path| OsString::from_vec(path.to_vec(vmctx)))
 }
 
 macro_rules! dec_enc_scalar {
@@ -183,10 +157,10 @@ pub fn dec_ciovec(
     vmctx: &Vmctx,
     ciovec: &wasm32::__wasi_ciovec_t,
 ) -> Result<host::__wasi_ciovec_t, host::__wasi_errno_t> {
-    let len = dec_usize(ciovec.buf_len);
+    let buf = check_slice_of::<u8>(vmctx, ciovec.buf, ciovec.buf_len)?;
     Ok(host::__wasi_ciovec_t {
-        buf: dec_ptr(vmctx, ciovec.buf, len)? as *const host::void,
-        buf_len: len,
+        buf: buf.as_ptr(vmctx) as *const host::void,
+        buf_len: dec_usize(ciovec.buf_len),
     })
 }
 
@@ -195,18 +169,18 @@ pub fn dec_ciovec_slice(
     ptr: wasm32::uintptr_t,
     len: wasm32::size_t,
 ) -> Result<Vec<host::__wasi_ciovec_t>, host::__wasi_errno_t> {
-    let slice = dec_slice_of::<wasm32::__wasi_ciovec_t>(vmctx, ptr, len)?;
-    slice.iter().map(|iov| dec_ciovec(vmctx, iov)).collect()
+    let iovs = check_slice_of::<wasm32::__wasi_ciovec_t>(vmctx, ptr, len)?.to_vec(vmctx);
+    iovs.iter().map(|iov| dec_ciovec(vmctx, iov)).collect()
 }
 
 pub fn dec_iovec(
     vmctx: &Vmctx,
     iovec: &wasm32::__wasi_iovec_t,
 ) -> Result<host::__wasi_iovec_t, host::__wasi_errno_t> {
-    let len = dec_usize(iovec.buf_len);
+    let buf = check_slice_of::<u8>(vmctx, iovec.buf, iovec.buf_len)?;
     Ok(host::__wasi_iovec_t {
-        buf: dec_ptr(vmctx, iovec.buf, len)? as *mut host::void,
-        buf_len: len,
+        buf: buf.as_mut_ptr(vmctx) as *mut host::void,
+        buf_len: dec_usize(iovec.buf_len),
     })
 }
 
@@ -215,8 +189,8 @@ pub fn dec_iovec_slice(
     ptr: wasm32::uintptr_t,
     len: wasm32::size_t,
 ) -> Result<Vec<host::__wasi_iovec_t>, host::__wasi_errno_t> {
-    let slice = dec_slice_of::<wasm32::__wasi_iovec_t>(vmctx, ptr, len)?;
-    slice.iter().map(|iov| dec_iovec(vmctx, iov)).collect()
+    let iovs = check_slice_of::<wasm32::__wasi_iovec_t>(vmctx, ptr, len)?.to_vec(vmctx);
+    iovs.iter().map(|iov| dec_iovec(vmctx, iov)).collect()
 }
 
 dec_enc_scalar!(
@@ -290,17 +264,16 @@ pub fn dec_filestat_byref(
     dec_pointee::<wasm32::__wasi_filestat_t>(vmctx, filestat_ptr).map(dec_filestat)
 }
 
-pub fn enc_filestat(filestat: host::__wasi_filestat_t) -> wasm32::__wasi_filestat_t {
-    wasm32::__wasi_filestat_t {
-        st_dev: enc_device(filestat.st_dev),
-        st_ino: enc_inode(filestat.st_ino),
-        st_filetype: enc_filetype(filestat.st_filetype),
-        st_nlink: enc_linkcount(filestat.st_nlink),
-        st_size: enc_filesize(filestat.st_size),
-        st_atim: enc_timestamp(filestat.st_atim),
-        st_mtim: enc_timestamp(filestat.st_mtim),
-        st_ctim: enc_timestamp(filestat.st_ctim),
-    }
+pub fn enc_filestat(dst: &mut wasm32::__wasi_filestat_t, filestat: host::__wasi_filestat_t) {
+    let dst = zeroed(dst);
+    dst.st_dev = enc_device(filestat.st_dev);
+    dst.st_ino = enc_inode(filestat.st_ino);
+    dst.st_filetype = enc_filetype(filestat.st_filetype);
+    dst.st_nlink = enc_linkcount(filestat.st_nlink);
+    dst.st_size = enc_filesize(filestat.st_size);
+    dst.st_atim = enc_timestamp(filestat.st_atim);
+    dst.st_mtim = enc_timestamp(filestat.st_mtim);
+    dst.st_ctim = enc_timestamp(filestat.st_ctim);
 }
 
 pub fn enc_filestat_byref(
@@ -308,8 +281,10 @@ pub fn enc_filestat_byref(
     filestat_ptr: wasm32::uintptr_t,
     host_filestat: host::__wasi_filestat_t,
 ) -> Result<(), host::__wasi_errno_t> {
-    let filestat = enc_filestat(host_filestat);
-    enc_pointee::<wasm32::__wasi_filestat_t>(vmctx, filestat_ptr, filestat)
+    enc_pointee_fields(vmctx, filestat_ptr, |filestat| {
+        enc_filestat(filestat, host_filestat);
+        Ok(())
+    })
 }
 
 pub fn dec_fdstat(fdstat: wasm32::__wasi_fdstat_t) -> host::__wasi_fdstat_t {
@@ -328,14 +303,12 @@ pub fn dec_fdstat_byref(
     dec_pointee::<wasm32::__wasi_fdstat_t>(vmctx, fdstat_ptr).map(dec_fdstat)
 }
 
-pub fn enc_fdstat(fdstat: host::__wasi_fdstat_t) -> wasm32::__wasi_fdstat_t {
-    wasm32::__wasi_fdstat_t {
-        fs_filetype: enc_filetype(fdstat.fs_filetype),
-        fs_flags: enc_fdflags(fdstat.fs_flags),
-        __bindgen_padding_0: 0,
-        fs_rights_base: enc_rights(fdstat.fs_rights_base),
-        fs_rights_inheriting: enc_rights(fdstat.fs_rights_inheriting),
-    }
+pub fn enc_fdstat(dst: &mut wasm32::__wasi_fdstat_t, fdstat: host::__wasi_fdstat_t) {
+    let dst = zeroed(dst);
+    dst.fs_filetype = enc_filetype(fdstat.fs_filetype);
+    dst.fs_flags = enc_fdflags(fdstat.fs_flags);
+    dst.fs_rights_base = enc_rights(fdstat.fs_rights_base);
+    dst.fs_rights_inheriting = enc_rights(fdstat.fs_rights_inheriting);
 }
 
 pub fn enc_fdstat_byref(
@@ -343,8 +316,10 @@ pub fn enc_fdstat_byref(
     fdstat_ptr: wasm32::uintptr_t,
     host_fdstat: host::__wasi_fdstat_t,
 ) -> Result<(), host::__wasi_errno_t> {
-    let fdstat = enc_fdstat(host_fdstat);
-    enc_pointee::<wasm32::__wasi_fdstat_t>(vmctx, fdstat_ptr, fdstat)
+    enc_pointee_fields(vmctx, fdstat_ptr, |fdstat| {
+        enc_fdstat(fdstat, host_fdstat);
+        Ok(())
+    })
 }
 
 dec_enc_scalar!(
@@ -413,19 +388,15 @@ pub fn dec_prestat_byref(
 }
 
 pub fn enc_prestat(
+    dst: &mut wasm32::__wasi_prestat_t,
     prestat: host::__wasi_prestat_t,
-) -> Result<wasm32::__wasi_prestat_t, host::__wasi_errno_t> {
+) -> Result<(), host::__wasi_errno_t> {
     match u32::from(prestat.pr_type) {
         host::__WASI_PREOPENTYPE_DIR => {
-            let u = wasm32::__wasi_prestat_t___wasi_prestat_u {
-                dir: wasm32::__wasi_prestat_t___wasi_prestat_u___wasi_prestat_u_dir_t {
-                    pr_name_len: enc_usize(unsafe { prestat.u.dir.pr_name_len }),
-                },
-            };
-            Ok(wasm32::__wasi_prestat_t {
-                pr_type: wasm32::__WASI_PREOPENTYPE_DIR as wasm32::__wasi_preopentype_t,
-                u,
-            })
+            let dst = zeroed(dst);
+            dst.pr_type = wasm32::__WASI_PREOPENTYPE_DIR as wasm32::__wasi_preopentype_t;
+            dst.u.dir.pr_name_len = enc_usize(unsafe { prestat.u.dir.pr_name_len });
+            Ok(())
         }
         _ => Err(host::__WASI_EINVAL as host::__wasi_errno_t),
     }
@@ -436,8 +407,9 @@ pub fn enc_prestat_byref(
     prestat_ptr: wasm32::uintptr_t,
     host_prestat: host::__wasi_prestat_t,
 ) -> Result<(), host::__wasi_errno_t> {
-    let prestat = enc_prestat(host_prestat)?;
-    enc_pointee::<wasm32::__wasi_prestat_t>(vmctx, prestat_ptr, prestat)
+    enc_pointee_fields(vmctx, prestat_ptr, |prestat| {
+        enc_prestat(prestat, host_prestat)
+    })
 }
 
 dec_enc_scalar!(
@@ -548,21 +520,17 @@ pub fn dec_subscription(
     Ok(host::__wasi_subscription_t { userdata, type_, u })
 }
 
-pub fn enc_event(event: host::__wasi_event_t) -> wasm32::__wasi_event_t {
+pub fn enc_event(dst: &mut wasm32::__wasi_event_t, event: host::__wasi_event_t) {
     let fd_readwrite = unsafe { event.u.fd_readwrite };
-    wasm32::__wasi_event_t {
-        userdata: enc_userdata(event.userdata),
-        type_: enc_eventtype(event.type_),
-        error: enc_errno(event.error),
-        __bindgen_anon_1: wasm32::__wasi_event_t__bindgen_ty_1 {
-            fd_readwrite: wasm32::__wasi_event_t__bindgen_ty_1__bindgen_ty_1 {
-                nbytes: enc_filesize(fd_readwrite.nbytes),
-                flags: enc_eventrwflags(fd_readwrite.flags),
-                __bindgen_padding_0: [0; 3],
-            },
-        },
-        __bindgen_padding_0: 0,
-    }
+    let dst = zeroed(dst);
+    dst.userdata = enc_userdata(event.userdata);
+    dst.type_ = enc_eventtype(event.type_);
+    dst.error = enc_errno(event.error);
+    dst.__bindgen_anon_1.fd_readwrite = wasm32::__wasi_event_t__bindgen_ty_1__bindgen_ty_1 {
+        nbytes: enc_filesize(fd_readwrite.nbytes),
+        flags: enc_eventrwflags(fd_readwrite.flags),
+        __bindgen_padding_0: [0; 3],
+    };
 }
 
 dec_enc_scalar!(
@@ -0,0 +1,43 @@
+#include <assert.h>
+#include <stddef.h>
+#include <string.h>
+#include <wasi/core.h>
+
+#define PREOPEN_FD 3
+
+// Check that the bytes of a struct that are not covered by its fields are zero, rather than left
+// alone or filled with host memory
+static void assert_zero(const void *buf, size_t start, size_t end)
+{
+    const unsigned char *bytes = buf;
+    size_t               i;
+
+    for (i = start; i < end; i++) {
+        assert(bytes[i] == 0);
+    }
+}
+
+int main(void)
+{
+    __wasi_prestat_t  prestat;
+    __wasi_fdstat_t   fdstat;
+    __wasi_filestat_t filestat;
+
+    memset(&prestat, 0xff, sizeof prestat);
+    assert(__wasi_fd_prestat_get(PREOPEN_FD, &prestat) == __WASI_ESUCCESS);
+    assert(prestat.pr_type == __WASI_PREOPENTYPE_DIR);
+    assert_zero(&prestat, sizeof prestat.pr_type, offsetof(__wasi_prestat_t, u));
+
+    memset(&fdstat, 0xff, sizeof fdstat);
+    assert(__wasi_fd_fdstat_get(PREOPEN_FD, &fdstat) == __WASI_ESUCCESS);
+    assert_zero(&fdstat, sizeof fdstat.fs_filetype, offsetof(__wasi_fdstat_t, fs_flags));
+    assert_zero(&fdstat, offsetof(__wasi_fdstat_t, fs_flags) + sizeof fdstat.fs_flags,
+                offsetof(__wasi_fdstat_t, fs_rights_base));
+
+    memset(&filestat, 0xff, sizeof filestat);
+    assert(__wasi_fd_filestat_get(PREOPEN_FD, &filestat) == __WASI_ESUCCESS);
+    assert_zero(&filestat, offsetof(__wasi_filestat_t, st_filetype) + sizeof filestat.st_filetype,
+                offsetof(__wasi_filestat_t, st_nlink));
+
+    return 0;
+}
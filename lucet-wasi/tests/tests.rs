@@ -134,6 +134,26 @@ fn preopen_populates() {
     assert_eq!(exitcode, 0);
 }
 
+#[test]
+fn struct_padding_is_zeroed() {
+    let tmpdir = TempDir::new().unwrap();
+    let preopen_host_path = tmpdir.path().join("preopen");
+    std::fs::create_dir(&preopen_host_path).unwrap();
+    let preopen_dir = File::open(preopen_host_path).unwrap();
+
+    let ctx = WasiCtxBuilder::new()
+        .args(&["padding"])
+        .preopened_dir(preopen_dir, "/preopen")
+        .build()
+        .expect("can build WasiCtx");
+
+    let exitcode = run("padding.c", ctx).unwrap();
+
+    drop(tmpdir);
+
+    assert_eq!(exitcode, 0);
+}
+
 #[test]
 fn write_file() {
     let tmpdir = TempDir::new().unwrap();
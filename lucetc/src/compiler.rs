@@ -1,13 +1,15 @@
 use crate::bindings::Bindings;
 use crate::decls::ModuleDecls;
 use crate::error::{LucetcError, LucetcErrorKind};
-use crate::function::FuncInfo;
+use crate::function::{define_import_trampoline, FuncInfo};
 use crate::heap::HeapSettings;
+use crate::instrumentation::InstrumentationSettings;
 use crate::module::ModuleInfo;
 use crate::output::{CraneliftFuncs, ObjectFile};
 use crate::runtime::Runtime;
 use crate::stack_probe;
 use crate::table::write_table_data;
+use crate::wasm_offsets::function_wasm_offsets;
 use cranelift_codegen::{
     ir,
     isa::TargetIsa,
@@ -19,7 +21,8 @@ use cranelift_module::{Backend as ClifBackend, Module as ClifModule};
 use cranelift_native;
 use cranelift_wasm::{translate_module, FuncTranslator, WasmError};
 use failure::{format_err, Fail, ResultExt};
-use lucet_module_data::{FunctionSpec, ModuleData};
+use lucet_module_data::{FunctionSpec, ImportLinking, ModuleData};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, Copy)]
 pub enum OptLevel {
@@ -44,10 +47,21 @@ impl OptLevel {
     }
 }
 
+/// The options for compiling a module other than its optimization level, bindings, and heap.
+///
+/// Options are added here rather than as further arguments to
+/// [`Compiler::new()`](struct.Compiler.html#method.new).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompilerSettings {
+    pub instrumentation: InstrumentationSettings,
+    pub import_linking: ImportLinking,
+}
+
 pub struct Compiler<'a> {
     decls: ModuleDecls<'a>,
     clif_module: ClifModule<FaerieBackend>,
     opt_level: OptLevel,
+    instrumentation: InstrumentationSettings,
 }
 
 impl<'a> Compiler<'a> {
@@ -56,6 +70,7 @@ impl<'a> Compiler<'a> {
         opt_level: OptLevel,
         bindings: &Bindings,
         heap_settings: HeapSettings,
+        settings: CompilerSettings,
     ) -> Result<Self, LucetcError> {
         let isa = Self::target_isa(opt_level);
 
@@ -99,12 +114,14 @@ impl<'a> Compiler<'a> {
             bindings,
             runtime,
             heap_settings,
+            settings.import_linking,
         )?;
 
         Ok(Self {
             decls,
             clif_module,
             opt_level,
+            instrumentation: settings.instrumentation,
         })
     }
 
@@ -114,9 +131,10 @@ impl<'a> Compiler<'a> {
 
     pub fn object_file(mut self) -> Result<ObjectFile, LucetcError> {
         let mut func_translator = FuncTranslator::new();
+        let mut wasm_offsets = HashMap::new();
 
         for (ref func, (code, code_offset)) in self.decls.function_bodies() {
-            let mut func_info = FuncInfo::new(&self.decls);
+            let mut func_info = FuncInfo::new(&self.decls, self.instrumentation);
             let mut clif_context = ClifContext::new();
             clif_context.func.name = func.name.as_externalname();
             clif_context.func.signature = func.signature.clone();
@@ -125,6 +143,26 @@ impl<'a> Compiler<'a> {
                 .translate(code, *code_offset, &mut clif_context.func, &mut func_info)
                 .map_err(|e| format_err!("in {}: {:?}", func.name.symbol(), e))
                 .context(LucetcErrorKind::FunctionTranslation)?;
+            func_info.instrument(&mut clif_context.func);
+
+            self.clif_module
+                .define_function(func.name.as_funcid().unwrap(), &mut clif_context)
+                .map_err(|e| format_err!("in {}: {:?}", func.name.symbol(), e))
+                .context(LucetcErrorKind::FunctionDefinition)?;
+
+            // `define_function` leaves the compiled function in `clif_context`, with the code
+            // offsets and source locations needed for the wasm offset map
+            wasm_offsets.insert(
+                func.name.symbol().to_owned(),
+                function_wasm_offsets(&clif_context.func, self.clif_module.isa()),
+            );
+        }
+
+        for (import_index, ref func) in self.decls.import_trampolines() {
+            let mut clif_context = ClifContext::new();
+            clif_context.func.name = func.name.as_externalname();
+            clif_context.func.signature = func.signature.clone();
+            define_import_trampoline(&mut clif_context.func, import_index);
 
             self.clif_module
                 .define_function(func.name.as_funcid().unwrap(), &mut clif_context)
@@ -147,24 +185,27 @@ impl<'a> Compiler<'a> {
                         f.compiled.as_ref().map(|c| c.code_length()).unwrap_or(0),
                         0,
                         0,
+                        0,
+                        wasm_offsets
+                            .get(&f.decl.name)
+                            .map(|entries| entries.len() as u64)
+                            .unwrap_or(0),
                     ),
                 )
             })
             .collect();
 
-        let obj = ObjectFile::new(self.clif_module.finish(), function_manifest)
+        let obj = ObjectFile::new(self.clif_module.finish(), function_manifest, wasm_offsets)
             .context(LucetcErrorKind::Output)?;
         Ok(obj)
     }
 
     pub fn cranelift_funcs(self) -> Result<CraneliftFuncs, LucetcError> {
-        use std::collections::HashMap;
-
         let mut funcs = HashMap::new();
         let mut func_translator = FuncTranslator::new();
 
         for (ref func, (code, code_offset)) in self.decls.function_bodies() {
-            let mut func_info = FuncInfo::new(&self.decls);
+            let mut func_info = FuncInfo::new(&self.decls, self.instrumentation);
             let mut clif_context = ClifContext::new();
             clif_context.func.name = func.name.as_externalname();
             clif_context.func.signature = func.signature.clone();
@@ -173,9 +214,19 @@ impl<'a> Compiler<'a> {
                 .translate(code, *code_offset, &mut clif_context.func, &mut func_info)
                 .map_err(|e| format_err!("in {}: {:?}", func.name.symbol(), e))
                 .context(LucetcErrorKind::FunctionTranslation)?;
+            func_info.instrument(&mut clif_context.func);
 
             funcs.insert(func.name.clone(), clif_context.func);
         }
+
+        for (import_index, ref func) in self.decls.import_trampolines() {
+            let mut clif_func = ir::Function::with_name_signature(
+                func.name.as_externalname(),
+                func.signature.clone(),
+            );
+            define_import_trampoline(&mut clif_func, import_index);
+            funcs.insert(func.name.clone(), clif_func);
+        }
         Ok(CraneliftFuncs::new(funcs, Self::target_isa(self.opt_level)))
     }
 
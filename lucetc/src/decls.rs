@@ -17,7 +17,7 @@ use failure::{format_err, Error, ResultExt};
 use lucet_module_data::{
     owned::OwnedLinearMemorySpec, ExportFunction, FunctionIndex as LucetFunctionIndex,
     FunctionMetadata, Global as GlobalVariant, GlobalDef, GlobalSpec, HeapSpec, ImportFunction,
-    ModuleData, Signature as LucetSignature, UniqueSignatureIndex,
+    ImportLinking, ModuleData, Signature as LucetSignature, UniqueSignatureIndex, ValueType,
 };
 use std::collections::HashMap;
 use std::convert::TryFrom;
@@ -63,6 +63,7 @@ pub struct ModuleDecls<'a> {
     runtime: Runtime,
     function_names: PrimaryMap<FuncIndex, Name>,
     imports: Vec<ImportFunction<'a>>,
+    import_linking: ImportLinking,
     exports: Vec<ExportFunction<'a>>,
     table_names: PrimaryMap<TableIndex, (Name, Name)>,
     runtime_names: HashMap<RuntimeFunc, Name>,
@@ -77,8 +78,10 @@ impl<'a> ModuleDecls<'a> {
         bindings: &Bindings,
         runtime: Runtime,
         heap_settings: HeapSettings,
+        import_linking: ImportLinking,
     ) -> Result<Self, LucetcError> {
-        let (function_names, imports, exports) = Self::declare_funcs(&info, clif_module, bindings)?;
+        let (function_names, imports, exports) =
+            Self::declare_funcs(&info, clif_module, bindings, import_linking)?;
         let table_names = Self::declare_tables(&info, clif_module)?;
         let runtime_names = Self::declare_runtime(&runtime, clif_module)?;
         let globals_spec = Self::declare_globals_spec(&info)?;
@@ -87,6 +90,7 @@ impl<'a> ModuleDecls<'a> {
             info,
             function_names,
             imports,
+            import_linking,
             exports,
             table_names,
             runtime_names,
@@ -102,6 +106,7 @@ impl<'a> ModuleDecls<'a> {
         info: &ModuleInfo<'a>,
         clif_module: &mut ClifModule<B>,
         bindings: &Bindings,
+        import_linking: ImportLinking,
     ) -> Result<
         (
             PrimaryMap<FuncIndex, Name>,
@@ -141,10 +146,20 @@ impl<'a> ModuleDecls<'a> {
                         module: import_mod,
                         name: import_field,
                     });
-                    let import_symbol = bindings
-                        .translate(import_mod, import_field)
-                        .context(LucetcErrorKind::TranslatingModule)?;
-                    Some((import_symbol, Linkage::Import))
+                    match import_linking {
+                        ImportLinking::Symbols => {
+                            let import_symbol = bindings
+                                .translate(import_mod, import_field)
+                                .context(LucetcErrorKind::TranslatingModule)?;
+                            Some((import_symbol, Linkage::Import))
+                        }
+                        // the trampoline that calls through the import table is defined in the
+                        // module, so bindings are not needed
+                        ImportLinking::Table => Some((
+                            format!("guest_import_{}", imports.len() - 1),
+                            Linkage::Local,
+                        )),
+                    }
                 } else {
                     None
                 };
@@ -231,21 +246,45 @@ impl<'a> ModuleDecls<'a> {
             let g_variant = if let Some((module, field)) = g_import {
                 GlobalVariant::Import { module, field }
             } else {
-                let init_val = match g_decl.entity.initializer {
-                    // Need to fix global spec in ModuleData and the runtime to support more:
-                    GlobalInit::I32Const(i) => i as i64,
-                    GlobalInit::I64Const(i) => i,
+                let def = match g_decl.entity.initializer {
+                    GlobalInit::I32Const(i) => GlobalDef::I32(i),
+                    GlobalInit::I64Const(i) => GlobalDef::I64(i),
+                    GlobalInit::F32Const(bits) => GlobalDef::F32(f32::from_bits(bits)),
+                    GlobalInit::F64Const(bits) => GlobalDef::F64(f64::from_bits(bits)),
+                    GlobalInit::GetGlobal(ref_ix) => {
+                        // the runtime initializes globals in order, so the referenced global must
+                        // already have its value
+                        if ref_ix.index() >= ix.index() {
+                            Err(format_err!(
+                                "global initializer refers to a later global: {:?}",
+                                g_decl.entity
+                            ))
+                            .context(LucetcErrorKind::Validation)?;
+                        }
+                        GlobalDef::GetGlobal(ref_ix.index() as u32)
+                    }
                     _ => Err(format_err!(
-                        "non-integer global initializer: {:?}",
+                        "unsupported global initializer: {:?}",
                         g_decl.entity
                     ))
                     .context(LucetcErrorKind::Unsupported)?,
                 };
-                GlobalVariant::Def {
-                    def: GlobalDef::new(init_val),
-                }
+                GlobalVariant::Def { def }
+            };
+            let ty = match g_decl.entity.ty {
+                ir::types::I32 => ValueType::I32,
+                ir::types::I64 => ValueType::I64,
+                ir::types::F32 => ValueType::F32,
+                ir::types::F64 => ValueType::F64,
+                _ => Err(format_err!("unsupported global type: {:?}", g_decl.entity))
+                    .context(LucetcErrorKind::Unsupported)?,
             };
-            globals.push(GlobalSpec::new(g_variant, None));
+            globals.push(GlobalSpec::new(
+                g_variant,
+                ty,
+                g_decl.entity.mutability,
+                g_decl.export_names.clone(),
+            ));
         }
         Ok(globals)
     }
@@ -321,6 +360,28 @@ impl<'a> ModuleDecls<'a> {
         })
     }
 
+    /// How calls to imported functions are linked.
+    pub fn import_linking(&self) -> ImportLinking {
+        self.import_linking
+    }
+
+    /// The imported functions that need a trampoline defined in the module, along with their
+    /// index in the import table.
+    ///
+    /// This is empty unless imports are linked through an import table.
+    pub fn import_trampolines(&self) -> impl Iterator<Item = (usize, FunctionDecl)> {
+        let trampolines = match self.import_linking {
+            ImportLinking::Symbols => &self.imports[..0],
+            ImportLinking::Table => &self.imports[..],
+        };
+        trampolines.iter().enumerate().map(move |(ix, import)| {
+            let decl = self
+                .get_func(FuncIndex::new(import.fn_idx.as_u32() as usize))
+                .unwrap();
+            (ix, decl)
+        })
+    }
+
     pub fn get_start_func(&self) -> Option<FuncIndex> {
         self.info.start_func.clone()
     }
@@ -435,6 +496,7 @@ impl<'a> ModuleDecls<'a> {
             self.globals_spec.clone(),
             functions,
             self.imports.clone(),
+            self.import_linking,
             self.exports.clone(),
             signatures,
         ))
@@ -1,31 +1,55 @@
 use super::runtime::RuntimeFunc;
 use crate::decls::ModuleDecls;
+use crate::instrumentation::InstrumentationSettings;
 use crate::pointer::{NATIVE_POINTER, NATIVE_POINTER_SIZE};
-use cranelift_codegen::cursor::FuncCursor;
+use crate::traps::FUEL_EXHAUSTED;
+use cranelift_codegen::cursor::{Cursor, FuncCursor};
+use cranelift_codegen::dominator_tree::DominatorTree;
 use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::flowgraph::ControlFlowGraph;
 use cranelift_codegen::ir::{self, InstBuilder};
 use cranelift_codegen::isa::TargetFrontendConfig;
+use cranelift_codegen::loop_analysis::LoopAnalysis;
 use cranelift_wasm::{
     FuncEnvironment, FuncIndex, GlobalIndex, GlobalVariable, MemoryIndex, SignatureIndex,
     TableIndex, WasmResult,
 };
+use lucet_module_data::ImportLinking;
 use std::collections::HashMap;
 
 // VMContext points directly to the heap (offset 0).
 // Directly before the heao is a pointer to the globals (offset -NATIVE_POINTER_SIZE).
 const GLOBAL_BASE_OFFSET: i32 = -1 * NATIVE_POINTER_SIZE as i32;
+// Directly before the globals pointer is the signed 64-bit fuel counter, which is only used by
+// code compiled with fuel metering.
+const FUEL_OFFSET: i32 = -2 * NATIVE_POINTER_SIZE as i32;
+// Directly before the fuel counter is a pointer to the one-byte kill flag, which is only used by
+// code compiled with interrupt checks.
+const KILL_FLAG_PTR_OFFSET: i32 = -3 * NATIVE_POINTER_SIZE as i32;
+// Directly before the kill flag pointer is a pointer to the import table, which is only used by
+// code compiled to link imports through the table. Each entry of the table is a function pointer
+// followed by a context pointer.
+const IMPORT_TABLE_PTR_OFFSET: i32 = -4 * NATIVE_POINTER_SIZE as i32;
+// Directly before the import table pointer is the context pointer of the import table entry being
+// called, which the runtime uses to find the closure behind a host function.
+const IMPORT_CTX_OFFSET: i32 = -5 * NATIVE_POINTER_SIZE as i32;
 
 pub struct FuncInfo<'a> {
     module_decls: &'a ModuleDecls<'a>,
+    instrumentation: InstrumentationSettings,
     vmctx_value: Option<ir::GlobalValue>,
     global_base_value: Option<ir::GlobalValue>,
     runtime_funcs: HashMap<RuntimeFunc, ir::FuncRef>,
 }
 
 impl<'a> FuncInfo<'a> {
-    pub fn new(module_decls: &'a ModuleDecls<'a>) -> Self {
+    pub fn new(
+        module_decls: &'a ModuleDecls<'a>,
+        instrumentation: InstrumentationSettings,
+    ) -> Self {
         Self {
             module_decls,
+            instrumentation,
             vmctx_value: None,
             global_base_value: None,
             runtime_funcs: HashMap::new(),
@@ -77,6 +101,72 @@ impl<'a> FuncInfo<'a> {
                 fref
             })
     }
+
+    /// Add the instrumentation enabled in the settings to a function that has already been
+    /// translated from WebAssembly.
+    pub fn instrument(&mut self, func: &mut ir::Function) {
+        if !(self.instrumentation.fuel_metering || self.instrumentation.interrupt_checks) {
+            return;
+        }
+
+        let cfg = ControlFlowGraph::with_function(func);
+        let domtree = DominatorTree::with_function(func, &cfg);
+        let mut loop_analysis = LoopAnalysis::new();
+        loop_analysis.compute(func, &cfg, &domtree);
+
+        // Every back-edge targets a loop header, so instrumenting the headers along with the
+        // function entry bounds the amount of work that can be done between instrumentation points.
+        let mut instrumented: Vec<ir::Ebb> = func.layout.entry_block().into_iter().collect();
+        for lp in loop_analysis.loops() {
+            let header = loop_analysis.loop_header(lp);
+            if !instrumented.contains(&header) {
+                instrumented.push(header);
+            }
+        }
+
+        for ebb in instrumented {
+            if self.instrumentation.fuel_metering {
+                self.consume_fuel(func, ebb);
+            }
+            if self.instrumentation.interrupt_checks {
+                self.check_interrupt(func, ebb);
+            }
+        }
+    }
+
+    fn consume_fuel(&mut self, func: &mut ir::Function, ebb: ir::Ebb) {
+        let vmctx = func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("vmctx available");
+        let mut pos = FuncCursor::new(func).at_first_insertion_point(ebb);
+        let fuel = pos
+            .ins()
+            .load(ir::types::I64, ir::MemFlags::trusted(), vmctx, FUEL_OFFSET);
+        let fuel = pos.ins().iadd_imm(fuel, -1);
+        pos.ins()
+            .store(ir::MemFlags::trusted(), fuel, vmctx, FUEL_OFFSET);
+        let exhausted = pos
+            .ins()
+            .icmp_imm(ir::condcodes::IntCC::SignedLessThan, fuel, 0);
+        pos.ins().trapnz(exhausted, FUEL_EXHAUSTED);
+    }
+
+    fn check_interrupt(&mut self, func: &mut ir::Function, ebb: ir::Ebb) {
+        let vmctx = func
+            .special_param(ir::ArgumentPurpose::VMContext)
+            .expect("vmctx available");
+        let mut pos = FuncCursor::new(func).at_first_insertion_point(ebb);
+        let kill_flag_ptr = pos.ins().load(
+            NATIVE_POINTER,
+            ir::MemFlags::trusted(),
+            vmctx,
+            KILL_FLAG_PTR_OFFSET,
+        );
+        let kill_flag = pos
+            .ins()
+            .uload8(ir::types::I32, ir::MemFlags::trusted(), kill_flag_ptr, 0);
+        pos.ins().trapnz(kill_flag, ir::TrapCode::Interrupt);
+    }
 }
 
 impl<'a> FuncEnvironment for FuncInfo<'a> {
@@ -205,7 +295,9 @@ impl<'a> FuncEnvironment for FuncInfo<'a> {
     fn make_direct_func(&mut self, func: &mut ir::Function, index: FuncIndex) -> ir::FuncRef {
         let func_decl = self.module_decls.get_func(index).unwrap();
         let signature = func.import_signature(func_decl.signature.clone());
-        let colocated = !func_decl.imported();
+        // imports linked through the import table are called through trampolines in the module
+        let colocated =
+            !func_decl.imported() || self.module_decls.import_linking() == ImportLinking::Table;
         func.import_function(ir::ExtFuncData {
             name: func_decl.name.into(),
             signature,
@@ -266,3 +358,46 @@ impl<'a> FuncEnvironment for FuncInfo<'a> {
         Ok(*pos.func.dfg.inst_results(inst).first().unwrap())
     }
 }
+
+/// Define the body of the trampoline for an import linked through the import table.
+///
+/// `func` must already have the name and signature of the imported function. The trampoline
+/// stores the context pointer of entry `import_index` of the table in the instance, and then
+/// calls the entry's function pointer with its own arguments.
+pub fn define_import_trampoline(func: &mut ir::Function, import_index: usize) {
+    let ebb = func.dfg.make_ebb();
+    for param in func.signature.params.clone() {
+        func.dfg.append_ebb_param(ebb, param.value_type);
+    }
+    let mut pos = FuncCursor::new(func);
+    pos.insert_ebb(ebb);
+    let args = pos.func.dfg.ebb_params(ebb).to_vec();
+    let vmctx = pos
+        .func
+        .special_param(ir::ArgumentPurpose::VMContext)
+        .expect("vmctx available");
+
+    let entry_offset = (import_index * 2 * NATIVE_POINTER_SIZE) as i32;
+    let table = pos.ins().load(
+        NATIVE_POINTER,
+        ir::MemFlags::trusted(),
+        vmctx,
+        IMPORT_TABLE_PTR_OFFSET,
+    );
+    let callee = pos
+        .ins()
+        .load(NATIVE_POINTER, ir::MemFlags::trusted(), table, entry_offset);
+    let ctx = pos.ins().load(
+        NATIVE_POINTER,
+        ir::MemFlags::trusted(),
+        table,
+        entry_offset + NATIVE_POINTER_SIZE as i32,
+    );
+    pos.ins()
+        .store(ir::MemFlags::trusted(), ctx, vmctx, IMPORT_CTX_OFFSET);
+
+    let sig_ref = pos.func.import_signature(pos.func.signature.clone());
+    let call = pos.ins().call_indirect(sig_ref, callee, &args);
+    let results = pos.func.dfg.inst_results(call).to_vec();
+    pos.ins().return_(&results);
+}
@@ -1,4 +1,5 @@
 use crate::traps::trap_sym_for_func;
+use crate::wasm_offsets::wasm_offsets_sym_for_func;
 use byteorder::{LittleEndian, WriteBytesExt};
 use faerie::{Artifact, Decl, Link};
 use failure::{Error, ResultExt};
@@ -81,6 +82,14 @@ pub fn write_function_manifest(
             &trap_sym_for_func(fn_name),
             fn_spec.traps_len() as u64,
         )?;
+        // Writes a (ptr, len) pair with relocation for this function's wasm offset map
+        write_relocated_slice(
+            obj,
+            &mut manifest_buf,
+            &manifest_sym,
+            &wasm_offsets_sym_for_func(fn_name),
+            fn_spec.wasm_offsets_len(),
+        )?;
     }
 
     obj.define(&manifest_sym, manifest_buf.into_inner())
@@ -0,0 +1,12 @@
+/// Optional code generated into every function, on top of the WebAssembly semantics.
+///
+/// All instrumentation is off by default, since each kind adds some overhead to the guest code.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct InstrumentationSettings {
+    /// Consume a unit of fuel at every function entry and loop header, trapping with
+    /// `TrapCode::FuelExhausted` once the instance runs out.
+    pub fuel_metering: bool,
+    /// Check the instance's kill flag at every function entry and loop header, trapping with
+    /// `TrapCode::Interrupt` once it is set.
+    pub interrupt_checks: bool,
+}
@@ -5,6 +5,7 @@ mod error;
 mod function;
 mod function_manifest;
 mod heap;
+mod instrumentation;
 mod load;
 mod module;
 mod name;
@@ -16,17 +17,21 @@ mod sparsedata;
 mod stack_probe;
 mod table;
 mod traps;
+mod wasm_offsets;
 
 pub use crate::{
     bindings::Bindings,
     compiler::Compiler,
+    compiler::CompilerSettings,
     compiler::OptLevel,
     error::{LucetcError, LucetcErrorKind},
     heap::HeapSettings,
+    instrumentation::InstrumentationSettings,
     load::read_module,
     patch::patch_module,
 };
 use failure::{format_err, Error, ResultExt};
+pub use lucet_module_data::ImportLinking;
 use std::env;
 use std::path::{Path, PathBuf};
 use tempfile;
@@ -36,6 +41,7 @@ pub struct Lucetc {
     bindings: Vec<Bindings>,
     opt_level: OptLevel,
     heap: HeapSettings,
+    settings: CompilerSettings,
     builtins_paths: Vec<PathBuf>,
 }
 
@@ -76,6 +82,41 @@ pub trait LucetcOpts {
 
     fn guard_size(&mut self, guard_size: u64);
     fn with_guard_size(self, guard_size: u64) -> Self;
+
+    /// Instrument the generated code to consume fuel from a per-instance counter.
+    ///
+    /// The runtime sets the amount of fuel available to an instance, and the guest traps with
+    /// `TrapCode::FuelExhausted` when it runs out.
+    fn fuel_metering(&mut self, enabled: bool);
+    /// Instrument the generated code to consume fuel from a per-instance counter.
+    ///
+    /// The runtime sets the amount of fuel available to an instance, and the guest traps with
+    /// `TrapCode::FuelExhausted` when it runs out.
+    fn with_fuel_metering(self, enabled: bool) -> Self;
+
+    /// Instrument the generated code to check whether the instance has been interrupted.
+    ///
+    /// This allows a `KillSwitch` in the runtime to terminate guests that are running WebAssembly
+    /// code, rather than only those that are running hostcalls.
+    fn interrupt_checks(&mut self, enabled: bool);
+    /// Instrument the generated code to check whether the instance has been interrupted.
+    ///
+    /// This allows a `KillSwitch` in the runtime to terminate guests that are running WebAssembly
+    /// code, rather than only those that are running hostcalls.
+    fn with_interrupt_checks(self, enabled: bool) -> Self;
+
+    /// Choose how calls to imported functions are linked.
+    ///
+    /// With `ImportLinking::Table`, imports are called through a table that the runtime fills in
+    /// with the host functions provided when each instance is created, rather than being resolved
+    /// as symbols by the dynamic linker. Bindings are not used in that case.
+    fn import_linking(&mut self, import_linking: ImportLinking);
+    /// Choose how calls to imported functions are linked.
+    ///
+    /// With `ImportLinking::Table`, imports are called through a table that the runtime fills in
+    /// with the host functions provided when each instance is created, rather than being resolved
+    /// as symbols by the dynamic linker. Bindings are not used in that case.
+    fn with_import_linking(self, import_linking: ImportLinking) -> Self;
 }
 
 impl<T: AsLucetc> LucetcOpts for T {
@@ -144,6 +185,33 @@ impl<T: AsLucetc> LucetcOpts for T {
         self.guard_size(guard_size);
         self
     }
+
+    fn fuel_metering(&mut self, enabled: bool) {
+        self.as_lucetc().settings.instrumentation.fuel_metering = enabled;
+    }
+
+    fn with_fuel_metering(mut self, enabled: bool) -> Self {
+        self.fuel_metering(enabled);
+        self
+    }
+
+    fn interrupt_checks(&mut self, enabled: bool) {
+        self.as_lucetc().settings.instrumentation.interrupt_checks = enabled;
+    }
+
+    fn with_interrupt_checks(mut self, enabled: bool) -> Self {
+        self.interrupt_checks(enabled);
+        self
+    }
+
+    fn import_linking(&mut self, import_linking: ImportLinking) {
+        self.as_lucetc().settings.import_linking = import_linking;
+    }
+
+    fn with_import_linking(mut self, import_linking: ImportLinking) -> Self {
+        self.import_linking(import_linking);
+        self
+    }
 }
 
 impl Lucetc {
@@ -154,6 +222,7 @@ impl Lucetc {
             bindings: vec![],
             opt_level: OptLevel::default(),
             heap: HeapSettings::default(),
+            settings: CompilerSettings::default(),
             builtins_paths: vec![],
         }
     }
@@ -191,6 +260,7 @@ impl Lucetc {
             self.opt_level,
             &bindings,
             self.heap.clone(),
+            self.settings,
         )?;
         let obj = compiler.object_file()?;
 
@@ -206,6 +276,7 @@ impl Lucetc {
             self.opt_level,
             &bindings,
             self.heap.clone(),
+            self.settings,
         )?;
 
         compiler
@@ -44,7 +44,10 @@ pub fn run(opts: &Options) -> Result<(), Error> {
 
     let mut c = Lucetc::new(PathBuf::from(input))
         .with_bindings(bindings)
-        .with_opt_level(opts.opt_level);
+        .with_opt_level(opts.opt_level)
+        .with_fuel_metering(opts.fuel_metering)
+        .with_interrupt_checks(opts.interrupt_checks)
+        .with_import_linking(opts.import_linking);
 
     if let Some(ref builtins) = opts.builtins_path {
         c.builtins(builtins);
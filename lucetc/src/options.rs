@@ -1,6 +1,6 @@
 use clap::{App, Arg, ArgMatches};
 use failure::Error;
-use lucetc::{HeapSettings, OptLevel};
+use lucetc::{HeapSettings, ImportLinking, OptLevel};
 use std::path::PathBuf;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -41,6 +41,9 @@ pub struct Options {
     pub reserved_size: Option<u64>,
     pub guard_size: Option<u64>,
     pub opt_level: OptLevel,
+    pub fuel_metering: bool,
+    pub interrupt_checks: bool,
+    pub import_linking: ImportLinking,
 }
 
 impl Options {
@@ -101,6 +104,16 @@ impl Options {
             Some(_) => panic!("unknown value for opt-level"),
         };
 
+        let fuel_metering = m.is_present("fuel_metering");
+        let interrupt_checks = m.is_present("interrupt_checks");
+
+        let import_linking = match m.value_of("import_linking") {
+            None => ImportLinking::Symbols,
+            Some("symbols") => ImportLinking::Symbols,
+            Some("table") => ImportLinking::Table,
+            Some(_) => panic!("unknown value for import-linking"),
+        };
+
         Ok(Options {
             output,
             input,
@@ -112,6 +125,9 @@ impl Options {
             reserved_size,
             guard_size,
             opt_level,
+            fuel_metering,
+            interrupt_checks,
+            import_linking,
         })
     }
     pub fn get() -> Result<Self, Error> {
@@ -198,6 +214,25 @@ impl Options {
                     .possible_values(&["default", "fastest", "best"])
                     .help("optimization level (default: 'default')"),
             )
+            .arg(
+                Arg::with_name("fuel_metering")
+                    .long("--fuel-metering")
+                    .takes_value(false)
+                    .help("instrument the generated code to consume fuel at function entries and loop headers"),
+            )
+            .arg(
+                Arg::with_name("interrupt_checks")
+                    .long("--interrupt-checks")
+                    .takes_value(false)
+                    .help("instrument the generated code to check for interruption at function entries and loop headers"),
+            )
+            .arg(
+                Arg::with_name("import_linking")
+                    .long("--import-linking")
+                    .takes_value(true)
+                    .possible_values(&["symbols", "table"])
+                    .help("link imported functions as symbols, or through a per-instance table of host functions (default: 'symbols')"),
+            )
             .get_matches();
 
         Self::from_args(&m)
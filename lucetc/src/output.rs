@@ -3,11 +3,12 @@ use crate::function_manifest::write_function_manifest;
 use crate::name::Name;
 use crate::stack_probe;
 use crate::traps::write_trap_tables;
+use crate::wasm_offsets::write_wasm_offset_tables;
 use cranelift_codegen::{ir, isa};
 use cranelift_faerie::FaerieProduct;
 use faerie::Artifact;
 use failure::{format_err, Error, ResultExt};
-use lucet_module_data::FunctionSpec;
+use lucet_module_data::{FunctionSpec, WasmOffset};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Write;
@@ -44,6 +45,7 @@ impl ObjectFile {
     pub fn new(
         mut product: FaerieProduct,
         mut function_manifest: Vec<(String, FunctionSpec)>,
+        wasm_offsets: HashMap<String, Vec<WasmOffset>>,
     ) -> Result<Self, Error> {
         stack_probe::declare_and_define(&mut product)?;
 
@@ -57,6 +59,8 @@ impl ObjectFile {
                 stack_probe::STACK_PROBE_BINARY.len() as u32,
                 0,
                 0, // fix up this FunctionSpec with trap info like any other
+                0,
+                0, // the stack probe is not compiled from wasm, so has no wasm offsets
             ),
         ));
 
@@ -79,7 +83,14 @@ impl ObjectFile {
 
                 std::mem::replace::<FunctionSpec>(
                     fn_spec,
-                    FunctionSpec::new(0, fn_spec.code_len(), 0, sink.sites.len() as u64),
+                    FunctionSpec::new(
+                        0,
+                        fn_spec.code_len(),
+                        0,
+                        sink.sites.len() as u64,
+                        0,
+                        fn_spec.wasm_offsets_len(),
+                    ),
                 );
             } else {
                 Err(format_err!("Inconsistent state: trap records present for function {} but the function does not exist?", sink.name))
@@ -88,6 +99,7 @@ impl ObjectFile {
         }
 
         write_trap_tables(trap_manifest, &mut product.artifact)?;
+        write_wasm_offset_tables(&wasm_offsets, &mut product.artifact)?;
         write_function_manifest(function_manifest.as_slice(), &mut product.artifact)?;
 
         Ok(Self {
@@ -1,3 +1,4 @@
+use crate::wasm_offsets::wasm_offset_for_srcloc;
 use cranelift_codegen::ir;
 use cranelift_faerie::traps::FaerieTrapManifest;
 
@@ -20,6 +21,7 @@ pub fn write_trap_tables(manifest: &FaerieTrapManifest, obj: &mut Artifact) -> R
             .map(|site| TrapSite {
                 offset: site.offset,
                 code: translate_trapcode(site.code),
+                wasm_offset: wasm_offset_for_srcloc(site.srcloc),
             })
             .collect();
 
@@ -38,6 +40,11 @@ pub fn write_trap_tables(manifest: &FaerieTrapManifest, obj: &mut Artifact) -> R
     Ok(())
 }
 
+/// The trap raised by fuel metering instrumentation when an instance runs out of fuel.
+///
+/// Cranelift has no trap code of its own for this, so we claim a user trap code for it.
+pub(crate) const FUEL_EXHAUSTED: ir::TrapCode = ir::TrapCode::User(0);
+
 pub(crate) fn trap_sym_for_func(sym: &str) -> String {
     return format!("lucet_trap_table_{}", sym);
 }
@@ -48,7 +55,7 @@ pub(crate) fn trap_sym_for_func(sym: &str) -> String {
 // high bits.
 //
 // Not all types have subtypes. Currently, only the user User type has a
-// subtype.
+// subtype. The only user trapcode we emit is the one for fuel exhaustion.
 fn translate_trapcode(code: ir::TrapCode) -> lucet_module_data::TrapCode {
     match code {
         ir::TrapCode::StackOverflow => lucet_module_data::TrapCode::StackOverflow,
@@ -62,6 +69,7 @@ fn translate_trapcode(code: ir::TrapCode) -> lucet_module_data::TrapCode {
         ir::TrapCode::Interrupt => lucet_module_data::TrapCode::Interrupt,
         ir::TrapCode::TableOutOfBounds => lucet_module_data::TrapCode::TableOutOfBounds,
         ir::TrapCode::UnreachableCodeReached => lucet_module_data::TrapCode::Unreachable,
+        FUEL_EXHAUSTED => lucet_module_data::TrapCode::FuelExhausted,
         ir::TrapCode::User(_) => panic!("we should never emit a user trapcode"),
     }
 }
@@ -0,0 +1,75 @@
+use cranelift_codegen::{ir, isa::TargetIsa};
+use faerie::{Artifact, Decl};
+use failure::{Error, ResultExt};
+use lucet_module_data::WasmOffset;
+use std::collections::HashMap;
+
+/// Build the map from native code offsets to wasm bytecode offsets for a compiled function.
+///
+/// Cranelift records the `SourceLoc` of the wasm instruction that each native instruction was
+/// translated from, which `cranelift-wasm` sets to the instruction's offset in the module binary.
+/// Consecutive instructions with the same location share an entry.
+pub(crate) fn function_wasm_offsets(func: &ir::Function, isa: &dyn TargetIsa) -> Vec<WasmOffset> {
+    let encinfo = isa.encoding_info();
+
+    // layout order is not necessarily code order, so make sure offsets always increase
+    let mut ebbs = func.layout.ebbs().collect::<Vec<_>>();
+    ebbs.sort_by_key(|ebb| func.offsets[*ebb]);
+
+    let mut entries: Vec<WasmOffset> = Vec::new();
+    for ebb in ebbs {
+        for (offset, inst, _size) in func.inst_offsets(ebb, &encinfo) {
+            let wasm_offset = wasm_offset_for_srcloc(func.srclocs[inst]);
+            match entries.last_mut() {
+                // instructions with no encoding take up no space, and are covered by whatever
+                // instruction comes next
+                Some(last) if last.offset == offset => last.wasm_offset = wasm_offset,
+                Some(last) if last.wasm_offset == wasm_offset => (),
+                _ => entries.push(WasmOffset {
+                    offset,
+                    wasm_offset,
+                }),
+            }
+        }
+    }
+    entries
+}
+
+pub(crate) fn wasm_offset_for_srcloc(srcloc: ir::SourceLoc) -> u32 {
+    if srcloc.is_default() {
+        WasmOffset::UNKNOWN
+    } else {
+        srcloc.bits()
+    }
+}
+
+pub fn write_wasm_offset_tables(
+    tables: &HashMap<String, Vec<WasmOffset>>,
+    obj: &mut Artifact,
+) -> Result<(), Error> {
+    for (func_sym, entries) in tables.iter() {
+        if entries.is_empty() {
+            continue;
+        }
+        let table_sym = wasm_offsets_sym_for_func(func_sym);
+
+        obj.declare(&table_sym, Decl::data())
+            .context(format!("declaring {}", &table_sym))?;
+
+        let table_bytes = unsafe {
+            std::slice::from_raw_parts(
+                entries.as_ptr() as *const u8,
+                entries.len() * std::mem::size_of::<WasmOffset>(),
+            )
+        };
+
+        obj.define(&table_sym, table_bytes.to_vec())
+            .context(format!("defining {}", &table_sym))?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn wasm_offsets_sym_for_func(sym: &str) -> String {
+    format!("lucet_wasm_offsets_{}", sym)
+}
@@ -32,7 +32,10 @@ fn test_bindings() -> Bindings {
 mod module_data {
     /// Tests of the `ModuleData` generated by the lucetc Compiler
     use super::load_wat_module;
-    use lucetc::{Bindings, Compiler, HeapSettings, LucetcErrorKind, OptLevel};
+    use lucetc::{
+        Bindings, Compiler, CompilerSettings, HeapSettings, ImportLinking, LucetcErrorKind,
+        OptLevel,
+    };
     use std::path::PathBuf;
 
     #[test]
@@ -40,7 +43,8 @@ mod module_data {
         let m = load_wat_module("fibonacci");
         let b = super::test_bindings();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compiling fibonacci");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compiling fibonacci");
         let mdata = c.module_data().unwrap();
         assert_eq!(mdata.globals_spec().len(), 0);
 
@@ -54,7 +58,8 @@ mod module_data {
         let m = load_wat_module("arith");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compiling arith");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compiling arith");
         let mdata = c.module_data().unwrap();
         assert_eq!(mdata.globals_spec().len(), 0);
 
@@ -70,7 +75,8 @@ mod module_data {
         ))
         .unwrap();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compile icall");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile icall");
         let mdata = c.module_data().unwrap();
 
         assert_eq!(mdata.import_functions().len(), 1);
@@ -101,12 +107,36 @@ mod module_data {
         */
     }
 
+    #[test]
+    fn import_table() {
+        let m = load_wat_module("import_many");
+        // imports linked through the import table do not need bindings
+        let b = Bindings::empty();
+        let h = HeapSettings::default();
+        let s = CompilerSettings {
+            import_linking: ImportLinking::Table,
+            ..CompilerSettings::default()
+        };
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, s).expect("compile import_many");
+        let mdata = c.module_data().unwrap();
+
+        assert_eq!(mdata.import_linking(), ImportLinking::Table);
+        assert_eq!(mdata.import_functions().len(), 4);
+        for (ix, import) in mdata.import_functions().iter().enumerate() {
+            assert_eq!(import.module, "env");
+            assert_eq!(import.name, format!("imp_{}", ix));
+            assert_eq!(import.fn_idx.as_u32(), ix as u32);
+        }
+        assert_eq!(mdata.function_info().len(), 6);
+    }
+
     #[test]
     fn icall() {
         let m = load_wat_module("icall");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compile icall");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile icall");
         let _module_data = c.module_data().unwrap();
 
         /*  TODO can't express these with module data
@@ -131,7 +161,8 @@ mod module_data {
         let m = load_wat_module("icall_sparse");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compile icall_sparse");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile icall_sparse");
         let _module_data = c.module_data().unwrap();
 
         /*  TODO can't express these with module data
@@ -170,7 +201,8 @@ mod module_data {
         let b = Bindings::empty();
         let h = HeapSettings::default();
 
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compile globals_import");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile globals_import");
         let module_data = c.module_data().unwrap();
         let gspec = module_data.globals_spec();
 
@@ -185,14 +217,71 @@ mod module_data {
         }
     }
 
+    #[test]
+    fn globals_definition() {
+        use lucet_module_data::ValueType;
+        let m = load_wat_module("globals_definition");
+        let b = Bindings::empty();
+        let h = HeapSettings::default();
+
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile globals_definition");
+        let module_data = c.module_data().unwrap();
+        let gspec = module_data.globals_spec();
+
+        assert_eq!(gspec.len(), 3);
+        assert!(gspec[0].export_names().is_empty());
+        assert!(gspec[0].is_mutable());
+        assert_eq!(gspec[2].export_names(), &["z"]);
+        assert!(!gspec[2].is_mutable());
+        assert!(gspec.iter().all(|g| g.ty() == ValueType::I32));
+    }
+
+    #[test]
+    fn globals_initializers() {
+        use lucet_module_data::{Global as GlobalVariant, GlobalDef};
+        let m = load_wat_module("globals_initializers");
+        let b = Bindings::empty();
+        let h = HeapSettings::default();
+
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile globals_initializers");
+        let module_data = c.module_data().unwrap();
+        let gspec = module_data.globals_spec();
+
+        assert_eq!(gspec.len(), 5);
+        let defs = gspec[1..]
+            .iter()
+            .map(|g| match g.global() {
+                GlobalVariant::Def { def } => def.clone(),
+                _ => panic!("global should be a definition"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            defs,
+            vec![
+                GlobalDef::I32(-7),
+                GlobalDef::F32(1.5),
+                GlobalDef::F64(-2.25),
+                GlobalDef::GetGlobal(0),
+            ]
+        );
+    }
+
     #[test]
     fn heap_spec_import() {
         use lucet_module_data::HeapSpec;
         let m = load_wat_module("heap_spec_import");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c =
-            Compiler::new(&m, OptLevel::Best, &b, h.clone()).expect("compiling heap_spec_import");
+        let c = Compiler::new(
+            &m,
+            OptLevel::Best,
+            &b,
+            h.clone(),
+            CompilerSettings::default(),
+        )
+        .expect("compiling heap_spec_import");
 
         assert_eq!(
             c.module_data().unwrap().heap_spec(),
@@ -214,8 +303,14 @@ mod module_data {
         let m = load_wat_module("heap_spec_definition");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h.clone())
-            .expect("compiling heap_spec_definition");
+        let c = Compiler::new(
+            &m,
+            OptLevel::Best,
+            &b,
+            h.clone(),
+            CompilerSettings::default(),
+        )
+        .expect("compiling heap_spec_definition");
 
         assert_eq!(
             c.module_data().unwrap().heap_spec(),
@@ -236,7 +331,8 @@ mod module_data {
         let m = load_wat_module("heap_spec_none");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compiling heap_spec_none");
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compiling heap_spec_none");
         assert_eq!(c.module_data().unwrap().heap_spec(), None,);
     }
 
@@ -245,7 +341,7 @@ mod module_data {
         let m = load_wat_module("oversize_data_segment");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h);
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default());
         assert!(
             c.is_err(),
             "compilation error because data initializers are oversized"
@@ -268,7 +364,7 @@ mod module_data {
 
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h);
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default());
         assert!(
             c.is_err(),
             "compilation error because wasm module is invalid"
@@ -281,7 +377,8 @@ mod module_data {
         let m = load_wat_module("start_section");
         let b = Bindings::empty();
         let h = HeapSettings::default();
-        let _c = Compiler::new(&m, OptLevel::Best, &b, h).expect("compile start_section");
+        let _c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect("compile start_section");
         /*
         assert!(
             p.module().start_section().is_some(),
@@ -294,12 +391,16 @@ mod module_data {
 mod compile {
     // Tests for compilation completion
     use super::load_wat_module;
-    use lucetc::{Compiler, HeapSettings, OptLevel};
+    use lucetc::{
+        Bindings, Compiler, CompilerSettings, HeapSettings, ImportLinking, InstrumentationSettings,
+        OptLevel,
+    };
     fn run_compile_test(file: &str) {
         let m = load_wat_module(file);
         let b = super::test_bindings();
         let h = HeapSettings::default();
-        let c = Compiler::new(&m, OptLevel::Best, &b, h).expect(&format!("compile {}", file));
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, CompilerSettings::default())
+            .expect(&format!("compile {}", file));
         let _obj = c.object_file().expect(&format!("codegen {}", file));
     }
     macro_rules! compile_test {
@@ -317,6 +418,7 @@ mod compile {
     compile_test!(fibonacci);
     compile_test!(globals_definition);
     compile_test!(globals_import);
+    compile_test!(globals_initializers);
     compile_test!(icall);
     compile_test!(icall_import);
     compile_test!(icall_sparse);
@@ -330,4 +432,52 @@ mod compile {
     compile_test!(grow_memory);
     compile_test!(unreachable_code);
     compile_test!(start_section);
+
+    #[test]
+    fn fuel_metering() {
+        let m = load_wat_module("fibonacci");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let s = CompilerSettings {
+            instrumentation: InstrumentationSettings {
+                fuel_metering: true,
+                ..InstrumentationSettings::default()
+            },
+            ..CompilerSettings::default()
+        };
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, s).expect("compile fibonacci");
+        let _obj = c.object_file().expect("codegen fibonacci");
+    }
+
+    #[test]
+    fn import_table() {
+        for name in &["import", "import_many", "icall_import"] {
+            let m = load_wat_module(name);
+            let b = Bindings::empty();
+            let h = HeapSettings::default();
+            let s = CompilerSettings {
+                import_linking: ImportLinking::Table,
+                ..CompilerSettings::default()
+            };
+            let c =
+                Compiler::new(&m, OptLevel::Best, &b, h, s).expect(&format!("compile {}", name));
+            let _obj = c.object_file().expect(&format!("codegen {}", name));
+        }
+    }
+
+    #[test]
+    fn interrupt_checks() {
+        let m = load_wat_module("fibonacci");
+        let b = super::test_bindings();
+        let h = HeapSettings::default();
+        let s = CompilerSettings {
+            instrumentation: InstrumentationSettings {
+                interrupt_checks: true,
+                ..InstrumentationSettings::default()
+            },
+            ..CompilerSettings::default()
+        };
+        let c = Compiler::new(&m, OptLevel::Best, &b, h, s).expect("compile fibonacci");
+        let _obj = c.object_file().expect("codegen fibonacci");
+    }
 }
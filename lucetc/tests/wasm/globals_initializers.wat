@@ -0,0 +1,7 @@
+(module
+  (global $x (import "env" "x") i32)
+  (global $i (mut i32) (i32.const -7))
+  (global $f32 (mut f32) (f32.const 1.5))
+  (global $f64 f64 (f64.const -2.25))
+  (global $copy i32 (get_global $x))
+)